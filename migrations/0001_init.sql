@@ -0,0 +1,54 @@
+-- Schéma du stockage relationnel (db_sql.rs), compatible SQLite et Postgres.
+-- Les ids sont attribués par la table counters, comme la collection counters de MongoDB.
+
+CREATE TABLE IF NOT EXISTS counters (
+    name TEXT PRIMARY KEY,
+    seq BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS servers (
+    id BIGINT PRIMARY KEY,
+    name TEXT NOT NULL,
+    owner_id BIGINT NOT NULL,
+    image TEXT
+);
+
+CREATE TABLE IF NOT EXISTS server_members (
+    server_id BIGINT NOT NULL REFERENCES servers (id),
+    user_id BIGINT NOT NULL,
+    joined_at TEXT NOT NULL,
+    PRIMARY KEY (server_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS server_members_user_id ON server_members (user_id);
+
+CREATE TABLE IF NOT EXISTS server_admins (
+    server_id BIGINT NOT NULL REFERENCES servers (id),
+    user_id BIGINT NOT NULL,
+    PRIMARY KEY (server_id, user_id)
+);
+
+-- Un seul lien d'invitation par serveur, comme le champ lien du document server
+CREATE TABLE IF NOT EXISTS invites (
+    code TEXT PRIMARY KEY,
+    server_id BIGINT NOT NULL UNIQUE REFERENCES servers (id)
+);
+
+CREATE TABLE IF NOT EXISTS channels (
+    id BIGINT PRIMARY KEY,
+    server_id BIGINT NOT NULL REFERENCES servers (id),
+    name TEXT NOT NULL,
+    position BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS channels_server_id ON channels (server_id);
+
+CREATE TABLE IF NOT EXISTS messages (
+    id BIGINT PRIMARY KEY,
+    channel_id BIGINT NOT NULL REFERENCES channels (id),
+    user_id BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    sent_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS messages_channel_id_id ON messages (channel_id, id);
@@ -0,0 +1,13 @@
+-- Modification des messages : date de la dernière modification et anciennes versions,
+-- comme le champ edited_at et le tableau revisions du document message.
+
+ALTER TABLE messages ADD COLUMN edited_at TEXT;
+
+CREATE TABLE IF NOT EXISTS message_revisions (
+    message_id BIGINT NOT NULL REFERENCES messages (id),
+    content TEXT NOT NULL,
+    editor_id BIGINT NOT NULL,
+    edited_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS message_revisions_message_id ON message_revisions (message_id);
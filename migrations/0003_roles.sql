@@ -0,0 +1,17 @@
+-- Rôles personnalisés d'un serveur, comme la collection role : permissions en entier (voir Permissions dans models.rs),
+-- position dans la hiérarchie et membres qui ont le rôle.
+
+CREATE TABLE IF NOT EXISTS roles (
+    id BIGINT PRIMARY KEY,
+    server_id BIGINT NOT NULL REFERENCES servers (id),
+    name TEXT NOT NULL,
+    permissions BIGINT NOT NULL,
+    position BIGINT NOT NULL,
+    UNIQUE (server_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS role_members (
+    role_id BIGINT NOT NULL REFERENCES roles (id),
+    user_id BIGINT NOT NULL,
+    PRIMARY KEY (role_id, user_id)
+);
@@ -0,0 +1,14 @@
+-- Channels privés et exceptions de permissions d'un channel, comme les champs private et overwrites du document channel :
+-- private vaut 0 ou 1 (le driver Any ne lit pas les booléens SQLite), kind vaut "role" ou "user",
+-- allowed et denied sont des masques de Permissions (voir models.rs).
+
+ALTER TABLE channels ADD COLUMN private BIGINT NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS channel_overwrites (
+    channel_id BIGINT NOT NULL REFERENCES channels (id),
+    kind TEXT NOT NULL,
+    target_id BIGINT NOT NULL,
+    allowed BIGINT NOT NULL,
+    denied BIGINT NOT NULL,
+    PRIMARY KEY (channel_id, kind, target_id)
+);
@@ -0,0 +1,15 @@
+-- Bannissements d'un serveur, comme la collection ban : un seul par utilisateur et par serveur.
+-- banned_at et expires_at sont des dates RFC 3339 à la seconde en UTC (voir Ban::format_date), comparables comme du texte ;
+-- expires_at est NULL pour un bannissement définitif.
+
+CREATE TABLE IF NOT EXISTS bans (
+    server_id BIGINT NOT NULL REFERENCES servers (id),
+    user_id BIGINT NOT NULL,
+    reason TEXT NOT NULL,
+    banned_by BIGINT NOT NULL,
+    banned_at TEXT NOT NULL,
+    expires_at TEXT,
+    PRIMARY KEY (server_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS bans_expires_at ON bans (expires_at);
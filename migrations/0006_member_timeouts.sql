@@ -0,0 +1,11 @@
+-- Membres réduits au silence, comme la collection timeout : un seul timeout par membre et par serveur.
+-- until est une date RFC 3339 à la seconde en UTC (voir Ban::format_date), comparable comme du texte ;
+-- un timeout terminé reste enregistré jusqu'au suivant mais n'a plus d'effet.
+
+CREATE TABLE IF NOT EXISTS member_timeouts (
+    server_id BIGINT NOT NULL REFERENCES servers (id),
+    user_id BIGINT NOT NULL,
+    moderator_id BIGINT NOT NULL,
+    until TEXT NOT NULL,
+    PRIMARY KEY (server_id, user_id)
+);
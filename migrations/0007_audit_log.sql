@@ -0,0 +1,15 @@
+-- Journal d'audit d'un serveur, comme la collection audit_log. les ids sont attribués par la table counters.
+-- before_state et after_state sont des instantanés JSON de la cible (NULL quand il n'y a rien avant ou après).
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGINT PRIMARY KEY,
+    server_id BIGINT NOT NULL REFERENCES servers (id),
+    actor_id BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    target_id BIGINT NOT NULL,
+    before_state TEXT,
+    after_state TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_server_id_id ON audit_log (server_id, id);
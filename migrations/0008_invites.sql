@@ -0,0 +1,19 @@
+-- Liens d'invitation, comme la collection invite : plusieurs liens par serveur, chacun avec son créateur et son compteur.
+-- created_at et expires_at sont des dates RFC 3339 à la seconde en UTC (voir Ban::format_date), comparables comme du texte ;
+-- expires_at et max_uses sont NULL pour un lien permanent ou illimité.
+-- L'ancienne table (un seul lien par serveur, sans créateur) est remplacée : ses liens ne sont pas repris,
+-- comme le champ lien des documents server de MongoDB.
+
+DROP TABLE IF EXISTS invites;
+
+CREATE TABLE invites (
+    code TEXT PRIMARY KEY,
+    server_id BIGINT NOT NULL REFERENCES servers (id),
+    creator_id BIGINT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT,
+    max_uses BIGINT,
+    uses BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS invites_server_id ON invites (server_id);
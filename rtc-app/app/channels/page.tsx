@@ -32,7 +32,7 @@ interface Channel {
 interface ServerMember {
   user_id: number;
   role: "fondateur" | "admin" | "membre" | string;
-  status?: "online" | "offline";
+  status?: "online" | "idle" | "dnd" | "offline";
 }
 
 export default function ChannelsPage() {
@@ -1123,7 +1123,7 @@ export default function ChannelsPage() {
                     username: username,
                     avatar: avatar,
                     email: emailsByNumericId[m.user_id],
-                    status: (m.status === "online" ? 'online' : 'offline') as 'online' | 'offline',
+                    status: m.status || "offline",
                     role: m.role === "fondateur" ? "Fondateur" : m.role === "admin" ? "Admin" : "Membre",
                     roleColor: roleColor,
                   };
@@ -44,6 +44,9 @@ export default function ChannelChat({ serverId, channelId, channelName, onChanne
   const [commonServers, setCommonServers] = useState<Array<{ id: number; name: string; image?: string }>>([]);
   const messagesEndRef = useRef<HTMLDivElement>(null);
   const messageIdRef = useRef(0);
+  const [typingUsers, setTypingUsers] = useState<Record<number, string>>({});
+  const lastTypingSentRef = useRef(0);
+  const lastSeenMessageIdRef = useRef<number | null>(null);
 
   const scrollToBottom = () => {
     messagesEndRef.current?.scrollIntoView({ behavior: "smooth" });
@@ -189,6 +192,11 @@ export default function ChannelChat({ serverId, channelId, channelName, onChanne
             typeof m.username === "string" ? m.username : undefined,
         }));
         setMessages(history);
+        for (const m of history) {
+          if (typeof m.id === "number") {
+            lastSeenMessageIdRef.current = Math.max(lastSeenMessageIdRef.current ?? 0, m.id);
+          }
+        }
       } catch (e) {
         console.error("Erreur lors du chargement de l'historique:", e);
       }
@@ -231,7 +239,11 @@ export default function ChannelChat({ serverId, channelId, channelName, onChanne
           setCurrentUserAvatar(user.avatar);
         }
 
-        const wsUrl = `ws://localhost:3000/ws?server_id=${serverId}&channel_id=${channelId}`;
+        // À la reconnexion, le serveur rejoue les messages postés depuis le dernier reçu
+        const lastSeen = lastSeenMessageIdRef.current;
+        const wsUrl =
+          `ws://localhost:3000/ws?server_id=${serverId}&channel_id=${channelId}` +
+          (lastSeen != null ? `&last_seen_message_id=${lastSeen}` : "");
         ws = new WebSocket(wsUrl);
 
         ws.addEventListener("open", () => {
@@ -246,19 +258,69 @@ export default function ChannelChat({ serverId, channelId, channelName, onChanne
         ws.addEventListener("message", (event) => {
           if (isCleaningUp) return;
 
-          const text = event.data;
+          let frame: any;
+          try {
+            frame = JSON.parse(event.data);
+          } catch {
+            console.error("Trame WebSocket illisible:", event.data);
+            return;
+          }
+
+          if (frame.type === "error") {
+            console.error(`Erreur WebSocket (${frame.code}):`, frame.message, frame.nonce ?? "");
+            return;
+          }
+
+          if (frame.type === "typing.start" || frame.type === "typing.stop") {
+            setTypingUsers((prev) => {
+              const next = { ...prev };
+              if (frame.type === "typing.start") {
+                next[frame.user_id] = frame.username;
+              } else {
+                delete next[frame.user_id];
+              }
+              return next;
+            });
+            return;
+          }
+
+          if (frame.type === "message.create") {
+            lastSeenMessageIdRef.current = Math.max(lastSeenMessageIdRef.current ?? 0, frame.id);
+          }
 
           setMessages((prev) => {
-            const isSystemMessage =
-              text.includes("a rejoint") || text.includes("a quitté");
-            const newMessage: Message = {
-              id: messageIdRef.current++,
-              text: text,
-              sender: isSystemMessage ? "system" : "bot",
-              timestamp: new Date(),
-            };
-
-            return [...prev, newMessage];
+            let newMessage: Message | null = null;
+            if (frame.type === "message.create") {
+              // Un message rejoué peut déjà être présent dans l'historique
+              if (prev.some((m) => m.id === frame.id)) return prev;
+              newMessage = {
+                id: frame.id,
+                text: frame.content,
+                sender: "bot",
+                timestamp: new Date(frame.timestamp),
+                userId: frame.author_id,
+                usernameFromBackend: frame.username,
+              };
+            } else if (frame.type === "subscription.revoked") {
+              newMessage = {
+                id: messageIdRef.current++,
+                text:
+                  frame.reason === "channel_deleted"
+                    ? "Ce channel a été supprimé"
+                    : "Vous n'avez plus accès à ce serveur",
+                sender: "system",
+                timestamp: new Date(),
+              };
+            } else if (frame.type === "member.join" || frame.type === "member.leave") {
+              newMessage = {
+                id: messageIdRef.current++,
+                text: `${frame.username} ${frame.type === "member.join" ? "a rejoint" : "a quitté"} le chat`,
+                sender: "system",
+                timestamp: new Date(frame.timestamp),
+              };
+            }
+
+            return newMessage ? [...prev, newMessage] : prev;
           });
         });
 
@@ -280,6 +342,7 @@ export default function ChannelChat({ serverId, channelId, channelName, onChanne
     };
 
     if (serverId && channelId) {
+      lastSeenMessageIdRef.current = null;
       initWebSocket();
     }
 
@@ -297,6 +360,19 @@ export default function ChannelChat({ serverId, channelId, channelName, onChanne
     };
   }, [serverId, channelId]);
 
+  // Signale la frappe au serveur au plus toutes les 3 s ; l'indicateur expire côté serveur
+  const handleInputChange = (value: string) => {
+    setInputMessage(value);
+    if (!socket || socket.readyState !== WebSocket.OPEN || !value.trim()) {
+      return;
+    }
+    const now = Date.now();
+    if (now - lastTypingSentRef.current > 3000) {
+      lastTypingSentRef.current = now;
+      socket.send(JSON.stringify({ v: 1, type: "typing.start", server_id: serverId, channel_id: channelId }));
+    }
+  };
+
   const handleSendMessage = (e: React.FormEvent) => {
     e.preventDefault();
 
@@ -314,8 +390,18 @@ export default function ChannelChat({ serverId, channelId, channelName, onChanne
       return;
     }
 
-    socket.send(inputMessage);
+    socket.send(
+      JSON.stringify({
+        v: 1,
+        type: "message.create",
+        server_id: serverId,
+        channel_id: channelId,
+        content: inputMessage,
+        nonce: crypto.randomUUID(),
+      })
+    );
     setInputMessage("");
+    lastTypingSentRef.current = 0;
   };
 
   const handleDeleteMessage = async (messageId: number) => {
@@ -587,11 +673,16 @@ export default function ChannelChat({ serverId, channelId, channelName, onChanne
       </div>
 
       <div className="bg-zinc-900 border-t border-zinc-900 p-4">
+        {Object.keys(typingUsers).length > 0 && (
+          <p className="text-xs text-gray-400 mb-2">
+            {Object.values(typingUsers).join(", ")} {Object.keys(typingUsers).length > 1 ? "écrivent" : "écrit"}...
+          </p>
+        )}
         <form onSubmit={handleSendMessage} className="flex gap-2">
           <input
             type="text"
             value={inputMessage}
-            onChange={(e) => setInputMessage(e.target.value)}
+            onChange={(e) => handleInputChange(e.target.value)}
             placeholder="Écrivez votre message..."
             className="flex-1 px-4 py-3 bg-zinc-800 border border-orange-500 rounded-xl focus:ring-2 focus:ring-orange-500 focus:border-transparent outline-none transition-all text-gray-200 placeholder-gray-500"
           />
@@ -8,7 +8,7 @@ interface User {
   username: string;
   avatar: string;
   email?: string;
-  status: 'online' | 'offline';
+  status: 'online' | 'idle' | 'dnd' | 'offline';
   role: string;
   roleColor: string;
 }
@@ -109,7 +109,7 @@ export default function MemberServeur({ members = [], currentUser, currentServer
   const StatusIndicator = ({ status }: { status: User['status'] }) => {
     return (
       <div className={`absolute bottom-0 right-0 w-3 h-3 rounded-full border-2 border-[#2f3136] ${
-        status === 'online' ? 'bg-green-500' : 'bg-gray-500'
+        status === 'online' ? 'bg-green-500' : status === 'idle' ? 'bg-yellow-500' : status === 'dnd' ? 'bg-red-500' : 'bg-gray-500'
       }`}></div>
     );
   };
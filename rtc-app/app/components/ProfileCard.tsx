@@ -13,7 +13,7 @@ interface ProfileCardProps {
   username: string;
   avatar: string;
   email?: string;
-  status?: 'online' | 'offline';
+  status?: 'online' | 'idle' | 'dnd' | 'offline';
   role?: string;
   roleColor?: string;
   commonServers?: Server[];
@@ -130,7 +130,7 @@ export default function ProfileCard({
               />
             </div>
             <div className={`absolute bottom-0 right-0 w-6 h-6 rounded-full border-4 border-zinc-900 ${
-              status === 'online' ? 'bg-green-500' : 'bg-gray-500'
+              status === 'online' ? 'bg-green-500' : status === 'idle' ? 'bg-yellow-500' : status === 'dnd' ? 'bg-red-500' : 'bg-gray-500'
             }`}></div>
           </div>
         </div>
@@ -164,8 +164,8 @@ export default function ProfileCard({
               <div className="flex-1 min-w-0">
                 <p className="text-xs text-gray-400 uppercase tracking-wide">Statut</p>
                 <div className="flex items-center gap-2">
-                  <div className={`w-2 h-2 rounded-full ${status === 'online' ? 'bg-green-500' : 'bg-gray-500'}`}></div>
-                  <p className="text-white capitalize">{status === 'online' ? 'En ligne' : 'Hors ligne'}</p>
+                  <div className={`w-2 h-2 rounded-full ${status === 'online' ? 'bg-green-500' : status === 'idle' ? 'bg-yellow-500' : status === 'dnd' ? 'bg-red-500' : 'bg-gray-500'}`}></div>
+                  <p className="text-white capitalize">{status === 'online' ? 'En ligne' : status === 'idle' ? 'Absent' : status === 'dnd' ? 'Ne pas déranger' : 'Hors ligne'}</p>
                 </div>
               </div>
             </div>
@@ -1,44 +1,249 @@
 use actix_web_actors::ws;
-use actix::{Actor, Addr, AsyncContext, Context, Handler, Recipient, Running, StreamHandler};
-use crate::models::{ChatMessage, ChatServer, JoinChat, LeaveChat, GetConnectedUsers, UserConnected};
-use crate::db_mongo_connection;
-use crate::db_mongo_setter;
-use std::env;
+use actix::{
+    Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, Context, ContextFutureSpawner, Handler,
+    MessageResult, Running, StreamHandler, WrapFuture, fut,
+};
+use crate::models::{
+    AppConfig, ChatEvent, ChatMessage, ChatServer, ChatSessionEntry, ClientCommand, CloseChannel, EndTimeout, JoinChat, LeaveChat,
+    GetConnectedUsers, GetPresences, PresenceStatus, RevokeChannelAccess, RevokeReason, RevokeServerAccess,
+    SetTyping, SetUserStatus, StartTimeout,
+    SubscribeChannel, TypingIndicator, UnsubscribeChannel, UserConnected, UserStatus, WsEnvelope, WsErrorCode, WS_PROTOCOL_VERSION,
+};
+use crate::db_store::ChatStore;
+use crate::error::FluxyError;
+use crate::services::{can_access_channel, load_missed_messages, store_message};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Durée au bout de laquelle un indicateur de frappe non rafraîchi expire.
+pub const TYPING_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Default for ChatServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl ChatServer {
     pub fn new() -> Self {
         Self { 
-            sessions: Vec::new(),
-            connected_users: std::collections::HashSet::new(),
-            user_session_count: std::collections::HashMap::new(),
+            sessions: HashMap::new(),
+            channels: HashMap::new(),
+            user_sessions: HashMap::new(),
+            next_session_id: 0,
+            connected_users: HashSet::new(),
+            typing: HashMap::new(),
+            user_servers: HashMap::new(),
+            server_users: HashMap::new(),
+            statuses: HashMap::new(),
+            timeouts: HashMap::new(),
+        }
+    }
+
+    fn user_status(&self, user_id: i64) -> UserStatus {
+        self.statuses.get(&user_id).copied().unwrap_or_default()
+    }
+
+    // Indexer les serveurs d'un utilisateur connecté pour retrouver ses co-membres connectés
+    fn add_user_servers(&mut self, user_id: i64, server_ids: impl IntoIterator<Item = i64>) {
+        let user_servers = self.user_servers.entry(user_id).or_default();
+        for server_id in server_ids {
+            user_servers.insert(server_id);
+            self.server_users.entry(server_id).or_default().insert(user_id);
+        }
+    }
+
+    fn remove_user_server(&mut self, user_id: i64, server_id: i64) {
+        if let Some(user_servers) = self.user_servers.get_mut(&user_id) {
+            user_servers.remove(&server_id);
+        }
+        if let Some(users) = self.server_users.get_mut(&server_id) {
+            users.remove(&user_id);
+            if users.is_empty() {
+                self.server_users.remove(&server_id);
+            }
         }
     }
 
-    fn send_to_channel(&self, server_id: i64, channel_id: i64, content: &str) {
+    // Pousser la présence d'un utilisateur aux sessions des autres membres connectés de ses serveurs
+    fn broadcast_presence(&self, user_id: i64, status: PresenceStatus) {
+        let Some(server_ids) = self.user_servers.get(&user_id) else {
+            return;
+        };
+        let co_members: HashSet<i64> = server_ids
+            .iter()
+            .filter_map(|server_id| self.server_users.get(server_id))
+            .flatten()
+            .copied()
+            .filter(|member_id| *member_id != user_id)
+            .collect();
+
+        let event = ChatEvent::PresenceUpdate { user_id, status };
+        for member_id in co_members {
+            for session_id in self.user_sessions.get(&member_id).into_iter().flatten() {
+                if let Some(session) = self.sessions.get(session_id) {
+                    // Événement hors channel : server_id / channel_id ne sont pas utilisés
+                    session.addr.do_send(ChatMessage { server_id: 0, channel_id: 0, content: event.clone() });
+                }
+            }
+        }
+    }
+
+    // Diffuser un événement de modération à toutes les sessions des membres connectés du serveur
+    fn send_to_server(&self, server_id: i64, content: &ChatEvent) {
+        for member_id in self.server_users.get(&server_id).into_iter().flatten() {
+            for session_id in self.user_sessions.get(member_id).into_iter().flatten() {
+                if let Some(session) = self.sessions.get(session_id) {
+                    // Événement hors channel : channel_id n'est pas utilisé
+                    session.addr.do_send(ChatMessage { server_id, channel_id: 0, content: content.clone() });
+                }
+            }
+        }
+    }
+
+    // Annoncer la fin du silence d'un membre (expiration programmée ou levée par un modérateur)
+    fn end_timeout(&mut self, key: (i64, i64), ctx: &mut Context<Self>) {
+        if let Some(handle) = self.timeouts.remove(&key) {
+            ctx.cancel_future(handle);
+        }
+        let (server_id, user_id) = key;
+        println!("[MODÉRATION] Fin du silence de l'utilisateur {} sur le serveur {}", user_id, server_id);
+        self.send_to_server(server_id, &ChatEvent::TimeoutEnd { server_id, user_id });
+    }
+
+    // Diffuser un événement aux sessions abonnées au channel, sauf éventuellement à la session émettrice
+    fn send_to_channel(&self, server_id: i64, channel_id: i64, content: &ChatEvent, skip: Option<usize>) {
+        let Some(subscribers) = self.channels.get(&(server_id, channel_id)) else {
+            return;
+        };
         println!(
             "Envoi du message au server {} channel {} pour {} sessions",
             server_id,
             channel_id,
-            self.sessions.len()
+            subscribers.len()
         );
-        for (session, s_id, ch_id) in &self.sessions {
-            if *s_id == server_id && *ch_id == channel_id {
-                let _ = session.do_send(ChatMessage {
+        for session_id in subscribers {
+            if Some(*session_id) == skip {
+                continue;
+            }
+            if let Some(session) = self.sessions.get(session_id) {
+                session.addr.do_send(ChatMessage {
                     server_id,
                     channel_id,
-                    content: content.to_owned(),
+                    content: content.clone(),
                 });
             }
         }
     }
     
-    // Retirer une session WebSocket de la liste
-    fn remove_session(&mut self, addr: &Recipient<ChatMessage>) {
-        let before = self.sessions.len();
-        self.sessions.retain(|(session_addr, _, _)| session_addr != addr);
-        let after = self.sessions.len();
-        if before != after {
-            println!("[DÉCONNEXION] Session WebSocket retirée. Sessions restantes: {}", after);
+    // Retirer un abonnement côté serveur et prévenir la session pour qu'elle cesse de l'afficher
+    fn revoke_subscription(
+        &mut self,
+        session_id: usize,
+        key: (i64, i64),
+        reason: RevokeReason,
+        ctx: &mut Context<Self>,
+    ) {
+        self.stop_typing((session_id, key.0, key.1), ctx);
+        let Some(session) = self.sessions.get_mut(&session_id) else {
+            return;
+        };
+        session.channels.remove(&key);
+        session.addr.do_send(ChatMessage {
+            server_id: key.0,
+            channel_id: key.1,
+            content: ChatEvent::SubscriptionRevoked { server_id: key.0, channel_id: key.1, reason },
+        });
+        if let Some(subscribers) = self.channels.get_mut(&key) {
+            subscribers.remove(&session_id);
+            if subscribers.is_empty() {
+                self.channels.remove(&key);
+            }
+        }
+    }
+
+    // Démarrer ou rafraîchir l'indicateur de frappe d'une session ; seul le premier signal est diffusé
+    fn start_typing(&mut self, msg: SetTyping, ctx: &mut Context<Self>) {
+        let subscribed = self
+            .sessions
+            .get(&msg.session_id)
+            .is_some_and(|session| session.channels.contains(&(msg.server_id, msg.channel_id)));
+        if !subscribed {
+            return;
+        }
+
+        let key = (msg.session_id, msg.server_id, msg.channel_id);
+        let handle = ctx.run_later(TYPING_TIMEOUT, move |act, ctx| act.stop_typing(key, ctx));
+        let indicator = TypingIndicator { handle, user_id: msg.user_id, username: msg.username.clone() };
+        match self.typing.insert(key, indicator) {
+            Some(previous) => {
+                ctx.cancel_future(previous.handle);
+            }
+            None => {
+                let event = ChatEvent::TypingStart {
+                    server_id: msg.server_id,
+                    channel_id: msg.channel_id,
+                    user_id: msg.user_id,
+                    username: msg.username,
+                };
+                self.send_to_channel(msg.server_id, msg.channel_id, &event, Some(msg.session_id));
+            }
+        }
+    }
+
+    // Arrêter l'indicateur de frappe (arrêt explicite, expiration, message envoyé ou session fermée)
+    fn stop_typing(&mut self, key: (usize, i64, i64), ctx: &mut Context<Self>) {
+        let Some(indicator) = self.typing.remove(&key) else {
+            return;
+        };
+        ctx.cancel_future(indicator.handle);
+        let (session_id, server_id, channel_id) = key;
+        let event = ChatEvent::TypingStop {
+            server_id,
+            channel_id,
+            user_id: indicator.user_id,
+            username: indicator.username,
+        };
+        self.send_to_channel(server_id, channel_id, &event, Some(session_id));
+    }
+
+    // Retirer une session WebSocket : seuls ses propres abonnements et son utilisateur sont touchés
+    fn remove_session(&mut self, session_id: usize, ctx: &mut Context<Self>) {
+        let Some(session) = self.sessions.remove(&session_id) else {
+            return;
+        };
+        for key in &session.channels {
+            self.stop_typing((session_id, key.0, key.1), ctx);
+            if let Some(subscribers) = self.channels.get_mut(key) {
+                subscribers.remove(&session_id);
+                if subscribers.is_empty() {
+                    self.channels.remove(key);
+                }
+            }
+        }
+        println!("[DÉCONNEXION] Session WebSocket retirée. Sessions restantes: {}", self.sessions.len());
+
+        let user_id = session.user_id;
+        if let Some(user_sessions) = self.user_sessions.get_mut(&user_id) {
+            user_sessions.remove(&session_id);
+            // Si l'utilisateur n'a plus de sessions WebSocket actives, le retirer de la liste des connectés
+            if user_sessions.is_empty() {
+                self.user_sessions.remove(&user_id);
+                self.connected_users.remove(&user_id);
+                if self.user_status(user_id) != UserStatus::Invisible {
+                    self.broadcast_presence(user_id, PresenceStatus::Offline);
+                }
+                if let Some(server_ids) = self.user_servers.remove(&user_id) {
+                    for server_id in server_ids {
+                        self.remove_user_server(user_id, server_id);
+                    }
+                }
+                println!("[DÉCONNEXION] Utilisateur {} déconnecté (plus de sessions WebSocket). Total connectés: {}", user_id, self.connected_users.len());
+            } else {
+                println!("[DÉCONNEXION] Utilisateur {} a encore {} session(s) WebSocket active(s)", user_id, user_sessions.len());
+            }
         }
     }
 }
@@ -52,7 +257,7 @@ impl Handler<ChatMessage> for ChatServer {
     type Result = ();
 
     fn handle(&mut self, msg: ChatMessage, _ctx: &mut Context<Self>) {
-        self.send_to_channel(msg.server_id, msg.channel_id, &msg.content);
+        self.send_to_channel(msg.server_id, msg.channel_id, &msg.content, None);
     }
 }
 
@@ -64,54 +269,206 @@ impl Handler<GetConnectedUsers> for ChatServer {
     }
 }
 
+impl Handler<GetPresences> for ChatServer {
+    type Result = MessageResult<GetPresences>;
+
+    fn handle(&mut self, _msg: GetPresences, _ctx: &mut Context<Self>) -> Self::Result {
+        // La présence ne dépend que des sessions WebSocket ouvertes : un login ou un logout via l'API
+        // ne modifie que connected_users et n'a donc pas de presence.update à émettre
+        MessageResult(
+            self.user_sessions
+                .keys()
+                .map(|user_id| (*user_id, self.user_status(*user_id).presence()))
+                .filter(|(_, status)| *status != PresenceStatus::Offline)
+                .collect(),
+        )
+    }
+}
+
 impl Handler<JoinChat> for ChatServer {
+    type Result = usize;
+
+    fn handle(&mut self, msg: JoinChat, _ctx: &mut Context<Self>) -> Self::Result {
+        self.next_session_id += 1;
+        let session_id = self.next_session_id;
+        self.sessions.insert(session_id, ChatSessionEntry {
+            addr: msg.addr,
+            user_id: msg.user_id,
+            channels: HashSet::new(),
+        });
+        let user_sessions = self.user_sessions.entry(msg.user_id).or_default();
+        user_sessions.insert(session_id);
+        let session_count = user_sessions.len();
+        println!("[CONNEXION] Utilisateur {} a maintenant {} session(s) WebSocket", msg.user_id, session_count);
+        self.connected_users.insert(msg.user_id);
+        self.add_user_servers(msg.user_id, msg.server_ids);
+
+        // Première session WebSocket : prévenir les co-membres (sauf en mode invisible)
+        let status = self.user_status(msg.user_id);
+        if session_count == 1 && status != UserStatus::Invisible {
+            self.broadcast_presence(msg.user_id, status.presence());
+        }
+        session_id
+    }
+}
+
+impl Handler<SubscribeChannel> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeChannel, _ctx: &mut Context<Self>) {
+        let key = (msg.server_id, msg.channel_id);
+        if let Some(session) = self.sessions.get_mut(&msg.session_id) {
+            session.channels.insert(key);
+            self.channels.entry(key).or_default().insert(msg.session_id);
+        }
+    }
+}
+
+impl Handler<UnsubscribeChannel> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnsubscribeChannel, ctx: &mut Context<Self>) {
+        self.stop_typing((msg.session_id, msg.server_id, msg.channel_id), ctx);
+        let key = (msg.server_id, msg.channel_id);
+        if let Some(session) = self.sessions.get_mut(&msg.session_id) {
+            session.channels.remove(&key);
+        }
+        if let Some(subscribers) = self.channels.get_mut(&key) {
+            subscribers.remove(&msg.session_id);
+            if subscribers.is_empty() {
+                self.channels.remove(&key);
+            }
+        }
+    }
+}
+
+impl Handler<SetUserStatus> for ChatServer {
     type Result = ();
 
-    fn handle(&mut self, msg: JoinChat, _ctx: &mut Context<Self>) {
-        // Si c'est une vraie session WebSocket (avec server_id et channel_id valides), l'ajouter aux sessions
-        // Sinon, c'est juste une mise à jour de statut (login)
-        if msg.server_id != 0 || msg.channel_id != 0 {
-            println!("Nouvelle session WebSocket ajoutée. Total: {}", self.sessions.len() + 1);
-            self.sessions.push((msg.addr, msg.server_id, msg.channel_id));
-            // Incrémenter le compteur de sessions pour cet utilisateur
-            let count = self.user_session_count.entry(msg.user_id).or_insert(0);
-            *count += 1;
-            println!("[CONNEXION] Utilisateur {} a maintenant {} session(s) WebSocket", msg.user_id, count);
+    fn handle(&mut self, msg: SetUserStatus, _ctx: &mut Context<Self>) {
+        let previous = self.user_status(msg.user_id);
+        self.statuses.insert(msg.user_id, msg.status);
+        if self.user_sessions.contains_key(&msg.user_id) && previous.presence() != msg.status.presence() {
+            self.broadcast_presence(msg.user_id, msg.status.presence());
+        }
+    }
+}
+
+impl Handler<SetTyping> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetTyping, ctx: &mut Context<Self>) {
+        if msg.typing {
+            self.start_typing(msg, ctx);
         } else {
-            // Si c'est juste un login sans WebSocket, initialiser le compteur à 0
-            self.user_session_count.entry(msg.user_id).or_insert(0);
+            self.stop_typing((msg.session_id, msg.server_id, msg.channel_id), ctx);
+        }
+    }
+}
+
+impl Handler<RevokeServerAccess> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: RevokeServerAccess, ctx: &mut Context<Self>) {
+        let Some(user_sessions) = self.user_sessions.get(&msg.user_id) else {
+            return;
+        };
+        let revoked: Vec<(usize, (i64, i64))> = user_sessions
+            .iter()
+            .filter_map(|session_id| self.sessions.get(session_id).map(|session| (session_id, session)))
+            .flat_map(|(session_id, session)| {
+                session
+                    .channels
+                    .iter()
+                    .filter(|(server_id, _)| *server_id == msg.server_id)
+                    .map(move |key| (*session_id, *key))
+            })
+            .collect();
+        for (session_id, key) in revoked {
+            self.revoke_subscription(session_id, key, msg.reason, ctx);
+        }
+        self.remove_user_server(msg.user_id, msg.server_id);
+    }
+}
+
+impl Handler<StartTimeout> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: StartTimeout, ctx: &mut Context<Self>) {
+        let timeout = msg.timeout;
+        let key = (timeout.server_id, timeout.user_id);
+        let handle = ctx.run_later(msg.duration, move |act, ctx| act.end_timeout(key, ctx));
+        // Un nouveau timeout remplace le précédent : seule la nouvelle fin sera annoncée
+        if let Some(previous) = self.timeouts.insert(key, handle) {
+            ctx.cancel_future(previous);
+        }
+        println!("[MODÉRATION] Utilisateur {} réduit au silence sur le serveur {} jusqu'à {}", timeout.user_id, timeout.server_id, timeout.until);
+        let event = ChatEvent::TimeoutStart {
+            server_id: timeout.server_id,
+            user_id: timeout.user_id,
+            moderator_id: timeout.moderator_id,
+            until: timeout.until,
+        };
+        self.send_to_server(key.0, &event);
+    }
+}
+
+impl Handler<EndTimeout> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: EndTimeout, ctx: &mut Context<Self>) {
+        self.end_timeout((msg.server_id, msg.user_id), ctx);
+    }
+}
+
+impl Handler<RevokeChannelAccess> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: RevokeChannelAccess, ctx: &mut Context<Self>) {
+        let key = (msg.server_id, msg.channel_id);
+        let Some(subscribers) = self.channels.get(&key) else {
+            return;
+        };
+        let revoked: Vec<usize> = subscribers
+            .iter()
+            .filter(|session_id| {
+                self.sessions
+                    .get(session_id)
+                    .is_some_and(|session| msg.user_ids.contains(&session.user_id))
+            })
+            .copied()
+            .collect();
+        for session_id in revoked {
+            self.revoke_subscription(session_id, key, RevokeReason::AccessRevoked, ctx);
+        }
+    }
+}
+
+impl Handler<CloseChannel> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: CloseChannel, ctx: &mut Context<Self>) {
+        let key = (msg.server_id, msg.channel_id);
+        let Some(subscribers) = self.channels.get(&key) else {
+            return;
+        };
+        for session_id in subscribers.clone() {
+            self.revoke_subscription(session_id, key, RevokeReason::ChannelDeleted, ctx);
         }
-        self.connected_users.insert(msg.user_id);
-        println!("[CONNEXION] Utilisateur {} connecté. Total connectés: {}", msg.user_id, self.connected_users.len());
     }
 }
 
 impl Handler<LeaveChat> for ChatServer {
     type Result = ();
 
-    fn handle(&mut self, msg: LeaveChat, _ctx: &mut Context<Self>) {
-        // Retirer la session de la liste si elle est fournie (fermeture WebSocket)
-        if let Some(addr) = msg.addr {
-            self.remove_session(&addr);
-            // Décrémenter le compteur de sessions pour cet utilisateur
-            if let Some(count) = self.user_session_count.get_mut(&msg.user_id) {
-                if *count > 0 {
-                    *count -= 1;
-                }
-                // Si l'utilisateur n'a plus de sessions WebSocket actives, le retirer de la liste des connectés
-                if *count == 0 {
-                    self.connected_users.remove(&msg.user_id);
-                    self.user_session_count.remove(&msg.user_id);
-                    println!("[DÉCONNEXION] Utilisateur {} déconnecté (plus de sessions WebSocket). Total connectés: {}", msg.user_id, self.connected_users.len());
-                } else {
-                    println!("[DÉCONNEXION] Utilisateur {} a encore {} session(s) WebSocket active(s)", msg.user_id, count);
-                }
-            }
+    fn handle(&mut self, msg: LeaveChat, ctx: &mut Context<Self>) {
+        // Retirer la session et ses abonnements si elle est fournie (fermeture WebSocket)
+        if let Some(session_id) = msg.session_id {
+            self.remove_session(session_id, ctx);
         } else {
-            // Si addr est None, c'est un logout explicite via API
-            // Dans ce cas, on retire toujours l'utilisateur, peu importe son compteur
+            // Si session_id est None, c'est un logout explicite via API
+            // Dans ce cas, on retire toujours l'utilisateur, peu importe ses sessions
             let was_connected = self.connected_users.remove(&msg.user_id);
-            self.user_session_count.remove(&msg.user_id);
             if was_connected {
                 println!("[DÉCONNEXION] Utilisateur {} déconnecté (logout explicite). Total connectés: {}", msg.user_id, self.connected_users.len());
             }
@@ -125,9 +482,6 @@ impl Handler<UserConnected> for ChatServer {
     fn handle(&mut self, msg: UserConnected, _ctx: &mut Context<Self>) {
         let was_present = self.connected_users.contains(&msg.user_id);
         self.connected_users.insert(msg.user_id);
-        // Initialiser le compteur à 0 si l'utilisateur n'a pas encore de sessions WebSocket
-        // Cela permet de le retirer correctement lors de la déconnexion
-        self.user_session_count.entry(msg.user_id).or_insert(0);
         // Log seulement si c'est une nouvelle connexion
         if !was_present {
             println!("[CONNEXION] Utilisateur {} connecté (via login/API). Total: {}", msg.user_id, self.connected_users.len());
@@ -135,12 +489,50 @@ impl Handler<UserConnected> for ChatServer {
     }
 }
 
+/// Session WebSocket d'un utilisateur, abonnée à un ensemble de channels (server_id, channel_id).
 pub struct ChatSession {
+    pub id: usize,
     pub name: String,
     pub server: Addr<ChatServer>,
     pub user_id: i64,
-    pub server_id: i64,
-    pub channel_id: i64,
+    pub subscriptions: HashSet<(i64, i64)>,
+    /// Abonnement demandé dans l'URL du WebSocket (server_id, channel_id, last_seen_message_id),
+    /// effectué au démarrage de la session.
+    pub initial_subscription: Option<(i64, i64, Option<i64>)>,
+    /// Channels dont l'historique manqué est en cours de reprise : le direct y est mis en attente.
+    pub replaying: HashMap<(i64, i64), Vec<ChatEvent>>,
+    /// Serveurs dont l'utilisateur est membre, transmis au ChatServer pour la présence.
+    pub server_ids: HashSet<i64>,
+    /// Dernière trame reçue du client (texte, ping ou pong).
+    pub hb: Instant,
+    pub config: AppConfig,
+    pub store: Arc<dyn ChatStore>,
+}
+
+impl ChatSession {
+    pub fn new(
+        name: String,
+        server: Addr<ChatServer>,
+        user_id: i64,
+        initial_subscription: Option<(i64, i64, Option<i64>)>,
+        server_ids: HashSet<i64>,
+        config: AppConfig,
+        store: Arc<dyn ChatStore>,
+    ) -> Self {
+        ChatSession {
+            id: 0,
+            name,
+            server,
+            user_id,
+            subscriptions: HashSet::new(),
+            initial_subscription,
+            replaying: HashMap::new(),
+            server_ids,
+            hb: Instant::now(),
+            config,
+            store,
+        }
+    }
 }
 
 impl Actor for ChatSession {
@@ -148,81 +540,330 @@ impl Actor for ChatSession {
 
     fn started(&mut self, ctx: &mut Self::Context) {
         println!("ChatSession démarrée pour: {}", self.name);
+        self.heartbeat(ctx);
         let addr = ctx.address().recipient();
-        let user_id = self.user_id;
+        // Attendre l'id de session avant de traiter les commandes du client
         self.server
-            .do_send(JoinChat { 
-                addr, 
-                server_id: self.server_id, 
-                channel_id: self.channel_id,
-                user_id: user_id,
-            });
-        self.server
-            .do_send(ChatMessage {
-                server_id: self.server_id,
-                channel_id: self.channel_id,
-                content: format!("{} a rejoint le chat", self.name),
-            });
+            .send(JoinChat { addr, user_id: self.user_id, server_ids: std::mem::take(&mut self.server_ids) })
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                match res {
+                    Ok(session_id) => act.id = session_id,
+                    Err(_) => ctx.stop(),
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+
+        if let Some((server_id, channel_id, last_seen_message_id)) = self.initial_subscription.take() {
+            self.subscribe(server_id, channel_id, last_seen_message_id, ctx);
+        }
     }
 
-    fn stopping(&mut self, ctx: &mut Self::Context) -> Running {
-        self.server
-            .do_send(ChatMessage {
-                server_id: self.server_id,
-                channel_id: self.channel_id,
-                content: format!("{} a quitté le chat", self.name),
+    fn stopping(&mut self, _ctx: &mut Self::Context) -> Running {
+        // Retirer cette session WebSocket et ses abonnements
+        self.server.do_send(LeaveChat {
+            user_id: self.user_id,
+            session_id: Some(self.id),
+        });
+
+        for (server_id, channel_id) in self.subscriptions.drain() {
+            self.server.do_send(ChatMessage {
+                server_id,
+                channel_id,
+                content: ChatEvent::MemberLeave {
+                    server_id,
+                    channel_id,
+                    user_id: self.user_id,
+                    username: self.name.clone(),
+                    timestamp: Utc::now().to_rfc3339(),
+                },
             });
-        
-        // Retirer cette session WebSocket de la liste
-        let addr = ctx.address().recipient();
+        }
+
+        Running::Stop
+    }
+}
+
+impl ChatSession {
+    /// Ping périodique du client ; sans réponse dans `client_timeout`, la session est arrêtée
+    /// et `stopping` retire la connexion du ChatServer.
+    fn heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(self.config.ws_heartbeat_interval, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > act.config.ws_client_timeout {
+                println!("[HEARTBEAT] Session {} de {} sans réponse, fermeture", act.id, act.name);
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    /// Sérialise un événement dans l'enveloppe versionnée et l'envoie à ce client uniquement.
+    fn send_event(&self, ctx: &mut ws::WebsocketContext<Self>, event: &ChatEvent) {
+        match serde_json::to_string(&WsEnvelope::new(event)) {
+            Ok(json) => ctx.text(json),
+            Err(e) => eprintln!("Erreur de sérialisation de l'événement WebSocket: {}", e),
+        }
+    }
+
+    fn send_error(
+        &self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        code: WsErrorCode,
+        message: &str,
+        nonce: Option<String>,
+    ) {
+        self.send_event(ctx, &ChatEvent::Error { code, message: message.to_string(), nonce, remaining_secs: None });
+    }
+
+    /// Abonne la session à un channel après vérification de l'appartenance au serveur et de la visibilité du channel.
+    fn subscribe(
+        &mut self,
+        server_id: i64,
+        channel_id: i64,
+        last_seen_message_id: Option<i64>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        if self.subscriptions.contains(&(server_id, channel_id)) {
+            self.send_event(ctx, &ChatEvent::Subscribed { server_id, channel_id });
+            return;
+        }
+
+        let store = self.store.clone();
         let user_id = self.user_id;
-        let server_addr = self.server.clone();
-        
-        // Envoyer LeaveChat avec l'adresse de la session pour la retirer de la liste
-        server_addr.do_send(LeaveChat { 
-            user_id,
-            addr: Some(addr),
+        async move { can_access_channel(store.as_ref(), user_id, server_id, channel_id).await }
+            .into_actor(self)
+            .map(move |res, act, ctx| match res {
+                Ok(true) => {
+                    act.subscriptions.insert((server_id, channel_id));
+                    match last_seen_message_id {
+                        Some(last_seen_id) => act.replay_and_subscribe(server_id, channel_id, last_seen_id, ctx),
+                        None => {
+                            act.server.do_send(SubscribeChannel { session_id: act.id, server_id, channel_id })
+                        }
+                    }
+                    act.send_event(ctx, &ChatEvent::Subscribed { server_id, channel_id });
+                    act.server.do_send(ChatMessage {
+                        server_id,
+                        channel_id,
+                        content: ChatEvent::MemberJoin {
+                            server_id,
+                            channel_id,
+                            user_id: act.user_id,
+                            username: act.name.clone(),
+                            timestamp: Utc::now().to_rfc3339(),
+                        },
+                    });
+                }
+                Ok(false) => act.send_error(ctx, WsErrorCode::Forbidden, "Accès refusé à ce channel", None),
+                Err(e) => {
+                    eprintln!("Erreur lors de la vérification de l'abonnement: {}", e);
+                    act.send_error(ctx, WsErrorCode::Storage, "Erreur de connexion à la base de données", None);
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Enregistre l'abonnement auprès du ChatServer puis rejoue les messages manqués.
+    /// Le direct reçu pendant la reprise est mis en attente, puis envoyé sans les doublons déjà rejoués.
+    fn replay_and_subscribe(
+        &mut self,
+        server_id: i64,
+        channel_id: i64,
+        last_seen_id: i64,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        let key = (server_id, channel_id);
+        self.replaying.insert(key, Vec::new());
+
+        let server = self.server.clone();
+        let subscribe = SubscribeChannel { session_id: self.id, server_id, channel_id };
+        let config = self.config.clone();
+        let store = self.store.clone();
+        async move {
+            // L'abonnement doit être actif avant la lecture en base pour ne perdre aucun message
+            server
+                .send(subscribe)
+                .await
+                .map_err(|_| FluxyError::Storage("ChatServer indisponible".to_string()))?;
+            load_missed_messages(store.as_ref(), &config, server_id, channel_id, last_seen_id).await
+        }
+        .into_actor(self)
+        .map(move |res, act, ctx| {
+            let live = act.replaying.remove(&key).unwrap_or_default();
+            if !act.subscriptions.contains(&key) {
+                return;
+            }
+
+            let mut last_replayed_id = last_seen_id;
+            match res {
+                Ok(missed) => {
+                    for event in &missed {
+                        match event {
+                            ChatEvent::MessageCreate { id, .. } => last_replayed_id = last_replayed_id.max(*id),
+                            ChatEvent::ReplayTruncated { .. } => {
+                                println!("[REPRISE] Reprise tronquée pour {} sur le channel {}", act.name, channel_id)
+                            }
+                            _ => {}
+                        }
+                        act.send_event(ctx, event);
+                    }
+                    println!("[REPRISE] Messages rejoués pour {} sur le channel {} jusqu'à l'id {}", act.name, channel_id, last_replayed_id);
+                }
+                Err(e) => {
+                    eprintln!("Erreur lors de la reprise de l'historique: {}", e);
+                    act.send_error(ctx, WsErrorCode::Storage, "Erreur lors de la reprise de l'historique", None);
+                }
+            }
+            for event in live {
+                match event {
+                    ChatEvent::MessageCreate { id, .. } if id <= last_replayed_id => {}
+                    event => act.send_event(ctx, &event),
+                }
+            }
+        })
+        .spawn(ctx);
+    }
+
+    fn unsubscribe(&mut self, server_id: i64, channel_id: i64, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.subscriptions.remove(&(server_id, channel_id)) {
+            self.server.do_send(UnsubscribeChannel { session_id: self.id, server_id, channel_id });
+            self.server.do_send(ChatMessage {
+                server_id,
+                channel_id,
+                content: ChatEvent::MemberLeave {
+                    server_id,
+                    channel_id,
+                    user_id: self.user_id,
+                    username: self.name.clone(),
+                    timestamp: Utc::now().to_rfc3339(),
+                },
+            });
+        }
+        self.send_event(ctx, &ChatEvent::Unsubscribed { server_id, channel_id });
+    }
+
+    /// Transmet l'état de frappe au ChatServer, qui le relaie aux autres abonnés du channel.
+    fn set_typing(&self, server_id: i64, channel_id: i64, typing: bool) {
+        self.server.do_send(SetTyping {
+            session_id: self.id,
+            user_id: self.user_id,
+            username: self.name.clone(),
+            server_id,
+            channel_id,
+            typing,
         });
-        
-        Running::Stop
+    }
+
+    /// Enregistre le message d'un channel abonné, puis le diffuse avec son id et accuse réception à l'auteur.
+    /// Le future bloque la session jusqu'à l'enregistrement pour conserver l'ordre des messages envoyés.
+    fn create_message(
+        &self,
+        server_id: i64,
+        channel_id: i64,
+        content: String,
+        nonce: Option<String>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        if !self.subscriptions.contains(&(server_id, channel_id)) {
+            self.send_error(ctx, WsErrorCode::NotSubscribed, "Session non abonnée à ce channel", nonce);
+            return;
+        }
+
+        let store = self.store.clone();
+        let user_id = self.user_id;
+        let stored_content = content.clone();
+        async move { store_message(store.as_ref(), user_id, server_id, channel_id, &stored_content).await }
+            .into_actor(self)
+            .map(move |res, act, ctx| match res {
+                Ok((id, timestamp)) => {
+                    act.set_typing(server_id, channel_id, false);
+                    act.server.do_send(ChatMessage {
+                        server_id,
+                        channel_id,
+                        content: ChatEvent::MessageCreate {
+                            id,
+                            server_id,
+                            channel_id,
+                            author_id: act.user_id,
+                            username: act.name.clone(),
+                            content,
+                            timestamp: timestamp.clone(),
+                        },
+                    });
+                    act.send_event(ctx, &ChatEvent::MessageAck { nonce, id, server_id, channel_id, timestamp });
+                }
+                Err(FluxyError::Forbidden(_)) => {
+                    act.send_error(ctx, WsErrorCode::Forbidden, "Accès refusé à ce channel", nonce);
+                }
+                Err(e @ FluxyError::TimedOut { .. }) => {
+                    let event = ChatEvent::Error {
+                        code: WsErrorCode::TimedOut,
+                        message: e.message().to_string(),
+                        nonce,
+                        remaining_secs: e.remaining_secs(),
+                    };
+                    act.send_event(ctx, &event);
+                }
+                Err(e) => {
+                    eprintln!("Erreur lors de l'enregistrement du message: {}", e);
+                    act.send_error(ctx, WsErrorCode::Storage, "Erreur lors de l'enregistrement du message", nonce);
+                }
+            })
+            .wait(ctx);
     }
 }
 
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ChatSession {
     fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        if msg.is_ok() {
+            self.hb = Instant::now();
+        }
         match msg {
             Ok(ws::Message::Text(text)) => {
                 println!("Message reçu de {}: {}", self.name, text);
-                let full = format!("{}: {}", self.name, text);
-                self.server.do_send(ChatMessage {
-                    server_id: self.server_id,
-                    channel_id: self.channel_id,
-                    content: full.clone(),
-                });
+                let envelope: WsEnvelope<ClientCommand> = match serde_json::from_str(&text) {
+                    Ok(envelope) => envelope,
+                    Err(e) => {
+                        self.send_error(ctx, WsErrorCode::InvalidPayload, &format!("Trame JSON invalide: {}", e), None);
+                        return;
+                    }
+                };
+                if envelope.v != WS_PROTOCOL_VERSION {
+                    self.send_error(
+                        ctx,
+                        WsErrorCode::UnsupportedVersion,
+                        &format!("Version de protocole non supportée: {}", envelope.v),
+                        None,
+                    );
+                    return;
+                }
 
-                let user_id = self.user_id;
-                let server_id = self.server_id;
-                let channel_id = self.channel_id;
-                let content = text.clone();
-
-                actix::spawn(async move {
-                    if let Ok(client) = db_mongo_connection::get_client().await {
-                        if let Ok(db_name) = env::var("MONGO_DATA_BASE_NAME") {
-                            if let Err(e) = db_mongo_setter::set_message(
-                                &client,
-                                &db_name,
-                                server_id,
-                                channel_id,
-                                &content,
-                                user_id,
-                            )
-                            .await
-                            {
-                                eprintln!("Erreur lors de l'enregistrement du message: {}", e);
-                            }
+                match envelope.event {
+                    ClientCommand::Subscribe { server_id, channel_id, last_seen_message_id } => {
+                        self.subscribe(server_id, channel_id, last_seen_message_id, ctx)
+                    }
+                    ClientCommand::Unsubscribe { server_id, channel_id } => {
+                        self.unsubscribe(server_id, channel_id, ctx)
+                    }
+                    ClientCommand::MessageCreate { server_id, channel_id, content, nonce } => {
+                        self.create_message(server_id, channel_id, content, nonce, ctx)
+                    }
+                    ClientCommand::PresenceSet { status } => {
+                        self.server.do_send(SetUserStatus { user_id: self.user_id, status })
+                    }
+                    ClientCommand::TypingStart { server_id, channel_id } => {
+                        if self.subscriptions.contains(&(server_id, channel_id)) {
+                            self.set_typing(server_id, channel_id, true);
+                        } else {
+                            self.send_error(ctx, WsErrorCode::NotSubscribed, "Session non abonnée à ce channel", None);
                         }
                     }
-                });
+                    ClientCommand::TypingStop { server_id, channel_id } => {
+                        self.set_typing(server_id, channel_id, false)
+                    }
+                }
             }
             Ok(ws::Message::Binary(_)) => {}
             Ok(ws::Message::Close(_)) => {
@@ -232,8 +873,10 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ChatSession {
             Ok(ws::Message::Ping(bytes)) => {
                 ctx.pong(&bytes);
             }
+            Ok(ws::Message::Pong(_)) => {}
             Err(e) => {
                 eprintln!("Erreur WebSocket: {:?}", e);
+                ctx.stop();
             }
             _ => {}
         }
@@ -244,8 +887,25 @@ impl Handler<ChatMessage> for ChatSession {
     type Result = ();
 
     fn handle(&mut self, msg: ChatMessage, ctx: &mut ws::WebsocketContext<Self>) {
-        if msg.server_id == self.server_id && msg.channel_id == self.channel_id {
-            ctx.text(msg.content);
+        let key = (msg.server_id, msg.channel_id);
+        match msg.content {
+            // Abonnement retiré par le ChatServer : ne plus rien recevoir de ce channel
+            ChatEvent::SubscriptionRevoked { .. } => {
+                if self.subscriptions.remove(&key) {
+                    self.send_event(ctx, &msg.content);
+                }
+            }
+            // La présence et la modération concernent tout le serveur, pas un channel en particulier
+            ChatEvent::PresenceUpdate { .. } | ChatEvent::TimeoutStart { .. } | ChatEvent::TimeoutEnd { .. } => {
+                self.send_event(ctx, &msg.content)
+            }
+            _ => {
+                if let Some(pending) = self.replaying.get_mut(&key) {
+                    pending.push(msg.content);
+                } else if self.subscriptions.contains(&key) {
+                    self.send_event(ctx, &msg.content);
+                }
+            }
         }
     }
 }
@@ -1,4 +1,35 @@
-use crate::models::AppConfig;
+use crate::models::{AppConfig, MongoPoolConfig, StorageBackend};
+use std::time::Duration;
+
+// Lit une durée en secondes depuis l'environnement, avec une valeur par défaut
+fn duration_from_env(name: &str, default_secs: u64) -> Duration {
+    let secs = match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} doit être un nombre de secondes", name)),
+        Err(_) => default_secs,
+    };
+    Duration::from_secs(secs)
+}
+
+// Lit la période d'une tâche répétée : une période nulle ferait paniquer tokio::time::interval
+fn interval_from_env(name: &str, default_secs: u64) -> Duration {
+    let interval = duration_from_env(name, default_secs);
+    if interval.is_zero() {
+        panic!("{} doit être un nombre de secondes strictement positif", name);
+    }
+    interval
+}
+
+// Lit un entier depuis l'environnement, avec une valeur par défaut
+fn number_from_env(name: &str, default: u32) -> u32 {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} doit être un nombre entier", name)),
+        Err(_) => default,
+    }
+}
 
 impl AppConfig {
     pub fn from_env() -> AppConfig {
@@ -6,12 +37,46 @@ impl AppConfig {
         let supabase_anon_key = std::env::var("SUPABASE_ANON_KEY").expect("SUPABASE_ANON_KEY manquant");
         let supabase_service_role_key = std::env::var("SUPABASE_SERVICE_ROLE_KEY").expect("SUPABASE_SERVICE_ROLE_KEY manquant");
         let session_key = std::env::var("SESSION_KEY").expect("SESSION_KEY manquant");
+        let ws_heartbeat_interval = interval_from_env("WS_HEARTBEAT_INTERVAL_SECS", 5);
+        let ws_client_timeout = duration_from_env("WS_CLIENT_TIMEOUT_SECS", 10);
+        let ban_expiry_interval = interval_from_env("BAN_EXPIRY_INTERVAL_SECS", 60);
+        let ws_replay_limit = i64::from(number_from_env("WS_REPLAY_LIMIT", 100));
 
         AppConfig {
             supabase_url,
             supabase_anon_key,
             supabase_service_role_key,
             session_key,
+            ws_heartbeat_interval,
+            ws_client_timeout,
+            ban_expiry_interval,
+            ws_replay_limit,
+        }
+    }
+}
+
+impl StorageBackend {
+    /// STORAGE_BACKEND : "mongo" (par défaut) ou "sql" avec DATABASE_URL (sqlite://fluxy.db?mode=rwc, postgres://...)
+    pub fn from_env() -> StorageBackend {
+        match std::env::var("STORAGE_BACKEND").unwrap_or_default().to_lowercase().as_str() {
+            "" | "mongo" => StorageBackend::Mongo(MongoPoolConfig::from_env()),
+            "sql" => StorageBackend::Sql(std::env::var("DATABASE_URL").expect("DATABASE_URL manquant")),
+            other => panic!("STORAGE_BACKEND inconnu: {} (mongo ou sql)", other),
+        }
+    }
+}
+
+impl MongoPoolConfig {
+    pub fn from_env() -> MongoPoolConfig {
+        let default = MongoPoolConfig::default();
+        MongoPoolConfig {
+            max_pool_size: number_from_env("MONGO_MAX_POOL_SIZE", default.max_pool_size),
+            min_pool_size: number_from_env("MONGO_MIN_POOL_SIZE", default.min_pool_size),
+            connect_timeout: duration_from_env("MONGO_CONNECT_TIMEOUT_SECS", default.connect_timeout.as_secs()),
+            server_selection_timeout: duration_from_env(
+                "MONGO_SERVER_SELECTION_TIMEOUT_SECS",
+                default.server_selection_timeout.as_secs(),
+            ),
         }
     }
 }
@@ -0,0 +1,909 @@
+//! db_memory.rs :
+//!     - MemoryStore :
+//!     implémentation en mémoire du ChatStore (voir db_store.rs). les documents ont la même forme que dans MongoDB
+//!     (server, channel, message) et les règles de permission sont les mêmes que dans les fichiers db_mongo_*.
+//!     les lectures passent par les mêmes modèles typés que MongoDB (voir models.rs) : un document qui ne correspond pas
+//!     au modèle renvoie une erreur au lieu d'être ignoré.
+//!     chaque MemoryStore est indépendant : les tests peuvent en créer un chacun et tourner en parallèle sans base de données.
+
+use crate::db_mongo_delete;
+use crate::db_mongo_setter;
+use crate::db_mongo_update;
+use crate::db_store::ChatStore;
+use crate::error::{FluxyError, FluxyResult};
+use crate::models::{format_date, AuditEntry, AuditLogFilter, Ban, Channel, Invite, MemberTimeout, Message, MessageRevision, NewAuditEntry, OverwriteKind, PermissionOverwrite, Permissions, RepairReport, Role, Server};
+use async_trait::async_trait;
+use chrono::Utc;
+use mongodb::bson::{self, Bson, Document};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Default)]
+struct MemoryData {
+    collections: HashMap<String, Vec<Document>>,
+    counters: HashMap<String, i64>,
+}
+
+/// MemoryStore :
+/// collections de documents gardées en mémoire, protégées par un mutex
+#[derive(Default)]
+pub struct MemoryStore {
+    data: Mutex<MemoryData>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryData> {
+        self.data.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// find :
+    /// renvoie les documents de la collection qui vérifient le filtre
+    fn find(&self, collection: &str, filter: impl Fn(&Document) -> bool) -> Vec<Document> {
+        self.lock()
+            .collections
+            .get(collection)
+            .map(|docs| docs.iter().filter(|doc| filter(doc)).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// find_as :
+    /// comme find, mais convertit chaque document dans son modèle (même erreur que db_mongo_getter si le schéma a dérivé)
+    fn find_as<T: DeserializeOwned>(&self, collection: &str, filter: impl Fn(&Document) -> bool) -> io::Result<Vec<T>> {
+        self.find(collection, filter)
+            .into_iter()
+            .map(|doc| {
+                bson::from_document(doc).map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("Document {} invalide: {}", collection, e))
+                })
+            })
+            .collect()
+    }
+
+    fn insert(&self, collection: &str, value: &impl Serialize) -> FluxyResult<()> {
+        let document = bson::to_document(value).map_err(|e| FluxyError::Storage(e.to_string()))?;
+        self.lock().collections.entry(collection.to_string()).or_default().push(document);
+        Ok(())
+    }
+
+    /// update_one :
+    /// modifie le premier document de la collection qui vérifie le filtre, sous un seul verrou. renvoie faux si aucun ne le vérifie
+    fn update_one(&self, collection: &str, filter: impl Fn(&Document) -> bool, update: impl FnOnce(&mut Document)) -> bool {
+        match self
+            .lock()
+            .collections
+            .get_mut(collection)
+            .and_then(|docs| docs.iter_mut().find(|doc| filter(doc)))
+        {
+            Some(doc) => {
+                update(doc);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn delete_many(&self, collection: &str, filter: impl Fn(&Document) -> bool) {
+        if let Some(docs) = self.lock().collections.get_mut(collection) {
+            docs.retain(|doc| !filter(doc));
+        }
+    }
+
+    /// cascade_delete :
+    /// équivalent de db_mongo_delete::cascade_delete : le serveur, les channels et leurs messages sont retirés sous un seul verrou
+    fn cascade_delete(&self, server_id: Option<i64>, channel_ids: &[i64]) {
+        let mut data = self.lock();
+        if let Some(server_id) = server_id {
+            data.collections.entry("server".to_string()).or_default().retain(|doc| !matches(doc, "id", server_id));
+            data.collections.entry("role".to_string()).or_default().retain(|doc| !matches(doc, "server_id", server_id));
+            data.collections.entry("invite".to_string()).or_default().retain(|doc| !matches(doc, "server_id", server_id));
+            data.collections.entry("ban".to_string()).or_default().retain(|doc| !matches(doc, "server_id", server_id));
+            data.collections.entry("timeout".to_string()).or_default().retain(|doc| !matches(doc, "server_id", server_id));
+            data.collections.entry("audit_log".to_string()).or_default().retain(|doc| !matches(doc, "server_id", server_id));
+        }
+        for (collection, field) in [("channel", "id"), ("message", "channel_id")] {
+            data.collections
+                .entry(collection.to_string())
+                .or_default()
+                .retain(|doc| !channel_ids.iter().any(|channel_id| matches(doc, field, *channel_id)));
+        }
+    }
+
+    /// server_with_roles :
+    /// équivalent de db_mongo_getter::get_server_with_roles
+    async fn server_with_roles(&self, server_id: i64) -> io::Result<Option<(Server, Vec<Role>)>> {
+        let Some(server) = self.get_server(&server_id).await? else {
+            return Ok(None);
+        };
+        let roles = self.get_roles_of_server(&server_id).await?;
+        Ok(Some((server, roles)))
+    }
+
+    /// channel_with_server :
+    /// équivalent de db_mongo_getter::get_channel_with_server
+    async fn channel_with_server(&self, channel_id: i64) -> io::Result<Option<(Channel, Server, Vec<Role>)>> {
+        let Some(channel) = self.get_channel_by_id(&channel_id).await? else {
+            return Ok(None);
+        };
+        Ok(self.server_with_roles(channel.server_id).await?.map(|(server, roles)| (channel, server, roles)))
+    }
+
+    /// set_overwrites :
+    /// remplace les exceptions du channel (même tableau overwrites que dans MongoDB)
+    fn set_overwrites(&self, channel: &Channel) -> FluxyResult<()> {
+        let overwrites = bson::to_bson(&channel.overwrites).map_err(|e| FluxyError::Storage(e.to_string()))?;
+        self.update_one("channel", |doc| matches(doc, "id", channel.id), |doc| {
+            doc.insert("overwrites", overwrites);
+        });
+        Ok(())
+    }
+
+    /// role_with_server :
+    /// rôle, serveur du rôle et rôles de ce serveur, pour les vérifications de gestion des rôles
+    async fn role_with_server(&self, role_id: i64) -> FluxyResult<(Role, Server, Vec<Role>)> {
+        let Some(role) = self.get_role_by_id(&role_id).await? else {
+            return Err(FluxyError::NotFound("Rôle introuvable".to_string()));
+        };
+        let Some((server, roles)) = self.server_with_roles(role.server_id).await? else {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        };
+        Ok((role, server, roles))
+    }
+
+    /// pull_member :
+    /// équivalent de db_mongo_delete::pull_member : l'utilisateur quitte les membres, les admins et les rôles du serveur
+    fn pull_member(&self, server_id: i64, user_to_remove: i64) {
+        self.update_one("server", |doc| matches(doc, "id", server_id), |doc| {
+            pull(doc, "admin_id", user_to_remove);
+            pull(doc, "member_id", user_to_remove);
+        });
+        if let Some(docs) = self.lock().collections.get_mut("role") {
+            for doc in docs.iter_mut().filter(|doc| matches(doc, "server_id", server_id)) {
+                pull(doc, "member_id", user_to_remove);
+            }
+        }
+    }
+
+    /// ids :
+    /// ids des documents de la collection
+    fn ids(&self, collection: &str) -> Vec<i64> {
+        self.find(collection, |_| true).iter().filter_map(|doc| doc.get_i64("id").ok()).collect()
+    }
+
+    /// delete_orphans :
+    /// supprime les documents dont le champ ne référence aucun des ids donnés, et renvoie leur nombre
+    fn delete_orphans(&self, collection: &str, field: &str, parent_ids: &[i64]) -> u64 {
+        let mut data = self.lock();
+        let docs = data.collections.entry(collection.to_string()).or_default();
+        let before = docs.len();
+        docs.retain(|doc| doc.get_i64(field).is_ok_and(|parent_id| parent_ids.contains(&parent_id)));
+        (before - docs.len()) as u64
+    }
+
+    /// next_id :
+    /// équivalent de db_mongo_setter::next_id : le compteur part du plus grand id déjà présent dans la collection
+    fn next_id(&self, collection: &str) -> i64 {
+        let mut data = self.lock();
+        let last_id = data
+            .collections
+            .get(collection)
+            .and_then(|docs| docs.iter().filter_map(|doc| doc.get_i64("id").ok()).max())
+            .unwrap_or(0);
+        let counter = data.counters.entry(collection.to_string()).or_insert(last_id);
+        *counter += 1;
+        *counter
+    }
+}
+
+/// is_invite :
+/// vrai si le document de la collection invite porte ce code
+fn is_invite(doc: &Document, code: &str) -> bool {
+    doc.get_str("code").is_ok_and(|invite_code| invite_code == code)
+}
+
+/// matches :
+/// équivalent du filtre MongoDB {field: value} : vrai si le champ vaut value ou, pour un tableau, s'il le contient
+fn matches(doc: &Document, field: &str, value: i64) -> bool {
+    match doc.get(field) {
+        Some(Bson::Array(values)) => values.iter().any(|v| v.as_i64() == Some(value)),
+        Some(v) => v.as_i64() == Some(value),
+        None => false,
+    }
+}
+
+/// équivalent de $addToSet
+fn add_to_set(doc: &mut Document, field: &str, value: i64) {
+    if let Ok(values) = doc.get_array_mut(field) {
+        if !values.iter().any(|v| v.as_i64() == Some(value)) {
+            values.push(Bson::Int64(value));
+        }
+    } else {
+        doc.insert(field, vec![Bson::Int64(value)]);
+    }
+}
+
+/// équivalent de $pull
+fn pull(doc: &mut Document, field: &str, value: i64) {
+    if let Ok(values) = doc.get_array_mut(field) {
+        values.retain(|v| v.as_i64() != Some(value));
+    }
+}
+
+fn sort_by_id(messages: &mut [Message]) {
+    messages.sort_by_key(|message| message.id);
+}
+
+#[async_trait]
+impl ChatStore for MemoryStore {
+    async fn ping(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn count_documents(&self, collection: &str) -> io::Result<u64> {
+        Ok(self.lock().collections.get(collection).map_or(0, |docs| docs.len() as u64))
+    }
+
+    async fn get_server(&self, server_id: &i64) -> io::Result<Option<Server>> {
+        Ok(self.find_as("server", |doc| matches(doc, "id", *server_id))?.into_iter().next())
+    }
+
+    async fn get_servers_by_owner(&self, owner_id: &i64) -> io::Result<Vec<Server>> {
+        self.find_as("server", |doc| matches(doc, "owner_id", *owner_id))
+    }
+
+    async fn get_servers_by_member(&self, user_id: &i64) -> io::Result<Vec<Server>> {
+        self.find_as("server", |doc| matches(doc, "member_id", *user_id))
+    }
+
+    async fn get_channels_of_server(&self, server_id: &i64) -> io::Result<Vec<Channel>> {
+        self.find_as("channel", |doc| matches(doc, "server_id", *server_id))
+    }
+
+    async fn get_messages_of_channel(&self, channel_id: &i64) -> io::Result<Vec<Message>> {
+        self.find_as("message", |doc| matches(doc, "channel_id", *channel_id))
+    }
+
+    async fn get_messages_page(&self, channel_id: &i64, before: Option<i64>, after: Option<i64>, limit: i64) -> io::Result<Vec<Message>> {
+        let mut messages: Vec<Message> = self.find_as("message", |doc| {
+            let id = doc.get_i64("id").unwrap_or(0);
+            matches(doc, "channel_id", *channel_id)
+                && before.is_none_or(|before| id < before)
+                && after.is_none_or(|after| id > after)
+        })?;
+        sort_by_id(&mut messages);
+
+        // Même ordre que db_mongo_getter::get_messages_page : les plus récents, sauf avec after seul
+        let ascending = after.is_some() && before.is_none();
+        let limit = limit.max(0) as usize;
+        if ascending {
+            messages.truncate(limit);
+        } else if messages.len() > limit {
+            messages.drain(..messages.len() - limit);
+        }
+        Ok(messages)
+    }
+
+    async fn get_messages_of_channel_after(&self, channel_id: &i64, last_seen_id: &i64, limit: i64) -> io::Result<Vec<Message>> {
+        let mut messages: Vec<Message> = self.find_as("message", |doc| {
+            matches(doc, "channel_id", *channel_id) && doc.get_i64("id").unwrap_or(0) > *last_seen_id
+        })?;
+        sort_by_id(&mut messages);
+        messages.truncate(limit.max(0) as usize);
+        Ok(messages)
+    }
+
+    async fn get_message_by_id(&self, message_id: &i64) -> io::Result<Option<Message>> {
+        Ok(self.find_as("message", |doc| matches(doc, "id", *message_id))?.into_iter().next())
+    }
+
+    async fn get_message_revisions(&self, message_id: &i64) -> io::Result<Vec<MessageRevision>> {
+        let Some(message) = self.find("message", |doc| matches(doc, "id", *message_id)).into_iter().next() else {
+            return Ok(Vec::new());
+        };
+        match message.get("revisions") {
+            Some(revisions) => bson::from_bson(revisions.clone()).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("Document message invalide: {}", e))
+            }),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn get_channel_by_id(&self, channel_id: &i64) -> io::Result<Option<Channel>> {
+        Ok(self.find_as("channel", |doc| matches(doc, "id", *channel_id))?.into_iter().next())
+    }
+
+    async fn get_server_id_by_message_id(&self, message_id: &i64) -> io::Result<i64> {
+        let Some(message) = self.get_message_by_id(message_id).await? else {
+            return Ok(0);
+        };
+        Ok(self.get_channel_by_id(&message.channel_id).await?.map_or(0, |channel| channel.server_id))
+    }
+
+    async fn get_last_id(&self, collection: &str) -> io::Result<i64> {
+        Ok(self
+            .find(collection, |_| true)
+            .iter()
+            .filter_map(|doc| doc.get_i64("id").ok())
+            .max()
+            .unwrap_or(0))
+    }
+
+    async fn is_owner(&self, server_id: &i64, user_id: &i64) -> io::Result<bool> {
+        Ok(!self
+            .find("server", |doc| matches(doc, "id", *server_id) && matches(doc, "owner_id", *user_id))
+            .is_empty())
+    }
+
+    async fn is_admin(&self, server_id: &i64, user_id: &i64) -> io::Result<bool> {
+        Ok(!self
+            .find("server", |doc| matches(doc, "id", *server_id) && matches(doc, "admin_id", *user_id))
+            .is_empty())
+    }
+
+    async fn is_member(&self, server_id: &i64, user_id: &i64) -> io::Result<bool> {
+        Ok(!self
+            .find("server", |doc| matches(doc, "id", *server_id) && matches(doc, "member_id", *user_id))
+            .is_empty())
+    }
+
+    async fn is_channel_of_server(&self, server_id: i64, channel_id: i64) -> io::Result<bool> {
+        Ok(!self
+            .find("channel", |doc| matches(doc, "server_id", server_id) && matches(doc, "id", channel_id))
+            .is_empty())
+    }
+
+    async fn verify_link_exist(&self, link_code: &str) -> io::Result<bool> {
+        Ok(!self.find("invite", |doc| is_invite(doc, link_code)).is_empty())
+    }
+
+    async fn get_roles_of_server(&self, server_id: &i64) -> io::Result<Vec<Role>> {
+        let mut roles: Vec<Role> = self.find_as("role", |doc| matches(doc, "server_id", *server_id))?;
+        // Même ordre que db_mongo_getter::get_roles_of_server : du plus haut au plus bas
+        roles.sort_by_key(|role| (std::cmp::Reverse(role.position), role.id));
+        Ok(roles)
+    }
+
+    async fn get_role_by_id(&self, role_id: &i64) -> io::Result<Option<Role>> {
+        Ok(self.find_as("role", |doc| matches(doc, "id", *role_id))?.into_iter().next())
+    }
+
+    async fn has_permission(&self, server_id: &i64, user_id: &i64, permission: Permissions) -> io::Result<bool> {
+        Ok(self
+            .server_with_roles(*server_id)
+            .await?
+            .is_some_and(|(server, roles)| server.permissions_of(&roles, *user_id).contains(permission)))
+    }
+
+    async fn get_channel_permissions(&self, channel_id: &i64, user_id: &i64) -> io::Result<Permissions> {
+        Ok(self
+            .channel_with_server(*channel_id)
+            .await?
+            .map_or(Permissions::NONE, |(channel, server, roles)| channel.permissions_of(&server, &roles, *user_id)))
+    }
+
+    async fn get_ban(&self, server_id: &i64, user_id: &i64) -> io::Result<Option<Ban>> {
+        Ok(self
+            .find_as::<Ban>("ban", |doc| matches(doc, "server_id", *server_id) && matches(doc, "user_id", *user_id))?
+            .into_iter()
+            .next())
+    }
+
+    async fn get_bans_of_server(&self, server_id: &i64) -> io::Result<Vec<Ban>> {
+        let mut bans: Vec<Ban> = self.find_as("ban", |doc| matches(doc, "server_id", *server_id))?;
+        bans.sort_by(|a, b| b.banned_at.cmp(&a.banned_at).then(a.user_id.cmp(&b.user_id)));
+        Ok(bans)
+    }
+
+    async fn get_timeout(&self, server_id: &i64, user_id: &i64) -> io::Result<Option<MemberTimeout>> {
+        Ok(self
+            .find_as::<MemberTimeout>("timeout", |doc| matches(doc, "server_id", *server_id) && matches(doc, "user_id", *user_id))?
+            .into_iter()
+            .next())
+    }
+
+    async fn get_active_timeouts(&self, now: &str) -> io::Result<Vec<MemberTimeout>> {
+        self.find_as("timeout", |doc| doc.get_str("until").is_ok_and(|until| until > now))
+    }
+
+    async fn get_invite(&self, link_code: &str) -> io::Result<Option<Invite>> {
+        Ok(self.find_as::<Invite>("invite", |doc| is_invite(doc, link_code))?.into_iter().next())
+    }
+
+    async fn get_invites_of_server(&self, server_id: &i64) -> io::Result<Vec<Invite>> {
+        let mut invites: Vec<Invite> = self.find_as("invite", |doc| matches(doc, "server_id", *server_id))?;
+        invites.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.code.cmp(&b.code)));
+        Ok(invites)
+    }
+
+    async fn get_audit_log(&self, server_id: &i64, filter: &AuditLogFilter) -> io::Result<Vec<AuditEntry>> {
+        let action = filter
+            .action
+            .map(|action| bson::to_bson(&action).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string())))
+            .transpose()?;
+        let mut entries: Vec<AuditEntry> = self.find_as("audit_log", |doc| {
+            matches(doc, "server_id", *server_id)
+                && action.as_ref().is_none_or(|action| doc.get("action") == Some(action))
+                && filter.user_id.is_none_or(|user_id| matches(doc, "actor_id", user_id))
+                && filter.before.is_none_or(|before| doc.get_i64("id").is_ok_and(|id| id < before))
+        })?;
+
+        // Même ordre que db_mongo_getter::get_audit_log : les plus récentes d'abord
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.id));
+        entries.truncate(filter.limit.max(0) as usize);
+        Ok(entries)
+    }
+
+    async fn set_server(&self, owner_id: i64, name: &str, image: Option<String>) -> FluxyResult<()> {
+        if name.trim().is_empty() {
+            return Err(FluxyError::Validation("Le nom du serveur est obligatoire".to_string()));
+        }
+        let server = Server {
+            id: self.next_id("server"),
+            name: name.to_string(),
+            owner_id,
+            admin_id: vec![],
+            member_id: vec![owner_id],
+            image: image.filter(|img| !img.trim().is_empty()),
+        };
+        self.insert("server", &server)
+    }
+
+    async fn set_channel(&self, server_id: i64, name: &str, user_id: i64) -> FluxyResult<()> {
+        if name.trim().is_empty() {
+            return Err(FluxyError::Validation("Le nom du channel est obligatoire".to_string()));
+        }
+        if !self.has_permission(&server_id, &user_id, Permissions::MANAGE_CHANNELS).await? {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de créer un channel".to_string()));
+        }
+
+        let id = self.next_id("channel");
+        let last_position = self.get_channels_of_server(&server_id).await?.len() as i64;
+        self.insert("channel", &Channel {
+            id,
+            server_id,
+            name: name.to_string(),
+            position: last_position + 1,
+            private: false,
+            overwrites: vec![],
+        })
+    }
+
+    async fn set_message(&self, server_id: i64, channel_id: i64, message: &str, user_id: i64) -> FluxyResult<Message> {
+        let is_channel = self.is_channel_of_server(server_id, channel_id).await?;
+        let permissions = self.get_channel_permissions(&channel_id, &user_id).await?;
+        db_mongo_setter::check_send_message(is_channel, permissions, message)?;
+        db_mongo_setter::check_not_timed_out(self.get_timeout(&server_id, &user_id).await?, permissions, Utc::now())?;
+
+        let message = Message {
+            id: self.next_id("message"),
+            channel_id,
+            message: message.to_string(),
+            user: user_id,
+            time: Utc::now().to_rfc3339(),
+            edited_at: None,
+        };
+        self.insert("message", &message)?;
+        Ok(message)
+    }
+
+    async fn add_member_to_server(&self, server_id: i64, user_id: i64) -> FluxyResult<()> {
+        if self.get_server(&server_id).await?.is_none() {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        }
+        db_mongo_setter::check_not_banned(self.get_ban(&server_id, &user_id).await?)?;
+        self.update_one("server", |doc| matches(doc, "id", server_id), |doc| add_to_set(doc, "member_id", user_id));
+        Ok(())
+    }
+
+    async fn add_admin_to_server(&self, server_id: i64, user_id: i64, user_to_add: i64) -> FluxyResult<()> {
+        if !self.is_owner(&server_id, &user_id).await? {
+            return Err(FluxyError::Forbidden("Seul le fondateur peut nommer un administrateur".to_string()));
+        }
+        if !self.is_member(&server_id, &user_to_add).await? {
+            return Err(FluxyError::Validation("L'utilisateur n'est pas membre du serveur".to_string()));
+        }
+        self.update_one("server", |doc| matches(doc, "id", server_id), |doc| add_to_set(doc, "admin_id", user_to_add));
+        Ok(())
+    }
+
+    async fn switch_owner(&self, server_id: i64, user_id: i64, user_to_replace: i64) -> FluxyResult<()> {
+        if !self.is_owner(&server_id, &user_id).await? {
+            return Err(FluxyError::Forbidden("Seul le fondateur peut transférer la propriété du serveur".to_string()));
+        }
+        db_mongo_setter::check_new_owner(
+            self.is_member(&server_id, &user_to_replace).await?,
+            self.get_ban(&server_id, &user_to_replace).await?,
+        )?;
+        self.update_one("server", |doc| matches(doc, "id", server_id), |doc| {
+            doc.insert("owner_id", user_to_replace);
+        });
+        Ok(())
+    }
+
+    async fn create_invite(&self, server_id: i64, user_id: i64, expires_at: Option<String>, max_uses: Option<i64>) -> FluxyResult<Invite> {
+        if !self.has_permission(&server_id, &user_id, Permissions::CREATE_INVITES).await? {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de créer un lien d'invitation".to_string()));
+        }
+        let mut link_code = db_mongo_setter::random_string();
+        while self.verify_link_exist(&link_code).await? {
+            link_code = db_mongo_setter::random_string();
+        }
+        let invite = db_mongo_setter::new_invite(link_code, server_id, user_id, expires_at, max_uses);
+        db_mongo_setter::check_invite(&invite)?;
+        self.insert("invite", &invite)?;
+        Ok(invite)
+    }
+
+    async fn join_by_link(&self, link: &str, user_id: i64) -> FluxyResult<()> {
+        let Some(invite) = self.get_invite(link).await? else {
+            return Err(FluxyError::NotFound("Lien d'invitation invalide".to_string()));
+        };
+        db_mongo_setter::check_not_banned(self.get_ban(&invite.server_id, &user_id).await?)?;
+        if self.is_member(&invite.server_id, &user_id).await? {
+            return Ok(());
+        }
+        // Même garde que l'update_one de db_mongo_setter::join_by_link : vérification et incrément sous le même verrou
+        let now = format_date(Utc::now());
+        let used = self.update_one(
+            "invite",
+            |doc| is_invite(doc, link) && bson::from_document::<Invite>(doc.clone()).is_ok_and(|invite| invite.is_usable_at(&now)),
+            |doc| {
+                let uses = doc.get_i64("uses").unwrap_or(0);
+                doc.insert("uses", uses + 1);
+            },
+        );
+        if !used {
+            if self.is_member(&invite.server_id, &user_id).await? {
+                return Ok(());
+            }
+            return Err(db_mongo_setter::invite_exhausted_error());
+        }
+        // Comme db_mongo_setter::join_by_link : l'ajout est filtré sur l'absence du membre, sinon l'utilisation est rendue
+        let added = self.update_one(
+            "server",
+            |doc| matches(doc, "id", invite.server_id) && !matches(doc, "member_id", user_id),
+            |doc| add_to_set(doc, "member_id", user_id),
+        );
+        if added {
+            return Ok(());
+        }
+        self.update_one("invite", |doc| is_invite(doc, link), |doc| {
+            let uses = doc.get_i64("uses").unwrap_or(0);
+            doc.insert("uses", (uses - 1).max(0));
+        });
+        if self.is_member(&invite.server_id, &user_id).await? {
+            Ok(())
+        } else {
+            Err(FluxyError::NotFound("Serveur introuvable".to_string()))
+        }
+    }
+
+    async fn set_role(&self, server_id: i64, name: &str, permissions: Permissions, user_id: i64) -> FluxyResult<Role> {
+        let Some((server, roles)) = self.server_with_roles(server_id).await? else {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        };
+        db_mongo_setter::check_new_role(&server, &roles, user_id, name, permissions)?;
+
+        // Comme db_mongo_setter::set_role : le nouveau rôle arrive en bas, les autres montent d'un cran
+        let role = Role {
+            id: self.next_id("role"),
+            server_id,
+            name: name.to_string(),
+            permissions,
+            position: 1,
+            member_id: vec![],
+        };
+        if let Some(docs) = self.lock().collections.get_mut("role") {
+            for doc in docs.iter_mut().filter(|doc| matches(doc, "server_id", server_id)) {
+                let position = doc.get_i64("position").unwrap_or(0);
+                doc.insert("position", position + 1);
+            }
+        }
+        self.insert("role", &role)?;
+        Ok(role)
+    }
+
+    async fn add_role_to_member(&self, role_id: i64, user_to_add: i64, user_id: i64) -> FluxyResult<()> {
+        let (role, server, roles) = self.role_with_server(role_id).await?;
+        db_mongo_setter::check_role_management(&server, &roles, user_id, role.position, Permissions::NONE)?;
+        if !server.member_id.contains(&user_to_add) {
+            return Err(FluxyError::Validation("L'utilisateur n'est pas membre du serveur".to_string()));
+        }
+        self.update_one("role", |doc| matches(doc, "id", role_id), |doc| add_to_set(doc, "member_id", user_to_add));
+        Ok(())
+    }
+
+    async fn set_ban(&self, server_id: i64, user_to_ban: i64, reason: &str, expires_at: Option<String>, user_id: i64) -> FluxyResult<Ban> {
+        let Some((server, roles)) = self.server_with_roles(server_id).await? else {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        };
+        let ban = db_mongo_setter::new_ban(server_id, user_to_ban, reason, expires_at, user_id);
+        db_mongo_setter::check_ban(&server, &roles, &ban)?;
+        self.delete_many("ban", |doc| matches(doc, "server_id", server_id) && matches(doc, "user_id", user_to_ban));
+        self.insert("ban", &ban)?;
+        self.pull_member(server_id, user_to_ban);
+        Ok(ban)
+    }
+
+    async fn set_timeout(&self, server_id: i64, user_to_mute: i64, until: String, user_id: i64) -> FluxyResult<MemberTimeout> {
+        let Some((server, roles)) = self.server_with_roles(server_id).await? else {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        };
+        let timeout = MemberTimeout { server_id, user_id: user_to_mute, moderator_id: user_id, until };
+        db_mongo_setter::check_timeout(&server, &roles, &timeout, Utc::now())?;
+        self.delete_many("timeout", |doc| matches(doc, "server_id", server_id) && matches(doc, "user_id", user_to_mute));
+        self.insert("timeout", &timeout)?;
+        Ok(timeout)
+    }
+
+    async fn set_audit_entry(&self, entry: NewAuditEntry) -> FluxyResult<AuditEntry> {
+        let entry = db_mongo_setter::audit_entry(self.next_id("audit_log"), entry);
+        self.insert("audit_log", &entry)?;
+        Ok(entry)
+    }
+
+    async fn update_message(&self, message_id: i64, message: &str, user_id: i64) -> FluxyResult<Message> {
+        if message.trim().is_empty() {
+            return Err(FluxyError::Validation("Le message ne peut pas être vide".to_string()));
+        }
+        let Some(message_by_id) = self.get_message_by_id(&message_id).await? else {
+            return Err(FluxyError::NotFound("Message introuvable".to_string()));
+        };
+        if message_by_id.user != user_id {
+            return Err(FluxyError::Forbidden("Seul l'auteur peut modifier son message".to_string()));
+        }
+
+        // Même document que db_mongo_update::update_message : ancien contenu ajouté à revisions
+        let edited_at = Utc::now().to_rfc3339();
+        let revision = bson::to_bson(&MessageRevision {
+            message: message_by_id.message.clone(),
+            editor_id: user_id,
+            edited_at: edited_at.clone(),
+        })
+        .map_err(|e| FluxyError::Storage(e.to_string()))?;
+        self.update_one("message", |doc| matches(doc, "id", message_id), |doc| {
+            doc.insert("message", message);
+            doc.insert("edited_at", edited_at.clone());
+            match doc.get_array_mut("revisions") {
+                Ok(revisions) => revisions.push(revision),
+                Err(_) => {
+                    doc.insert("revisions", vec![revision]);
+                }
+            }
+        });
+        Ok(Message {
+            message: message.to_string(),
+            edited_at: Some(edited_at),
+            ..message_by_id
+        })
+    }
+
+    async fn update_channel_name(&self, channel_id: i64, name: &str, user_id: i64) -> FluxyResult<()> {
+        if name.trim().is_empty() {
+            return Err(FluxyError::Validation("Le nom du channel est obligatoire".to_string()));
+        }
+        let Some((channel, server, roles)) = self.channel_with_server(channel_id).await? else {
+            return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+        };
+        if !channel.permissions_of(&server, &roles, user_id).contains(Permissions::MANAGE_CHANNELS) {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de modifier un channel".to_string()));
+        }
+        self.update_one("channel", |doc| matches(doc, "id", channel_id), |doc| {
+            doc.insert("name", name);
+        });
+        Ok(())
+    }
+
+    async fn update_server_name(&self, server_id: i64, name: &str, user_id: i64) -> FluxyResult<()> {
+        self.update_server(server_id, Some(name), None, user_id).await
+    }
+
+    async fn update_server(&self, server_id: i64, name: Option<&str>, image: Option<&str>, user_id: i64) -> FluxyResult<()> {
+        if !self.has_permission(&server_id, &user_id, Permissions::ADMINISTRATOR).await? {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de modifier le serveur".to_string()));
+        }
+        self.update_one("server", |doc| matches(doc, "id", server_id), |doc| {
+            if let Some(n) = name.filter(|n| !n.trim().is_empty()) {
+                doc.insert("name", n);
+            }
+            if let Some(img) = image {
+                doc.insert("image", img);
+            }
+        });
+        Ok(())
+    }
+
+    async fn update_member_role(&self, server_id: i64, user_id: i64, role: &str, actor_id: i64) -> FluxyResult<()> {
+        if role != "admin" && role != "membre" {
+            let Some(custom_role) = self.get_roles_of_server(&server_id).await?.into_iter().find(|custom_role| custom_role.name == role) else {
+                return Err(FluxyError::Validation("Rôle inconnu".to_string()));
+            };
+            return self.add_role_to_member(custom_role.id, user_id, actor_id).await;
+        }
+        if !self.is_owner(&server_id, &actor_id).await? {
+            return Err(FluxyError::Forbidden("Seul le fondateur peut modifier les rôles".to_string()));
+        }
+
+        match role {
+            "admin" => {
+                self.update_one("server", |doc| matches(doc, "id", server_id), |doc| add_to_set(doc, "admin_id", user_id));
+            }
+            "membre" => {
+                self.update_one("server", |doc| matches(doc, "id", server_id), |doc| pull(doc, "admin_id", user_id));
+            }
+            _ => {
+                return Err(FluxyError::Validation("Rôle inconnu".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    async fn update_role(&self, role_id: i64, name: Option<&str>, permissions: Option<Permissions>, position: Option<i64>, user_id: i64) -> FluxyResult<Role> {
+        let (role, server, roles) = self.role_with_server(role_id).await?;
+        let role = db_mongo_update::check_role_update(&server, &roles, user_id, role, name, permissions, position)?;
+        let updated = bson::to_document(&role).map_err(|e| FluxyError::Storage(e.to_string()))?;
+        self.update_one("role", |doc| matches(doc, "id", role_id), |doc| *doc = updated);
+        Ok(role)
+    }
+
+    async fn update_channel_privacy(&self, channel_id: i64, private: bool, user_id: i64) -> FluxyResult<Channel> {
+        let Some((channel, server, roles)) = self.channel_with_server(channel_id).await? else {
+            return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+        };
+        db_mongo_update::check_channel_management(&server, &roles, &channel, user_id)?;
+        self.update_one("channel", |doc| matches(doc, "id", channel_id), |doc| {
+            doc.insert("private", private);
+        });
+        Ok(Channel { private, ..channel })
+    }
+
+    async fn update_channel_overwrite(&self, channel_id: i64, overwrite: PermissionOverwrite, user_id: i64) -> FluxyResult<Channel> {
+        let Some((channel, server, roles)) = self.channel_with_server(channel_id).await? else {
+            return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+        };
+        let channel = db_mongo_update::check_channel_overwrite(&server, &roles, channel, user_id, overwrite)?;
+        self.set_overwrites(&channel)?;
+        Ok(channel)
+    }
+
+    async fn delete_message(&self, message_id: i64, user_id: i64) -> FluxyResult<Message> {
+        let Some(message_by_id) = self.get_message_by_id(&message_id).await? else {
+            return Err(FluxyError::NotFound("Message introuvable".to_string()));
+        };
+        let server_id = self.get_server_id_by_message_id(&message_id).await?;
+        let is_author = message_by_id.user == user_id;
+        if !is_author && !self.has_permission(&server_id, &user_id, Permissions::MANAGE_MESSAGES).await? {
+            return Err(FluxyError::Forbidden(
+                "Seuls l'auteur ou les membres qui gèrent les messages peuvent supprimer ce message".to_string(),
+            ));
+        }
+        self.delete_many("message", |doc| matches(doc, "id", message_id));
+        Ok(message_by_id)
+    }
+
+    async fn delete_channel(&self, channel_id: i64, user_id: i64) -> FluxyResult<()> {
+        let Some((channel, server, roles)) = self.channel_with_server(channel_id).await? else {
+            return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+        };
+        if !channel.permissions_of(&server, &roles, user_id).contains(Permissions::MANAGE_CHANNELS) {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de supprimer un channel".to_string()));
+        }
+        self.cascade_delete(None, &[channel_id]);
+        Ok(())
+    }
+
+    async fn delete_server(&self, server_id: i64, user_id: i64) -> FluxyResult<()> {
+        if !self.is_owner(&server_id, &user_id).await? {
+            return Err(FluxyError::Forbidden("Seul le fondateur peut supprimer le serveur".to_string()));
+        }
+        let channel_ids: Vec<i64> = self.get_channels_of_server(&server_id).await?.iter().map(|channel| channel.id).collect();
+        self.cascade_delete(Some(server_id), &channel_ids);
+        Ok(())
+    }
+
+    async fn delete_admin(&self, server_id: i64, user_id: i64, user_to_remove: i64) -> FluxyResult<()> {
+        if !self.is_owner(&server_id, &user_id).await? {
+            return Err(FluxyError::Forbidden("Seul le fondateur peut retirer un administrateur".to_string()));
+        }
+        self.update_one("server", |doc| matches(doc, "id", server_id), |doc| pull(doc, "admin_id", user_to_remove));
+        Ok(())
+    }
+
+    async fn delete_member(&self, server_id: i64, user_id: i64, user_to_remove: i64) -> FluxyResult<()> {
+        // Mêmes règles que db_mongo_delete::delete_member
+        let Some((server, roles)) = self.server_with_roles(server_id).await? else {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        };
+        db_mongo_delete::check_member_removal(&server, &roles, user_id, user_to_remove, Permissions::KICK_MEMBERS)?;
+        self.pull_member(server_id, user_to_remove);
+        Ok(())
+    }
+
+    async fn delete_invite(&self, link: &str, user_id: i64) -> FluxyResult<()> {
+        let Some(invite) = self.get_invite(link).await? else {
+            return Err(FluxyError::NotFound("Lien d'invitation introuvable".to_string()));
+        };
+        if invite.creator_id != user_id && !self.has_permission(&invite.server_id, &user_id, Permissions::ADMINISTRATOR).await? {
+            return Err(db_mongo_delete::invite_revocation_error());
+        }
+        self.delete_many("invite", |doc| is_invite(doc, link));
+        Ok(())
+    }
+
+    async fn delete_role(&self, role_id: i64, user_id: i64) -> FluxyResult<()> {
+        let (role, server, roles) = self.role_with_server(role_id).await?;
+        db_mongo_setter::check_role_management(&server, &roles, user_id, role.position, Permissions::NONE)?;
+        self.delete_many("role", |doc| matches(doc, "id", role_id));
+        for mut channel in self.get_channels_of_server(&role.server_id).await? {
+            if channel.overwrite_of(OverwriteKind::Role, role_id).is_some() {
+                channel.overwrites.retain(|overwrite| overwrite.kind != OverwriteKind::Role || overwrite.target_id != role_id);
+                self.set_overwrites(&channel)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_role_from_member(&self, role_id: i64, user_to_remove: i64, user_id: i64) -> FluxyResult<()> {
+        let (role, server, roles) = self.role_with_server(role_id).await?;
+        db_mongo_setter::check_role_management(&server, &roles, user_id, role.position, Permissions::NONE)?;
+        self.update_one("role", |doc| matches(doc, "id", role_id), |doc| pull(doc, "member_id", user_to_remove));
+        Ok(())
+    }
+
+    async fn delete_channel_overwrite(&self, channel_id: i64, kind: OverwriteKind, target_id: i64, user_id: i64) -> FluxyResult<Channel> {
+        let Some((channel, server, roles)) = self.channel_with_server(channel_id).await? else {
+            return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+        };
+        let channel = db_mongo_delete::remove_channel_overwrite(&server, &roles, channel, user_id, kind, target_id)?;
+        self.set_overwrites(&channel)?;
+        Ok(channel)
+    }
+
+    async fn delete_ban(&self, server_id: i64, user_to_unban: i64, user_id: i64) -> FluxyResult<()> {
+        if !self.has_permission(&server_id, &user_id, Permissions::BAN_MEMBERS).await? {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de bannir des membres".to_string()));
+        }
+        if self.get_ban(&server_id, &user_to_unban).await?.is_none() {
+            return Err(FluxyError::NotFound("Bannissement introuvable".to_string()));
+        }
+        self.delete_many("ban", |doc| matches(doc, "server_id", server_id) && matches(doc, "user_id", user_to_unban));
+        Ok(())
+    }
+
+    async fn delete_timeout(&self, server_id: i64, user_to_unmute: i64, user_id: i64) -> FluxyResult<()> {
+        if !self.has_permission(&server_id, &user_id, Permissions::MODERATE_MEMBERS).await? {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de réduire des membres au silence".to_string()));
+        }
+        let timeout = self.get_timeout(&server_id, &user_to_unmute).await?;
+        if timeout.is_none_or(|timeout| timeout.remaining_secs(Utc::now()) == 0) {
+            return Err(FluxyError::NotFound("Timeout introuvable".to_string()));
+        }
+        self.delete_many("timeout", |doc| matches(doc, "server_id", server_id) && matches(doc, "user_id", user_to_unmute));
+        Ok(())
+    }
+
+    async fn repair_orphans(&self) -> io::Result<RepairReport> {
+        let server_ids = self.ids("server");
+        let roles = self.delete_orphans("role", "server_id", &server_ids);
+        let invites = self.delete_orphans("invite", "server_id", &server_ids);
+        let bans = self.delete_orphans("ban", "server_id", &server_ids);
+        let timeouts = self.delete_orphans("timeout", "server_id", &server_ids);
+        let audit_entries = self.delete_orphans("audit_log", "server_id", &server_ids);
+        let channels = self.delete_orphans("channel", "server_id", &server_ids);
+        let messages = self.delete_orphans("message", "channel_id", &self.ids("channel"));
+        Ok(RepairReport { roles, invites, bans, timeouts, audit_entries, channels, messages })
+    }
+
+    async fn delete_expired_bans(&self, now: &str) -> io::Result<u64> {
+        let mut data = self.lock();
+        let bans = data.collections.entry("ban".to_string()).or_default();
+        let before = bans.len();
+        bans.retain(|doc| !doc.get_str("expires_at").is_ok_and(|expires_at| expires_at <= now));
+        Ok((before - bans.len()) as u64)
+    }
+}
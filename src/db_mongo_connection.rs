@@ -1,47 +1,169 @@
-//! Tous les éléments liés à MongoDB correspondent aux serveurs, channels et messages rémanents. Les rôles, les permissions et les codes d'invitation sont liés à cette base de données.
-//!
-//! Les setters permettent d'ajouter des éléments dans la base de données ou de remplacer une ligne dans un des objets de la base.
-//! Les updates permettent de modifier les données de la base partiellement, on peut considérer cela comme des changements souples qui n'impliquent pas de grandes conséquences.
-//! Les getters permettent de récupérer des informations ou de vérifier des informations dans la base de données
-//! Les deletes permettent de supprimer une information ou un objet de la base de données.
-//!
-//! Toutes les fonctions utilisées prennent en premier paramètre la connexion avec le client et en second paramètre le nom de la base de données.
-//!
-//! La connexion à la base de données est gérée par le fichier db_mongo_connection et doit être générée dans une variable qui sera appelée par les autres fonctions.
-
-use std::{env, io};
-use mongodb::{bson::doc, options::{ClientOptions, ServerApi, ServerApiVersion}, Client};
-
-// pour le main a ajouté pour se connecter à la db
-// let client_mongo_db = db_mongo_connection::get_client().await?;
-// avoir un -> io::Result<()> pour le main
-pub async fn get_client() -> io::Result<Client> {
-    dotenvy::dotenv().ok();
-    let db_mongo_username = env::var("MONGO_USERNAME")
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "MONGO_USERNAME manquant dans .env"))?;
-    let db_mongo_password = env::var("MONGO_PASSWORD")
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "MONGO_PASSWORD manquant dans .env"))?;
-    let db_mongo_url = env::var("MONGO_URL")
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "MONGO_URL manquant dans .env"))?;
-    let db_mongo_name = env::var("MONGO_DATA_BASE_NAME")
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "MONGO_DATA_BASE_NAME manquant dans .env"))?;
-    
-    let base_connection = format!("mongodb+srv://{}:{}@{}", db_mongo_username, db_mongo_password, db_mongo_url);
-    let mut client_options = ClientOptions::parse(&base_connection)
-        .await
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Erreur de connexion MongoDB: {}", e)))?;
-
-    let server_api = ServerApi::builder().version(ServerApiVersion::V1).build();
-    client_options.server_api = Some(server_api);
-
-    let client = Client::with_options(client_options)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Erreur de création du client: {}", e)))?;
-
-    client
-        .database(&db_mongo_name)
-        .run_command(doc! {"ping": 1})
-        .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur de ping MongoDB"))?;
-
-    Ok(client)
-}
\ No newline at end of file
+//! Tous les éléments liés à MongoDB correspondent aux serveurs, channels et messages rémanents. Les rôles, les permissions et les codes d'invitation sont liés à cette base de données.
+//!
+//! Les setters permettent d'ajouter des éléments dans la base de données ou de remplacer une ligne dans un des objets de la base.
+//! Les updates permettent de modifier les données de la base partiellement, on peut considérer cela comme des changements souples qui n'impliquent pas de grandes conséquences.
+//! Les getters permettent de récupérer des informations ou de vérifier des informations dans la base de données
+//! Les deletes permettent de supprimer une information ou un objet de la base de données.
+//!
+//! Toutes les fonctions utilisées prennent en premier paramètre la connexion avec le client et en second paramètre le nom de la base de données.
+//!
+//! La connexion à la base de données est gérée par le fichier db_mongo_connection : le client est créé une seule fois au démarrage (create_client) et partagé par le MongoStore.
+//!
+//! Les handlers et le chat n'appellent pas directement ces fonctions : ils passent par le trait ChatStore (db_store.rs).
+//! MongoStore délègue aux fonctions db_mongo_*, MemoryStore (db_memory.rs) reproduit les mêmes règles en mémoire pour les tests.
+//! SqlStore (db_sql.rs, feature sql) reproduit les mêmes règles sur SQLite ou Postgres ; le stockage est choisi au démarrage par STORAGE_BACKEND.
+
+use crate::models::MongoPoolConfig;
+use std::{env, io, time::Duration};
+use mongodb::{
+    bson::{doc, Document},
+    options::{ClientOptions, IndexOptions, ServerApi, ServerApiVersion},
+    Client, IndexModel,
+};
+
+impl Default for MongoPoolConfig {
+    // mêmes valeurs que les défauts du driver MongoDB
+    fn default() -> Self {
+        MongoPoolConfig {
+            max_pool_size: 10,
+            min_pool_size: 0,
+            connect_timeout: Duration::from_secs(10),
+            server_selection_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// create_client :  
+///     configuration du pool  
+/// crée le client MongoDB à partir des variables MONGO_* sans se connecter. le client garde un pool de connexions :
+/// il doit être créé une seule fois (au démarrage) puis partagé, chaque clone réutilise le même pool
+pub async fn create_client(pool: &MongoPoolConfig) -> io::Result<Client> {
+    dotenvy::dotenv().ok();
+    let db_mongo_username = env::var("MONGO_USERNAME")
+        .map_err(|_| io::Error::other("MONGO_USERNAME manquant dans .env"))?;
+    let db_mongo_password = env::var("MONGO_PASSWORD")
+        .map_err(|_| io::Error::other("MONGO_PASSWORD manquant dans .env"))?;
+    let db_mongo_url = env::var("MONGO_URL")
+        .map_err(|_| io::Error::other("MONGO_URL manquant dans .env"))?;
+    
+    let base_connection = format!("mongodb+srv://{}:{}@{}", db_mongo_username, db_mongo_password, db_mongo_url);
+    let mut client_options = ClientOptions::parse(&base_connection)
+        .await
+        .map_err(|e| io::Error::other(format!("Erreur de connexion MongoDB: {}", e)))?;
+
+    let server_api = ServerApi::builder().version(ServerApiVersion::V1).build();
+    client_options.server_api = Some(server_api);
+    client_options.max_pool_size = Some(pool.max_pool_size);
+    client_options.min_pool_size = Some(pool.min_pool_size);
+    client_options.connect_timeout = Some(pool.connect_timeout);
+    client_options.server_selection_timeout = Some(pool.server_selection_timeout);
+
+    Client::with_options(client_options)
+        .map_err(|e| io::Error::other(format!("Erreur de création du client: {}", e)))
+}
+
+/// ping :  
+/// vérifie que la base répond (utilisé par /healthz)
+pub async fn ping(client: &Client, db_name: &str) -> io::Result<()> {
+    client
+        .database(db_name)
+        .run_command(doc! {"ping": 1})
+        .await
+        .map_err(|_| io::Error::other("Erreur de ping MongoDB"))?;
+    Ok(())
+}
+
+/// get_client :  
+/// crée un client avec le pool par défaut et vérifie la connexion. à réserver aux outils ponctuels :
+/// l'application partage le client du MongoStore créé au démarrage
+pub async fn get_client() -> io::Result<Client> {
+    let db_mongo_name = env::var("MONGO_DATA_BASE_NAME")
+        .map_err(|_| io::Error::other("MONGO_DATA_BASE_NAME manquant dans .env"))?;
+    let client = create_client(&MongoPoolConfig::default()).await?;
+    ping(&client, &db_mongo_name).await?;
+    Ok(client)
+}
+
+/// ensure_indexes :  
+/// crée les index utilisés par les requêtes fréquentes s'ils n'existent pas encore (à appeler au démarrage).
+/// message (channel_id, id) : pagination des messages d'un channel triés par id
+/// server, channel, message, role, audit_log (id) unique : une insertion avec un id déjà attribué est rejetée
+/// role (server_id, position) : rôles d'un serveur triés par hiérarchie
+/// audit_log (server_id, id) : journal d'audit d'un serveur du plus récent au plus ancien
+/// ban, timeout (server_id, user_id) unique : un seul bannissement et un seul timeout par utilisateur et par serveur
+/// invite (code) unique : deux liens d'invitation ne partagent pas le même code ; invite (server_id) : liens d'un serveur
+pub async fn ensure_indexes(client: &Client, db_name: &str) -> io::Result<()> {
+    let database = client.database(db_name);
+
+    let message_index = IndexModel::builder()
+        .keys(doc! {"channel_id": 1, "id": 1})
+        .options(IndexOptions::builder().name("channel_id_id".to_string()).build())
+        .build();
+    database
+        .collection::<Document>("message")
+        .create_index(message_index)
+        .await
+        .map_err(|e| io::Error::other(format!("Erreur lors de la création des index: {}", e)))?;
+
+    let role_index = IndexModel::builder()
+        .keys(doc! {"server_id": 1, "position": -1})
+        .options(IndexOptions::builder().name("server_id_position".to_string()).build())
+        .build();
+    database
+        .collection::<Document>("role")
+        .create_index(role_index)
+        .await
+        .map_err(|e| io::Error::other(format!("Erreur lors de la création des index: {}", e)))?;
+
+    let audit_index = IndexModel::builder()
+        .keys(doc! {"server_id": 1, "id": -1})
+        .options(IndexOptions::builder().name("server_id_id".to_string()).build())
+        .build();
+    database
+        .collection::<Document>("audit_log")
+        .create_index(audit_index)
+        .await
+        .map_err(|e| io::Error::other(format!("Erreur lors de la création des index: {}", e)))?;
+
+    for collection in ["ban", "timeout"] {
+        let member_index = IndexModel::builder()
+            .keys(doc! {"server_id": 1, "user_id": 1})
+            .options(IndexOptions::builder().name("server_id_user_id_unique".to_string()).unique(true).build())
+            .build();
+        database
+            .collection::<Document>(collection)
+            .create_index(member_index)
+            .await
+            .map_err(|e| io::Error::other(format!("Erreur lors de la création des index: {}", e)))?;
+    }
+
+    let invite_indexes = vec![
+        IndexModel::builder()
+            .keys(doc! {"code": 1})
+            .options(IndexOptions::builder().name("code_unique".to_string()).unique(true).build())
+            .build(),
+        IndexModel::builder()
+            .keys(doc! {"server_id": 1})
+            .options(IndexOptions::builder().name("server_id".to_string()).build())
+            .build(),
+    ];
+    database
+        .collection::<Document>("invite")
+        .create_indexes(invite_indexes)
+        .await
+        .map_err(|e| io::Error::other(format!("Erreur lors de la création des index: {}", e)))?;
+
+    for collection in ["server", "channel", "message", "role", "audit_log"] {
+        let unique_id = IndexModel::builder()
+            .keys(doc! {"id": 1})
+            .options(IndexOptions::builder().name("id_unique".to_string()).unique(true).build())
+            .build();
+        database
+            .collection::<Document>(collection)
+            .create_index(unique_id)
+            .await
+            .map_err(|e| io::Error::other(format!("Erreur lors de la création de l'index unique sur {}.id: {}", collection, e)))?;
+    }
+
+    Ok(())
+}
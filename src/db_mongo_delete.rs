@@ -2,12 +2,12 @@
 //!     - delete_message :
 //!         message id
 //!         utilisateur qui fait l'action   
-//!     permet au créateur du message, un administrateur ou un possesseur de supprimer le message correspondant
+//!     permet au créateur du message ou à un utilisateur qui a la permission MANAGE_MESSAGES de supprimer le message correspondant. renvoie le message supprimé
 //!
 //!     - delete_channel :  
 //!         channel id  
 //!         id utilisateur  
-//!     permet à un administrateur ou un possesseur de supprimer un channel
+//!     permet à un utilisateur qui a la permission MANAGE_CHANNELS dans le channel de supprimer un channel
 //!
 //!     - delete_server :  
 //!         serveur id  
@@ -23,99 +23,113 @@
 //!     - delete_member :  
 //!         utilisateur id  
 //!         utilisateur à supprimer  
-//!     permet à un membre de partir, à un administrateur de partir et de supprimer des membres et à un possesseur de supprimer des membres. un possesseur ne peut pas partir.
+//!     permet à un membre de partir, et à un utilisateur qui a la permission KICK_MEMBERS d'exclure les membres de rang inférieur au sien.
+//!     un possesseur ne peut pas partir. le membre perd aussi ses rôles personnalisés
 //!
-//!     - delete_link :  
+//!     - delete_role :  
+//!         rôle id  
+//!         id utilisateur  
+//!     permet de supprimer un rôle placé sous son rang (MANAGE_ROLES). les exceptions de channel du rôle sont retirées
+//!
+//!     - delete_channel_overwrite :  
+//!         channel id  
+//!         rôle ou membre visé et son id  
+//!         id utilisateur  
+//!     permet de retirer l'exception d'un rôle ou d'un membre sur un channel (MANAGE_CHANNELS dans le channel)
+//!
+//!     - remove_role_from_member :  
+//!         rôle id  
+//!         membre qui perd le rôle  
+//!         id utilisateur  
+//!     permet de retirer à un membre un rôle placé sous son rang (MANAGE_ROLES)
+//!
+//!     - delete_invite :  
 //!         lien/code  
-//!     permet de supprimer un lien d'invitation directement
+//!         id utilisateur  
+//!     permet au créateur du lien ou à un utilisateur qui a la permission ADMINISTRATOR de révoquer un lien d'invitation
+//!
+//!     - delete_ban :  
+//!         serveur id  
+//!         utilisateur banni  
+//!         id utilisateur  
+//!     permet à un utilisateur qui a la permission BAN_MEMBERS de lever un bannissement
+//!
+//!     - delete_timeout :  
+//!         serveur id  
+//!         membre réduit au silence  
+//!         id utilisateur  
+//!     permet à un utilisateur qui a la permission MODERATE_MEMBERS de rendre la parole à un membre avant la fin de son timeout
+//!
+//!     - delete_expired_bans :  
+//!         date actuelle  
+//!     lève les bannissements expirés (tâche périodique lancée au démarrage)
+//!
+//!     - repair_orphans :
+//!     supprime les rôles, liens d'invitation, bannissements, timeouts, entrées d'audit et channels dont le serveur n'existe plus,
+//!     puis les messages dont le channel n'existe plus
+//!
+//! delete_channel et delete_server (rôles, liens d'invitation, bannissements, timeouts et journal d'audit du serveur compris) suppriment le parent et ses enfants dans une transaction multi-documents (voir cascade_delete).
+//! Les refus renvoient FluxyError::Forbidden, un élément inexistant FluxyError::NotFound et le départ du possesseur FluxyError::Conflict (voir error.rs).
 
 use crate::db_mongo_getter;
+use crate::db_mongo_setter::check_role_management;
+use crate::db_mongo_update::check_channel_management;
+use crate::error::{FluxyError, FluxyResult};
+use chrono::Utc;
+use crate::models::{format_date, Channel, Message, OverwriteKind, Permissions, RepairReport, Role, Server};
 
 use std::{
+    collections::HashSet,
     io,
 };
 use mongodb::{
-    bson::{doc,Document,Bson,},
-    options::FindOptions,
-    Client
+    bson::{self,doc,oid::ObjectId,Bson,Document,},
+    error::ErrorKind,
+    Client,
+    ClientSession,
+    Database
 };
 
-use futures_util::{
-    TryStreamExt,
-    StreamExt
-};
+use futures_util::TryStreamExt;
 
 /// delete_message :
 ///     message id
 ///     utilisateur qui fait l'action   
-/// permet au créateur du message, un administrateur ou un possesseur de supprimer le message correspondant
+/// permet au créateur du message ou à un utilisateur qui a la permission MANAGE_MESSAGES de supprimer le message correspondant
 //supprime un message
-pub async fn delete_message(client: &Client, db_name: &str, message_id: i64,user_id: i64)-> io::Result<()>{
-    // println!("test");
-    let mut can_del = false;
+pub async fn delete_message(client: &Client, db_name: &str, message_id: i64,user_id: i64)-> FluxyResult<Message>{
+    let Some(message_by_id) = db_mongo_getter::get_message_by_id(client, db_name, &message_id).await? else {
+        return Err(FluxyError::NotFound("Message introuvable".to_string()));
+    };
     let server_id = db_mongo_getter::get_server_id_by_message_id(client, db_name, &message_id).await?;
-    if db_mongo_getter::is_owner(&client,&db_name, &server_id,&user_id).await?==true 
-    || db_mongo_getter::is_admin(&client,&db_name, &server_id,&user_id).await?==true{
-        can_del = true;
-    }
-    let message_by_id = db_mongo_getter::get_message_by_id(client, db_name, &message_id).await?;
-    for i in message_by_id{
-        for (key,value) in i{
-            if key == "user"{
-                if value == Bson::Int64(user_id){
-                    can_del = true;
-                }
-            }
-        }
+    if message_by_id.user != user_id
+        && !db_mongo_getter::has_permission(client, db_name, &server_id, &user_id, Permissions::MANAGE_MESSAGES).await?
+    {
+        return Err(FluxyError::Forbidden("Seuls l'auteur ou les membres qui gèrent les messages peuvent supprimer ce message".to_string()));
     }
-
-    if can_del == false{return Ok(());};
-    let collection = client
-    .database(db_name)
-    .collection::<Document>("message")
-    .delete_one(doc!{"id":message_id})
-    .await
-    .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
-    Ok(())
+    client
+        .database(db_name)
+        .collection::<Document>("message")
+        .delete_one(doc!{"id":message_id})
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la suppression du message: {}", e)))?;
+    Ok(message_by_id)
 }
 
 /// delete_channel :  
 ///     channel id  
 ///     id utilisateur  
-/// permet à un administrateur ou un possesseur de supprimer un channel
+/// permet à un utilisateur qui a la permission MANAGE_CHANNELS dans le channel de supprimer un channel
 //supprime un salon
-pub async fn delete_channel(client: &Client, db_name: &str,channel_id: i64,user_id: i64)-> io::Result<()>{
-    let channel = db_mongo_getter::get_channel_by_id(client, db_name,&channel_id).await?;
-    let mut server_id = 0;
-    for i in channel{
-        for (key,value) in i{
-            if key == "server_id"{
-                server_id = value.to_string().parse::<i64>().unwrap();
-            }
-        }
-    }
-    if !db_mongo_getter::is_owner(&client,&db_name, &server_id,&user_id).await?
-        && !db_mongo_getter::is_admin(&client,&db_name, &server_id,&user_id).await?{
-        return Ok(());
+pub async fn delete_channel(client: &Client, db_name: &str,channel_id: i64,user_id: i64)-> FluxyResult<()>{
+    let Some((channel, server, roles)) = db_mongo_getter::get_channel_with_server(client, db_name,&channel_id).await? else {
+        return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+    };
+    if !channel.permissions_of(&server, &roles, user_id).contains(Permissions::MANAGE_CHANNELS) {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de supprimer un channel".to_string()));
     }
-    //supprime tout les message
-    client
-        .database(db_name)
-        .collection::<Document>("message")
-        .delete_many(doc!{"channel_id":channel_id})
-        .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
-
-    //supprime le channel
-    client
-        .database(db_name)
-        .collection::<Document>("channel")
-        .delete_one(doc!{"id":channel_id})
-        .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
-
-
-    Ok(())  
+    //supprime le channel et tout ses messages
+    cascade_delete(client, db_name, None, &[channel_id]).await
 }
 
 /// delete_server :  
@@ -123,55 +137,96 @@ pub async fn delete_channel(client: &Client, db_name: &str,channel_id: i64,user_
 ///     id utilisateur  
 /// permet au possesseur du serveur de le supprimer
 //supprime le serveur au complet
-pub async fn delete_server(client: &Client, db_name: &str,server_id: i64,user_id: i64)-> io::Result<()>{
-    if !db_mongo_getter::is_owner(&client,&db_name, &server_id,&user_id).await?{
-        return Ok(());
+pub async fn delete_server(client: &Client, db_name: &str,server_id: i64,user_id: i64)-> FluxyResult<()>{
+    if !db_mongo_getter::is_owner(client, db_name, &server_id, &user_id).await?{
+        return Err(FluxyError::Forbidden("Seul le fondateur peut supprimer le serveur".to_string()));
     }
 
     //cherche les salon pour tout supprimer
-    let mut channels_id: Vec<i64> = vec![];
-    let mut channel_id_doc = client
-        .database(db_name)
-        .collection::<Document>("channel")
-        .find(doc!{"server_id":server_id})
-        .projection(doc! { "id": 1, "_id": 0 })
-        .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
+    let channel_ids: Vec<i64> = db_mongo_getter::get_channels_of_server(client, db_name, &server_id)
+        .await?
+        .iter()
+        .map(|channel| channel.id)
+        .collect();
 
-    let docs: Vec<Document> = channel_id_doc
-        .try_collect()
+    //supprime le server, ses rôles, ses salons et leurs messages
+    cascade_delete(client, db_name, Some(server_id), &channel_ids).await
+}
+
+/// cascade_delete :
+///     serveur à supprimer (None pour ne supprimer que des channels)
+///     ids des channels à supprimer avec leurs messages
+/// fait les suppressions dans une transaction multi-documents : soit tout est supprimé, soit rien.
+/// les transactions demandent un replica set ; sur un serveur MongoDB seul, les suppressions sont faites sans transaction,
+/// le parent d'abord : un arrêt au milieu ne laisse alors que des orphelins invisibles, retirés par repair_orphans.
+async fn cascade_delete(client: &Client, db_name: &str, server_id: Option<i64>, channel_ids: &[i64]) -> FluxyResult<()> {
+    let database = client.database(db_name);
+    let mut session = client
+        .start_session()
         .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "pas de vecteur disponible"))?;
-    
-    let mut temp ="".to_string();
-    for i in docs{
-        let string = i.to_string();
-        for a in string.chars(){
-            // println!("{:?}",a);
-            match a {
-                '1'|'2'|'3'|'4'|'5'|'6'|'7'|'8'|'9'|'0'=>temp.push(a),
-                '}'=>{
-                        // println!("{:?}",temp);
-                        channels_id.push(temp.parse::<i64>().unwrap_or(0));
-                        temp = "".to_string();
-                    },
-                    _ => continue,
-                };
-            }
-        }
-        for i in channels_id{
-            delete_channel(client,db_name,i,user_id).await?;
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de l'ouverture de la session: {}", e)))?;
+
+    // la transaction est annulée automatiquement si session est libérée sans commit
+    let result = match session.start_transaction().await {
+        Ok(()) => match delete_steps(&database, server_id, channel_ids, Some(&mut session)).await {
+            Ok(()) => session.commit_transaction().await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) if transactions_unsupported(&e) => {
+            drop(session);
+            println!("[DELETE] Transactions indisponibles (pas de replica set), suppression sans transaction");
+            delete_steps(&database, server_id, channel_ids, None)
+                .await
+                .map_err(|e| FluxyError::Storage(format!("Erreur lors de la suppression: {}", e)))
         }
-   
-    //supprime le server
-    let collection = client
-        .database(db_name)
-        .collection::<Document>("server")
-        .delete_one(doc!{"id":server_id})
-        .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
+        Err(e) => Err(FluxyError::Storage(format!("Erreur lors de la suppression: {}", e))),
+    }
+}
+
+/// delete_steps :
+/// suppressions de cascade_delete, parent d'abord, dans la session si elle est fournie
+async fn delete_steps(
+    database: &Database,
+    server_id: Option<i64>,
+    channel_ids: &[i64],
+    mut session: Option<&mut ClientSession>,
+) -> mongodb::error::Result<()> {
+    let mut steps = Vec::new();
+    if let Some(server_id) = server_id {
+        steps.push(("server", doc!{"id": server_id}));
+        steps.push(("role", doc!{"server_id": server_id}));
+        steps.push(("invite", doc!{"server_id": server_id}));
+        steps.push(("ban", doc!{"server_id": server_id}));
+        steps.push(("timeout", doc!{"server_id": server_id}));
+        steps.push(("audit_log", doc!{"server_id": server_id}));
+    }
+    steps.push(("channel", doc!{"id": {"$in": channel_ids}}));
+    steps.push(("message", doc!{"channel_id": {"$in": channel_ids}}));
+
+    for (collection, filter) in steps {
+        let collection = database.collection::<Document>(collection);
+        let delete = collection.delete_many(filter);
+        match session.as_deref_mut() {
+            Some(session) => delete.session(session).await?,
+            None => delete.await?,
+        };
+    }
     Ok(())
+}
 
+/// transactions_unsupported :
+/// vrai si l'erreur vient d'un déploiement sans transactions (IllegalOperation, code 20, sur un serveur seul)
+fn transactions_unsupported(e: &mongodb::error::Error) -> bool {
+    match e.kind.as_ref() {
+        ErrorKind::Transaction { .. } => true,
+        ErrorKind::Command(command_error) => command_error.code == 20,
+        _ => false,
+    }
 }
 
 /// delete_admin :  
@@ -179,10 +234,10 @@ pub async fn delete_server(client: &Client, db_name: &str,server_id: i64,user_id
 ///     id possesseur  
 ///     id administrateur  
 /// permet à un possesseur de serveur de retirer la permission administrateur à un des membres
-pub async fn delete_admin(client: &Client, db_name: &str,server_id: i64,user_id: i64,user_to_remove: i64)-> io::Result<()>{
+pub async fn delete_admin(client: &Client, db_name: &str,server_id: i64,user_id: i64,user_to_remove: i64)-> FluxyResult<()>{
     // Seul l'owner peut supprimer un admin
-    if !db_mongo_getter::is_owner(&client,&db_name,&server_id,&user_id).await?{
-        return Ok(());
+    if !db_mongo_getter::is_owner(client, db_name, &server_id, &user_id).await?{
+        return Err(FluxyError::Forbidden("Seul le fondateur peut retirer un administrateur".to_string()));
     }
 
     client
@@ -193,7 +248,7 @@ pub async fn delete_admin(client: &Client, db_name: &str,server_id: i64,user_id:
             doc!{"$pull":{"admin_id":user_to_remove}}
         )
         .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
+        .map_err(|e| FluxyError::Storage(format!("base de donnée ou collection de la base non trouver: {}", e)))?;
 
     Ok(())
 }
@@ -201,52 +256,371 @@ pub async fn delete_admin(client: &Client, db_name: &str,server_id: i64,user_id:
 /// delete_member :  
 ///     utilisateur id  
 ///     utilisateur à supprimer  
-/// permet à un membre de partir, à un administrateur de partir et de supprimer des membres et à un possesseur de supprimer des membres. un possesseur ne peut pas partir.
-pub async fn delete_member(client: &Client, db_name: &str,server_id: i64,user_id: i64,user_to_remove: i64)-> io::Result<()>{
-    if db_mongo_getter::is_admin(&client,&db_name,&server_id,&user_id).await?
-    && db_mongo_getter::is_admin(&client,&db_name,&server_id,&user_to_remove).await?
-    && user_id != user_to_remove{
-        return Ok(());
+/// permet à un membre de partir, et à un utilisateur qui a la permission KICK_MEMBERS d'exclure les membres de rang inférieur au sien.
+/// un possesseur ne peut pas partir. le membre perd aussi ses rôles personnalisés
+pub async fn delete_member(client: &Client, db_name: &str,server_id: i64,user_id: i64,user_to_remove: i64)-> FluxyResult<()>{
+    let Some((server, roles)) = db_mongo_getter::get_server_with_roles(client, db_name, &server_id).await? else {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    };
+    check_member_removal(&server, &roles, user_id, user_to_remove, Permissions::KICK_MEMBERS)?;
+    pull_member(client, db_name, server_id, user_to_remove).await
+}
 
-    }
-    if db_mongo_getter::is_owner(&client,&db_name,&server_id,&user_to_remove).await?{
-        return Ok(());
+/// pull_member :
+/// retire l'utilisateur des membres, des admins et des rôles du serveur (départ, exclusion et bannissement)
+pub(crate) async fn pull_member(client: &Client, db_name: &str, server_id: i64, user_to_remove: i64) -> FluxyResult<()> {
+    client
+        .database(db_name)
+        .collection::<Document>("server")
+        .update_one(
+            doc! {"id":server_id},
+            doc!{"$pull":{"admin_id":user_to_remove,"member_id":user_to_remove}}
+        )
+        .await
+        .map_err(|e| FluxyError::Storage(format!("base de donnée ou collection de la base non trouver: {}", e)))?;
+
+    client
+        .database(db_name)
+        .collection::<Document>("role")
+        .update_many(
+            doc! {"server_id":server_id},
+            doc!{"$pull":{"member_id":user_to_remove}}
+        )
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors du retrait des rôles: {}", e)))?;
 
+    Ok(())
+}
+
+/// check_member_removal :
+/// règles communes au départ, à l'exclusion, au bannissement et au timeout d'un membre : le possesseur ne part pas, et pour
+/// retirer quelqu'un d'autre il faut la permission demandée (KICK_MEMBERS, BAN_MEMBERS ou MODERATE_MEMBERS) et un rang
+/// strictement supérieur au sien
+pub(crate) fn check_member_removal(
+    server: &Server,
+    roles: &[Role],
+    user_id: i64,
+    user_to_remove: i64,
+    permission: Permissions,
+) -> FluxyResult<()> {
+    if server.owner_id == user_to_remove {
+        return Err(owner_removal_error(user_id, user_to_remove));
     }
-    
-    if !db_mongo_getter::is_owner(&client,&db_name,&server_id,&user_id).await?
-    && !db_mongo_getter::is_admin(&client,&db_name,&server_id,&user_id).await?
-    && user_id != user_to_remove
-    {
+    if user_id == user_to_remove {
         return Ok(());
     }
+    let (action, permission_error) = match permission {
+        Permissions::BAN_MEMBERS => ("bannir", "Vous n'avez pas la permission de bannir des membres"),
+        Permissions::MODERATE_MEMBERS => ("réduire au silence", "Vous n'avez pas la permission de réduire des membres au silence"),
+        _ => ("exclure", "Vous n'avez pas la permission d'exclure des membres"),
+    };
+    if !server.permissions_of(roles, user_id).contains(permission) {
+        return Err(FluxyError::Forbidden(permission_error.to_string()));
+    }
+    if server.rank_of(roles, user_to_remove) >= server.rank_of(roles, user_id) {
+        return Err(FluxyError::Forbidden(format!("Vous ne pouvez pas {} un membre de rang égal ou supérieur au vôtre", action)));
+    }
+    Ok(())
+}
+
+/// owner_removal_error :
+/// le possesseur ne peut ni partir (Conflict : il doit d'abord transférer la propriété) ni être exclu (Forbidden)
+pub(crate) fn owner_removal_error(user_id: i64, user_to_remove: i64) -> FluxyError {
+    if user_id == user_to_remove {
+        FluxyError::Conflict("Le fondateur ne peut pas quitter le serveur, il doit d'abord transférer la propriété".to_string())
+    } else {
+        FluxyError::Forbidden("Le fondateur du serveur ne peut pas être exclu".to_string())
+    }
+}
+
+/// delete_role :  
+///     rôle id  
+///     id utilisateur  
+/// permet de supprimer un rôle placé sous son rang (MANAGE_ROLES). les membres perdent le rôle avec lui
+pub async fn delete_role(client: &Client, db_name: &str, role_id: i64, user_id: i64) -> FluxyResult<()> {
+    let Some(role) = db_mongo_getter::get_role_by_id(client, db_name, &role_id).await? else {
+        return Err(FluxyError::NotFound("Rôle introuvable".to_string()));
+    };
+    let Some((server, roles)) = db_mongo_getter::get_server_with_roles(client, db_name, &role.server_id).await? else {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    };
+    check_role_management(&server, &roles, user_id, role.position, Permissions::NONE)?;
 
     client
         .database(db_name)
-        .collection::<Document>("server")
+        .collection::<Document>("role")
+        .delete_one(doc! {"id": role_id})
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la suppression du rôle: {}", e)))?;
+    // les exceptions des channels qui visaient ce rôle n'ont plus de cible
+    client
+        .database(db_name)
+        .collection::<Document>("channel")
+        .update_many(
+            doc! {"server_id": role.server_id},
+            doc! {"$pull": {"overwrites": {"kind": "role", "target_id": role_id}}},
+        )
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la suppression des exceptions du rôle: {}", e)))?;
+
+    Ok(())
+}
+
+/// delete_channel_overwrite :  
+///     channel id  
+///     rôle ou membre visé et son id  
+///     id utilisateur  
+/// permet de retirer l'exception d'un rôle ou d'un membre sur un channel (MANAGE_CHANNELS dans le channel). renvoie le channel modifié
+pub async fn delete_channel_overwrite(
+    client: &Client,
+    db_name: &str,
+    channel_id: i64,
+    kind: OverwriteKind,
+    target_id: i64,
+    user_id: i64,
+) -> FluxyResult<Channel> {
+    let Some((channel, server, roles)) = db_mongo_getter::get_channel_with_server(client, db_name, &channel_id).await? else {
+        return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+    };
+    let channel = remove_channel_overwrite(&server, &roles, channel, user_id, kind, target_id)?;
+    let kind = bson::to_bson(&kind).map_err(|e| FluxyError::Storage(e.to_string()))?;
+
+    client
+        .database(db_name)
+        .collection::<Document>("channel")
         .update_one(
-            doc! {"id":server_id},
-            doc!{"$pull":{"admin_id":user_to_remove,"member_id":user_to_remove}}
+            doc! {"id": channel_id},
+            doc! {"$pull": {"overwrites": {"kind": kind, "target_id": target_id}}},
         )
         .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la suppression de l'exception: {}", e)))?;
+
+    Ok(channel)
+}
+
+/// remove_channel_overwrite :
+/// vérifie le retrait d'une exception (voir delete_channel_overwrite) et renvoie le channel tel qu'il sera enregistré
+pub(crate) fn remove_channel_overwrite(
+    server: &Server,
+    roles: &[Role],
+    mut channel: Channel,
+    user_id: i64,
+    kind: OverwriteKind,
+    target_id: i64,
+) -> FluxyResult<Channel> {
+    check_channel_management(server, roles, &channel, user_id)?;
+    if channel.overwrite_of(kind, target_id).is_none() {
+        return Err(FluxyError::NotFound("Exception introuvable".to_string()));
+    }
+    channel
+        .overwrites
+        .retain(|overwrite| overwrite.kind != kind || overwrite.target_id != target_id);
+    Ok(channel)
+}
+
+/// remove_role_from_member :  
+///     rôle id  
+///     membre qui perd le rôle  
+///     id utilisateur  
+/// permet de retirer à un membre un rôle placé sous son rang (MANAGE_ROLES)
+pub async fn remove_role_from_member(client: &Client, db_name: &str, role_id: i64, user_to_remove: i64, user_id: i64) -> FluxyResult<()> {
+    let Some(role) = db_mongo_getter::get_role_by_id(client, db_name, &role_id).await? else {
+        return Err(FluxyError::NotFound("Rôle introuvable".to_string()));
+    };
+    let Some((server, roles)) = db_mongo_getter::get_server_with_roles(client, db_name, &role.server_id).await? else {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    };
+    check_role_management(&server, &roles, user_id, role.position, Permissions::NONE)?;
+
+    client
+        .database(db_name)
+        .collection::<Document>("role")
+        .update_one(doc! {"id": role_id}, doc! {"$pull": {"member_id": user_to_remove}})
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors du retrait du rôle: {}", e)))?;
 
     Ok(())
 }
 
-/// delete_link :  
+/// delete_invite :  
 ///     lien/code  
-/// permet de supprimer un lien d'invitation directement
-pub async fn delete_link(client: &Client, db_name: &str,link: &str)-> io::Result<()>{
+///     id utilisateur  
+/// permet de révoquer un lien d'invitation : son créateur ou un utilisateur qui a la permission ADMINISTRATOR sur le serveur
+pub async fn delete_invite(client: &Client, db_name: &str, link: &str, user_id: i64) -> FluxyResult<()> {
+    let Some(invite) = db_mongo_getter::get_invite(client, db_name, link).await? else {
+        return Err(FluxyError::NotFound("Lien d'invitation introuvable".to_string()));
+    };
+    if invite.creator_id != user_id
+        && !db_mongo_getter::has_permission(client, db_name, &invite.server_id, &user_id, Permissions::ADMINISTRATOR).await?
+    {
+        return Err(invite_revocation_error());
+    }
+
     client
-    .database(db_name)
-    .collection::<Document>("server")
-    .update_one(
-        doc!{"lien":link},
-        doc!{"$unset":{"lien":""}}
-    )
-    .await
-    .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
-
-    return Ok(());
-}
\ No newline at end of file
+        .database(db_name)
+        .collection::<Document>("invite")
+        .delete_one(doc! {"code": link})
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la révocation du lien d'invitation: {}", e)))?;
+
+    Ok(())
+}
+
+pub(crate) fn invite_revocation_error() -> FluxyError {
+    FluxyError::Forbidden("Seuls le créateur du lien et les administrateurs peuvent révoquer ce lien d'invitation".to_string())
+}
+
+/// delete_ban :  
+///     serveur id  
+///     utilisateur banni  
+///     id utilisateur  
+/// permet à un utilisateur qui a la permission BAN_MEMBERS de lever un bannissement
+pub async fn delete_ban(client: &Client, db_name: &str, server_id: i64, user_to_unban: i64, user_id: i64) -> FluxyResult<()> {
+    if !db_mongo_getter::has_permission(client, db_name, &server_id, &user_id, Permissions::BAN_MEMBERS).await? {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de bannir des membres".to_string()));
+    }
+
+    let result = client
+        .database(db_name)
+        .collection::<Document>("ban")
+        .delete_one(doc! {"server_id": server_id, "user_id": user_to_unban})
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la levée du bannissement: {}", e)))?;
+    if result.deleted_count == 0 {
+        return Err(FluxyError::NotFound("Bannissement introuvable".to_string()));
+    }
+
+    Ok(())
+}
+
+/// delete_timeout :  
+///     serveur id  
+///     membre réduit au silence  
+///     id utilisateur  
+/// permet à un utilisateur qui a la permission MODERATE_MEMBERS de lever un timeout en cours (NotFound s'il est déjà terminé)
+pub async fn delete_timeout(client: &Client, db_name: &str, server_id: i64, user_to_unmute: i64, user_id: i64) -> FluxyResult<()> {
+    if !db_mongo_getter::has_permission(client, db_name, &server_id, &user_id, Permissions::MODERATE_MEMBERS).await? {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de réduire des membres au silence".to_string()));
+    }
+
+    let now = format_date(Utc::now());
+    let result = client
+        .database(db_name)
+        .collection::<Document>("timeout")
+        .delete_one(doc! {"server_id": server_id, "user_id": user_to_unmute, "until": {"$gt": now}})
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la levée du timeout: {}", e)))?;
+    if result.deleted_count == 0 {
+        return Err(FluxyError::NotFound("Timeout introuvable".to_string()));
+    }
+
+    Ok(())
+}
+
+/// delete_expired_bans :  
+///     date actuelle (voir format_date)  
+/// lève les bannissements dont la date d'expiration est passée. renvoie le nombre de bannissements levés
+pub async fn delete_expired_bans(client: &Client, db_name: &str, now: &str) -> io::Result<u64> {
+    let result = client
+        .database(db_name)
+        .collection::<Document>("ban")
+        .delete_many(doc! {"expires_at": {"$lte": now}})
+        .await
+        .map_err(|e| io::Error::other(format!("Erreur lors de la levée des bannissements: {}", e)))?;
+
+    Ok(result.deleted_count)
+}
+
+/// repair_orphans :
+/// supprime les documents dont le serveur n'existe plus (rôles, liens d'invitation, bannissements, timeouts, journal d'audit
+/// et channels), puis les messages dont le channel n'existe plus (restes d'une suppression interrompue hors transaction).
+/// seuls les documents créés avant REPAIR_GRACE_SECS secondes sont examinés (date de leur _id) : un serveur ou un channel
+/// créé pendant la réparation garde ses enfants. renvoie le nombre de documents supprimés par collection
+pub async fn repair_orphans(client: &Client, db_name: &str) -> io::Result<RepairReport> {
+    let database = client.database(db_name);
+    let storage_error = |e: mongodb::error::Error| io::Error::other(format!("Erreur lors de la réparation: {}", e));
+
+    let cutoff_secs = Utc::now().timestamp() - REPAIR_GRACE_SECS;
+    let cutoff = ObjectId::from_parts(u32::try_from(cutoff_secs).unwrap_or(0), [0; 5], [0; 3]);
+    let roles = delete_orphans(&database, "role", "server_id", "server", cutoff).await.map_err(storage_error)?;
+    let invites = delete_orphans(&database, "invite", "server_id", "server", cutoff).await.map_err(storage_error)?;
+    let bans = delete_orphans(&database, "ban", "server_id", "server", cutoff).await.map_err(storage_error)?;
+    let timeouts = delete_orphans(&database, "timeout", "server_id", "server", cutoff).await.map_err(storage_error)?;
+    let audit_entries = delete_orphans(&database, "audit_log", "server_id", "server", cutoff).await.map_err(storage_error)?;
+    let channels = delete_orphans(&database, "channel", "server_id", "server", cutoff).await.map_err(storage_error)?;
+    // après les channels, pour retirer aussi les messages des channels orphelins
+    let messages = delete_orphans(&database, "message", "channel_id", "channel", cutoff).await.map_err(storage_error)?;
+
+    Ok(RepairReport { roles, invites, bans, timeouts, audit_entries, channels, messages })
+}
+
+/// ancienneté minimale (en secondes) d'un document examiné par repair_orphans, marge comprise pour l'écart d'horloge
+/// entre les instances qui créent les documents et celle qui répare
+const REPAIR_GRACE_SECS: i64 = 60;
+
+/// nombre de documents vérifiés à la fois par repair_orphans, pour garder les filtres $in loin de la limite de 16 Mo
+const REPAIR_BATCH_SIZE: usize = 1000;
+
+/// delete_orphans :
+/// parcourt par lots les documents créés avant `cutoff` et supprime ceux dont le champ ne référence aucun document
+/// existant de la collection parente (ou qui n'ont pas ce champ). renvoie le nombre de documents supprimés
+async fn delete_orphans(database: &Database, collection: &str, field: &str, parent: &str, cutoff: ObjectId) -> mongodb::error::Result<u64> {
+    let mut cursor = database
+        .collection::<Document>(collection)
+        .find(doc!{"_id": {"$lt": cutoff}})
+        .projection(doc!{"_id": 1, field: 1})
+        .await?;
+
+    let mut deleted = 0;
+    let mut batch = Vec::new();
+    loop {
+        let next = cursor.try_next().await?;
+        let done = next.is_none();
+        batch.extend(next);
+        if batch.len() == REPAIR_BATCH_SIZE || (done && !batch.is_empty()) {
+            deleted += delete_orphan_batch(database, collection, field, parent, &batch).await?;
+            batch.clear();
+        }
+        if done {
+            return Ok(deleted);
+        }
+    }
+}
+
+/// id_of :
+/// valeur entière d'un champ id, qu'il soit enregistré en Int32 ou en Int64
+fn id_of(value: &Bson) -> Option<i64> {
+    match value {
+        Bson::Int32(id) => Some(i64::from(*id)),
+        Bson::Int64(id) => Some(*id),
+        _ => None,
+    }
+}
+
+/// delete_orphan_batch :
+/// vérifie en une requête quels parents du lot existent encore, puis supprime les documents du lot sans parent
+async fn delete_orphan_batch(database: &Database, collection: &str, field: &str, parent: &str, batch: &[Document]) -> mongodb::error::Result<u64> {
+    let parent_ids: Vec<Bson> = batch.iter().filter_map(|doc| doc.get(field).cloned()).collect();
+    // ids comparés en i64 : un id enregistré en Int32 référence le même parent qu'en Int64
+    let existing: HashSet<i64> = database
+        .collection::<Document>(parent)
+        .distinct("id", doc!{"id": {"$in": parent_ids}})
+        .await?
+        .iter()
+        .filter_map(id_of)
+        .collect();
+    let orphan_ids: Vec<Bson> = batch
+        .iter()
+        .filter(|doc| doc.get(field).and_then(id_of).is_none_or(|parent_id| !existing.contains(&parent_id)))
+        .filter_map(|doc| doc.get("_id").cloned())
+        .collect();
+    if orphan_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let result = database
+        .collection::<Document>(collection)
+        .delete_many(doc!{"_id": {"$in": orphan_ids}})
+        .await?;
+    Ok(result.deleted_count)
+}
@@ -1,11 +1,15 @@
 //! db_mongo_getter.rs :
-//!     - get_collection :
-//!         nom de la collection    
-//!     permet de récupérer l'ensemble de la collection associée pour avoir des statistiques ou des vérifications
+//!     - count_documents :
+//!         nom de la collection
+//!     permet de compter les documents de la collection sans les charger
 //!
 //!     - get_server :  
 //!         serveur id  
-//!     permet de récupérer les données initiées par set_server  
+//!     permet de récupérer le serveur initié par set_server, s'il existe  
+//!
+//!     - get_message_revisions :  
+//!         message id  
+//!     permet de récupérer les anciennes versions d'un message modifié (tableau revisions rempli par update_message)
 //!
 //!     - get_channel_by_id :  
 //!         channel id  
@@ -27,13 +31,25 @@
 //!         channel id  
 //!     permet de récupérer l'ensemble des messages d'un channel
 //!
+//!     - get_messages_page :  
+//!         channel id  
+//!         curseur before / after (id de message)  
+//!         nombre maximum de messages  
+//!     permet de récupérer une page de messages d'un channel triée par id (croissant), avant ou après un curseur
+//!
+//!     - get_messages_of_channel_after :  
+//!         channel id  
+//!         dernier message id vu  
+//!         nombre maximum de messages  
+//!     permet de récupérer, triés par id, les premiers messages postés après un message donné (reprise après reconnexion)
+//!
 //!     - get_server_id_by_message_id :  
 //!         message id  
 //!     permet de récupérer l'id du serveur où se trouve le message
 //!
 //!     - get_last_id :  
 //!         collection  
-//!     permet de récupérer le dernier id de la collection. utile pour incrémenter les identifiants
+//!     permet de récupérer le plus grand id de la collection. utile pour initialiser le compteur d'ids
 //!
 //!     - is_owner :  
 //!         serveur id  
@@ -50,11 +66,71 @@
 //!         id de l'utilisateur  
 //!     permet de vérifier s'il est sur le serveur
 //!
+//!     - get_roles_of_server :  
+//!         serveur id  
+//!     permet de récupérer les rôles d'un serveur, du plus haut au plus bas dans la hiérarchie
+//!
+//!     - get_role_by_id :  
+//!         rôle id  
+//!     permet de récupérer les données initiées par set_role
+//!
+//!     - get_server_with_roles :  
+//!         serveur id  
+//!     permet de récupérer le serveur et ses rôles, de quoi résoudre les permissions et le rang d'un utilisateur
+//!
+//!     - has_permission :  
+//!         serveur id  
+//!         id de l'utilisateur  
+//!         permission  
+//!     permet de vérifier si l'utilisateur a la permission sur le serveur (fondateur, admins, puis rôles).
+//!     toutes les vérifications de permission des fichiers db_mongo_* passent par là ; seules les actions réservées
+//!     au fondateur (transfert, suppression du serveur, nomination des admins) gardent is_owner
+//!
+//!     - get_channel_permissions :  
+//!         channel id  
+//!         id de l'utilisateur  
+//!     permet de récupérer les permissions de l'utilisateur dans un channel, exceptions du channel comprises (voir Channel::permissions_of)
+//!
+//!     - get_channel_with_server :  
+//!         channel id  
+//!     permet de récupérer le channel, son serveur et les rôles du serveur
+//!
 //!     - is_channel_of_server :  
 //!         serveur id  
 //!         channel id  
 //!     permet de vérifier si le channel existe sur le serveur. utile dans le setter du message pour ajouter une protection supplémentaire pour écrire un message
 //!
+//!     - get_ban :  
+//!         serveur id  
+//!         id de l'utilisateur  
+//!     permet de récupérer le bannissement de l'utilisateur sur le serveur, même expiré s'il n'a pas encore été levé
+//!
+//!     - get_bans_of_server :  
+//!         serveur id  
+//!     permet de récupérer les bannissements d'un serveur, du plus récent au plus ancien
+//!
+//!     - get_invite :  
+//!         lien/code  
+//!     permet de récupérer le lien d'invitation, même expiré ou épuisé
+//!
+//!     - get_invites_of_server :  
+//!         serveur id  
+//!     permet de récupérer les liens d'invitation d'un serveur, du plus récent au plus ancien
+//!
+//!     - get_audit_log :  
+//!         serveur id  
+//!         filtres (action, auteur, curseur, nombre maximum)  
+//!     permet de récupérer le journal d'audit d'un serveur, de l'entrée la plus récente à la plus ancienne
+//!
+//!     - get_timeout :  
+//!         serveur id  
+//!         id de l'utilisateur  
+//!     permet de récupérer le timeout de l'utilisateur sur le serveur, même terminé s'il n'a pas encore été remplacé
+//!
+//!     - get_active_timeouts :  
+//!         date actuelle (voir format_date)  
+//!     permet de récupérer les timeouts encore en cours, tous serveurs confondus
+//!
 //!     - vec_doc_to_number :  
 //!         vecteur document  
 //!     permet de transformer un vecteur document obtenu auparavant pour avoir un nombre. attention il faut qu'il y ait qu'un seul nombre unique
@@ -70,64 +146,65 @@
 //!     - convert_utc_to_paris_time  
 //!         utc temps  
 //!     permet de convertir un UTC en heure de Paris sous format chaîne de caractères
+//!
+//! Les collections server, channel et message sont lues avec les structs Server, Channel et Message de models.rs :
+//! un document qui ne correspond plus au schéma renvoie une erreur InvalidData au lieu d'être lu à moitié.
 
+use crate::models::{AuditEntry, AuditLogFilter, Ban, Channel, Invite, MemberTimeout, Message, MessageRevision, Permissions, Role, Server};
+use serde::Deserialize;
 use std::io;
-use mongodb::{bson::{doc, Document}, Client};
+use mongodb::{bson::{doc, Document}, error::ErrorKind, Client};
 use futures_util::TryStreamExt;
 use chrono::{Utc, DateTime};
 use chrono_tz::Europe::Paris;
 
-/// get_collection :
-///     nom de la collection    
-/// permet de récupérer l'ensemble de la collection associée pour avoir des statistiques ou des vérifications
-pub async fn get_collection(client: &Client, db_name: &str, collection: &str) -> io::Result<Vec<Document>> {
-    let collection = client
-        .database(db_name)
-        .collection(collection)
-        .find(doc!{})
-        .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
+// Erreur de lecture d'un document : InvalidData seulement s'il ne correspond pas à la struct attendue,
+// les autres erreurs du driver (réseau, requête...) restent des erreurs de stockage
+fn invalid_document(collection: &str, e: mongodb::error::Error) -> io::Error {
+    match *e.kind {
+        ErrorKind::BsonDeserialization(_) => {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Document {} invalide: {}", collection, e))
+        }
+        _ => io::Error::other(format!("Erreur lors de la lecture de la collection {}: {}", collection, e)),
+    }
+}
 
-    let docs: Vec<Document> = collection
-        .try_collect()
+/// count_documents :
+///     nom de la collection
+/// permet de compter les documents de la collection sans les charger, pour avoir des statistiques ou des vérifications
+pub async fn count_documents(client: &Client, db_name: &str, collection: &str) -> io::Result<u64> {
+    client
+        .database(db_name)
+        .collection::<Document>(collection)
+        .count_documents(doc!{})
         .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "pas de vecteur disponible"))?;
-
-    Ok(docs)
-
+        .map_err(|e| io::Error::other(format!("Erreur lors du comptage de {}: {}", collection, e)))
 }
 
 /// get_server :  
 ///     serveur id  
 /// permet de récupérer les données initiées par set_server  
-pub async fn get_server(client: &Client, db_name: &str, server_id: &i64) -> io::Result<Vec<Document>> {
-    let collection = client
+pub async fn get_server(client: &Client, db_name: &str, server_id: &i64) -> io::Result<Option<Server>> {
+    client
         .database(db_name)
-        .collection("server")
-        .find(doc!{"id":server_id})
-        .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
-
-    let docs: Vec<Document> = collection
-        .try_collect()
+        .collection::<Server>("server")
+        .find_one(doc!{"id":server_id})
         .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "pas de vecteur disponible"))?;
-
-    Ok(docs)
+        .map_err(|e| invalid_document("server", e))
 }
 
-pub async fn get_servers_by_owner(client: &Client, db_name: &str, owner_id: &i64) -> io::Result<Vec<Document>> {
+pub async fn get_servers_by_owner(client: &Client, db_name: &str, owner_id: &i64) -> io::Result<Vec<Server>> {
     let collection = client
         .database(db_name)
-        .collection("server")
+        .collection::<Server>("server")
         .find(doc! {"owner_id": owner_id})
         .await
         .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la recherche des serveurs"))?;
 
-    let docs: Vec<Document> = collection
+    let docs: Vec<Server> = collection
         .try_collect()
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la collecte des documents"))?;
+        .map_err(|e| invalid_document("server", e))?;
 
     Ok(docs)
 }
@@ -135,20 +212,20 @@ pub async fn get_servers_by_owner(client: &Client, db_name: &str, owner_id: &i64
 /// get_servers_by_member :  
 ///     id de l'utilisateur  
 /// permet de récupérer l'ensemble des serveurs où l'utilisateur est.
-pub async fn get_servers_by_member(client: &Client, db_name: &str, user_id: &i64) -> io::Result<Vec<Document>> {
+pub async fn get_servers_by_member(client: &Client, db_name: &str, user_id: &i64) -> io::Result<Vec<Server>> {
     // MongoDB cherche automatiquement dans les tableaux avec cette syntaxe
     // La syntaxe doc!{"member_id": user_id} fonctionne pour chercher dans un tableau
     let collection = client
         .database(db_name)
-        .collection("server")
+        .collection::<Server>("server")
         .find(doc!{"member_id": user_id})
         .await
         .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la recherche des serveurs"))?;
 
-    let docs: Vec<Document> = collection
+    let docs: Vec<Server> = collection
         .try_collect()
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la collecte des documents"))?;
+        .map_err(|e| invalid_document("server", e))?;
 
     Ok(docs)
 }
@@ -156,18 +233,18 @@ pub async fn get_servers_by_member(client: &Client, db_name: &str, user_id: &i64
 /// get_channels_of_server :  
 ///     serveur id  
 /// permet de récupérer l'ensemble des channels d'un serveur
-pub async fn get_channels_of_server(client: &Client, db_name: &str, server_id: &i64) -> io::Result<Vec<Document>> {
+pub async fn get_channels_of_server(client: &Client, db_name: &str, server_id: &i64) -> io::Result<Vec<Channel>> {
     let collection = client
         .database(db_name)
-        .collection("channel")
+        .collection::<Channel>("channel")
         .find(doc! {"server_id": server_id})
         .await
         .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la recherche"))?;
 
-    let docs: Vec<Document> = collection
+    let docs: Vec<Channel> = collection
         .try_collect()
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la collecte"))?;
+        .map_err(|e| invalid_document("channel", e))?;
 
     Ok(docs)
 }
@@ -175,98 +252,156 @@ pub async fn get_channels_of_server(client: &Client, db_name: &str, server_id: &
 /// get_messages_of_channel :  
 ///     channel id  
 /// permet de récupérer l'ensemble des messages d'un channel
-pub async fn get_messages_of_channel(client: &Client, db_name: &str, channel_id: &i64) -> io::Result<Vec<Document>> {
+pub async fn get_messages_of_channel(client: &Client, db_name: &str, channel_id: &i64) -> io::Result<Vec<Message>> {
     let collection = client
         .database(db_name)
-        .collection("message")
+        .collection::<Message>("message")
         .find(doc! {"channel_id": channel_id})
         .await
         .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la recherche"))?;
 
-    let docs: Vec<Document> = collection
+    let docs: Vec<Message> = collection
         .try_collect()
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la collecte"))?;
+        .map_err(|e| invalid_document("message", e))?;
 
     Ok(docs)
 }
 
-/// get_message_by_id :  
-///     message id  
-/// permet de récupérer les données initiées par set_message  
-pub async fn get_message_by_id(client: &Client, db_name: &str, message_id: &i64) -> io::Result<Vec<Document>> {
+/// get_messages_page :  
+///     channel id  
+///     curseur before / after (id de message)  
+///     nombre maximum de messages  
+/// permet de récupérer une page de messages d'un channel triée par id (croissant), avant ou après un curseur.
+/// sans curseur, renvoie les messages les plus récents. utilise l'index (channel_id, id)
+pub async fn get_messages_page(client: &Client, db_name: &str, channel_id: &i64, before: Option<i64>, after: Option<i64>, limit: i64) -> io::Result<Vec<Message>> {
+    let mut filter = doc! {"channel_id": channel_id};
+    let mut id_filter = Document::new();
+    if let Some(before) = before {
+        id_filter.insert("$lt", before);
+    }
+    if let Some(after) = after {
+        id_filter.insert("$gt", after);
+    }
+    if !id_filter.is_empty() {
+        filter.insert("id", id_filter);
+    }
+
+    // Vers le passé (par défaut) : les plus récents d'abord, remis ensuite dans l'ordre croissant
+    let ascending = after.is_some() && before.is_none();
     let collection = client
         .database(db_name)
-        .collection("message")
-        .find(doc! {"id": message_id})
+        .collection::<Message>("message")
+        .find(filter)
+        .sort(doc! {"id": if ascending { 1 } else { -1 }})
+        .limit(limit)
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la recherche"))?;
+        .map_err(|_| io::Error::other("Erreur lors de la recherche"))?;
 
-    let docs: Vec<Document> = collection
+    let mut docs: Vec<Message> = collection
         .try_collect()
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la collecte"))?;
+        .map_err(|e| invalid_document("message", e))?;
 
+    if !ascending {
+        docs.reverse();
+    }
     Ok(docs)
 }
 
-/// get_channel_by_id :  
+/// get_messages_of_channel_after :  
 ///     channel id  
-/// permet de récupérer les données initiées par set_channel  
-pub async fn get_channel_by_id(client: &Client, db_name: &str, channel_id: &i64) -> io::Result<Vec<Document>> {
+///     dernier message id vu  
+///     nombre maximum de messages  
+/// permet de récupérer, triés par id, les premiers messages d'un channel postés après un message donné (reprise après reconnexion)
+pub async fn get_messages_of_channel_after(client: &Client, db_name: &str, channel_id: &i64, last_seen_id: &i64, limit: i64) -> io::Result<Vec<Message>> {
     let collection = client
         .database(db_name)
-        .collection("channel")
-        .find(doc! {"id": channel_id})
+        .collection::<Message>("message")
+        .find(doc! {"channel_id": channel_id, "id": {"$gt": last_seen_id}})
+        .sort(doc! {"id": 1})
+        .limit(limit)
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la recherche"))?;
+        .map_err(|_| io::Error::other("Erreur lors de la recherche"))?;
 
-    let docs: Vec<Document> = collection
+    let docs: Vec<Message> = collection
         .try_collect()
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la collecte"))?;
+        .map_err(|e| invalid_document("message", e))?;
 
     Ok(docs)
 }
 
+/// get_message_by_id :  
+///     message id  
+/// permet de récupérer les données initiées par set_message  
+pub async fn get_message_by_id(client: &Client, db_name: &str, message_id: &i64) -> io::Result<Option<Message>> {
+    client
+        .database(db_name)
+        .collection::<Message>("message")
+        .find_one(doc! {"id": message_id})
+        .await
+        .map_err(|e| invalid_document("message", e))
+}
+
+// Projection du document message sur son historique
+#[derive(Deserialize)]
+struct MessageRevisions {
+    #[serde(default)]
+    revisions: Vec<MessageRevision>,
+}
+
+/// get_message_revisions :  
+///     message id  
+/// permet de récupérer les anciennes versions d'un message modifié, de la plus ancienne à la plus récente.
+/// renvoie un vecteur vide si le message n'a jamais été modifié ou n'existe pas
+pub async fn get_message_revisions(client: &Client, db_name: &str, message_id: &i64) -> io::Result<Vec<MessageRevision>> {
+    let message = client
+        .database(db_name)
+        .collection::<MessageRevisions>("message")
+        .find_one(doc! {"id": message_id})
+        .projection(doc! {"_id": 0, "revisions": 1})
+        .await
+        .map_err(|e| invalid_document("message", e))?;
+    Ok(message.map(|message| message.revisions).unwrap_or_default())
+}
+
+/// get_channel_by_id :  
+///     channel id  
+/// permet de récupérer les données initiées par set_channel  
+pub async fn get_channel_by_id(client: &Client, db_name: &str, channel_id: &i64) -> io::Result<Option<Channel>> {
+    client
+        .database(db_name)
+        .collection::<Channel>("channel")
+        .find_one(doc! {"id": channel_id})
+        .await
+        .map_err(|e| invalid_document("channel", e))
+}
+
 
 /// get_server_id_by_message_id :  
 ///     message id  
 /// permet de récupérer l'id du serveur où se trouve le message
 pub async fn get_server_id_by_message_id(client: &Client, db_name: &str, message_id: &i64) -> io::Result<i64> {
-    let message_by_id = get_message_by_id(client, db_name, message_id).await?;
-    
-    for message in message_by_id {
-        for (key, value) in message {
-            if key == "channel_id" {
-                if let Some(channel_id) = value.as_i64() {
-                    let channel_by_id = get_channel_by_id(client, db_name, &channel_id).await?;
-                    for channel in channel_by_id {
-                        for (key_c, value_c) in channel {
-                            if key_c == "server_id" {
-                                if let Some(server_id) = value_c.as_i64() {
-                                    return Ok(server_id);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-    Ok(0)
+    let Some(message) = get_message_by_id(client, db_name, message_id).await? else {
+        return Ok(0);
+    };
+    Ok(get_channel_by_id(client, db_name, &message.channel_id)
+        .await?
+        .map(|channel| channel.server_id)
+        .unwrap_or(0))
 }
 
 /// get_last_id :  
 ///     collection  
-/// permet de récupérer le dernier id de la collection. utile pour incrémenter les identifiants
+/// permet de récupérer le plus grand id de la collection. utile pour initialiser le compteur d'ids (voir next_id)
 pub async fn get_last_id(client: &Client, db_name: &str, collection: &str) -> io::Result<i64> {
     let collection = client
         .database(db_name)
         .collection(collection)
         .find(doc! {})
         .projection(doc! {"id": 1, "_id": 0})
-        .sort(doc! {"_id": -1})
+        .sort(doc! {"id": -1})
         .limit(1)
         .await
         .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la recherche"))?;
@@ -339,6 +474,84 @@ pub async fn is_member(client: &Client, db_name: &str,  server_id: &i64, user_id
     Ok(!docs.is_empty())
 }
 
+/// get_roles_of_server :  
+///     serveur id  
+/// permet de récupérer les rôles d'un serveur, du plus haut au plus bas dans la hiérarchie
+pub async fn get_roles_of_server(client: &Client, db_name: &str, server_id: &i64) -> io::Result<Vec<Role>> {
+    let collection = client
+        .database(db_name)
+        .collection::<Role>("role")
+        .find(doc! {"server_id": server_id})
+        .sort(doc! {"position": -1, "id": 1})
+        .await
+        .map_err(|_| io::Error::other("Erreur lors de la recherche"))?;
+
+    let docs: Vec<Role> = collection
+        .try_collect()
+        .await
+        .map_err(|e| invalid_document("role", e))?;
+
+    Ok(docs)
+}
+
+/// get_role_by_id :  
+///     rôle id  
+/// permet de récupérer les données initiées par set_role
+pub async fn get_role_by_id(client: &Client, db_name: &str, role_id: &i64) -> io::Result<Option<Role>> {
+    client
+        .database(db_name)
+        .collection::<Role>("role")
+        .find_one(doc! {"id": role_id})
+        .await
+        .map_err(|e| invalid_document("role", e))
+}
+
+/// get_server_with_roles :  
+///     serveur id  
+/// permet de récupérer le serveur et ses rôles, de quoi résoudre les permissions et le rang d'un utilisateur
+/// (voir Server::permissions_of et Server::rank_of)
+pub async fn get_server_with_roles(client: &Client, db_name: &str, server_id: &i64) -> io::Result<Option<(Server, Vec<Role>)>> {
+    let Some(server) = get_server(client, db_name, server_id).await? else {
+        return Ok(None);
+    };
+    let roles = get_roles_of_server(client, db_name, server_id).await?;
+    Ok(Some((server, roles)))
+}
+
+/// has_permission :  
+///     serveur id  
+///     id de l'utilisateur  
+///     permission  
+/// permet de vérifier si l'utilisateur a la permission sur le serveur : le fondateur et les admins les ont toutes,
+/// un membre a celles de ses rôles. faux si le serveur n'existe pas
+pub async fn has_permission(client: &Client, db_name: &str, server_id: &i64, user_id: &i64, permission: Permissions) -> io::Result<bool> {
+    Ok(get_server_with_roles(client, db_name, server_id)
+        .await?
+        .is_some_and(|(server, roles)| server.permissions_of(&roles, *user_id).contains(permission)))
+}
+
+/// get_channel_permissions :  
+///     channel id  
+///     id de l'utilisateur  
+/// permet de récupérer les permissions de l'utilisateur dans un channel : celles du serveur, puis les exceptions du channel
+/// (voir Channel::permissions_of). aucune si le channel ou le serveur n'existe pas
+pub async fn get_channel_permissions(client: &Client, db_name: &str, channel_id: &i64, user_id: &i64) -> io::Result<Permissions> {
+    Ok(get_channel_with_server(client, db_name, channel_id)
+        .await?
+        .map_or(Permissions::NONE, |(channel, server, roles)| channel.permissions_of(&server, &roles, *user_id)))
+}
+
+/// get_channel_with_server :  
+///     channel id  
+/// permet de récupérer le channel, son serveur et les rôles du serveur, de quoi résoudre les permissions dans le channel
+pub async fn get_channel_with_server(client: &Client, db_name: &str, channel_id: &i64) -> io::Result<Option<(Channel, Server, Vec<Role>)>> {
+    let Some(channel) = get_channel_by_id(client, db_name, channel_id).await? else {
+        return Ok(None);
+    };
+    Ok(get_server_with_roles(client, db_name, &channel.server_id)
+        .await?
+        .map(|(server, roles)| (channel, server, roles)))
+}
 
 /// is_channel_of_server :  
 ///     serveur id  
@@ -346,16 +559,135 @@ pub async fn is_member(client: &Client, db_name: &str,  server_id: &i64, user_id
 /// permet de vérifier si le channel existe sur le serveur. utile dans le setter du message pour ajouter une protection supplémentaire pour écrire un message
 pub async fn is_channel_of_server(client: &Client, db_name: &str, server_id: i64, channel_id: i64) -> io::Result<bool> {
     let channels = get_channels_of_server(client, db_name, &server_id).await?;
-    for ch in channels {
-        if let Some(id) = ch.get("id").and_then(|v| v.as_i64()) {
-            if id == channel_id {
-                return Ok(true);
-            }
-        }
+    Ok(channels.iter().any(|channel| channel.id == channel_id))
+}
+
+
+/// get_ban :  
+///     serveur id  
+///     id de l'utilisateur  
+/// permet de récupérer le bannissement de l'utilisateur sur le serveur, même expiré s'il n'a pas encore été levé (voir Ban::is_active_at)
+pub async fn get_ban(client: &Client, db_name: &str, server_id: &i64, user_id: &i64) -> io::Result<Option<Ban>> {
+    client
+        .database(db_name)
+        .collection::<Ban>("ban")
+        .find_one(doc! {"server_id": server_id, "user_id": user_id})
+        .await
+        .map_err(|e| invalid_document("ban", e))
+}
+
+/// get_bans_of_server :  
+///     serveur id  
+/// permet de récupérer les bannissements d'un serveur, du plus récent au plus ancien
+pub async fn get_bans_of_server(client: &Client, db_name: &str, server_id: &i64) -> io::Result<Vec<Ban>> {
+    let collection = client
+        .database(db_name)
+        .collection::<Ban>("ban")
+        .find(doc! {"server_id": server_id})
+        .sort(doc! {"banned_at": -1, "user_id": 1})
+        .await
+        .map_err(|_| io::Error::other("Erreur lors de la recherche"))?;
+
+    let docs: Vec<Ban> = collection
+        .try_collect()
+        .await
+        .map_err(|e| invalid_document("ban", e))?;
+
+    Ok(docs)
+}
+
+/// get_timeout :  
+///     serveur id  
+///     id de l'utilisateur  
+/// permet de récupérer le timeout de l'utilisateur sur le serveur, même terminé s'il n'a pas encore été remplacé
+/// (voir MemberTimeout::remaining_secs)
+pub async fn get_timeout(client: &Client, db_name: &str, server_id: &i64, user_id: &i64) -> io::Result<Option<MemberTimeout>> {
+    client
+        .database(db_name)
+        .collection::<MemberTimeout>("timeout")
+        .find_one(doc! {"server_id": server_id, "user_id": user_id})
+        .await
+        .map_err(|e| invalid_document("timeout", e))
+}
+
+/// get_active_timeouts :  
+///     date actuelle (voir format_date)  
+/// permet de récupérer les timeouts encore en cours, tous serveurs confondus, pour reprogrammer leur fin au démarrage
+/// (voir services::restore_timeouts)
+pub async fn get_active_timeouts(client: &Client, db_name: &str, now: &str) -> io::Result<Vec<MemberTimeout>> {
+    client
+        .database(db_name)
+        .collection::<MemberTimeout>("timeout")
+        .find(doc! {"until": {"$gt": now}})
+        .await
+        .map_err(|e| invalid_document("timeout", e))?
+        .try_collect()
+        .await
+        .map_err(|e| invalid_document("timeout", e))
+}
+
+/// get_audit_log :  
+///     serveur id  
+///     filtres (action, auteur, curseur, nombre maximum)  
+/// permet de récupérer le journal d'audit d'un serveur, de l'entrée la plus récente à la plus ancienne.
+/// `before` ne garde que les entrées d'id inférieur. utilise l'index (server_id, id)
+pub async fn get_audit_log(client: &Client, db_name: &str, server_id: &i64, filter: &AuditLogFilter) -> io::Result<Vec<AuditEntry>> {
+    let mut query = doc! {"server_id": server_id};
+    if let Some(action) = filter.action {
+        let action = mongodb::bson::to_bson(&action).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        query.insert("action", action);
+    }
+    if let Some(user_id) = filter.user_id {
+        query.insert("actor_id", user_id);
+    }
+    if let Some(before) = filter.before {
+        query.insert("id", doc! {"$lt": before});
     }
-    Ok(false)
+
+    let collection = client
+        .database(db_name)
+        .collection::<AuditEntry>("audit_log")
+        .find(query)
+        .sort(doc! {"id": -1})
+        .limit(filter.limit)
+        .await
+        .map_err(|_| io::Error::other("Erreur lors de la recherche"))?;
+
+    collection
+        .try_collect()
+        .await
+        .map_err(|e| invalid_document("audit_log", e))
+}
+
+/// get_invite :  
+///     lien/code  
+/// permet de récupérer le lien d'invitation, même expiré ou épuisé (voir Invite::is_usable_at)
+pub async fn get_invite(client: &Client, db_name: &str, link_code: &str) -> io::Result<Option<Invite>> {
+    client
+        .database(db_name)
+        .collection::<Invite>("invite")
+        .find_one(doc! {"code": link_code})
+        .await
+        .map_err(|e| invalid_document("invite", e))
 }
 
+/// get_invites_of_server :  
+///     serveur id  
+/// permet de récupérer les liens d'invitation d'un serveur, du plus récent au plus ancien
+pub async fn get_invites_of_server(client: &Client, db_name: &str, server_id: &i64) -> io::Result<Vec<Invite>> {
+    let collection = client
+        .database(db_name)
+        .collection::<Invite>("invite")
+        .find(doc! {"server_id": server_id})
+        .sort(doc! {"created_at": -1, "code": 1})
+        .await
+        .map_err(|_| io::Error::other("Erreur lors de la recherche"))?;
+
+    collection
+        .try_collect()
+        .await
+        .map_err(|e| invalid_document("invite", e))
+}
 
 /// vec_doc_to_number :  
 ///     vecteur document  
@@ -379,8 +711,8 @@ pub fn vec_doc_to_number(docs: Vec<Document>) -> i64 {
 pub async fn verify_link_exist(client: &Client, db_name: &str,link_code: &str)-> io::Result<bool>{
     let collection = client
         .database(db_name)
-        .collection::<Document>("server")
-        .find(doc!{"lien":&link_code})
+        .collection::<Document>("invite")
+        .find(doc!{"code":&link_code})
         .await
         .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
     
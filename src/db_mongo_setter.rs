@@ -1,4 +1,8 @@
 //! db_mongo_setter.rs :
+//!     - next_id :
+//!         collection
+//!     permet d'attribuer atomiquement le prochain id d'une collection grâce au compteur de la collection counters ($inc)
+//!
 //!     -set_server :
 //!         utilisateur qui le crée
 //!         nom du serveur
@@ -8,14 +12,16 @@
 //!         serveur id  
 //!         nom du channel  
 //!         utilisateur qui le crée  
-//!     permet de créer un channel dans le serveur que si l'utilisateur a la permission (administrateur ou possesseur).
+//!     permet de créer un channel dans le serveur que si l'utilisateur a la permission MANAGE_CHANNELS.
 //!
 //!     - set_message :  
 //!         serveur id  
 //!         channel id  
 //!         message  
 //!         utilisateur qui écrit  
-//!     permet d'écrire dans le channel du serveur correspondant. une vérification est effectuée pour vérifier que le membre et le salon existent bien dans le serveur
+//!     permet d'écrire dans le channel du serveur correspondant. une vérification est effectuée pour vérifier que le salon existe bien dans le serveur
+//!     et que le membre peut le voir et y écrire (exceptions du channel comprises).
+//!     @everyone et @here demandent la permission MENTION_EVERYONE. renvoie le message enregistré
 //!
 //!     - add_member_to_server :  
 //!         serveur id  
@@ -34,24 +40,117 @@
 //!         membre à passer possesseur  
 //!     permet uniquement au possesseur du serveur de passer un autre membre possesseur du serveur à sa place  
 //!
-//!     - create_link_one_use :  
+//!     - create_invite :  
 //!         serveur id  
 //!         utilisateur qui veut créer le lien  
-//!     permet de créer un lien que si l'on est administrateur ou possesseur du serveur. le terme one_use ne s'effectue que sur la ligne commentée dans join_by_link est décommentée. la gestion de l'aléatoire du code est effectuée grâce à random_string
+//!         date d'expiration (None : permanent)  
+//!         nombre maximum d'utilisations (None : illimité)  
+//!     permet de créer un lien d'invitation que si l'on a la permission CREATE_INVITES. un serveur peut avoir plusieurs liens,
+//!     chacun dans la collection invite avec son créateur et son compteur d'utilisations. la gestion de l'aléatoire du code est effectuée grâce à random_string
 //!
 //!     - join_by_link :  
 //!         lien/code  
 //!         utilisateur qui veut rejoindre  
-//!     permet de rejoindre le serveur grâce à un lien.
+//!     permet de rejoindre le serveur grâce à un lien ni expiré ni épuisé. l'utilisation est comptée atomiquement
+//!
+//!     - set_role :  
+//!         serveur id  
+//!         nom du rôle  
+//!         permissions  
+//!         utilisateur qui le crée  
+//!     permet de créer un rôle personnalisé, placé tout en bas de la hiérarchie. demande MANAGE_ROLES, et on ne peut pas donner
+//!     une permission qu'on n'a pas
+//!
+//!     - add_role_to_member :  
+//!         rôle id  
+//!         membre qui reçoit le rôle  
+//!         utilisateur qui donne le rôle  
+//!     permet de donner un rôle placé sous son propre rang (MANAGE_ROLES) à un membre du serveur
+//!
+//!     - set_ban :  
+//!         serveur id  
+//!         utilisateur à bannir  
+//!         raison  
+//!         date d'expiration (None : définitif)  
+//!         utilisateur qui bannit  
+//!     permet à un utilisateur qui a la permission BAN_MEMBERS de bannir un utilisateur de rang inférieur au sien.
+//!     le banni quitte le serveur s'il en était membre, et add_member_to_server comme join_by_link le refusent tant que le bannissement dure
+//!
+//!     - set_timeout :  
+//!         serveur id  
+//!         membre à réduire au silence  
+//!         date de fin  
+//!         modérateur  
+//!     permet à un utilisateur qui a la permission MODERATE_MEMBERS de réduire au silence un membre de rang inférieur au sien,
+//!     au plus MAX_TIMEOUT_SECS secondes. set_message le refuse avec FluxyError::TimedOut tant que le timeout dure
+//!
+//!     - set_audit_entry :  
+//!         entrée d'audit  
+//!     permet d'ajouter une entrée au journal d'audit du serveur, avec le prochain id de la collection audit_log et la date actuelle.
+//!     aucune permission n'est vérifiée : l'entrée est écrite par les handlers après une action déjà autorisée
+//!
+//! Les permissions sont résolues par db_mongo_getter::has_permission (voir Permissions dans models.rs).
+//! Les fonctions qui modifient la base renvoient une FluxyError (error.rs) : Forbidden si l'utilisateur n'a pas la permission,
+//! NotFound si le lien n'existe pas ou n'est plus utilisable, Conflict si un id existe déjà (index unique).
 
 use crate::db_mongo_getter;
-// use crate::db_mongo_delete;
+use crate::error::{FluxyError, FluxyResult};
+use crate::models::{format_date, AuditEntry, Ban, Channel, Invite, MemberTimeout, Message, NewAuditEntry, Permissions, Role, Server};
+use crate::db_mongo_delete;
 use std::io;
-use mongodb::{bson::{doc, Document}, Client};
-use chrono::Utc;
-use futures_util::TryStreamExt;
+use mongodb::{bson::{doc, Document}, error::{ErrorKind, WriteFailure}, options::ReturnDocument, Client};
+use chrono::{DateTime, Utc};
 use rand::Rng;
 
+/// next_id :
+///     collection
+/// permet d'attribuer atomiquement le prochain id d'une collection grâce au compteur de la collection counters ($inc).
+/// à la première utilisation, le compteur part du plus grand id déjà présent dans la collection
+pub async fn next_id(client: &Client, db_name: &str, collection: &str) -> io::Result<i64> {
+    let counters = client.database(db_name).collection::<Document>("counters");
+
+    let counter = counters
+        .find_one_and_update(doc! {"_id": collection}, doc! {"$inc": {"seq": 1_i64}})
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|_| io::Error::other("Erreur lors de l'attribution de l'id"))?;
+
+    let counter = match counter {
+        Some(counter) => counter,
+        None => {
+            // $max rend l'initialisation sans effet si un autre appel a déjà créé le compteur
+            let last_id = db_mongo_getter::get_last_id(client, db_name, collection).await?;
+            counters
+                .update_one(doc! {"_id": collection}, doc! {"$max": {"seq": last_id}})
+                .upsert(true)
+                .await
+                .ok();
+            counters
+                .find_one_and_update(doc! {"_id": collection}, doc! {"$inc": {"seq": 1_i64}})
+                .upsert(true)
+                .return_document(ReturnDocument::After)
+                .await
+                .map_err(|_| io::Error::other("Erreur lors de l'attribution de l'id"))?
+                .ok_or_else(|| io::Error::other("Compteur d'id introuvable"))?
+        }
+    };
+
+    counter
+        .get_i64("seq")
+        .map_err(|_| io::Error::other("Compteur d'id invalide"))
+}
+
+/// insert_error :
+/// une violation d'index unique (code 11000) devient Conflict, le reste une erreur de stockage
+pub(crate) fn insert_error(e: mongodb::error::Error, message: &str) -> FluxyError {
+    match *e.kind {
+        ErrorKind::Write(WriteFailure::WriteError(ref write_error)) if write_error.code == 11000 => {
+            FluxyError::Conflict(format!("{} : cet identifiant existe déjà", message))
+        }
+        _ => FluxyError::Storage(format!("{}: {}", message, e)),
+    }
+}
+
 /// set_server :
 ///     utilisateur qui le crée
 ///     nom du serveur
@@ -63,29 +162,27 @@ pub async fn set_server(
     owner_id: i64,
     name: &str,
     image: Option<String>,
-) -> io::Result<()> {
-    let last_id = db_mongo_getter::get_last_id(client, db_name, "server").await?;
+) -> FluxyResult<()> {
+    if name.trim().is_empty() {
+        return Err(FluxyError::Validation("Le nom du serveur est obligatoire".to_string()));
+    }
+    let id = next_id(client, db_name, "server").await?;
     
-    let mut server_doc = doc! {
-        "id": last_id + 1,
-        "name": name,
-        "owner_id": owner_id,
-        "admin_id": [],
-        "member_id": [owner_id]
+    let server = Server {
+        id,
+        name: name.to_string(),
+        owner_id,
+        admin_id: vec![],
+        member_id: vec![owner_id],
+        image: image.filter(|img| !img.trim().is_empty()),
     };
 
-    if let Some(img) = image {
-        if !img.trim().is_empty() {
-            server_doc.insert("image", img);
-        }
-    }
-
     client
         .database(db_name)
-        .collection("server")
-        .insert_one(server_doc)
+        .collection::<Server>("server")
+        .insert_one(server)
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la création du serveur"))?;
+        .map_err(|e| insert_error(e, "Erreur lors de la création du serveur"))?;
 
     Ok(())
 }
@@ -94,29 +191,31 @@ pub async fn set_server(
 ///     serveur id  
 ///     nom du channel  
 ///     utilisateur qui le crée  
-/// permet de créer un channel dans le serveur que si l'utilisateur a la permission (administrateur ou possesseur).
-pub async fn set_channel(client: &Client, db_name: &str, server_id: i64, name: &str, user_id: i64) -> io::Result<()> {
-    // Vérifie que l'utilisateur est owner ou admin du serveur ciblé
-    if !db_mongo_getter::is_owner(&client, &db_name, &server_id, &user_id).await?
-        && !db_mongo_getter::is_admin(&client, &db_name, &server_id, &user_id).await?
-    {
-        return Ok(());
+/// permet de créer un channel dans le serveur que si l'utilisateur a la permission MANAGE_CHANNELS.
+pub async fn set_channel(client: &Client, db_name: &str, server_id: i64, name: &str, user_id: i64) -> FluxyResult<()> {
+    if name.trim().is_empty() {
+        return Err(FluxyError::Validation("Le nom du channel est obligatoire".to_string()));
+    }
+    if !db_mongo_getter::has_permission(client, db_name, &server_id, &user_id, Permissions::MANAGE_CHANNELS).await? {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de créer un channel".to_string()));
     }
 
-    let last_id = db_mongo_getter::get_last_id(client, db_name,"channel").await?;
+    let id = next_id(client, db_name, "channel").await?;
     let last_position = db_mongo_getter::get_channels_of_server(client,db_name,&server_id).await?.len()as i64;
     
     client
         .database(db_name)
-        .collection("channel")
-        .insert_one(doc! {
-            "id": last_id + 1,
-            "server_id": server_id,
-            "position": last_position+1,
-            "name": name,
+        .collection::<Channel>("channel")
+        .insert_one(Channel {
+            id,
+            server_id,
+            name: name.to_string(),
+            position: last_position+1,
+            private: false,
+            overwrites: vec![],
         })
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la création du channel"))?;
+        .map_err(|e| insert_error(e, "Erreur lors de la création du channel"))?;
 
     Ok(())
 }
@@ -126,40 +225,53 @@ pub async fn set_channel(client: &Client, db_name: &str, server_id: i64, name: &
 ///     channel id  
 ///     message  
 ///     utilisateur qui écrit  
-/// permet d'écrire dans le channel du serveur correspondant. une vérification est effectuée pour vérifier que le membre et le salon existent bien dans le serveur
-pub async fn set_message(client: &Client, db_name: &str, server_id: i64, channel_id: i64, message: &str, user_id: i64) -> io::Result<()> {
-    let is_member = db_mongo_getter::is_member(&client, &db_name, &server_id, &user_id).await?;
+/// permet d'écrire dans le channel du serveur correspondant. une vérification est effectuée pour vérifier que le salon existe bien dans le serveur
+/// et que l'utilisateur peut le voir et y écrire (VIEW_CHANNEL et SEND_MESSAGES, exceptions du channel comprises).
+/// @everyone et @here demandent la permission MENTION_EVERYONE.
+/// renvoie le message enregistré (id et time attribués par le serveur), ou une erreur Forbidden si la vérification échoue
+pub async fn set_message(client: &Client, db_name: &str, server_id: i64, channel_id: i64, message: &str, user_id: i64) -> FluxyResult<Message> {
     let is_channel = db_mongo_getter::is_channel_of_server(client, db_name, server_id, channel_id).await?;
+    let permissions = db_mongo_getter::get_channel_permissions(client, db_name, &channel_id, &user_id).await?;
     
-    if !is_member || !is_channel {
-        println!("set_message: is_member={}, is_channel={} pour server_id={}, channel_id={}, user_id={}", 
-                 is_member, is_channel, server_id, channel_id, user_id);
-        return Ok(());
+    if let Err(e) = check_send_message(is_channel, permissions, message) {
+        println!("set_message: is_channel={}, permissions={} pour server_id={}, channel_id={}, user_id={}", 
+                 is_channel, permissions.0, server_id, channel_id, user_id);
+        return Err(e);
     }
+    let timeout = db_mongo_getter::get_timeout(client, db_name, &server_id, &user_id).await?;
+    check_not_timed_out(timeout, permissions, Utc::now())?;
+
+    let id = next_id(client, db_name, "message").await?;
 
-    let last_id = db_mongo_getter::get_last_id(client, db_name, "message").await?;
+    let message = Message {
+        id,
+        channel_id,
+        message: message.to_string(),
+        user: user_id,
+        time: Utc::now().to_rfc3339(),
+        edited_at: None,
+    };
 
     client
         .database(db_name)
-        .collection("message")
-        .insert_one(doc! {
-            "id": last_id + 1,
-            "channel_id": channel_id,
-            "message": message,
-            "user": user_id,
-            "time": Utc::now().to_rfc3339()
-        })
+        .collection::<Message>("message")
+        .insert_one(&message)
         .await
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Erreur lors de la création du message"))?;
+        .map_err(|e| insert_error(e, "Erreur lors de la création du message"))?;
 
-    Ok(())
+    Ok(message)
 }
 
 /// add_member_to_server :  
 ///     serveur id  
 ///     membre id  
-/// permet de forcer un membre à rejoindre un serveur. pas de lien d'invitation ni de confirmation de l'utilisateur pour rejoindre
-pub async fn add_member_to_server(client: &Client, db_name: &str, server_id: i64, user_id: i64) -> io::Result<()> {
+/// permet de forcer un membre à rejoindre un serveur. pas de lien d'invitation ni de confirmation de l'utilisateur pour rejoindre.
+/// refusé si l'utilisateur est banni du serveur
+pub async fn add_member_to_server(client: &Client, db_name: &str, server_id: i64, user_id: i64) -> FluxyResult<()> {
+    if db_mongo_getter::get_server(client, db_name, &server_id).await?.is_none() {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    }
+    check_not_banned(db_mongo_getter::get_ban(client, db_name, &server_id, &user_id).await?)?;
 
     client
         .database(db_name)
@@ -169,12 +281,7 @@ pub async fn add_member_to_server(client: &Client, db_name: &str, server_id: i64
             doc! {"$addToSet": {"member_id": user_id}},
         )
         .await
-        .map_err(|_| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                "Erreur lors de l'ajout du membre",
-            )
-        })?;
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de l'ajout du membre: {}", e)))?;
 
     Ok(())
 }
@@ -184,10 +291,12 @@ pub async fn add_member_to_server(client: &Client, db_name: &str, server_id: i64
 ///     possesseur id  
 ///     membre à passer administrateur  
 /// permet uniquement au possesseur du serveur d'ajouter un administrateur
-pub async fn add_admin_to_server(client: &Client, db_name: &str, server_id: i64, user_id: i64, user_to_add: i64) -> io::Result<()> {
-    if !db_mongo_getter::is_owner(&client, &db_name,&server_id, &user_id).await? 
-        || !db_mongo_getter::is_member(&client,&db_name,&server_id, &user_to_add).await?{
-        return Ok(());
+pub async fn add_admin_to_server(client: &Client, db_name: &str, server_id: i64, user_id: i64, user_to_add: i64) -> FluxyResult<()> {
+    if !db_mongo_getter::is_owner(client, db_name, &server_id, &user_id).await? {
+        return Err(FluxyError::Forbidden("Seul le fondateur peut nommer un administrateur".to_string()));
+    }
+    if !db_mongo_getter::is_member(client, db_name, &server_id, &user_to_add).await?{
+        return Err(FluxyError::Validation("L'utilisateur n'est pas membre du serveur".to_string()));
     }
 
     client
@@ -198,12 +307,7 @@ pub async fn add_admin_to_server(client: &Client, db_name: &str, server_id: i64,
             doc! {"$addToSet": {"admin_id": user_to_add}},
         )
         .await
-        .map_err(|_| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                "Erreur lors de l'ajout de l'admin",
-            )
-        })?;
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de l'ajout de l'admin: {}", e)))?;
 
     Ok(())
 }
@@ -213,10 +317,14 @@ pub async fn add_admin_to_server(client: &Client, db_name: &str, server_id: i64,
 ///     possesseur id  
 ///     membre à passer possesseur  
 /// permet uniquement au possesseur du serveur de passer un autre membre possesseur du serveur à sa place  
-pub async fn switch_owner(client: &Client, db_name: &str, server_id: i64, user_id: i64, user_to_replace: i64) -> io::Result<()> {
-    if !db_mongo_getter::is_owner(&client, &db_name, &server_id, &user_id).await? {
-        return Ok(());
+pub async fn switch_owner(client: &Client, db_name: &str, server_id: i64, user_id: i64, user_to_replace: i64) -> FluxyResult<()> {
+    if !db_mongo_getter::is_owner(client, db_name, &server_id, &user_id).await? {
+        return Err(FluxyError::Forbidden("Seul le fondateur peut transférer la propriété du serveur".to_string()));
     }
+    check_new_owner(
+        db_mongo_getter::is_member(client, db_name, &server_id, &user_to_replace).await?,
+        db_mongo_getter::get_ban(client, db_name, &server_id, &user_to_replace).await?,
+    )?;
 
     client
         .database(db_name)
@@ -226,74 +334,488 @@ pub async fn switch_owner(client: &Client, db_name: &str, server_id: i64, user_i
             doc! {"$set": {"owner_id": user_to_replace}},
         )
         .await
-        .map_err(|_| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                "Erreur lors du transfert de propriété",
-            )
-        })?;
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors du transfert de propriété: {}", e)))?;
 
     Ok(())
 }
 
-/// create_link_one_use :  
+/// create_invite :  
 ///     serveur id  
 ///     utilisateur qui veut créer le lien  
-/// permet de créer un lien que si l'on est administrateur ou possesseur du serveur. le terme one_use ne s'effectue que sur la ligne commentée dans join_by_link est décommentée. la gestion de l'aléatoire du code est effectuée grâce à random_string
-pub async fn create_link_one_use(client: &Client, db_name: &str, server_id: i64, user_id: i64)->io::Result<String>{
-    if !db_mongo_getter::is_owner(&client,&db_name, &server_id,&user_id).await?
-        && !db_mongo_getter::is_admin(&client,&db_name, &server_id,&user_id).await?{
-        return Ok("a".to_string());
+///     date d'expiration (None : permanent, voir format_date)  
+///     nombre maximum d'utilisations (None : illimité)  
+/// permet de créer un lien d'invitation (collection invite) si l'on a la permission CREATE_INVITES. un serveur peut avoir
+/// plusieurs liens ; le code est tiré par random_string jusqu'à en trouver un libre. renvoie le lien enregistré
+pub async fn create_invite(
+    client: &Client,
+    db_name: &str,
+    server_id: i64,
+    user_id: i64,
+    expires_at: Option<String>,
+    max_uses: Option<i64>,
+) -> FluxyResult<Invite> {
+    if !db_mongo_getter::has_permission(client, db_name, &server_id, &user_id, Permissions::CREATE_INVITES).await? {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de créer un lien d'invitation".to_string()));
     }
     let mut link_code = random_string();
-    while db_mongo_getter::verify_link_exist(client, db_name,&link_code).await?{
+    while db_mongo_getter::verify_link_exist(client, db_name, &link_code).await? {
         link_code = random_string();
-        db_mongo_getter::verify_link_exist(client, db_name,&link_code);
     }
+    let invite = new_invite(link_code, server_id, user_id, expires_at, max_uses);
+    check_invite(&invite)?;
 
     client
         .database(db_name)
-        .collection::<Document>("server")
-        .update_one(
-            doc! {"id":server_id},
-            doc!{"$set":{"lien":&link_code}})
+        .collection::<Invite>("invite")
+        .insert_one(&invite)
         .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
-    
-    
-    Ok(link_code)
-    
+        .map_err(|e| insert_error(e, "Erreur lors de la création du lien d'invitation"))?;
+
+    Ok(invite)
+}
+
+/// new_invite :
+/// lien d'invitation daté de maintenant, encore jamais utilisé
+pub(crate) fn new_invite(code: String, server_id: i64, creator_id: i64, expires_at: Option<String>, max_uses: Option<i64>) -> Invite {
+    Invite {
+        code,
+        server_id,
+        creator_id,
+        created_at: format_date(Utc::now()),
+        expires_at,
+        max_uses,
+        uses: 0,
+    }
+}
+
+/// check_invite :
+/// règles d'un lien d'invitation (voir create_invite) : au moins une utilisation et une expiration dans le futur
+pub(crate) fn check_invite(invite: &Invite) -> FluxyResult<()> {
+    if invite.max_uses.is_some_and(|max_uses| max_uses < 1) {
+        return Err(FluxyError::Validation("Le nombre maximum d'utilisations doit être positif".to_string()));
+    }
+    if !invite.is_usable_at(&invite.created_at) {
+        return Err(FluxyError::Validation("La date d'expiration du lien d'invitation est déjà passée".to_string()));
+    }
+    Ok(())
 }
 
 /// join_by_link :  
 ///     lien/code  
 ///     utilisateur qui veut rejoindre  
-/// permet de rejoindre le serveur grâce à un lien.
-pub async fn join_by_link(client: &Client, db_name: &str,link: &str,user_id: i64)->io::Result<()>{
-    if !db_mongo_getter::verify_link_exist(client, db_name,link).await?{
+/// permet de rejoindre le serveur grâce à un lien, sauf si l'on en est banni. un membre du serveur ne consomme pas d'utilisation.
+/// le compteur est incrémenté par un seul update_one filtré sur l'expiration et le maximum : deux arrivées simultanées
+/// ne peuvent pas dépasser max_uses. si l'ajout au serveur échoue ou n'ajoute personne, l'utilisation est rendue
+pub async fn join_by_link(client: &Client, db_name: &str,link: &str,user_id: i64)->FluxyResult<()>{
+    let Some(invite) = db_mongo_getter::get_invite(client, db_name, link).await? else {
+        return Err(FluxyError::NotFound("Lien d'invitation invalide".to_string()));
+    };
+    check_not_banned(db_mongo_getter::get_ban(client, db_name, &invite.server_id, &user_id).await?)?;
+    if db_mongo_getter::is_member(client, db_name, &invite.server_id, &user_id).await? {
         return Ok(());
     }
-    // println!("entrain de rejoindre");
-    client
+
+    let now = format_date(Utc::now());
+    let result = client
+        .database(db_name)
+        .collection::<Document>("invite")
+        .update_one(
+            doc! {
+                "code": link,
+                "$and": [
+                    {"$or": [{"max_uses": null}, {"$expr": {"$lt": ["$uses", "$max_uses"]}}]},
+                    {"$or": [{"expires_at": null}, {"expires_at": {"$gt": &now}}]},
+                ],
+            },
+            doc! {"$inc": {"uses": 1}},
+        )
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de l'utilisation du lien d'invitation: {}", e)))?;
+    if result.matched_count == 0 {
+        // une arrivée simultanée du même utilisateur a pu prendre la dernière utilisation
+        if db_mongo_getter::is_member(client, db_name, &invite.server_id, &user_id).await? {
+            return Ok(());
+        }
+        return Err(invite_exhausted_error());
+    }
+
+    // l'ajout est filtré sur l'absence du membre : une seule des arrivées simultanées du même utilisateur
+    // l'ajoute, les autres rendent leur utilisation
+    let added = client
         .database(db_name)
         .collection::<Document>("server")
         .update_one(
-            doc! {"lien":link},
+            doc! {"id": invite.server_id, "member_id": {"$ne": user_id}},
             doc!{"$addToSet":{"member_id":user_id}}
         )
+        .await;
+    match added {
+        Ok(result) if result.matched_count == 1 => Ok(()),
+        Ok(_) => {
+            release_invite_use(client, db_name, link).await?;
+            if db_mongo_getter::is_member(client, db_name, &invite.server_id, &user_id).await? {
+                Ok(())
+            } else {
+                Err(FluxyError::NotFound("Serveur introuvable".to_string()))
+            }
+        }
+        Err(e) => {
+            release_invite_use(client, db_name, link).await?;
+            Err(FluxyError::Storage(format!("Erreur lors de l'ajout au serveur par lien: {}", e)))
+        }
+    }
+}
+
+/// rend l'utilisation consommée par join_by_link quand l'ajout au serveur n'a pas eu lieu
+async fn release_invite_use(client: &Client, db_name: &str, link: &str) -> FluxyResult<()> {
+    client
+        .database(db_name)
+        .collection::<Document>("invite")
+        .update_one(doc! {"code": link, "uses": {"$gt": 0}}, doc! {"$inc": {"uses": -1}})
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de l'utilisation du lien d'invitation: {}", e)))?;
+    Ok(())
+}
+
+pub(crate) fn invite_exhausted_error() -> FluxyError {
+    FluxyError::NotFound("Lien d'invitation expiré ou épuisé".to_string())
+}
+
+/// set_role :  
+///     serveur id  
+///     nom du rôle  
+///     permissions  
+///     utilisateur qui le crée  
+/// permet de créer un rôle personnalisé. le nouveau rôle est placé tout en bas de la hiérarchie (position 1, les autres
+/// rôles montent d'un cran) : il suffit d'avoir MANAGE_ROLES et les permissions données. renvoie le rôle créé
+pub async fn set_role(client: &Client, db_name: &str, server_id: i64, name: &str, permissions: Permissions, user_id: i64) -> FluxyResult<Role> {
+    let Some((server, roles)) = db_mongo_getter::get_server_with_roles(client, db_name, &server_id).await? else {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    };
+    check_new_role(&server, &roles, user_id, name, permissions)?;
+
+    let id = next_id(client, db_name, "role").await?;
+    let collection = client.database(db_name).collection::<Role>("role");
+    collection
+        .update_many(doc! {"server_id": server_id}, doc! {"$inc": {"position": 1_i64}})
         .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "add_member_to_server : base de donnée ou collection de la base non trouver"))?;
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors du déplacement des rôles: {}", e)))?;
 
-    // db_mongo_delete::delete_link(client, db_name,link).await?;
+    let role = Role {
+        id,
+        server_id,
+        name: name.to_string(),
+        permissions,
+        position: 1,
+        member_id: vec![],
+    };
+    collection
+        .insert_one(&role)
+        .await
+        .map_err(|e| insert_error(e, "Erreur lors de la création du rôle"))?;
 
-    return Ok(());
+    Ok(role)
 }
 
-fn random_string()-> String{
+/// add_role_to_member :  
+///     rôle id  
+///     membre qui reçoit le rôle  
+///     utilisateur qui donne le rôle  
+/// permet de donner un rôle placé sous son propre rang (MANAGE_ROLES) à un membre du serveur
+pub async fn add_role_to_member(client: &Client, db_name: &str, role_id: i64, user_to_add: i64, user_id: i64) -> FluxyResult<()> {
+    let Some(role) = db_mongo_getter::get_role_by_id(client, db_name, &role_id).await? else {
+        return Err(FluxyError::NotFound("Rôle introuvable".to_string()));
+    };
+    let Some((server, roles)) = db_mongo_getter::get_server_with_roles(client, db_name, &role.server_id).await? else {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    };
+    check_role_management(&server, &roles, user_id, role.position, Permissions::NONE)?;
+    if !server.member_id.contains(&user_to_add) {
+        return Err(FluxyError::Validation("L'utilisateur n'est pas membre du serveur".to_string()));
+    }
+
+    client
+        .database(db_name)
+        .collection::<Document>("role")
+        .update_one(doc! {"id": role_id}, doc! {"$addToSet": {"member_id": user_to_add}})
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de l'ajout du rôle: {}", e)))?;
+
+    Ok(())
+}
+
+/// set_ban :  
+///     serveur id  
+///     utilisateur à bannir  
+///     raison  
+///     date d'expiration (None : définitif, voir format_date)  
+///     utilisateur qui bannit  
+/// permet de bannir un utilisateur de rang inférieur au sien (BAN_MEMBERS), membre du serveur ou non. il perd ses rôles et
+/// quitte le serveur ; un bannissement existant est remplacé. renvoie le bannissement enregistré
+pub async fn set_ban(
+    client: &Client,
+    db_name: &str,
+    server_id: i64,
+    user_to_ban: i64,
+    reason: &str,
+    expires_at: Option<String>,
+    user_id: i64,
+) -> FluxyResult<Ban> {
+    let Some((server, roles)) = db_mongo_getter::get_server_with_roles(client, db_name, &server_id).await? else {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    };
+    let ban = new_ban(server_id, user_to_ban, reason, expires_at, user_id);
+    check_ban(&server, &roles, &ban)?;
+
+    // Le bannissement d'abord : si le retrait échoue ensuite, l'utilisateur reste banni et ne peut pas revenir
+    client
+        .database(db_name)
+        .collection::<Ban>("ban")
+        .replace_one(doc! {"server_id": server_id, "user_id": user_to_ban}, &ban)
+        .upsert(true)
+        .await
+        .map_err(|e| insert_error(e, "Erreur lors du bannissement"))?;
+    db_mongo_delete::pull_member(client, db_name, server_id, user_to_ban).await?;
+
+    Ok(ban)
+}
+
+/// new_ban :
+/// bannissement daté de maintenant, la raison sans espaces autour
+pub(crate) fn new_ban(server_id: i64, user_id: i64, reason: &str, expires_at: Option<String>, banned_by: i64) -> Ban {
+    Ban {
+        server_id,
+        user_id,
+        reason: reason.trim().to_string(),
+        banned_by,
+        banned_at: format_date(Utc::now()),
+        expires_at,
+    }
+}
+
+/// check_ban :
+/// règles d'un bannissement (voir set_ban) : mêmes règles que l'exclusion avec BAN_MEMBERS, on ne se bannit pas soi-même,
+/// la raison est limitée à MAX_BAN_REASON_LENGTH caractères et l'expiration doit être dans le futur
+pub(crate) fn check_ban(server: &Server, roles: &[Role], ban: &Ban) -> FluxyResult<()> {
+    if ban.banned_by == ban.user_id {
+        return Err(FluxyError::Validation("Vous ne pouvez pas vous bannir vous-même".to_string()));
+    }
+    db_mongo_delete::check_member_removal(server, roles, ban.banned_by, ban.user_id, Permissions::BAN_MEMBERS)?;
+    if ban.reason.chars().count() > MAX_BAN_REASON_LENGTH {
+        return Err(FluxyError::Validation(format!(
+            "La raison du bannissement ne doit pas dépasser {} caractères",
+            MAX_BAN_REASON_LENGTH
+        )));
+    }
+    if !ban.is_active_at(&ban.banned_at) {
+        return Err(FluxyError::Validation("La date d'expiration du bannissement est déjà passée".to_string()));
+    }
+    Ok(())
+}
+
+/// Longueur maximale de la raison d'un bannissement.
+pub(crate) const MAX_BAN_REASON_LENGTH: usize = 512;
+
+/// check_not_banned :
+/// refuse l'entrée dans un serveur tant que le bannissement de l'utilisateur n'a pas expiré
+pub(crate) fn check_not_banned(ban: Option<Ban>) -> FluxyResult<()> {
+    match ban {
+        Some(ban) if ban.is_active_at(&format_date(Utc::now())) => {
+            Err(FluxyError::Forbidden("Vous êtes banni de ce serveur".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// check_new_owner :
+/// refuse de transférer la propriété du serveur à un non-membre ou à un membre dont le bannissement n'a pas expiré
+pub(crate) fn check_new_owner(is_member: bool, ban: Option<Ban>) -> FluxyResult<()> {
+    if !is_member {
+        return Err(FluxyError::Validation("L'utilisateur n'est pas membre du serveur".to_string()));
+    }
+    match ban {
+        Some(ban) if ban.is_active_at(&format_date(Utc::now())) => {
+            Err(FluxyError::Validation("L'utilisateur est banni du serveur".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// set_timeout :  
+///     serveur id  
+///     membre à réduire au silence  
+///     date de fin (voir format_date)  
+///     modérateur  
+/// permet de réduire au silence un membre de rang inférieur au sien (MODERATE_MEMBERS) jusqu'à la date de fin.
+/// un timeout existant est remplacé. renvoie le timeout enregistré
+pub async fn set_timeout(client: &Client, db_name: &str, server_id: i64, user_to_mute: i64, until: String, user_id: i64) -> FluxyResult<MemberTimeout> {
+    let Some((server, roles)) = db_mongo_getter::get_server_with_roles(client, db_name, &server_id).await? else {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    };
+    let timeout = MemberTimeout { server_id, user_id: user_to_mute, moderator_id: user_id, until };
+    check_timeout(&server, &roles, &timeout, Utc::now())?;
+
+    client
+        .database(db_name)
+        .collection::<MemberTimeout>("timeout")
+        .replace_one(doc! {"server_id": server_id, "user_id": user_to_mute}, &timeout)
+        .upsert(true)
+        .await
+        .map_err(|e| insert_error(e, "Erreur lors de la mise en silence"))?;
+
+    Ok(timeout)
+}
+
+/// Durée maximale d'un timeout (28 jours).
+pub const MAX_TIMEOUT_SECS: u64 = 28 * 24 * 60 * 60;
+
+/// check_timeout :
+/// règles d'un timeout (voir set_timeout) : mêmes règles que l'exclusion avec MODERATE_MEMBERS, on ne se réduit pas
+/// soi-même au silence, la cible doit être membre et la fin doit tomber dans les MAX_TIMEOUT_SECS secondes à venir
+pub(crate) fn check_timeout(server: &Server, roles: &[Role], timeout: &MemberTimeout, now: DateTime<Utc>) -> FluxyResult<()> {
+    if timeout.moderator_id == timeout.user_id {
+        return Err(FluxyError::Validation("Vous ne pouvez pas vous réduire vous-même au silence".to_string()));
+    }
+    db_mongo_delete::check_member_removal(server, roles, timeout.moderator_id, timeout.user_id, Permissions::MODERATE_MEMBERS)?;
+    if !server.member_id.contains(&timeout.user_id) {
+        return Err(FluxyError::Validation("L'utilisateur n'est pas membre du serveur".to_string()));
+    }
+    match timeout.remaining_secs(now) {
+        0 => Err(FluxyError::Validation("La fin du timeout est déjà passée".to_string())),
+        remaining if remaining > MAX_TIMEOUT_SECS => Err(FluxyError::Validation(format!(
+            "Un timeout ne peut pas dépasser {} jours",
+            MAX_TIMEOUT_SECS / (24 * 60 * 60)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// check_not_timed_out :
+/// refuse l'envoi d'un message tant que le timeout du membre dure. les administrateurs ne sont pas concernés
+pub(crate) fn check_not_timed_out(timeout: Option<MemberTimeout>, permissions: Permissions, now: DateTime<Utc>) -> FluxyResult<()> {
+    if permissions.contains(Permissions::ADMINISTRATOR) {
+        return Ok(());
+    }
+    match timeout.map(|timeout| timeout.remaining_secs(now)) {
+        Some(remaining_secs) if remaining_secs > 0 => Err(FluxyError::TimedOut {
+            message: "Vous êtes réduit au silence sur ce serveur".to_string(),
+            remaining_secs,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// set_audit_entry :  
+///     entrée d'audit  
+/// permet d'ajouter une entrée au journal d'audit (collection audit_log). renvoie l'entrée enregistrée
+pub async fn set_audit_entry(client: &Client, db_name: &str, entry: NewAuditEntry) -> FluxyResult<AuditEntry> {
+    let entry = audit_entry(next_id(client, db_name, "audit_log").await?, entry);
+
+    client
+        .database(db_name)
+        .collection::<AuditEntry>("audit_log")
+        .insert_one(&entry)
+        .await
+        .map_err(|e| insert_error(e, "Erreur lors de l'écriture du journal d'audit"))?;
+
+    Ok(entry)
+}
+
+/// audit_entry :
+/// entrée d'audit avec son id, datée de maintenant
+pub(crate) fn audit_entry(id: i64, entry: NewAuditEntry) -> AuditEntry {
+    AuditEntry {
+        id,
+        server_id: entry.server_id,
+        actor_id: entry.actor_id,
+        action: entry.action,
+        target_id: entry.target_id,
+        before: entry.before,
+        after: entry.after,
+        created_at: Utc::now().to_rfc3339(),
+    }
+}
+
+/// check_role_management :
+/// règles communes à la gestion des rôles : il faut MANAGE_ROLES, le rôle touché doit être sous le rang de l'utilisateur
+/// et on ne peut pas donner une permission qu'on n'a pas
+pub(crate) fn check_role_management(server: &Server, roles: &[Role], user_id: i64, position: i64, permissions: Permissions) -> FluxyResult<()> {
+    let own_permissions = server.permissions_of(roles, user_id);
+    if !own_permissions.contains(Permissions::MANAGE_ROLES) {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de gérer les rôles".to_string()));
+    }
+    if position >= server.rank_of(roles, user_id) {
+        return Err(FluxyError::Forbidden("Ce rôle n'est pas sous votre rang dans la hiérarchie".to_string()));
+    }
+    if !own_permissions.contains(permissions) {
+        return Err(FluxyError::Forbidden("Vous ne pouvez pas donner une permission que vous n'avez pas".to_string()));
+    }
+    Ok(())
+}
+
+/// check_new_role :
+/// vérifie la création d'un rôle (voir set_role). le nouveau rôle arrive sous tous les autres : la position 0 passe
+/// sous le rang de tout utilisateur qui a MANAGE_ROLES
+pub(crate) fn check_new_role(server: &Server, roles: &[Role], user_id: i64, name: &str, permissions: Permissions) -> FluxyResult<()> {
+    validate_role_name(name)?;
+    if !permissions.is_valid() {
+        return Err(FluxyError::Validation("Permissions inconnues".to_string()));
+    }
+    check_role_management(server, roles, user_id, 0, permissions)?;
+    if roles.iter().any(|role| role.name == name) {
+        return Err(FluxyError::Conflict("Un rôle porte déjà ce nom sur ce serveur".to_string()));
+    }
+    Ok(())
+}
+
+/// validate_role_name :
+/// "admin" et "membre" sont réservés par update_member_role
+pub(crate) fn validate_role_name(name: &str) -> FluxyResult<()> {
+    if name.trim().is_empty() {
+        return Err(FluxyError::Validation("Le nom du rôle est obligatoire".to_string()));
+    }
+    if name == "admin" || name == "membre" {
+        return Err(FluxyError::Validation("Ce nom de rôle est réservé".to_string()));
+    }
+    Ok(())
+}
+
+/// check_send_message :
+/// règles d'écriture d'un message (voir set_message), à partir des permissions de l'utilisateur dans le channel.
+/// un channel invisible est traité comme un channel qui n'existe pas
+pub(crate) fn check_send_message(is_channel: bool, permissions: Permissions, message: &str) -> FluxyResult<()> {
+    if !is_channel || !permissions.contains(Permissions::VIEW_CHANNEL) {
+        return Err(FluxyError::Forbidden(
+            "l'utilisateur n'est pas membre du serveur ou le channel n'existe pas".to_string(),
+        ));
+    }
+    if !permissions.contains(Permissions::SEND_MESSAGES) {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission d'écrire dans ce channel".to_string()));
+    }
+    if mentions_everyone(message) && !permissions.contains(Permissions::MENTION_EVERYONE) {
+        return Err(mention_everyone_error());
+    }
+    Ok(())
+}
+
+/// mentions_everyone :
+/// vrai si le message mentionne tout le serveur (@everyone ou @here)
+pub(crate) fn mentions_everyone(message: &str) -> bool {
+    message.contains("@everyone") || message.contains("@here")
+}
+
+pub(crate) fn mention_everyone_error() -> FluxyError {
+    FluxyError::Forbidden("Vous n'avez pas la permission de mentionner @everyone ou @here".to_string())
+}
+
+/// Longueur des codes d'invitation tirés par random_string.
+pub(crate) const INVITE_CODE_LENGTH: usize = 10;
+
+pub(crate) fn random_string()-> String{
     let mut rng = rand::rng();
     let mut link_code = "".to_string();
 
-    for i in 1..10{
+    for _ in 0..INVITE_CODE_LENGTH{
         link_code.push(rng.sample(rand::distr::Alphanumeric) as char)
     }
     return link_code;
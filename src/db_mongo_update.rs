@@ -3,24 +3,56 @@
 //!         message id
 //!         message
 //!         utilisateur qui a écrit le message
-//!     permet à l'utilisateur ayant écrit son message de le modifier
+//!     permet à l'utilisateur ayant écrit son message de le modifier. l'ancien contenu est ajouté au tableau revisions
+//!     du document et la date de modification est enregistrée dans edited_at. renvoie le message modifié
 //!
 //!     - update_channel_name  
 //!         channel id  
 //!         nom  
 //!         administrateur/possesseur du serveur  
-//!     permet à un administrateur ou au possesseur de modifier le nom du channel  
+//!     permet à un utilisateur qui a la permission MANAGE_CHANNELS dans le channel de modifier le nom du channel  
+//!
+//!     - update_channel_privacy  
+//!         channel id  
+//!         privé ou non  
+//!         utilisateur qui fait l'action  
+//!     permet de rendre un channel privé ou public (MANAGE_CHANNELS dans le channel). renvoie le channel modifié
+//!
+//!     - update_channel_overwrite  
+//!         channel id  
+//!         exception (rôle ou membre, permissions autorisées et refusées)  
+//!         utilisateur qui fait l'action  
+//!     permet d'autoriser ou de refuser VIEW_CHANNEL, SEND_MESSAGES ou MANAGE_CHANNELS à un rôle ou à un membre sur un channel.
+//!     renvoie le channel modifié
 //!
 //!     - update_server_name  
 //!         serveur id  
 //!         nom  
 //!         administrateur/possesseur du serveur  
-//!     permet au possesseur de modifier le nom du serveur
+//!     permet à un utilisateur qui a la permission ADMINISTRATOR de modifier le nom du serveur
+//!
+//!     - update_member_role  
+//!         serveur id  
+//!         membre  
+//!         "admin", "membre" ou nom d'un rôle du serveur  
+//!         utilisateur qui fait l'action  
+//!     permet au possesseur de nommer ou retirer un administrateur, et de donner un rôle personnalisé (voir add_role_to_member)
+//!
+//!     - update_role  
+//!         rôle id  
+//!         nom, permissions et position (optionnels)  
+//!         utilisateur qui fait l'action  
+//!     permet de modifier un rôle placé sous son rang (MANAGE_ROLES). renvoie le rôle modifié
+//!
+//! Les refus renvoient FluxyError::Forbidden, un message ou un channel inexistant FluxyError::NotFound (voir error.rs).
 
 use crate::db_mongo_getter;
-use std::io;
+use crate::db_mongo_setter::{self, check_role_management, validate_role_name};
+use crate::error::{FluxyError, FluxyResult};
+use crate::models::{Channel, Message, MessageRevision, OverwriteKind, PermissionOverwrite, Permissions, Role, Server};
+use chrono::Utc;
 use mongodb::{
-    bson::{doc, Document, Bson},
+    bson::{self, doc, Document},
     Client,
 };
 
@@ -29,79 +61,63 @@ use mongodb::{
 ///     message
 ///     utilisateur qui a écrit le message
 /// permet à l'utilisateur ayant écrit son message de le modifier
-/// Mise à jour d'un message (contenu uniquement)
+/// Mise à jour d'un message (contenu uniquement), l'ancien contenu est gardé dans revisions
 pub async fn update_message(
     client: &Client,
     db_name: &str,
     message_id: i64,
     message: &str,
     user_id: i64,
-) -> io::Result<()> {
-    let mut can_modify = false;
-    
-    let message_by_id = db_mongo_getter::get_message_by_id(client, db_name, &message_id).await?;
-    for i in message_by_id{
-        for (key,value) in i{
-            if key == "user"{
-                if let Some(msg_user_id) = value.as_i64() {
-                    if msg_user_id == user_id {
-                        can_modify = true;
-                        break;
-                    }
-                }
-            }
-        }
-        if can_modify {
-            break;
-        }
+) -> FluxyResult<Message> {
+    if message.trim().is_empty() {
+        return Err(FluxyError::Validation("Le message ne peut pas être vide".to_string()));
     }
+    let Some(message_by_id) = db_mongo_getter::get_message_by_id(client, db_name, &message_id).await? else {
+        return Err(FluxyError::NotFound("Message introuvable".to_string()));
+    };
 
-    if !can_modify {
-        return Ok(());
+    if message_by_id.user != user_id {
+        return Err(FluxyError::Forbidden("Seul l'auteur peut modifier son message".to_string()));
     }
 
+    let edited_at = Utc::now().to_rfc3339();
+    let revision = MessageRevision {
+        message: message_by_id.message.clone(),
+        editor_id: user_id,
+        edited_at: edited_at.clone(),
+    };
+    let revision = bson::to_bson(&revision).map_err(|e| FluxyError::Storage(e.to_string()))?;
+
     client
         .database(db_name)
         .collection::<Document>("message")
         .update_one(
             doc! {"id":message_id},
-            doc!{"$set":{"message":message}})
+            doc!{"$set":{"message":message,"edited_at":&edited_at},"$push":{"revisions":revision}})
         .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
+        .map_err(|e| FluxyError::Storage(format!("base de donnée ou collection de la base non trouver: {}", e)))?;
     
-    Ok(())
+    Ok(Message {
+        message: message.to_string(),
+        edited_at: Some(edited_at),
+        ..message_by_id
+    })
 }
 
 /// update_channel_name  
 ///     channel id  
 ///     nom  
 ///     administrateur/possesseur du serveur  
-/// permet à un administrateur ou au possesseur de modifier le nom du channel  
-pub async fn update_channel_name(client: &Client, db_name: &str,channel_id: i64,name: &str,user_id: i64)-> io::Result<()>{
-    let channel = db_mongo_getter::get_channel_by_id(client, db_name,&channel_id).await?;
-    if channel.is_empty() {
-        return Ok(());
+/// permet à un utilisateur qui a la permission MANAGE_CHANNELS de modifier le nom du channel  
+pub async fn update_channel_name(client: &Client, db_name: &str,channel_id: i64,name: &str,user_id: i64)-> FluxyResult<()>{
+    if name.trim().is_empty() {
+        return Err(FluxyError::Validation("Le nom du channel est obligatoire".to_string()));
     }
-    let mut server_id = 0;
-    for i in channel{
-        for (key,value) in i{
-            if key == "server_id"{
-                if let Some(id) = value.as_i64() {
-                    server_id = id;
-                    break;
-                }
-            }
-        }
-        if server_id != 0 {
-            break;
-        }
-    }
-    if server_id == 0 {
-        return Ok(());
-    }
-    if !db_mongo_getter::is_owner(&client,&db_name,&server_id,&user_id).await? 
-        && !db_mongo_getter::is_admin(&client,&db_name,&server_id,&user_id).await?{
-        return Ok(());
+    let Some((channel, server, roles)) = db_mongo_getter::get_channel_with_server(client, db_name,&channel_id).await? else {
+        return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+    };
+    if !channel.permissions_of(&server, &roles, user_id).contains(Permissions::MANAGE_CHANNELS) {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de modifier un channel".to_string()));
     }
 
     client
@@ -111,12 +127,7 @@ pub async fn update_channel_name(client: &Client, db_name: &str,channel_id: i64,
             doc! {"id":channel_id},
             doc!{"$set":{"name":name}})
         .await
-        .map_err(|_| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                "Erreur lors de la mise à jour du channel",
-            )
-        })?;
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la mise à jour du channel: {}", e)))?;
 
     Ok(())
 }
@@ -125,11 +136,10 @@ pub async fn update_channel_name(client: &Client, db_name: &str,channel_id: i64,
 ///     serveur id  
 ///     nom  
 ///     administrateur/possesseur du serveur  
-/// permet au possesseur de modifier le nom du serveur
-pub async fn update_server_name(client: &Client, db_name: &str,server_id: i64,name: &str,user_id: i64)-> io::Result<()>{
-    if !db_mongo_getter::is_owner(&client,&db_name,&server_id,&user_id).await?
-        && !db_mongo_getter::is_admin(&client,&db_name,&server_id,&user_id).await?{
-        return Ok(());
+/// permet à un utilisateur qui a la permission ADMINISTRATOR de modifier le nom du serveur
+pub async fn update_server_name(client: &Client, db_name: &str,server_id: i64,name: &str,user_id: i64)-> FluxyResult<()>{
+    if !db_mongo_getter::has_permission(client, db_name, &server_id, &user_id, Permissions::ADMINISTRATOR).await? {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de modifier le serveur".to_string()));
     }
 
     client
@@ -139,7 +149,7 @@ pub async fn update_server_name(client: &Client, db_name: &str,server_id: i64,na
             doc! {"id":server_id},
             doc!{"$set":{"name":name}})
         .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
+        .map_err(|e| FluxyError::Storage(format!("base de donnée ou collection de la base non trouver: {}", e)))?;
     
     Ok(())
 }
@@ -153,10 +163,9 @@ pub async fn update_server(
     name: Option<&str>,
     image: Option<&str>,
     user_id: i64,
-) -> io::Result<()> {
-    if !db_mongo_getter::is_owner(&client,&db_name,&server_id,&user_id).await?
-        && !db_mongo_getter::is_admin(&client,&db_name,&server_id,&user_id).await?{
-        return Ok(());
+) -> FluxyResult<()> {
+    if !db_mongo_getter::has_permission(client, db_name, &server_id, &user_id, Permissions::ADMINISTRATOR).await? {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de modifier le serveur".to_string()));
     }
 
     let mut set_doc = doc!{};
@@ -182,26 +191,35 @@ pub async fn update_server(
             doc! {"id":server_id},
             doc!{"$set":set_doc})
         .await
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "base de donnée ou collection de la base non trouver"))?;
+        .map_err(|e| FluxyError::Storage(format!("base de donnée ou collection de la base non trouver: {}", e)))?;
     
     Ok(())
 }
 
-// Met à jour le rôle d'un membre (admin / membre) sur un serveur
+// Met à jour le rôle d'un membre (admin / membre, ou rôle personnalisé) sur un serveur
 pub async fn update_member_role(
     client: &Client,
     db_name: &str,
     server_id: i64,
     user_id: i64,
     role: &str,
-    owner_id: i64,
-) -> io::Result<()> {
-    // Seul l'owner peut modifier les rôles
-    if !db_mongo_getter::is_owner(client, db_name, &server_id, &owner_id).await? {
-        return Err(io::Error::new(
-            io::ErrorKind::PermissionDenied,
-            "PermissionDenied: seul le fondateur peut modifier les rôles",
-        ));
+    actor_id: i64,
+) -> FluxyResult<()> {
+    if role != "admin" && role != "membre" {
+        // Rôle personnalisé : mêmes règles que add_role_to_member
+        let Some(custom_role) = db_mongo_getter::get_roles_of_server(client, db_name, &server_id)
+            .await?
+            .into_iter()
+            .find(|custom_role| custom_role.name == role)
+        else {
+            return Err(FluxyError::Validation("Rôle inconnu".to_string()));
+        };
+        return db_mongo_setter::add_role_to_member(client, db_name, custom_role.id, user_id, actor_id).await;
+    }
+
+    // Seul l'owner peut nommer ou retirer un admin
+    if !db_mongo_getter::is_owner(client, db_name, &server_id, &actor_id).await? {
+        return Err(FluxyError::Forbidden("Seul le fondateur peut modifier les rôles".to_string()));
     }
 
     let collection = client
@@ -217,12 +235,7 @@ pub async fn update_member_role(
                     doc! { "$addToSet": { "admin_id": user_id } },
                 )
                 .await
-                .map_err(|_| {
-                    io::Error::new(
-                        io::ErrorKind::Other,
-                        "Erreur lors de l'ajout de l'admin",
-                    )
-                })?;
+                .map_err(|e| FluxyError::Storage(format!("Erreur lors de l'ajout de l'admin: {}", e)))?;
         }
         "membre" => {
             // Retirer l'utilisateur de la liste des admins
@@ -232,24 +245,193 @@ pub async fn update_member_role(
                     doc! { "$pull": { "admin_id": user_id } },
                 )
                 .await
-                .map_err(|_| {
-                    io::Error::new(
-                        io::ErrorKind::Other,
-                        "Erreur lors du retrait de l'admin",
-                    )
-                })?;
+                .map_err(|e| FluxyError::Storage(format!("Erreur lors du retrait de l'admin: {}", e)))?;
         }
         _ => {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "InvalidInput: rôle inconnu",
-            ));
+            return Err(FluxyError::Validation("Rôle inconnu".to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// update_role  
+///     rôle id  
+///     nom, permissions et position (optionnels)  
+///     utilisateur qui fait l'action  
+/// permet de modifier un rôle placé sous son rang (MANAGE_ROLES). la nouvelle position doit rester sous son rang
+/// et seules les permissions qu'on a soi-même peuvent être ajoutées. renvoie le rôle modifié
+pub async fn update_role(
+    client: &Client,
+    db_name: &str,
+    role_id: i64,
+    name: Option<&str>,
+    permissions: Option<Permissions>,
+    position: Option<i64>,
+    user_id: i64,
+) -> FluxyResult<Role> {
+    let Some(role) = db_mongo_getter::get_role_by_id(client, db_name, &role_id).await? else {
+        return Err(FluxyError::NotFound("Rôle introuvable".to_string()));
+    };
+    let Some((server, roles)) = db_mongo_getter::get_server_with_roles(client, db_name, &role.server_id).await? else {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    };
+    let role = check_role_update(&server, &roles, user_id, role, name, permissions, position)?;
+
+    client
+        .database(db_name)
+        .collection::<Document>("role")
+        .update_one(
+            doc! {"id": role_id},
+            doc! {"$set": {"name": &role.name, "permissions": role.permissions.0, "position": role.position}},
+        )
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la mise à jour du rôle: {}", e)))?;
+
+    Ok(role)
+}
+
+/// check_role_update :
+/// vérifie une modification de rôle (voir update_role) et renvoie le rôle tel qu'il sera enregistré
+pub(crate) fn check_role_update(
+    server: &Server,
+    roles: &[Role],
+    user_id: i64,
+    role: Role,
+    name: Option<&str>,
+    permissions: Option<Permissions>,
+    position: Option<i64>,
+) -> FluxyResult<Role> {
+    if let Some(name) = name {
+        validate_role_name(name)?;
+        if roles.iter().any(|other| other.id != role.id && other.name == name) {
+            return Err(FluxyError::Conflict("Un rôle porte déjà ce nom sur ce serveur".to_string()));
         }
     }
+    if permissions.is_some_and(|permissions| !permissions.is_valid()) {
+        return Err(FluxyError::Validation("Permissions inconnues".to_string()));
+    }
+    if position.is_some_and(|position| position < 1) {
+        return Err(FluxyError::Validation("La position d'un rôle commence à 1".to_string()));
+    }
+
+    // seules les permissions ajoutées au rôle comptent : on peut retirer une permission qu'on n'a pas
+    let added = permissions.map_or(Permissions::NONE, |permissions| Permissions(permissions.0 & !role.permissions.0));
+    check_role_management(server, roles, user_id, role.position, added)?;
+    if let Some(position) = position {
+        check_role_management(server, roles, user_id, position, Permissions::NONE)?;
+    }
+
+    Ok(Role {
+        name: name.map_or(role.name, str::to_string),
+        permissions: permissions.unwrap_or(role.permissions),
+        position: position.unwrap_or(role.position),
+        ..role
+    })
+}
+
+/// update_channel_privacy  
+///     channel id  
+///     privé ou non  
+///     utilisateur qui fait l'action  
+/// permet de rendre un channel privé (invisible sauf pour les admins et ceux qu'un rôle ou une exception autorise) ou public.
+/// demande MANAGE_CHANNELS dans le channel. renvoie le channel modifié
+pub async fn update_channel_privacy(client: &Client, db_name: &str, channel_id: i64, private: bool, user_id: i64) -> FluxyResult<Channel> {
+    let Some((channel, server, roles)) = db_mongo_getter::get_channel_with_server(client, db_name, &channel_id).await? else {
+        return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+    };
+    check_channel_management(&server, &roles, &channel, user_id)?;
 
+    client
+        .database(db_name)
+        .collection::<Document>("channel")
+        .update_one(doc! {"id": channel_id}, doc! {"$set": {"private": private}})
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la mise à jour du channel: {}", e)))?;
+
+    Ok(Channel { private, ..channel })
+}
+
+/// update_channel_overwrite  
+///     channel id  
+///     exception (rôle ou membre, permissions autorisées et refusées)  
+///     utilisateur qui fait l'action  
+/// permet de créer ou remplacer l'exception d'un rôle ou d'un membre sur un channel (voir check_channel_overwrite).
+/// une exception sans permission autorisée ni refusée est retirée. renvoie le channel modifié
+pub async fn update_channel_overwrite(
+    client: &Client,
+    db_name: &str,
+    channel_id: i64,
+    overwrite: PermissionOverwrite,
+    user_id: i64,
+) -> FluxyResult<Channel> {
+    let Some((channel, server, roles)) = db_mongo_getter::get_channel_with_server(client, db_name, &channel_id).await? else {
+        return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+    };
+    let channel = check_channel_overwrite(&server, &roles, channel, user_id, overwrite)?;
+    let overwrites = bson::to_bson(&channel.overwrites).map_err(|e| FluxyError::Storage(e.to_string()))?;
+
+    client
+        .database(db_name)
+        .collection::<Document>("channel")
+        .update_one(doc! {"id": channel_id}, doc! {"$set": {"overwrites": overwrites}})
+        .await
+        .map_err(|e| FluxyError::Storage(format!("Erreur lors de la mise à jour des exceptions du channel: {}", e)))?;
+
+    Ok(channel)
+}
+
+/// check_channel_management :
+/// il faut MANAGE_CHANNELS dans le channel (exceptions comprises) pour le modifier ou changer ses exceptions
+pub(crate) fn check_channel_management(server: &Server, roles: &[Role], channel: &Channel, user_id: i64) -> FluxyResult<()> {
+    if !channel.permissions_of(server, roles, user_id).contains(Permissions::MANAGE_CHANNELS) {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de gérer ce channel".to_string()));
+    }
     Ok(())
 }
 
+/// check_channel_overwrite :
+/// vérifie une exception (voir update_channel_overwrite) et renvoie le channel tel qu'il sera enregistré :
+/// seules les permissions de Permissions::CHANNEL sont acceptées, la cible doit être un rôle ou un membre du serveur
+/// et on ne peut pas autoriser une permission qu'on n'a pas soi-même dans le channel
+pub(crate) fn check_channel_overwrite(
+    server: &Server,
+    roles: &[Role],
+    mut channel: Channel,
+    user_id: i64,
+    overwrite: PermissionOverwrite,
+) -> FluxyResult<Channel> {
+    check_channel_management(server, roles, &channel, user_id)?;
+    if !Permissions::CHANNEL.contains(overwrite.allow | overwrite.deny) {
+        return Err(FluxyError::Validation(
+            "Une exception ne peut porter que sur VIEW_CHANNEL, SEND_MESSAGES et MANAGE_CHANNELS".to_string(),
+        ));
+    }
+    if overwrite.allow.0 & overwrite.deny.0 != 0 {
+        return Err(FluxyError::Validation("Une permission ne peut pas être à la fois autorisée et refusée".to_string()));
+    }
+    match overwrite.kind {
+        OverwriteKind::Role if !roles.iter().any(|role| role.id == overwrite.target_id) => {
+            return Err(FluxyError::Validation("Rôle inconnu".to_string()));
+        }
+        OverwriteKind::User if !server.member_id.contains(&overwrite.target_id) => {
+            return Err(FluxyError::Validation("L'utilisateur n'est pas membre du serveur".to_string()));
+        }
+        _ => {}
+    }
+    if !channel.permissions_of(server, roles, user_id).contains(overwrite.allow) {
+        return Err(FluxyError::Forbidden("Vous ne pouvez pas donner une permission que vous n'avez pas".to_string()));
+    }
+
+    channel
+        .overwrites
+        .retain(|other| other.kind != overwrite.kind || other.target_id != overwrite.target_id);
+    if overwrite.allow != Permissions::NONE || overwrite.deny != Permissions::NONE {
+        channel.overwrites.push(overwrite);
+    }
+    Ok(channel)
+}
+
 //non fonctionnel
 /*pub async fn update_channel_position(client: &Client, db_name: &str,channel_id: i64,position: i64,user_id: i64)-> io::Result<()>{
     
@@ -0,0 +1,1371 @@
+//! db_sql.rs (feature sql) :
+//!     - SqlStore :
+//!         url de la base (sqlite://... ou postgres://...)
+//!     implémentation relationnelle du ChatStore (voir db_store.rs), pour héberger Fluxy sans MongoDB.
+//!     le schéma est créé par les migrations du dossier migrations (servers, server_members, server_admins, invites, channels, messages,
+//!     message_revisions, roles, role_members, channel_overwrites, bans, member_timeouts, audit_log),
+//!     et les lignes sont renvoyées dans les mêmes modèles que MongoDB (voir models.rs) pour que les handlers restent identiques.
+//!     les règles de permission sont les mêmes que dans les fichiers db_mongo_*.
+
+use crate::db_mongo_delete;
+use crate::db_mongo_setter;
+use crate::db_mongo_update;
+use crate::db_store::ChatStore;
+use crate::error::{FluxyError, FluxyResult};
+use crate::models::{format_date, AuditAction, AuditEntry, AuditLogFilter, Ban, Channel, Invite, MemberTimeout, Message, MessageRevision, NewAuditEntry, OverwriteKind, PermissionOverwrite, Permissions, RepairReport, Role, Server};
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::any::{AnyPoolOptions, AnyRow};
+use sqlx::migrate::Migrator;
+use sqlx::{AnyPool, Row};
+use std::io;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+const SERVER_COLUMNS: &str = "SELECT id, name, owner_id, image FROM servers";
+const CHANNEL_COLUMNS: &str = "SELECT id, server_id, name, position, private FROM channels";
+const MESSAGE_COLUMNS: &str = "SELECT id, channel_id, user_id, content, sent_at, edited_at FROM messages";
+const ROLE_COLUMNS: &str = "SELECT id, server_id, name, permissions, position FROM roles";
+const BAN_COLUMNS: &str = "SELECT server_id, user_id, reason, banned_by, banned_at, expires_at FROM bans";
+const TIMEOUT_COLUMNS: &str = "SELECT server_id, user_id, moderator_id, until FROM member_timeouts";
+const INVITE_COLUMNS: &str = "SELECT code, server_id, creator_id, created_at, expires_at, max_uses, uses FROM invites";
+const AUDIT_COLUMNS: &str = "SELECT id, server_id, actor_id, action, target_id, before_state, after_state, created_at FROM audit_log";
+
+/// Retrait d'un membre (départ, exclusion, bannissement) : ses rôles, son rang d'admin puis sa place de membre
+const MEMBER_REMOVAL: [&str; 3] = [
+    "DELETE FROM role_members WHERE role_id IN (SELECT id FROM roles WHERE server_id = $1) AND user_id = $2",
+    "DELETE FROM server_admins WHERE server_id = $1 AND user_id = $2",
+    "DELETE FROM server_members WHERE server_id = $1 AND user_id = $2",
+];
+
+fn sql_error(e: sqlx::Error) -> io::Error {
+    io::Error::other(format!("Erreur SQL: {}", e))
+}
+
+/// insert_error :
+/// équivalent de db_mongo_setter::insert_error : une violation de contrainte unique devient Conflict
+fn insert_error(e: sqlx::Error, message: &str) -> FluxyError {
+    match e.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => {
+            FluxyError::Conflict(format!("{} : cet identifiant existe déjà", message))
+        }
+        _ => FluxyError::Storage(format!("{}: {}", message, e)),
+    }
+}
+
+/// valeur de la colonne kind de channel_overwrites (même nom que dans le document channel)
+fn kind_name(kind: OverwriteKind) -> &'static str {
+    match kind {
+        OverwriteKind::Role => "role",
+        OverwriteKind::User => "user",
+    }
+}
+
+/// valeur de la colonne action de audit_log (même nom que dans le document audit_log)
+fn action_name(action: AuditAction) -> String {
+    serde_json::to_value(action)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// member_timeout :
+/// ligne de member_timeouts
+fn member_timeout(row: &AnyRow) -> io::Result<MemberTimeout> {
+    Ok(MemberTimeout {
+        server_id: row.try_get("server_id").map_err(sql_error)?,
+        user_id: row.try_get("user_id").map_err(sql_error)?,
+        moderator_id: row.try_get("moderator_id").map_err(sql_error)?,
+        until: row.try_get("until").map_err(sql_error)?,
+    })
+}
+
+/// invite :
+/// ligne de invites
+fn invite(row: &AnyRow) -> io::Result<Invite> {
+    Ok(Invite {
+        code: row.try_get("code").map_err(sql_error)?,
+        server_id: row.try_get("server_id").map_err(sql_error)?,
+        creator_id: row.try_get("creator_id").map_err(sql_error)?,
+        created_at: row.try_get("created_at").map_err(sql_error)?,
+        expires_at: row.try_get("expires_at").map_err(sql_error)?,
+        max_uses: row.try_get("max_uses").map_err(sql_error)?,
+        uses: row.try_get("uses").map_err(sql_error)?,
+    })
+}
+
+/// audit_entry :
+/// ligne de audit_log, les instantanés relus depuis leur texte JSON
+fn audit_entry(row: &AnyRow) -> io::Result<AuditEntry> {
+    let invalid = |e: serde_json::Error| io::Error::new(io::ErrorKind::InvalidData, format!("Entrée audit_log invalide: {}", e));
+    let state = |column: &str| -> io::Result<Option<serde_json::Value>> {
+        row.try_get::<Option<String>, _>(column)
+            .map_err(sql_error)?
+            .map(|text| serde_json::from_str(&text).map_err(invalid))
+            .transpose()
+    };
+    Ok(AuditEntry {
+        id: row.try_get("id").map_err(sql_error)?,
+        server_id: row.try_get("server_id").map_err(sql_error)?,
+        actor_id: row.try_get("actor_id").map_err(sql_error)?,
+        action: serde_json::from_value(serde_json::Value::String(row.try_get("action").map_err(sql_error)?)).map_err(invalid)?,
+        target_id: row.try_get("target_id").map_err(sql_error)?,
+        before: state("before_state")?,
+        after: state("after_state")?,
+        created_at: row.try_get("created_at").map_err(sql_error)?,
+    })
+}
+
+/// SqlStore :
+/// pool de connexions SQLite ou Postgres (driver sqlx Any)
+pub struct SqlStore {
+    pool: AnyPool,
+}
+
+impl SqlStore {
+    /// connect :
+    ///     url de la base
+    /// ouvre le pool et applique les migrations manquantes
+    pub async fn connect(url: &str) -> io::Result<Self> {
+        sqlx::any::install_default_drivers();
+
+        let mut options = AnyPoolOptions::new();
+        if url.contains(":memory:") || url.contains("mode=memory") {
+            // Une base SQLite en mémoire n'existe que pour sa connexion : une seule connexion, jamais fermée
+            options = options.max_connections(1).min_connections(1).idle_timeout(None).max_lifetime(None);
+        }
+        let pool = options
+            .connect(url)
+            .await
+            .map_err(|e| io::Error::other(format!("Erreur de connexion SQL: {}", e)))?;
+
+        MIGRATOR
+            .run(&pool)
+            .await
+            .map_err(|e| io::Error::other(format!("Erreur lors des migrations: {}", e)))?;
+
+        Ok(SqlStore { pool })
+    }
+
+    /// next_id :
+    ///     collection
+    /// équivalent de db_mongo_setter::next_id avec la table counters
+    async fn next_id(&self, collection: &str) -> io::Result<i64> {
+        sqlx::query(
+            "INSERT INTO counters (name, seq) VALUES ($1, 1) \
+             ON CONFLICT (name) DO UPDATE SET seq = counters.seq + 1 RETURNING seq",
+        )
+        .bind(collection)
+        .fetch_one(&self.pool)
+        .await
+        .and_then(|row| row.try_get::<i64, _>("seq"))
+        .map_err(sql_error)
+    }
+
+    /// exists :
+    /// vrai si la requête (deux paramètres entiers) renvoie au moins une ligne
+    async fn exists(&self, sql: &str, first: i64, second: i64) -> io::Result<bool> {
+        sqlx::query(sql)
+            .bind(first)
+            .bind(second)
+            .fetch_optional(&self.pool)
+            .await
+            .map(|row| row.is_some())
+            .map_err(sql_error)
+    }
+
+    async fn user_ids(&self, sql: &str, server_id: i64) -> io::Result<Vec<i64>> {
+        sqlx::query(sql)
+            .bind(server_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .iter()
+            .map(|row| row.try_get::<i64, _>("user_id").map_err(sql_error))
+            .collect()
+    }
+
+    /// server_from_row :
+    /// reconstruit le Server de MongoDB (admin_id, member_id et image compris)
+    async fn server_from_row(&self, row: &AnyRow) -> io::Result<Server> {
+        let id: i64 = row.try_get("id").map_err(sql_error)?;
+        let name: String = row.try_get("name").map_err(sql_error)?;
+        let owner_id: i64 = row.try_get("owner_id").map_err(sql_error)?;
+        let image: Option<String> = row.try_get("image").map_err(sql_error)?;
+
+        let admin_id = self.user_ids("SELECT user_id FROM server_admins WHERE server_id = $1 ORDER BY user_id", id).await?;
+        let member_id = self
+            .user_ids("SELECT user_id FROM server_members WHERE server_id = $1 ORDER BY joined_at, user_id", id)
+            .await?;
+
+        Ok(Server { id, name, owner_id, admin_id, member_id, image })
+    }
+
+    async fn servers(&self, sql: &str, value: Option<i64>) -> io::Result<Vec<Server>> {
+        let mut query = sqlx::query(sql);
+        if let Some(value) = value {
+            query = query.bind(value);
+        }
+        let rows = query.fetch_all(&self.pool).await.map_err(sql_error)?;
+
+        let mut servers = Vec::with_capacity(rows.len());
+        for row in &rows {
+            servers.push(self.server_from_row(row).await?);
+        }
+        Ok(servers)
+    }
+
+    async fn channels(&self, sql: &str, value: Option<i64>) -> io::Result<Vec<Channel>> {
+        let mut query = sqlx::query(sql);
+        if let Some(value) = value {
+            query = query.bind(value);
+        }
+        let rows = query.fetch_all(&self.pool).await.map_err(sql_error)?;
+
+        let mut channels = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let id: i64 = row.try_get("id").map_err(sql_error)?;
+            channels.push(Channel {
+                id,
+                server_id: row.try_get("server_id").map_err(sql_error)?,
+                name: row.try_get("name").map_err(sql_error)?,
+                position: row.try_get("position").map_err(sql_error)?,
+                private: row.try_get::<i64, _>("private").map_err(sql_error)? != 0,
+                overwrites: self.overwrites(id).await?,
+            });
+        }
+        Ok(channels)
+    }
+
+    /// overwrites :
+    /// exceptions d'un channel, dans l'ordre où elles ont été enregistrées
+    async fn overwrites(&self, channel_id: i64) -> io::Result<Vec<PermissionOverwrite>> {
+        sqlx::query("SELECT kind, target_id, allowed, denied FROM channel_overwrites WHERE channel_id = $1 ORDER BY kind, target_id")
+            .bind(channel_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .iter()
+            .map(|row| {
+                let kind = match row.try_get::<String, _>("kind").map_err(sql_error)?.as_str() {
+                    "role" => OverwriteKind::Role,
+                    "user" => OverwriteKind::User,
+                    other => {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Exception de channel invalide: {}", other)));
+                    }
+                };
+                Ok(PermissionOverwrite {
+                    kind,
+                    target_id: row.try_get("target_id").map_err(sql_error)?,
+                    allow: Permissions(row.try_get("allowed").map_err(sql_error)?),
+                    deny: Permissions(row.try_get("denied").map_err(sql_error)?),
+                })
+            })
+            .collect()
+    }
+
+    /// save_overwrites :
+    /// remplace les exceptions du channel par celles du modèle, dans une transaction
+    async fn save_overwrites(&self, channel: &Channel) -> io::Result<()> {
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        sqlx::query("DELETE FROM channel_overwrites WHERE channel_id = $1")
+            .bind(channel.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(sql_error)?;
+        for overwrite in &channel.overwrites {
+            sqlx::query("INSERT INTO channel_overwrites (channel_id, kind, target_id, allowed, denied) VALUES ($1, $2, $3, $4, $5)")
+                .bind(channel.id)
+                .bind(kind_name(overwrite.kind))
+                .bind(overwrite.target_id)
+                .bind(overwrite.allow.0)
+                .bind(overwrite.deny.0)
+                .execute(&mut *tx)
+                .await
+                .map_err(sql_error)?;
+        }
+        tx.commit().await.map_err(sql_error)
+    }
+
+    async fn messages(&self, sql: &str, values: &[i64]) -> io::Result<Vec<Message>> {
+        let mut query = sqlx::query(sql);
+        for value in values {
+            query = query.bind(*value);
+        }
+        query
+            .fetch_all(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .iter()
+            .map(|row| {
+                Ok(Message {
+                    id: row.try_get("id").map_err(sql_error)?,
+                    channel_id: row.try_get("channel_id").map_err(sql_error)?,
+                    message: row.try_get("content").map_err(sql_error)?,
+                    user: row.try_get("user_id").map_err(sql_error)?,
+                    time: row.try_get("sent_at").map_err(sql_error)?,
+                    edited_at: row.try_get("edited_at").map_err(sql_error)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn roles(&self, sql: &str, value: Option<i64>) -> io::Result<Vec<Role>> {
+        let mut query = sqlx::query(sql);
+        if let Some(value) = value {
+            query = query.bind(value);
+        }
+        let rows = query.fetch_all(&self.pool).await.map_err(sql_error)?;
+
+        let mut roles = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let id: i64 = row.try_get("id").map_err(sql_error)?;
+            roles.push(Role {
+                id,
+                server_id: row.try_get("server_id").map_err(sql_error)?,
+                name: row.try_get("name").map_err(sql_error)?,
+                permissions: Permissions(row.try_get("permissions").map_err(sql_error)?),
+                position: row.try_get("position").map_err(sql_error)?,
+                member_id: self.user_ids("SELECT user_id FROM role_members WHERE role_id = $1 ORDER BY user_id", id).await?,
+            });
+        }
+        Ok(roles)
+    }
+
+    async fn bans(&self, sql: &str, values: &[i64]) -> io::Result<Vec<Ban>> {
+        let mut query = sqlx::query(sql);
+        for value in values {
+            query = query.bind(*value);
+        }
+        query
+            .fetch_all(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .iter()
+            .map(|row| {
+                Ok(Ban {
+                    server_id: row.try_get("server_id").map_err(sql_error)?,
+                    user_id: row.try_get("user_id").map_err(sql_error)?,
+                    reason: row.try_get("reason").map_err(sql_error)?,
+                    banned_by: row.try_get("banned_by").map_err(sql_error)?,
+                    banned_at: row.try_get("banned_at").map_err(sql_error)?,
+                    expires_at: row.try_get("expires_at").map_err(sql_error)?,
+                })
+            })
+            .collect()
+    }
+
+    /// server_with_roles :
+    /// équivalent de db_mongo_getter::get_server_with_roles
+    async fn server_with_roles(&self, server_id: i64) -> io::Result<Option<(Server, Vec<Role>)>> {
+        let Some(server) = self.get_server(&server_id).await? else {
+            return Ok(None);
+        };
+        let roles = self.get_roles_of_server(&server_id).await?;
+        Ok(Some((server, roles)))
+    }
+
+    /// role_with_server :
+    /// rôle, serveur du rôle et rôles de ce serveur, pour les vérifications de gestion des rôles
+    async fn role_with_server(&self, role_id: i64) -> FluxyResult<(Role, Server, Vec<Role>)> {
+        let Some(role) = self.get_role_by_id(&role_id).await? else {
+            return Err(FluxyError::NotFound("Rôle introuvable".to_string()));
+        };
+        let Some((server, roles)) = self.server_with_roles(role.server_id).await? else {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        };
+        Ok((role, server, roles))
+    }
+
+    /// channel_with_server :
+    /// équivalent de db_mongo_getter::get_channel_with_server
+    async fn channel_with_server(&self, channel_id: i64) -> io::Result<Option<(Channel, Server, Vec<Role>)>> {
+        let Some(channel) = self.get_channel_by_id(&channel_id).await? else {
+            return Ok(None);
+        };
+        Ok(self.server_with_roles(channel.server_id).await?.map(|(server, roles)| (channel, server, roles)))
+    }
+}
+
+#[async_trait]
+impl ChatStore for SqlStore {
+    async fn ping(&self) -> io::Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await.map_err(sql_error)?;
+        Ok(())
+    }
+
+    async fn count_documents(&self, collection: &str) -> io::Result<u64> {
+        // Chaque collection MongoDB correspond à une table ; les tables de liaison ne sont pas des collections
+        let table = match collection {
+            "server" => "servers",
+            "channel" => "channels",
+            "message" => "messages",
+            "role" => "roles",
+            "ban" => "bans",
+            "timeout" => "member_timeouts",
+            "invite" => "invites",
+            "audit_log" => "audit_log",
+            _ => return Ok(0),
+        };
+        let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(&self.pool)
+            .await
+            .map_err(sql_error)?;
+        Ok(count as u64)
+    }
+
+    async fn get_server(&self, server_id: &i64) -> io::Result<Option<Server>> {
+        Ok(self.servers(&format!("{} WHERE id = $1", SERVER_COLUMNS), Some(*server_id)).await?.into_iter().next())
+    }
+
+    async fn get_servers_by_owner(&self, owner_id: &i64) -> io::Result<Vec<Server>> {
+        self.servers(&format!("{} WHERE owner_id = $1 ORDER BY id", SERVER_COLUMNS), Some(*owner_id)).await
+    }
+
+    async fn get_servers_by_member(&self, user_id: &i64) -> io::Result<Vec<Server>> {
+        self.servers(
+            &format!("{} WHERE id IN (SELECT server_id FROM server_members WHERE user_id = $1) ORDER BY id", SERVER_COLUMNS),
+            Some(*user_id),
+        )
+        .await
+    }
+
+    async fn get_channels_of_server(&self, server_id: &i64) -> io::Result<Vec<Channel>> {
+        self.channels(&format!("{} WHERE server_id = $1 ORDER BY id", CHANNEL_COLUMNS), Some(*server_id)).await
+    }
+
+    async fn get_messages_of_channel(&self, channel_id: &i64) -> io::Result<Vec<Message>> {
+        self.messages(&format!("{} WHERE channel_id = $1 ORDER BY id", MESSAGE_COLUMNS), &[*channel_id]).await
+    }
+
+    async fn get_messages_page(&self, channel_id: &i64, before: Option<i64>, after: Option<i64>, limit: i64) -> io::Result<Vec<Message>> {
+        let mut sql = format!("{} WHERE channel_id = $1", MESSAGE_COLUMNS);
+        let mut values = vec![*channel_id];
+        if let Some(before) = before {
+            values.push(before);
+            sql.push_str(&format!(" AND id < ${}", values.len()));
+        }
+        if let Some(after) = after {
+            values.push(after);
+            sql.push_str(&format!(" AND id > ${}", values.len()));
+        }
+
+        // Même ordre que db_mongo_getter::get_messages_page : les plus récents, sauf avec after seul
+        let ascending = after.is_some() && before.is_none();
+        values.push(limit);
+        sql.push_str(&format!(" ORDER BY id {} LIMIT ${}", if ascending { "ASC" } else { "DESC" }, values.len()));
+
+        let mut messages = self.messages(&sql, &values).await?;
+        if !ascending {
+            messages.reverse();
+        }
+        Ok(messages)
+    }
+
+    async fn get_messages_of_channel_after(&self, channel_id: &i64, last_seen_id: &i64, limit: i64) -> io::Result<Vec<Message>> {
+        self.messages(
+            &format!("{} WHERE channel_id = $1 AND id > $2 ORDER BY id LIMIT $3", MESSAGE_COLUMNS),
+            &[*channel_id, *last_seen_id, limit],
+        )
+        .await
+    }
+
+    async fn get_message_by_id(&self, message_id: &i64) -> io::Result<Option<Message>> {
+        Ok(self.messages(&format!("{} WHERE id = $1", MESSAGE_COLUMNS), &[*message_id]).await?.into_iter().next())
+    }
+
+    async fn get_message_revisions(&self, message_id: &i64) -> io::Result<Vec<MessageRevision>> {
+        sqlx::query("SELECT content, editor_id, edited_at FROM message_revisions WHERE message_id = $1 ORDER BY edited_at")
+            .bind(*message_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .iter()
+            .map(|row| {
+                Ok(MessageRevision {
+                    message: row.try_get("content").map_err(sql_error)?,
+                    editor_id: row.try_get("editor_id").map_err(sql_error)?,
+                    edited_at: row.try_get("edited_at").map_err(sql_error)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_channel_by_id(&self, channel_id: &i64) -> io::Result<Option<Channel>> {
+        Ok(self.channels(&format!("{} WHERE id = $1", CHANNEL_COLUMNS), Some(*channel_id)).await?.into_iter().next())
+    }
+
+    async fn get_server_id_by_message_id(&self, message_id: &i64) -> io::Result<i64> {
+        sqlx::query("SELECT channels.server_id FROM messages JOIN channels ON channels.id = messages.channel_id WHERE messages.id = $1")
+            .bind(*message_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .map(|row| row.try_get::<i64, _>("server_id").map_err(sql_error))
+            .transpose()
+            .map(|server_id| server_id.unwrap_or(0))
+    }
+
+    async fn get_last_id(&self, collection: &str) -> io::Result<i64> {
+        let table = match collection {
+            "server" => "servers",
+            "channel" => "channels",
+            "message" => "messages",
+            "role" => "roles",
+            _ => return Ok(0),
+        };
+        sqlx::query(&format!("SELECT MAX(id) AS id FROM {}", table))
+            .fetch_one(&self.pool)
+            .await
+            .and_then(|row| row.try_get::<Option<i64>, _>("id"))
+            .map(|id| id.unwrap_or(0))
+            .map_err(sql_error)
+    }
+
+    async fn is_owner(&self, server_id: &i64, user_id: &i64) -> io::Result<bool> {
+        self.exists("SELECT 1 FROM servers WHERE id = $1 AND owner_id = $2", *server_id, *user_id).await
+    }
+
+    async fn is_admin(&self, server_id: &i64, user_id: &i64) -> io::Result<bool> {
+        self.exists("SELECT 1 FROM server_admins WHERE server_id = $1 AND user_id = $2", *server_id, *user_id).await
+    }
+
+    async fn is_member(&self, server_id: &i64, user_id: &i64) -> io::Result<bool> {
+        self.exists("SELECT 1 FROM server_members WHERE server_id = $1 AND user_id = $2", *server_id, *user_id).await
+    }
+
+    async fn is_channel_of_server(&self, server_id: i64, channel_id: i64) -> io::Result<bool> {
+        self.exists("SELECT 1 FROM channels WHERE server_id = $1 AND id = $2", server_id, channel_id).await
+    }
+
+    async fn verify_link_exist(&self, link_code: &str) -> io::Result<bool> {
+        sqlx::query("SELECT 1 FROM invites WHERE code = $1")
+            .bind(link_code)
+            .fetch_optional(&self.pool)
+            .await
+            .map(|row| row.is_some())
+            .map_err(sql_error)
+    }
+
+    async fn get_roles_of_server(&self, server_id: &i64) -> io::Result<Vec<Role>> {
+        self.roles(&format!("{} WHERE server_id = $1 ORDER BY position DESC, id", ROLE_COLUMNS), Some(*server_id)).await
+    }
+
+    async fn get_role_by_id(&self, role_id: &i64) -> io::Result<Option<Role>> {
+        Ok(self.roles(&format!("{} WHERE id = $1", ROLE_COLUMNS), Some(*role_id)).await?.into_iter().next())
+    }
+
+    async fn has_permission(&self, server_id: &i64, user_id: &i64, permission: Permissions) -> io::Result<bool> {
+        Ok(self
+            .server_with_roles(*server_id)
+            .await?
+            .is_some_and(|(server, roles)| server.permissions_of(&roles, *user_id).contains(permission)))
+    }
+
+    async fn get_channel_permissions(&self, channel_id: &i64, user_id: &i64) -> io::Result<Permissions> {
+        Ok(self
+            .channel_with_server(*channel_id)
+            .await?
+            .map_or(Permissions::NONE, |(channel, server, roles)| channel.permissions_of(&server, &roles, *user_id)))
+    }
+
+    async fn get_ban(&self, server_id: &i64, user_id: &i64) -> io::Result<Option<Ban>> {
+        Ok(self
+            .bans(&format!("{} WHERE server_id = $1 AND user_id = $2", BAN_COLUMNS), &[*server_id, *user_id])
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    async fn get_bans_of_server(&self, server_id: &i64) -> io::Result<Vec<Ban>> {
+        self.bans(&format!("{} WHERE server_id = $1 ORDER BY banned_at DESC, user_id", BAN_COLUMNS), &[*server_id])
+            .await
+    }
+
+    async fn get_timeout(&self, server_id: &i64, user_id: &i64) -> io::Result<Option<MemberTimeout>> {
+        sqlx::query(&format!("{} WHERE server_id = $1 AND user_id = $2", TIMEOUT_COLUMNS))
+            .bind(*server_id)
+            .bind(*user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .as_ref()
+            .map(member_timeout)
+            .transpose()
+    }
+
+    async fn get_active_timeouts(&self, now: &str) -> io::Result<Vec<MemberTimeout>> {
+        sqlx::query(&format!("{} WHERE until > $1", TIMEOUT_COLUMNS))
+            .bind(now)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .iter()
+            .map(member_timeout)
+            .collect()
+    }
+
+    async fn get_invite(&self, link_code: &str) -> io::Result<Option<Invite>> {
+        sqlx::query(&format!("{} WHERE code = $1", INVITE_COLUMNS))
+            .bind(link_code)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .as_ref()
+            .map(invite)
+            .transpose()
+    }
+
+    async fn get_invites_of_server(&self, server_id: &i64) -> io::Result<Vec<Invite>> {
+        sqlx::query(&format!("{} WHERE server_id = $1 ORDER BY created_at DESC, code", INVITE_COLUMNS))
+            .bind(*server_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .iter()
+            .map(invite)
+            .collect()
+    }
+
+    async fn get_audit_log(&self, server_id: &i64, filter: &AuditLogFilter) -> io::Result<Vec<AuditEntry>> {
+        // Les paramètres sont numérotés dans l'ordre où ils sont liés plus bas
+        let mut sql = format!("{} WHERE server_id = $1", AUDIT_COLUMNS);
+        let mut count = 1;
+        for (column, used) in [("action =", filter.action.is_some()), ("actor_id =", filter.user_id.is_some()), ("id <", filter.before.is_some())] {
+            if used {
+                count += 1;
+                sql.push_str(&format!(" AND {} ${}", column, count));
+            }
+        }
+        sql.push_str(&format!(" ORDER BY id DESC LIMIT ${}", count + 1));
+
+        let mut query = sqlx::query(&sql).bind(*server_id);
+        if let Some(action) = filter.action {
+            query = query.bind(action_name(action));
+        }
+        if let Some(user_id) = filter.user_id {
+            query = query.bind(user_id);
+        }
+        if let Some(before) = filter.before {
+            query = query.bind(before);
+        }
+        query
+            .bind(filter.limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .iter()
+            .map(audit_entry)
+            .collect()
+    }
+
+    async fn set_server(&self, owner_id: i64, name: &str, image: Option<String>) -> FluxyResult<()> {
+        if name.trim().is_empty() {
+            return Err(FluxyError::Validation("Le nom du serveur est obligatoire".to_string()));
+        }
+        let id = self.next_id("server").await?;
+        let image = image.filter(|img| !img.trim().is_empty());
+
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        sqlx::query("INSERT INTO servers (id, name, owner_id, image) VALUES ($1, $2, $3, $4)")
+            .bind(id)
+            .bind(name)
+            .bind(owner_id)
+            .bind(image)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| insert_error(e, "Erreur lors de la création du serveur"))?;
+        sqlx::query("INSERT INTO server_members (server_id, user_id, joined_at) VALUES ($1, $2, $3)")
+            .bind(id)
+            .bind(owner_id)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&mut *tx)
+            .await
+            .map_err(sql_error)?;
+        Ok(tx.commit().await.map_err(sql_error)?)
+    }
+
+    async fn set_channel(&self, server_id: i64, name: &str, user_id: i64) -> FluxyResult<()> {
+        if name.trim().is_empty() {
+            return Err(FluxyError::Validation("Le nom du channel est obligatoire".to_string()));
+        }
+        if !self.has_permission(&server_id, &user_id, Permissions::MANAGE_CHANNELS).await? {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de créer un channel".to_string()));
+        }
+
+        let id = self.next_id("channel").await?;
+        let last_position = self.get_channels_of_server(&server_id).await?.len() as i64;
+        sqlx::query("INSERT INTO channels (id, server_id, name, position) VALUES ($1, $2, $3, $4)")
+            .bind(id)
+            .bind(server_id)
+            .bind(name)
+            .bind(last_position + 1)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| insert_error(e, "Erreur lors de la création du channel"))?;
+        Ok(())
+    }
+
+    async fn set_message(&self, server_id: i64, channel_id: i64, message: &str, user_id: i64) -> FluxyResult<Message> {
+        let is_channel = self.is_channel_of_server(server_id, channel_id).await?;
+        let permissions = self.get_channel_permissions(&channel_id, &user_id).await?;
+        db_mongo_setter::check_send_message(is_channel, permissions, message)?;
+        db_mongo_setter::check_not_timed_out(self.get_timeout(&server_id, &user_id).await?, permissions, Utc::now())?;
+
+        let id = self.next_id("message").await?;
+        let time = Utc::now().to_rfc3339();
+        sqlx::query("INSERT INTO messages (id, channel_id, user_id, content, sent_at) VALUES ($1, $2, $3, $4, $5)")
+            .bind(id)
+            .bind(channel_id)
+            .bind(user_id)
+            .bind(message)
+            .bind(&time)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| insert_error(e, "Erreur lors de la création du message"))?;
+
+        Ok(Message {
+            id,
+            channel_id,
+            message: message.to_string(),
+            user: user_id,
+            time,
+            edited_at: None,
+        })
+    }
+
+    async fn add_member_to_server(&self, server_id: i64, user_id: i64) -> FluxyResult<()> {
+        if self.get_server(&server_id).await?.is_none() {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        }
+        db_mongo_setter::check_not_banned(self.get_ban(&server_id, &user_id).await?)?;
+        sqlx::query(
+            "INSERT INTO server_members (server_id, user_id, joined_at) SELECT id, $2, $3 FROM servers WHERE id = $1 \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(server_id)
+        .bind(user_id)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await
+        .map_err(sql_error)?;
+        Ok(())
+    }
+
+    async fn add_admin_to_server(&self, server_id: i64, user_id: i64, user_to_add: i64) -> FluxyResult<()> {
+        if !self.is_owner(&server_id, &user_id).await? {
+            return Err(FluxyError::Forbidden("Seul le fondateur peut nommer un administrateur".to_string()));
+        }
+        if !self.is_member(&server_id, &user_to_add).await? {
+            return Err(FluxyError::Validation("L'utilisateur n'est pas membre du serveur".to_string()));
+        }
+        sqlx::query("INSERT INTO server_admins (server_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(server_id)
+            .bind(user_to_add)
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    async fn switch_owner(&self, server_id: i64, user_id: i64, user_to_replace: i64) -> FluxyResult<()> {
+        if !self.is_owner(&server_id, &user_id).await? {
+            return Err(FluxyError::Forbidden("Seul le fondateur peut transférer la propriété du serveur".to_string()));
+        }
+        db_mongo_setter::check_new_owner(
+            self.is_member(&server_id, &user_to_replace).await?,
+            self.get_ban(&server_id, &user_to_replace).await?,
+        )?;
+        sqlx::query("UPDATE servers SET owner_id = $1 WHERE id = $2")
+            .bind(user_to_replace)
+            .bind(server_id)
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    async fn create_invite(&self, server_id: i64, user_id: i64, expires_at: Option<String>, max_uses: Option<i64>) -> FluxyResult<Invite> {
+        if !self.has_permission(&server_id, &user_id, Permissions::CREATE_INVITES).await? {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de créer un lien d'invitation".to_string()));
+        }
+        let mut link_code = db_mongo_setter::random_string();
+        while self.verify_link_exist(&link_code).await? {
+            link_code = db_mongo_setter::random_string();
+        }
+        let invite = db_mongo_setter::new_invite(link_code, server_id, user_id, expires_at, max_uses);
+        db_mongo_setter::check_invite(&invite)?;
+
+        sqlx::query(
+            "INSERT INTO invites (code, server_id, creator_id, created_at, expires_at, max_uses, uses) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&invite.code)
+        .bind(invite.server_id)
+        .bind(invite.creator_id)
+        .bind(&invite.created_at)
+        .bind(invite.expires_at.clone())
+        .bind(invite.max_uses)
+        .bind(invite.uses)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| insert_error(e, "Erreur lors de la création du lien d'invitation"))?;
+        Ok(invite)
+    }
+
+    async fn join_by_link(&self, link: &str, user_id: i64) -> FluxyResult<()> {
+        let Some(invite) = self.get_invite(link).await? else {
+            return Err(FluxyError::NotFound("Lien d'invitation invalide".to_string()));
+        };
+        db_mongo_setter::check_not_banned(self.get_ban(&invite.server_id, &user_id).await?)?;
+        if self.is_member(&invite.server_id, &user_id).await? {
+            return Ok(());
+        }
+
+        // Le compteur n'est incrémenté que si le lien est encore utilisable : deux arrivées simultanées ne dépassent pas max_uses
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        let used = sqlx::query(
+            "UPDATE invites SET uses = uses + 1 WHERE code = $1 \
+             AND (max_uses IS NULL OR uses < max_uses) AND (expires_at IS NULL OR expires_at > $2)",
+        )
+        .bind(link)
+        .bind(format_date(Utc::now()))
+        .execute(&mut *tx)
+        .await
+        .map_err(sql_error)?;
+        if used.rows_affected() == 0 {
+            drop(tx);
+            if self.is_member(&invite.server_id, &user_id).await? {
+                return Ok(());
+            }
+            return Err(db_mongo_setter::invite_exhausted_error());
+        }
+        let added = sqlx::query("INSERT INTO server_members (server_id, user_id, joined_at) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING")
+            .bind(invite.server_id)
+            .bind(user_id)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| FluxyError::Storage(format!("Erreur lors de l'ajout au serveur par lien: {}", e)))?;
+        // Déjà ajouté par une arrivée simultanée : la transaction est annulée et l'utilisation rendue
+        if added.rows_affected() == 0 {
+            tx.rollback().await.map_err(sql_error)?;
+            return Ok(());
+        }
+        Ok(tx.commit().await.map_err(sql_error)?)
+    }
+
+    async fn set_role(&self, server_id: i64, name: &str, permissions: Permissions, user_id: i64) -> FluxyResult<Role> {
+        let Some((server, roles)) = self.server_with_roles(server_id).await? else {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        };
+        db_mongo_setter::check_new_role(&server, &roles, user_id, name, permissions)?;
+
+        // Comme db_mongo_setter::set_role : le nouveau rôle arrive en bas, les autres montent d'un cran
+        let id = self.next_id("role").await?;
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        sqlx::query("UPDATE roles SET position = position + 1 WHERE server_id = $1")
+            .bind(server_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(sql_error)?;
+        sqlx::query("INSERT INTO roles (id, server_id, name, permissions, position) VALUES ($1, $2, $3, $4, 1)")
+            .bind(id)
+            .bind(server_id)
+            .bind(name)
+            .bind(permissions.0)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| insert_error(e, "Erreur lors de la création du rôle"))?;
+        tx.commit().await.map_err(sql_error)?;
+
+        Ok(Role {
+            id,
+            server_id,
+            name: name.to_string(),
+            permissions,
+            position: 1,
+            member_id: vec![],
+        })
+    }
+
+    async fn add_role_to_member(&self, role_id: i64, user_to_add: i64, user_id: i64) -> FluxyResult<()> {
+        let (role, server, roles) = self.role_with_server(role_id).await?;
+        db_mongo_setter::check_role_management(&server, &roles, user_id, role.position, Permissions::NONE)?;
+        if !server.member_id.contains(&user_to_add) {
+            return Err(FluxyError::Validation("L'utilisateur n'est pas membre du serveur".to_string()));
+        }
+        sqlx::query("INSERT INTO role_members (role_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(role_id)
+            .bind(user_to_add)
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    async fn set_ban(&self, server_id: i64, user_to_ban: i64, reason: &str, expires_at: Option<String>, user_id: i64) -> FluxyResult<Ban> {
+        let Some((server, roles)) = self.server_with_roles(server_id).await? else {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        };
+        let ban = db_mongo_setter::new_ban(server_id, user_to_ban, reason, expires_at, user_id);
+        db_mongo_setter::check_ban(&server, &roles, &ban)?;
+
+        // Le banni quitte le serveur et le bannissement est enregistré ensemble
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        for sql in MEMBER_REMOVAL {
+            sqlx::query(sql)
+                .bind(server_id)
+                .bind(user_to_ban)
+                .execute(&mut *tx)
+                .await
+                .map_err(sql_error)?;
+        }
+        sqlx::query(
+            "INSERT INTO bans (server_id, user_id, reason, banned_by, banned_at, expires_at) VALUES ($1, $2, $3, $4, $5, $6) \
+             ON CONFLICT (server_id, user_id) DO UPDATE SET reason = excluded.reason, banned_by = excluded.banned_by, \
+             banned_at = excluded.banned_at, expires_at = excluded.expires_at",
+        )
+        .bind(server_id)
+        .bind(user_to_ban)
+        .bind(&ban.reason)
+        .bind(ban.banned_by)
+        .bind(&ban.banned_at)
+        .bind(ban.expires_at.clone())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| insert_error(e, "Erreur lors du bannissement"))?;
+        tx.commit().await.map_err(sql_error)?;
+
+        Ok(ban)
+    }
+
+    async fn set_timeout(&self, server_id: i64, user_to_mute: i64, until: String, user_id: i64) -> FluxyResult<MemberTimeout> {
+        let Some((server, roles)) = self.server_with_roles(server_id).await? else {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        };
+        let timeout = MemberTimeout { server_id, user_id: user_to_mute, moderator_id: user_id, until };
+        db_mongo_setter::check_timeout(&server, &roles, &timeout, Utc::now())?;
+
+        sqlx::query(
+            "INSERT INTO member_timeouts (server_id, user_id, moderator_id, until) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (server_id, user_id) DO UPDATE SET moderator_id = excluded.moderator_id, until = excluded.until",
+        )
+        .bind(server_id)
+        .bind(user_to_mute)
+        .bind(user_id)
+        .bind(&timeout.until)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| insert_error(e, "Erreur lors de la mise en silence"))?;
+
+        Ok(timeout)
+    }
+
+    async fn set_audit_entry(&self, entry: NewAuditEntry) -> FluxyResult<AuditEntry> {
+        let entry = db_mongo_setter::audit_entry(self.next_id("audit_log").await?, entry);
+        sqlx::query(
+            "INSERT INTO audit_log (id, server_id, actor_id, action, target_id, before_state, after_state, created_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(entry.id)
+        .bind(entry.server_id)
+        .bind(entry.actor_id)
+        .bind(action_name(entry.action))
+        .bind(entry.target_id)
+        .bind(entry.before.as_ref().map(|state| state.to_string()))
+        .bind(entry.after.as_ref().map(|state| state.to_string()))
+        .bind(&entry.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| insert_error(e, "Erreur lors de l'écriture du journal d'audit"))?;
+
+        Ok(entry)
+    }
+
+    async fn update_message(&self, message_id: i64, message: &str, user_id: i64) -> FluxyResult<Message> {
+        if message.trim().is_empty() {
+            return Err(FluxyError::Validation("Le message ne peut pas être vide".to_string()));
+        }
+        let Some(message_by_id) = self.get_message_by_id(&message_id).await? else {
+            return Err(FluxyError::NotFound("Message introuvable".to_string()));
+        };
+        if message_by_id.user != user_id {
+            return Err(FluxyError::Forbidden("Seul l'auteur peut modifier son message".to_string()));
+        }
+
+        let edited_at = Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        sqlx::query("INSERT INTO message_revisions (message_id, content, editor_id, edited_at) VALUES ($1, $2, $3, $4)")
+            .bind(message_id)
+            .bind(&message_by_id.message)
+            .bind(user_id)
+            .bind(&edited_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(sql_error)?;
+        sqlx::query("UPDATE messages SET content = $1, edited_at = $2 WHERE id = $3")
+            .bind(message)
+            .bind(&edited_at)
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(sql_error)?;
+        tx.commit().await.map_err(sql_error)?;
+
+        Ok(Message {
+            message: message.to_string(),
+            edited_at: Some(edited_at),
+            ..message_by_id
+        })
+    }
+
+    async fn update_channel_name(&self, channel_id: i64, name: &str, user_id: i64) -> FluxyResult<()> {
+        if name.trim().is_empty() {
+            return Err(FluxyError::Validation("Le nom du channel est obligatoire".to_string()));
+        }
+        let Some((channel, server, roles)) = self.channel_with_server(channel_id).await? else {
+            return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+        };
+        if !channel.permissions_of(&server, &roles, user_id).contains(Permissions::MANAGE_CHANNELS) {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de modifier un channel".to_string()));
+        }
+        sqlx::query("UPDATE channels SET name = $1 WHERE id = $2")
+            .bind(name)
+            .bind(channel_id)
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    async fn update_server_name(&self, server_id: i64, name: &str, user_id: i64) -> FluxyResult<()> {
+        self.update_server(server_id, Some(name), None, user_id).await
+    }
+
+    async fn update_server(&self, server_id: i64, name: Option<&str>, image: Option<&str>, user_id: i64) -> FluxyResult<()> {
+        if !self.has_permission(&server_id, &user_id, Permissions::ADMINISTRATOR).await? {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de modifier le serveur".to_string()));
+        }
+        if let Some(name) = name.filter(|n| !n.trim().is_empty()) {
+            sqlx::query("UPDATE servers SET name = $1 WHERE id = $2")
+                .bind(name)
+                .bind(server_id)
+                .execute(&self.pool)
+                .await
+                .map_err(sql_error)?;
+        }
+        if let Some(image) = image {
+            // on autorise image vide pour effacer, comme db_mongo_update::update_server
+            sqlx::query("UPDATE servers SET image = $1 WHERE id = $2")
+                .bind(image)
+                .bind(server_id)
+                .execute(&self.pool)
+                .await
+                .map_err(sql_error)?;
+        }
+        Ok(())
+    }
+
+    async fn update_member_role(&self, server_id: i64, user_id: i64, role: &str, actor_id: i64) -> FluxyResult<()> {
+        if role != "admin" && role != "membre" {
+            let Some(custom_role) = self.get_roles_of_server(&server_id).await?.into_iter().find(|custom_role| custom_role.name == role) else {
+                return Err(FluxyError::Validation("Rôle inconnu".to_string()));
+            };
+            return self.add_role_to_member(custom_role.id, user_id, actor_id).await;
+        }
+        if !self.is_owner(&server_id, &actor_id).await? {
+            return Err(FluxyError::Forbidden("Seul le fondateur peut modifier les rôles".to_string()));
+        }
+
+        let sql = match role {
+            "admin" => "INSERT INTO server_admins (server_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            "membre" => "DELETE FROM server_admins WHERE server_id = $1 AND user_id = $2",
+            _ => {
+                return Err(FluxyError::Validation("Rôle inconnu".to_string()));
+            }
+        };
+        sqlx::query(sql)
+            .bind(server_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    async fn update_role(&self, role_id: i64, name: Option<&str>, permissions: Option<Permissions>, position: Option<i64>, user_id: i64) -> FluxyResult<Role> {
+        let (role, server, roles) = self.role_with_server(role_id).await?;
+        let role = db_mongo_update::check_role_update(&server, &roles, user_id, role, name, permissions, position)?;
+        sqlx::query("UPDATE roles SET name = $1, permissions = $2, position = $3 WHERE id = $4")
+            .bind(&role.name)
+            .bind(role.permissions.0)
+            .bind(role.position)
+            .bind(role_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| insert_error(e, "Erreur lors de la mise à jour du rôle"))?;
+        Ok(role)
+    }
+
+    async fn update_channel_privacy(&self, channel_id: i64, private: bool, user_id: i64) -> FluxyResult<Channel> {
+        let Some((channel, server, roles)) = self.channel_with_server(channel_id).await? else {
+            return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+        };
+        db_mongo_update::check_channel_management(&server, &roles, &channel, user_id)?;
+        sqlx::query("UPDATE channels SET private = $1 WHERE id = $2")
+            .bind(private as i64)
+            .bind(channel_id)
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?;
+        Ok(Channel { private, ..channel })
+    }
+
+    async fn update_channel_overwrite(&self, channel_id: i64, overwrite: PermissionOverwrite, user_id: i64) -> FluxyResult<Channel> {
+        let Some((channel, server, roles)) = self.channel_with_server(channel_id).await? else {
+            return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+        };
+        let channel = db_mongo_update::check_channel_overwrite(&server, &roles, channel, user_id, overwrite)?;
+        self.save_overwrites(&channel).await?;
+        Ok(channel)
+    }
+
+    async fn delete_message(&self, message_id: i64, user_id: i64) -> FluxyResult<Message> {
+        let Some(message_by_id) = self.get_message_by_id(&message_id).await? else {
+            return Err(FluxyError::NotFound("Message introuvable".to_string()));
+        };
+        let server_id = self.get_server_id_by_message_id(&message_id).await?;
+        let is_author = message_by_id.user == user_id;
+        if !is_author && !self.has_permission(&server_id, &user_id, Permissions::MANAGE_MESSAGES).await? {
+            return Err(FluxyError::Forbidden(
+                "Seuls l'auteur ou les membres qui gèrent les messages peuvent supprimer ce message".to_string(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        for sql in ["DELETE FROM message_revisions WHERE message_id = $1", "DELETE FROM messages WHERE id = $1"] {
+            sqlx::query(sql).bind(message_id).execute(&mut *tx).await.map_err(sql_error)?;
+        }
+        tx.commit().await.map_err(sql_error)?;
+        Ok(message_by_id)
+    }
+
+    async fn delete_channel(&self, channel_id: i64, user_id: i64) -> FluxyResult<()> {
+        let Some((channel, server, roles)) = self.channel_with_server(channel_id).await? else {
+            return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+        };
+        if !channel.permissions_of(&server, &roles, user_id).contains(Permissions::MANAGE_CHANNELS) {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de supprimer un channel".to_string()));
+        }
+
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        for sql in [
+            "DELETE FROM message_revisions WHERE message_id IN (SELECT id FROM messages WHERE channel_id = $1)",
+            "DELETE FROM messages WHERE channel_id = $1",
+            "DELETE FROM channel_overwrites WHERE channel_id = $1",
+            "DELETE FROM channels WHERE id = $1",
+        ] {
+            sqlx::query(sql).bind(channel_id).execute(&mut *tx).await.map_err(sql_error)?;
+        }
+        Ok(tx.commit().await.map_err(sql_error)?)
+    }
+
+    async fn delete_server(&self, server_id: i64, user_id: i64) -> FluxyResult<()> {
+        if !self.is_owner(&server_id, &user_id).await? {
+            return Err(FluxyError::Forbidden("Seul le fondateur peut supprimer le serveur".to_string()));
+        }
+
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        for sql in [
+            "DELETE FROM message_revisions WHERE message_id IN \
+             (SELECT id FROM messages WHERE channel_id IN (SELECT id FROM channels WHERE server_id = $1))",
+            "DELETE FROM messages WHERE channel_id IN (SELECT id FROM channels WHERE server_id = $1)",
+            "DELETE FROM channel_overwrites WHERE channel_id IN (SELECT id FROM channels WHERE server_id = $1)",
+            "DELETE FROM channels WHERE server_id = $1",
+            "DELETE FROM invites WHERE server_id = $1",
+            "DELETE FROM bans WHERE server_id = $1",
+            "DELETE FROM member_timeouts WHERE server_id = $1",
+            "DELETE FROM audit_log WHERE server_id = $1",
+            "DELETE FROM role_members WHERE role_id IN (SELECT id FROM roles WHERE server_id = $1)",
+            "DELETE FROM roles WHERE server_id = $1",
+            "DELETE FROM server_admins WHERE server_id = $1",
+            "DELETE FROM server_members WHERE server_id = $1",
+            "DELETE FROM servers WHERE id = $1",
+        ] {
+            sqlx::query(sql).bind(server_id).execute(&mut *tx).await.map_err(sql_error)?;
+        }
+        Ok(tx.commit().await.map_err(sql_error)?)
+    }
+
+    async fn delete_admin(&self, server_id: i64, user_id: i64, user_to_remove: i64) -> FluxyResult<()> {
+        if !self.is_owner(&server_id, &user_id).await? {
+            return Err(FluxyError::Forbidden("Seul le fondateur peut retirer un administrateur".to_string()));
+        }
+        sqlx::query("DELETE FROM server_admins WHERE server_id = $1 AND user_id = $2")
+            .bind(server_id)
+            .bind(user_to_remove)
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    async fn delete_member(&self, server_id: i64, user_id: i64, user_to_remove: i64) -> FluxyResult<()> {
+        // Mêmes règles que db_mongo_delete::delete_member
+        let Some((server, roles)) = self.server_with_roles(server_id).await? else {
+            return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+        };
+        db_mongo_delete::check_member_removal(&server, &roles, user_id, user_to_remove, Permissions::KICK_MEMBERS)?;
+
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        for sql in MEMBER_REMOVAL {
+            sqlx::query(sql)
+                .bind(server_id)
+                .bind(user_to_remove)
+                .execute(&mut *tx)
+                .await
+                .map_err(sql_error)?;
+        }
+        Ok(tx.commit().await.map_err(sql_error)?)
+    }
+
+    async fn delete_invite(&self, link: &str, user_id: i64) -> FluxyResult<()> {
+        let Some(invite) = self.get_invite(link).await? else {
+            return Err(FluxyError::NotFound("Lien d'invitation introuvable".to_string()));
+        };
+        if invite.creator_id != user_id && !self.has_permission(&invite.server_id, &user_id, Permissions::ADMINISTRATOR).await? {
+            return Err(db_mongo_delete::invite_revocation_error());
+        }
+        sqlx::query("DELETE FROM invites WHERE code = $1")
+            .bind(link)
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    async fn delete_role(&self, role_id: i64, user_id: i64) -> FluxyResult<()> {
+        let (role, server, roles) = self.role_with_server(role_id).await?;
+        db_mongo_setter::check_role_management(&server, &roles, user_id, role.position, Permissions::NONE)?;
+
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        for sql in [
+            "DELETE FROM channel_overwrites WHERE kind = 'role' AND target_id = $1",
+            "DELETE FROM role_members WHERE role_id = $1",
+            "DELETE FROM roles WHERE id = $1",
+        ] {
+            sqlx::query(sql).bind(role_id).execute(&mut *tx).await.map_err(sql_error)?;
+        }
+        Ok(tx.commit().await.map_err(sql_error)?)
+    }
+
+    async fn remove_role_from_member(&self, role_id: i64, user_to_remove: i64, user_id: i64) -> FluxyResult<()> {
+        let (role, server, roles) = self.role_with_server(role_id).await?;
+        db_mongo_setter::check_role_management(&server, &roles, user_id, role.position, Permissions::NONE)?;
+        sqlx::query("DELETE FROM role_members WHERE role_id = $1 AND user_id = $2")
+            .bind(role_id)
+            .bind(user_to_remove)
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    async fn delete_channel_overwrite(&self, channel_id: i64, kind: OverwriteKind, target_id: i64, user_id: i64) -> FluxyResult<Channel> {
+        let Some((channel, server, roles)) = self.channel_with_server(channel_id).await? else {
+            return Err(FluxyError::NotFound("Channel introuvable".to_string()));
+        };
+        let channel = db_mongo_delete::remove_channel_overwrite(&server, &roles, channel, user_id, kind, target_id)?;
+        self.save_overwrites(&channel).await?;
+        Ok(channel)
+    }
+
+    async fn delete_ban(&self, server_id: i64, user_to_unban: i64, user_id: i64) -> FluxyResult<()> {
+        if !self.has_permission(&server_id, &user_id, Permissions::BAN_MEMBERS).await? {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de bannir des membres".to_string()));
+        }
+        let deleted = sqlx::query("DELETE FROM bans WHERE server_id = $1 AND user_id = $2")
+            .bind(server_id)
+            .bind(user_to_unban)
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .rows_affected();
+        if deleted == 0 {
+            return Err(FluxyError::NotFound("Bannissement introuvable".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn delete_timeout(&self, server_id: i64, user_to_unmute: i64, user_id: i64) -> FluxyResult<()> {
+        if !self.has_permission(&server_id, &user_id, Permissions::MODERATE_MEMBERS).await? {
+            return Err(FluxyError::Forbidden("Vous n'avez pas la permission de réduire des membres au silence".to_string()));
+        }
+        let deleted = sqlx::query("DELETE FROM member_timeouts WHERE server_id = $1 AND user_id = $2 AND until > $3")
+            .bind(server_id)
+            .bind(user_to_unmute)
+            .bind(format_date(Utc::now()))
+            .execute(&self.pool)
+            .await
+            .map_err(sql_error)?
+            .rows_affected();
+        if deleted == 0 {
+            return Err(FluxyError::NotFound("Timeout introuvable".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn repair_orphans(&self) -> io::Result<RepairReport> {
+        // Les clés étrangères empêchent normalement les orphelins : on répare les bases où elles n'étaient pas appliquées.
+        // Même ordre que delete_server, enfants d'abord, pour que les clés étrangères tiennent ;
+        // les messages et overwrites des channels orphelins partent avec eux
+        let mut report = RepairReport::default();
+        let mut tx = self.pool.begin().await.map_err(sql_error)?;
+        for (sql, deleted) in [
+            (
+                "DELETE FROM message_revisions WHERE message_id NOT IN (SELECT messages.id FROM messages \
+                 JOIN channels ON channels.id = messages.channel_id JOIN servers ON servers.id = channels.server_id)",
+                None,
+            ),
+            (
+                "DELETE FROM messages WHERE channel_id NOT IN \
+                 (SELECT id FROM channels WHERE server_id IN (SELECT id FROM servers))",
+                Some(&mut report.messages),
+            ),
+            (
+                "DELETE FROM channel_overwrites WHERE channel_id NOT IN \
+                 (SELECT id FROM channels WHERE server_id IN (SELECT id FROM servers))",
+                None,
+            ),
+            ("DELETE FROM channels WHERE server_id NOT IN (SELECT id FROM servers)", Some(&mut report.channels)),
+            ("DELETE FROM invites WHERE server_id NOT IN (SELECT id FROM servers)", Some(&mut report.invites)),
+            ("DELETE FROM bans WHERE server_id NOT IN (SELECT id FROM servers)", Some(&mut report.bans)),
+            ("DELETE FROM member_timeouts WHERE server_id NOT IN (SELECT id FROM servers)", Some(&mut report.timeouts)),
+            ("DELETE FROM audit_log WHERE server_id NOT IN (SELECT id FROM servers)", Some(&mut report.audit_entries)),
+            (
+                "DELETE FROM role_members WHERE role_id NOT IN \
+                 (SELECT id FROM roles WHERE server_id IN (SELECT id FROM servers))",
+                None,
+            ),
+            ("DELETE FROM roles WHERE server_id NOT IN (SELECT id FROM servers)", Some(&mut report.roles)),
+            ("DELETE FROM server_admins WHERE server_id NOT IN (SELECT id FROM servers)", None),
+            ("DELETE FROM server_members WHERE server_id NOT IN (SELECT id FROM servers)", None),
+        ] {
+            let rows = sqlx::query(sql).execute(&mut *tx).await.map_err(sql_error)?.rows_affected();
+            if let Some(deleted) = deleted {
+                *deleted = rows;
+            }
+        }
+        tx.commit().await.map_err(sql_error)?;
+        Ok(report)
+    }
+
+    async fn delete_expired_bans(&self, now: &str) -> io::Result<u64> {
+        sqlx::query("DELETE FROM bans WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(sql_error)
+    }
+}
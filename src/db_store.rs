@@ -0,0 +1,388 @@
+//! db_store.rs :
+//!     - ChatStore :
+//!     trait regroupant les getters, setters, updates et deletes de la base. les handlers et le chat ne dépendent que de ce trait,
+//!     ce qui permet de changer de stockage sans les modifier. les signatures reprennent celles des fonctions db_mongo_*
+//!     sans le client ni le nom de la base, et les règles de permission sont les mêmes pour toutes les implémentations.
+//!     - MongoStore :
+//!         client partagé, nom de la base
+//!     implémentation MongoDB, qui délègue aux fonctions des fichiers db_mongo_*
+//!     - MemoryStore (db_memory.rs) :
+//!     implémentation en mémoire, utilisée par les tests pour tourner sans base de données
+//!     - SqlStore (db_sql.rs, feature sql) :
+//!     implémentation SQLite / Postgres
+
+use crate::{db_mongo_connection, db_mongo_delete, db_mongo_getter, db_mongo_setter, db_mongo_update};
+use crate::error::FluxyResult;
+use crate::models::{AuditEntry, AuditLogFilter, Ban, Channel, Invite, MemberTimeout, Message, MessageRevision, NewAuditEntry, OverwriteKind, PermissionOverwrite, Permissions, RepairReport, Role, Server};
+use async_trait::async_trait;
+use mongodb::Client;
+use std::io;
+
+/// ChatStore :
+/// accès aux serveurs, channels, messages et liens d'invitation. voir db_mongo_getter, db_mongo_setter,
+/// db_mongo_update et db_mongo_delete pour le détail de chaque opération.
+/// les getters renvoient une io::Error en cas de problème de lecture ; les setters, updates et deletes renvoient une FluxyError
+/// (Forbidden, NotFound...) que les handlers transforment directement en réponse HTTP.
+/// les permissions passent par has_permission : le fondateur et les admins les ont toutes, un membre celles de ses rôles.
+/// dans un channel, get_channel_permissions y ajoute les exceptions du channel (voir Channel::permissions_of).
+#[async_trait]
+pub trait ChatStore: Send + Sync {
+    // getters
+    async fn ping(&self) -> io::Result<()>;
+    /// nombre de documents de la collection (lignes de la table correspondante en SQL), pour les statistiques et les vérifications
+    async fn count_documents(&self, collection: &str) -> io::Result<u64>;
+    async fn get_server(&self, server_id: &i64) -> io::Result<Option<Server>>;
+    async fn get_servers_by_owner(&self, owner_id: &i64) -> io::Result<Vec<Server>>;
+    async fn get_servers_by_member(&self, user_id: &i64) -> io::Result<Vec<Server>>;
+    async fn get_channels_of_server(&self, server_id: &i64) -> io::Result<Vec<Channel>>;
+    async fn get_messages_of_channel(&self, channel_id: &i64) -> io::Result<Vec<Message>>;
+    async fn get_messages_page(&self, channel_id: &i64, before: Option<i64>, after: Option<i64>, limit: i64) -> io::Result<Vec<Message>>;
+    async fn get_messages_of_channel_after(&self, channel_id: &i64, last_seen_id: &i64, limit: i64) -> io::Result<Vec<Message>>;
+    async fn get_message_by_id(&self, message_id: &i64) -> io::Result<Option<Message>>;
+    async fn get_message_revisions(&self, message_id: &i64) -> io::Result<Vec<MessageRevision>>;
+    async fn get_channel_by_id(&self, channel_id: &i64) -> io::Result<Option<Channel>>;
+    async fn get_server_id_by_message_id(&self, message_id: &i64) -> io::Result<i64>;
+    async fn get_last_id(&self, collection: &str) -> io::Result<i64>;
+    async fn is_owner(&self, server_id: &i64, user_id: &i64) -> io::Result<bool>;
+    async fn is_admin(&self, server_id: &i64, user_id: &i64) -> io::Result<bool>;
+    async fn is_member(&self, server_id: &i64, user_id: &i64) -> io::Result<bool>;
+    async fn is_channel_of_server(&self, server_id: i64, channel_id: i64) -> io::Result<bool>;
+    async fn verify_link_exist(&self, link_code: &str) -> io::Result<bool>;
+    async fn get_roles_of_server(&self, server_id: &i64) -> io::Result<Vec<Role>>;
+    async fn get_role_by_id(&self, role_id: &i64) -> io::Result<Option<Role>>;
+    async fn has_permission(&self, server_id: &i64, user_id: &i64, permission: Permissions) -> io::Result<bool>;
+    async fn get_channel_permissions(&self, channel_id: &i64, user_id: &i64) -> io::Result<Permissions>;
+    async fn get_ban(&self, server_id: &i64, user_id: &i64) -> io::Result<Option<Ban>>;
+    async fn get_bans_of_server(&self, server_id: &i64) -> io::Result<Vec<Ban>>;
+    async fn get_timeout(&self, server_id: &i64, user_id: &i64) -> io::Result<Option<MemberTimeout>>;
+    /// timeouts encore en cours à la date `now`, tous serveurs confondus (voir services::restore_timeouts)
+    async fn get_active_timeouts(&self, now: &str) -> io::Result<Vec<MemberTimeout>>;
+    async fn get_invite(&self, link_code: &str) -> io::Result<Option<Invite>>;
+    async fn get_invites_of_server(&self, server_id: &i64) -> io::Result<Vec<Invite>>;
+    async fn get_audit_log(&self, server_id: &i64, filter: &AuditLogFilter) -> io::Result<Vec<AuditEntry>>;
+
+    // setters
+    async fn set_server(&self, owner_id: i64, name: &str, image: Option<String>) -> FluxyResult<()>;
+    async fn set_channel(&self, server_id: i64, name: &str, user_id: i64) -> FluxyResult<()>;
+    async fn set_message(&self, server_id: i64, channel_id: i64, message: &str, user_id: i64) -> FluxyResult<Message>;
+    async fn add_member_to_server(&self, server_id: i64, user_id: i64) -> FluxyResult<()>;
+    async fn add_admin_to_server(&self, server_id: i64, user_id: i64, user_to_add: i64) -> FluxyResult<()>;
+    async fn switch_owner(&self, server_id: i64, user_id: i64, user_to_replace: i64) -> FluxyResult<()>;
+    async fn create_invite(&self, server_id: i64, user_id: i64, expires_at: Option<String>, max_uses: Option<i64>) -> FluxyResult<Invite>;
+    async fn join_by_link(&self, link: &str, user_id: i64) -> FluxyResult<()>;
+    async fn set_role(&self, server_id: i64, name: &str, permissions: Permissions, user_id: i64) -> FluxyResult<Role>;
+    async fn add_role_to_member(&self, role_id: i64, user_to_add: i64, user_id: i64) -> FluxyResult<()>;
+    async fn set_ban(&self, server_id: i64, user_to_ban: i64, reason: &str, expires_at: Option<String>, user_id: i64) -> FluxyResult<Ban>;
+    async fn set_timeout(&self, server_id: i64, user_to_mute: i64, until: String, user_id: i64) -> FluxyResult<MemberTimeout>;
+    async fn set_audit_entry(&self, entry: NewAuditEntry) -> FluxyResult<AuditEntry>;
+
+    // updates
+    async fn update_message(&self, message_id: i64, message: &str, user_id: i64) -> FluxyResult<Message>;
+    async fn update_channel_name(&self, channel_id: i64, name: &str, user_id: i64) -> FluxyResult<()>;
+    async fn update_server_name(&self, server_id: i64, name: &str, user_id: i64) -> FluxyResult<()>;
+    async fn update_server(&self, server_id: i64, name: Option<&str>, image: Option<&str>, user_id: i64) -> FluxyResult<()>;
+    async fn update_member_role(&self, server_id: i64, user_id: i64, role: &str, actor_id: i64) -> FluxyResult<()>;
+    async fn update_role(&self, role_id: i64, name: Option<&str>, permissions: Option<Permissions>, position: Option<i64>, user_id: i64) -> FluxyResult<Role>;
+    async fn update_channel_privacy(&self, channel_id: i64, private: bool, user_id: i64) -> FluxyResult<Channel>;
+    async fn update_channel_overwrite(&self, channel_id: i64, overwrite: PermissionOverwrite, user_id: i64) -> FluxyResult<Channel>;
+
+    // deletes
+    async fn delete_message(&self, message_id: i64, user_id: i64) -> FluxyResult<Message>;
+    async fn delete_channel(&self, channel_id: i64, user_id: i64) -> FluxyResult<()>;
+    async fn delete_server(&self, server_id: i64, user_id: i64) -> FluxyResult<()>;
+    async fn delete_admin(&self, server_id: i64, user_id: i64, user_to_remove: i64) -> FluxyResult<()>;
+    async fn delete_member(&self, server_id: i64, user_id: i64, user_to_remove: i64) -> FluxyResult<()>;
+    async fn delete_invite(&self, link: &str, user_id: i64) -> FluxyResult<()>;
+    async fn delete_role(&self, role_id: i64, user_id: i64) -> FluxyResult<()>;
+    async fn remove_role_from_member(&self, role_id: i64, user_to_remove: i64, user_id: i64) -> FluxyResult<()>;
+    async fn delete_channel_overwrite(&self, channel_id: i64, kind: OverwriteKind, target_id: i64, user_id: i64) -> FluxyResult<Channel>;
+    async fn delete_ban(&self, server_id: i64, user_to_unban: i64, user_id: i64) -> FluxyResult<()>;
+    async fn delete_timeout(&self, server_id: i64, user_to_unmute: i64, user_id: i64) -> FluxyResult<()>;
+
+    // maintenance
+    /// supprime ce qui reste d'un serveur supprimé (rôles, liens, bannissements, timeouts, audit, channels) et les messages sans channel
+    /// (voir db_mongo_delete::repair_orphans)
+    async fn repair_orphans(&self) -> io::Result<RepairReport>;
+    /// lève les bannissements expirés à la date `now` (voir db_mongo_delete::delete_expired_bans)
+    async fn delete_expired_bans(&self, now: &str) -> io::Result<u64>;
+}
+
+/// MongoStore :
+///     client partagé, nom de la base
+/// implémentation MongoDB du ChatStore. le client est créé une seule fois au démarrage (db_mongo_connection::create_client)
+/// et son pool de connexions est réutilisé par toutes les requêtes et toutes les sessions WebSocket
+pub struct MongoStore {
+    client: Client,
+    db_name: String,
+}
+
+impl MongoStore {
+    pub fn new(client: Client, db_name: &str) -> Self {
+        MongoStore { client, db_name: db_name.to_string() }
+    }
+
+    /// ensure_indexes :
+    /// voir db_mongo_connection::ensure_indexes
+    pub async fn ensure_indexes(&self) -> io::Result<()> {
+        db_mongo_connection::ensure_indexes(&self.client, &self.db_name).await
+    }
+}
+
+#[async_trait]
+impl ChatStore for MongoStore {
+    async fn ping(&self) -> io::Result<()> {
+        db_mongo_connection::ping(&self.client, &self.db_name).await
+    }
+
+    async fn count_documents(&self, collection: &str) -> io::Result<u64> {
+        db_mongo_getter::count_documents(&self.client, &self.db_name, collection).await
+    }
+
+    async fn get_server(&self, server_id: &i64) -> io::Result<Option<Server>> {
+        db_mongo_getter::get_server(&self.client, &self.db_name, server_id).await
+    }
+
+    async fn get_servers_by_owner(&self, owner_id: &i64) -> io::Result<Vec<Server>> {
+        db_mongo_getter::get_servers_by_owner(&self.client, &self.db_name, owner_id).await
+    }
+
+    async fn get_servers_by_member(&self, user_id: &i64) -> io::Result<Vec<Server>> {
+        db_mongo_getter::get_servers_by_member(&self.client, &self.db_name, user_id).await
+    }
+
+    async fn get_channels_of_server(&self, server_id: &i64) -> io::Result<Vec<Channel>> {
+        db_mongo_getter::get_channels_of_server(&self.client, &self.db_name, server_id).await
+    }
+
+    async fn get_messages_of_channel(&self, channel_id: &i64) -> io::Result<Vec<Message>> {
+        db_mongo_getter::get_messages_of_channel(&self.client, &self.db_name, channel_id).await
+    }
+
+    async fn get_messages_page(&self, channel_id: &i64, before: Option<i64>, after: Option<i64>, limit: i64) -> io::Result<Vec<Message>> {
+        db_mongo_getter::get_messages_page(&self.client, &self.db_name, channel_id, before, after, limit).await
+    }
+
+    async fn get_messages_of_channel_after(&self, channel_id: &i64, last_seen_id: &i64, limit: i64) -> io::Result<Vec<Message>> {
+        db_mongo_getter::get_messages_of_channel_after(&self.client, &self.db_name, channel_id, last_seen_id, limit).await
+    }
+
+    async fn get_message_by_id(&self, message_id: &i64) -> io::Result<Option<Message>> {
+        db_mongo_getter::get_message_by_id(&self.client, &self.db_name, message_id).await
+    }
+
+    async fn get_message_revisions(&self, message_id: &i64) -> io::Result<Vec<MessageRevision>> {
+        db_mongo_getter::get_message_revisions(&self.client, &self.db_name, message_id).await
+    }
+
+    async fn get_channel_by_id(&self, channel_id: &i64) -> io::Result<Option<Channel>> {
+        db_mongo_getter::get_channel_by_id(&self.client, &self.db_name, channel_id).await
+    }
+
+    async fn get_server_id_by_message_id(&self, message_id: &i64) -> io::Result<i64> {
+        db_mongo_getter::get_server_id_by_message_id(&self.client, &self.db_name, message_id).await
+    }
+
+    async fn get_last_id(&self, collection: &str) -> io::Result<i64> {
+        db_mongo_getter::get_last_id(&self.client, &self.db_name, collection).await
+    }
+
+    async fn is_owner(&self, server_id: &i64, user_id: &i64) -> io::Result<bool> {
+        db_mongo_getter::is_owner(&self.client, &self.db_name, server_id, user_id).await
+    }
+
+    async fn is_admin(&self, server_id: &i64, user_id: &i64) -> io::Result<bool> {
+        db_mongo_getter::is_admin(&self.client, &self.db_name, server_id, user_id).await
+    }
+
+    async fn is_member(&self, server_id: &i64, user_id: &i64) -> io::Result<bool> {
+        db_mongo_getter::is_member(&self.client, &self.db_name, server_id, user_id).await
+    }
+
+    async fn is_channel_of_server(&self, server_id: i64, channel_id: i64) -> io::Result<bool> {
+        db_mongo_getter::is_channel_of_server(&self.client, &self.db_name, server_id, channel_id).await
+    }
+
+    async fn verify_link_exist(&self, link_code: &str) -> io::Result<bool> {
+        db_mongo_getter::verify_link_exist(&self.client, &self.db_name, link_code).await
+    }
+
+    async fn get_roles_of_server(&self, server_id: &i64) -> io::Result<Vec<Role>> {
+        db_mongo_getter::get_roles_of_server(&self.client, &self.db_name, server_id).await
+    }
+
+    async fn get_role_by_id(&self, role_id: &i64) -> io::Result<Option<Role>> {
+        db_mongo_getter::get_role_by_id(&self.client, &self.db_name, role_id).await
+    }
+
+    async fn has_permission(&self, server_id: &i64, user_id: &i64, permission: Permissions) -> io::Result<bool> {
+        db_mongo_getter::has_permission(&self.client, &self.db_name, server_id, user_id, permission).await
+    }
+
+    async fn get_channel_permissions(&self, channel_id: &i64, user_id: &i64) -> io::Result<Permissions> {
+        db_mongo_getter::get_channel_permissions(&self.client, &self.db_name, channel_id, user_id).await
+    }
+
+    async fn get_ban(&self, server_id: &i64, user_id: &i64) -> io::Result<Option<Ban>> {
+        db_mongo_getter::get_ban(&self.client, &self.db_name, server_id, user_id).await
+    }
+
+    async fn get_bans_of_server(&self, server_id: &i64) -> io::Result<Vec<Ban>> {
+        db_mongo_getter::get_bans_of_server(&self.client, &self.db_name, server_id).await
+    }
+
+    async fn get_timeout(&self, server_id: &i64, user_id: &i64) -> io::Result<Option<MemberTimeout>> {
+        db_mongo_getter::get_timeout(&self.client, &self.db_name, server_id, user_id).await
+    }
+
+    async fn get_active_timeouts(&self, now: &str) -> io::Result<Vec<MemberTimeout>> {
+        db_mongo_getter::get_active_timeouts(&self.client, &self.db_name, now).await
+    }
+
+    async fn get_invite(&self, link_code: &str) -> io::Result<Option<Invite>> {
+        db_mongo_getter::get_invite(&self.client, &self.db_name, link_code).await
+    }
+
+    async fn get_invites_of_server(&self, server_id: &i64) -> io::Result<Vec<Invite>> {
+        db_mongo_getter::get_invites_of_server(&self.client, &self.db_name, server_id).await
+    }
+
+    async fn get_audit_log(&self, server_id: &i64, filter: &AuditLogFilter) -> io::Result<Vec<AuditEntry>> {
+        db_mongo_getter::get_audit_log(&self.client, &self.db_name, server_id, filter).await
+    }
+
+    async fn set_server(&self, owner_id: i64, name: &str, image: Option<String>) -> FluxyResult<()> {
+        db_mongo_setter::set_server(&self.client, &self.db_name, owner_id, name, image).await
+    }
+
+    async fn set_channel(&self, server_id: i64, name: &str, user_id: i64) -> FluxyResult<()> {
+        db_mongo_setter::set_channel(&self.client, &self.db_name, server_id, name, user_id).await
+    }
+
+    async fn set_message(&self, server_id: i64, channel_id: i64, message: &str, user_id: i64) -> FluxyResult<Message> {
+        db_mongo_setter::set_message(&self.client, &self.db_name, server_id, channel_id, message, user_id).await
+    }
+
+    async fn add_member_to_server(&self, server_id: i64, user_id: i64) -> FluxyResult<()> {
+        db_mongo_setter::add_member_to_server(&self.client, &self.db_name, server_id, user_id).await
+    }
+
+    async fn add_admin_to_server(&self, server_id: i64, user_id: i64, user_to_add: i64) -> FluxyResult<()> {
+        db_mongo_setter::add_admin_to_server(&self.client, &self.db_name, server_id, user_id, user_to_add).await
+    }
+
+    async fn switch_owner(&self, server_id: i64, user_id: i64, user_to_replace: i64) -> FluxyResult<()> {
+        db_mongo_setter::switch_owner(&self.client, &self.db_name, server_id, user_id, user_to_replace).await
+    }
+
+    async fn create_invite(&self, server_id: i64, user_id: i64, expires_at: Option<String>, max_uses: Option<i64>) -> FluxyResult<Invite> {
+        db_mongo_setter::create_invite(&self.client, &self.db_name, server_id, user_id, expires_at, max_uses).await
+    }
+
+    async fn join_by_link(&self, link: &str, user_id: i64) -> FluxyResult<()> {
+        db_mongo_setter::join_by_link(&self.client, &self.db_name, link, user_id).await
+    }
+
+    async fn set_role(&self, server_id: i64, name: &str, permissions: Permissions, user_id: i64) -> FluxyResult<Role> {
+        db_mongo_setter::set_role(&self.client, &self.db_name, server_id, name, permissions, user_id).await
+    }
+
+    async fn add_role_to_member(&self, role_id: i64, user_to_add: i64, user_id: i64) -> FluxyResult<()> {
+        db_mongo_setter::add_role_to_member(&self.client, &self.db_name, role_id, user_to_add, user_id).await
+    }
+
+    async fn set_ban(&self, server_id: i64, user_to_ban: i64, reason: &str, expires_at: Option<String>, user_id: i64) -> FluxyResult<Ban> {
+        db_mongo_setter::set_ban(&self.client, &self.db_name, server_id, user_to_ban, reason, expires_at, user_id).await
+    }
+
+    async fn set_timeout(&self, server_id: i64, user_to_mute: i64, until: String, user_id: i64) -> FluxyResult<MemberTimeout> {
+        db_mongo_setter::set_timeout(&self.client, &self.db_name, server_id, user_to_mute, until, user_id).await
+    }
+
+    async fn set_audit_entry(&self, entry: NewAuditEntry) -> FluxyResult<AuditEntry> {
+        db_mongo_setter::set_audit_entry(&self.client, &self.db_name, entry).await
+    }
+
+    async fn update_message(&self, message_id: i64, message: &str, user_id: i64) -> FluxyResult<Message> {
+        db_mongo_update::update_message(&self.client, &self.db_name, message_id, message, user_id).await
+    }
+
+    async fn update_channel_name(&self, channel_id: i64, name: &str, user_id: i64) -> FluxyResult<()> {
+        db_mongo_update::update_channel_name(&self.client, &self.db_name, channel_id, name, user_id).await
+    }
+
+    async fn update_server_name(&self, server_id: i64, name: &str, user_id: i64) -> FluxyResult<()> {
+        db_mongo_update::update_server_name(&self.client, &self.db_name, server_id, name, user_id).await
+    }
+
+    async fn update_server(&self, server_id: i64, name: Option<&str>, image: Option<&str>, user_id: i64) -> FluxyResult<()> {
+        db_mongo_update::update_server(&self.client, &self.db_name, server_id, name, image, user_id).await
+    }
+
+    async fn update_member_role(&self, server_id: i64, user_id: i64, role: &str, actor_id: i64) -> FluxyResult<()> {
+        db_mongo_update::update_member_role(&self.client, &self.db_name, server_id, user_id, role, actor_id).await
+    }
+
+    async fn update_role(&self, role_id: i64, name: Option<&str>, permissions: Option<Permissions>, position: Option<i64>, user_id: i64) -> FluxyResult<Role> {
+        db_mongo_update::update_role(&self.client, &self.db_name, role_id, name, permissions, position, user_id).await
+    }
+
+    async fn update_channel_privacy(&self, channel_id: i64, private: bool, user_id: i64) -> FluxyResult<Channel> {
+        db_mongo_update::update_channel_privacy(&self.client, &self.db_name, channel_id, private, user_id).await
+    }
+
+    async fn update_channel_overwrite(&self, channel_id: i64, overwrite: PermissionOverwrite, user_id: i64) -> FluxyResult<Channel> {
+        db_mongo_update::update_channel_overwrite(&self.client, &self.db_name, channel_id, overwrite, user_id).await
+    }
+
+    async fn delete_message(&self, message_id: i64, user_id: i64) -> FluxyResult<Message> {
+        db_mongo_delete::delete_message(&self.client, &self.db_name, message_id, user_id).await
+    }
+
+    async fn delete_channel(&self, channel_id: i64, user_id: i64) -> FluxyResult<()> {
+        db_mongo_delete::delete_channel(&self.client, &self.db_name, channel_id, user_id).await
+    }
+
+    async fn delete_server(&self, server_id: i64, user_id: i64) -> FluxyResult<()> {
+        db_mongo_delete::delete_server(&self.client, &self.db_name, server_id, user_id).await
+    }
+
+    async fn delete_admin(&self, server_id: i64, user_id: i64, user_to_remove: i64) -> FluxyResult<()> {
+        db_mongo_delete::delete_admin(&self.client, &self.db_name, server_id, user_id, user_to_remove).await
+    }
+
+    async fn delete_member(&self, server_id: i64, user_id: i64, user_to_remove: i64) -> FluxyResult<()> {
+        db_mongo_delete::delete_member(&self.client, &self.db_name, server_id, user_id, user_to_remove).await
+    }
+
+    async fn delete_invite(&self, link: &str, user_id: i64) -> FluxyResult<()> {
+        db_mongo_delete::delete_invite(&self.client, &self.db_name, link, user_id).await
+    }
+
+    async fn delete_role(&self, role_id: i64, user_id: i64) -> FluxyResult<()> {
+        db_mongo_delete::delete_role(&self.client, &self.db_name, role_id, user_id).await
+    }
+
+    async fn remove_role_from_member(&self, role_id: i64, user_to_remove: i64, user_id: i64) -> FluxyResult<()> {
+        db_mongo_delete::remove_role_from_member(&self.client, &self.db_name, role_id, user_to_remove, user_id).await
+    }
+
+    async fn delete_channel_overwrite(&self, channel_id: i64, kind: OverwriteKind, target_id: i64, user_id: i64) -> FluxyResult<Channel> {
+        db_mongo_delete::delete_channel_overwrite(&self.client, &self.db_name, channel_id, kind, target_id, user_id).await
+    }
+
+    async fn delete_ban(&self, server_id: i64, user_to_unban: i64, user_id: i64) -> FluxyResult<()> {
+        db_mongo_delete::delete_ban(&self.client, &self.db_name, server_id, user_to_unban, user_id).await
+    }
+
+    async fn delete_timeout(&self, server_id: i64, user_to_unmute: i64, user_id: i64) -> FluxyResult<()> {
+        db_mongo_delete::delete_timeout(&self.client, &self.db_name, server_id, user_to_unmute, user_id).await
+    }
+
+    async fn repair_orphans(&self) -> io::Result<RepairReport> {
+        db_mongo_delete::repair_orphans(&self.client, &self.db_name).await
+    }
+
+    async fn delete_expired_bans(&self, now: &str) -> io::Result<u64> {
+        db_mongo_delete::delete_expired_bans(&self.client, &self.db_name, now).await
+    }
+}
@@ -0,0 +1,116 @@
+//! error.rs :
+//!     - FluxyError :
+//!     erreur renvoyée par les setters, updates et deletes du ChatStore (voir db_store.rs). chaque variante correspond
+//!     à un statut HTTP et à un code stable (champ "code" du JSON) que le front peut tester sans lire le message.
+//!         NotFound -> 404 "not_found"
+//!         Forbidden -> 403 "forbidden"
+//!         Conflict -> 409 "conflict"
+//!         Validation -> 400 "validation"
+//!         TimedOut -> 403 "timed_out" (le JSON porte aussi "remaining_secs", les secondes de silence restantes)
+//!         Storage -> 500 "storage" (le détail n'est écrit que dans les logs)
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use std::{fmt, io};
+
+/// FluxyError :
+/// le message est en français et destiné à l'utilisateur, sauf pour Storage
+#[derive(Debug, Clone, PartialEq)]
+pub enum FluxyError {
+    NotFound(String),   // serveur, channel, message ou lien inexistant
+    Forbidden(String),  // l'utilisateur n'a pas la permission
+    Conflict(String),   // l'opération contredit l'état actuel (doublon, possesseur qui part...)
+    Validation(String), // données envoyées invalides
+    TimedOut { message: String, remaining_secs: u64 }, // membre réduit au silence sur le serveur
+    Storage(String),    // erreur de la base de données
+}
+
+pub type FluxyResult<T> = Result<T, FluxyError>;
+
+impl FluxyError {
+    /// code stable renvoyé dans le champ "code" des réponses d'erreur
+    pub fn code(&self) -> &'static str {
+        match self {
+            FluxyError::NotFound(_) => "not_found",
+            FluxyError::Forbidden(_) => "forbidden",
+            FluxyError::Conflict(_) => "conflict",
+            FluxyError::Validation(_) => "validation",
+            FluxyError::TimedOut { .. } => "timed_out",
+            FluxyError::Storage(_) => "storage",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            FluxyError::NotFound(message)
+            | FluxyError::Forbidden(message)
+            | FluxyError::Conflict(message)
+            | FluxyError::Validation(message)
+            | FluxyError::TimedOut { message, .. }
+            | FluxyError::Storage(message) => message,
+        }
+    }
+
+    /// secondes de silence restantes pour une erreur TimedOut
+    pub fn remaining_secs(&self) -> Option<u64> {
+        match self {
+            FluxyError::TimedOut { remaining_secs, .. } => Some(*remaining_secs),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FluxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for FluxyError {}
+
+// Les getters renvoient des io::Error : une erreur de lecture devient une erreur de stockage
+impl From<io::Error> for FluxyError {
+    fn from(e: io::Error) -> Self {
+        FluxyError::Storage(e.to_string())
+    }
+}
+
+// Pour les appelants qui travaillent en io::Result (tests, outils) : la variante est gardée dans le kind
+impl From<FluxyError> for io::Error {
+    fn from(e: FluxyError) -> Self {
+        let kind = match e {
+            FluxyError::NotFound(_) => io::ErrorKind::NotFound,
+            FluxyError::Forbidden(_) | FluxyError::TimedOut { .. } => io::ErrorKind::PermissionDenied,
+            FluxyError::Conflict(_) => io::ErrorKind::AlreadyExists,
+            FluxyError::Validation(_) => io::ErrorKind::InvalidInput,
+            FluxyError::Storage(_) => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+impl ResponseError for FluxyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            FluxyError::NotFound(_) => StatusCode::NOT_FOUND,
+            FluxyError::Forbidden(_) | FluxyError::TimedOut { .. } => StatusCode::FORBIDDEN,
+            FluxyError::Conflict(_) => StatusCode::CONFLICT,
+            FluxyError::Validation(_) => StatusCode::BAD_REQUEST,
+            FluxyError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let message = match self {
+            FluxyError::Storage(_) => "Erreur interne de la base de données",
+            _ => self.message(),
+        };
+        let mut body = serde_json::json!({
+            "error": message,
+            "code": self.code()
+        });
+        if let Some(remaining_secs) = self.remaining_secs() {
+            body["remaining_secs"] = remaining_secs.into();
+        }
+        HttpResponse::build(self.status_code()).json(body)
+    }
+}
@@ -23,9 +23,24 @@ fn extract_id(json: &serde_json::Value) -> Option<String> {
 
 /// Récupère tous les utilisateurs depuis Supabase
 pub async fn get_all_users(config: &AppConfig) -> Result<Vec<User>, String> {
+    get_users(config, "").await
+}
+
+/// Récupère uniquement les utilisateurs dont l'id est donné (par exemple les auteurs d'un lot de messages)
+pub async fn get_users_by_ids(config: &AppConfig, ids: &[i64]) -> Result<Vec<User>, String> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    get_users(config, &format!("&id=in.({})", ids.join(","))).await
+}
+
+/// Helper pour lire la table user, avec un filtre PostgREST optionnel (commençant par &)
+async fn get_users(config: &AppConfig, filter: &str) -> Result<Vec<User>, String> {
     let url = format!(
-        "{}/rest/v1/user?select=id,auth_id,username,email,avatar",
-        config.supabase_url.trim_end_matches('/')
+        "{}/rest/v1/user?select=id,auth_id,username,email,avatar{}",
+        config.supabase_url.trim_end_matches('/'),
+        filter
     );
 
     let client = reqwest::Client::new();
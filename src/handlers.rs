@@ -1,31 +1,45 @@
 use actix_session::Session;
-use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
 use actix_web_actors::ws;
 use actix::Addr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::models::{
     LoginForm, RegisterForm, ForgotForm, ResetPasswordForm, CreateServerForm, CreateChannelForm,
     UpdateChannelForm, DeleteChannelForm, ServerChannelsQuery, ChannelMessagesQuery, JoinServerForm,
     ServerMembersQuery, DeleteServerForm, UpdateServerForm, LeaveServerForm, UpdateMemberRoleForm,
-    KickMemberForm, SwitchOwnerForm, DeleteMessageForm, CreateInviteLinkForm, JoinByLinkForm, AppConfig,
+    KickMemberForm, SwitchOwnerForm, UpdateMessageForm, DeleteMessageForm, MessageRevisionsQuery, CreateInviteLinkForm, JoinByLinkForm, RevokeInviteForm, ServerInvitesQuery, AppConfig,
+    ServerRolesQuery, CreateRoleForm, UpdateRoleForm, DeleteRoleForm, MemberRoleForm,
+    ChannelPrivacyForm, ChannelOverwriteForm, DeleteChannelOverwriteForm, Channel, Permissions,
+    BanMemberForm, UnbanMemberForm, ServerBansQuery, TimeoutMemberForm, RemoveTimeoutForm,
+    AuditAction, AuditLogFilter, AuditLogQuery, NewAuditEntry, Server, format_date,
 };
 use crate::chat::ChatSession;
-use crate::models::{ChatServer, GetConnectedUsers, LeaveChat, UserConnected};
+use crate::services;
+use crate::models::{
+    ChatEvent, ChatMessage, ChatServer, CloseChannel, EndTimeout, GetPresences, LeaveChat, MessageWithUsername, PresenceStatus, RevokeChannelAccess, RevokeReason,
+    RevokeServerAccess, StartTimeout, UserConnected,
+};
 use crate::supabase;
 use crate::getters;
-use crate::db_mongo_setter;
-use crate::db_mongo_delete;
-use crate::db_mongo_update;
-use crate::db_mongo_connection;
-use crate::db_mongo_getter;
-use std::env;
+use crate::db_store::ChatStore;
+use crate::error::FluxyError;
+use chrono::{TimeDelta, Utc};
 use serde::Deserialize;
 
+const DEFAULT_MESSAGES_PAGE: i64 = 50;
+const MAX_MESSAGES_PAGE: i64 = 100;
+const DEFAULT_AUDIT_PAGE: i64 = 50;
+const MAX_AUDIT_PAGE: i64 = 100;
+
+/// Abonnement initial optionnel du WebSocket ; les autres channels passent par `subscribe`.
 #[derive(Deserialize)]
 pub struct WsChatQuery {
-    pub server_id: i64,
-    pub channel_id: i64,
+    pub server_id: Option<i64>,
+    pub channel_id: Option<i64>,
+    /// Dernier message reçu sur ce channel : les messages suivants sont rejoués à la reconnexion.
+    pub last_seen_message_id: Option<i64>,
 }
 
 fn get_user_id_from_session(user_response: &crate::models::UserResponse) -> Result<i64, HttpResponse> {
@@ -45,23 +59,6 @@ fn get_user_id_from_session(user_response: &crate::models::UserResponse) -> Resu
     })
 }
 
-async fn get_mongo_client_and_db() -> Result<(mongodb::Client, String), HttpResponse> {
-    let client = db_mongo_connection::get_client().await.map_err(|e| {
-        eprintln!("Erreur de connexion MongoDB: {}", e);
-        HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": "Erreur de connexion à la base de données"
-        }))
-    })?;
-    
-    let db_name = env::var("MONGO_DATA_BASE_NAME").map_err(|_| {
-        HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": "Configuration de la base de données manquante"
-        }))
-    })?;
-    
-    Ok((client, db_name))
-}
-
 // Helper pour marquer un utilisateur comme connecté s'il a une session valide
 fn mark_user_connected_if_session_valid(
     user_response: &crate::models::UserResponse,
@@ -116,7 +113,7 @@ pub async fn api_logout(
         if let Ok(user_id) = user_id_str.parse::<i64>() {
             println!("[LOGOUT] Utilisateur {} déconnecté", user_id);
             let addr = chat_data.lock().unwrap().clone();
-            addr.do_send(LeaveChat { user_id, addr: None });
+            addr.do_send(LeaveChat { user_id, session_id: None });
         }
     }
     
@@ -330,6 +327,7 @@ pub async fn chat_ws(
     session: Session,
     config: web::Data<AppConfig>,
     query: web::Query<WsChatQuery>,
+    store: web::Data<dyn ChatStore>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let maybe_email: Option<String> = session.get("user_email").ok().flatten();
     
@@ -341,10 +339,50 @@ pub async fn chat_ws(
         Ok(id) => id,
         Err(resp) => return Ok(resp),
     };
-    let server_id = query.server_id;
-    let channel_id = query.channel_id;
+    let initial_subscription = match (query.server_id, query.channel_id) {
+        (Some(server_id), Some(channel_id)) => Some((server_id, channel_id, query.last_seen_message_id)),
+        (None, None) => None,
+        _ => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "server_id et channel_id doivent être fournis ensemble"
+            })));
+        }
+    };
+
+    // Refuser l'ouverture du WebSocket sur un channel auquel l'utilisateur n'a pas accès
+    if let Some((server_id, channel_id, _)) = initial_subscription {
+        match services::can_access_channel(store.get_ref(), user_id, server_id, channel_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return Ok(HttpResponse::Forbidden().json(serde_json::json!({
+                    "error": "Vous n'êtes pas membre de ce serveur ou le channel n'existe pas"
+                })));
+            }
+            Err(e) => {
+                eprintln!("Erreur lors de la vérification de l'accès au channel: {}", e);
+                return Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": "Erreur de connexion à la base de données"
+                })));
+            }
+        }
+    }
+
+    // Serveurs de l'utilisateur, pour pousser sa présence aux co-membres
+    let server_ids = services::member_server_ids(store.get_ref(), user_id).await.unwrap_or_else(|e| {
+        eprintln!("Erreur lors de la récupération des serveurs de l'utilisateur: {}", e);
+        Default::default()
+    });
+
     let server_addr = data.lock().unwrap().clone();
-    let chat_session = ChatSession { name, server: server_addr, user_id, server_id, channel_id };
+    let chat_session = ChatSession::new(
+        name,
+        server_addr,
+        user_id,
+        initial_subscription,
+        server_ids,
+        config.get_ref().clone(),
+        store.into_inner(),
+    );
 
     ws::start(chat_session, &req, stream)
 }
@@ -353,6 +391,7 @@ pub async fn create_server(
     form: web::Json<CreateServerForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let owner_id = match get_user_id_from_session(&user_response) {
@@ -360,14 +399,7 @@ pub async fn create_server(
         Err(resp) => return resp,
     };
     
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
-    
-    match db_mongo_setter::set_server(
-        &client,
-        &db_name,
+    match store.set_server(
         owner_id,
         &form.name,
         form.image.clone(),
@@ -380,9 +412,7 @@ pub async fn create_server(
         })),
         Err(e) => {
             eprintln!("Erreur lors de la création du serveur: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la création du serveur"
-            }))
+            e.error_response()
         }
     }
 }
@@ -390,6 +420,7 @@ pub async fn create_server(
 pub async fn has_servers(
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -402,18 +433,8 @@ pub async fn has_servers(
         }
     };
     
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(_) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur de connexion à la base de données",
-                "has_servers": false
-            }));
-        }
-    };
-    
     // Utiliser la même logique que get_user_servers : propriétaire, membre ou admin
-    match db_mongo_getter::get_servers_by_member(&client, &db_name, &user_id).await {
+    match store.get_servers_by_member(&user_id).await {
         Ok(servers) => HttpResponse::Ok().json(serde_json::json!({
             "has_servers": !servers.is_empty(),
             "count": servers.len()
@@ -432,6 +453,7 @@ pub async fn get_user_servers(
     session: Session,
     config: web::Data<AppConfig>,
     chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     
@@ -448,65 +470,24 @@ pub async fn get_user_servers(
         }
     };
     
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(_) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur de connexion à la base de données",
-                "servers": []
-            }));
-        }
-    };
-    
     // Récupérer tous les serveurs où l'utilisateur est owner / admin / membre
-    match db_mongo_getter::get_servers_by_member(&client, &db_name, &user_id).await {
+    match store.get_servers_by_member(&user_id).await {
         Ok(servers) => {
-            let servers_json: Vec<serde_json::Value> = servers
-                .into_iter()
-                .map(|doc| {
-                    let mut json_obj = serde_json::Map::new();
-                    if let Some(id) = doc.get("id").and_then(|v| v.as_i64()) {
-                        json_obj.insert("id".to_string(), serde_json::json!(id));
-                    }
-                    if let Some(name) = doc.get("name").and_then(|v| v.as_str()) {
-                        json_obj.insert("name".to_string(), serde_json::json!(name));
-                    }
-                    // Récupérer l'image si elle existe, sinon utiliser le logo par défaut
-                    if let Some(image) = doc.get("image").and_then(|v| v.as_str()) {
-                        json_obj.insert("image".to_string(), serde_json::json!(image));
-                    } else {
-                        json_obj.insert("image".to_string(), serde_json::json!("/logo_fluxy.png"));
-                    }
-
+            let mut servers_json: Vec<serde_json::Value> = Vec::with_capacity(servers.len());
+            for server in servers {
+                // Permissions effectives, pour afficher les actions disponibles
+                let roles = store.get_roles_of_server(&server.id).await.unwrap_or_default();
+                servers_json.push(serde_json::json!({
+                    "id": server.id,
+                    "name": server.name,
+                    // Logo par défaut si le serveur n'a pas d'image
+                    "image": server.image.clone().unwrap_or_else(|| "/logo_fluxy.png".to_string()),
                     // Rôles côté frontend
-                    let mut is_owner = false;
-                    let mut is_admin = false;
-
-                    if let Some(owner) = doc.get("owner_id").and_then(|v| v.as_i64()) {
-                        if owner == user_id {
-                            is_owner = true;
-                        }
-                    }
-
-                    if let Some(admin_field) = doc.get("admin_id") {
-                        // admin_id peut être un nombre ou un tableau
-                        if let Some(admin_single) = admin_field.as_i64() {
-                            if admin_single == user_id {
-                                is_admin = true;
-                            }
-                        } else if let Some(admin_array) = admin_field.as_array() {
-                            if admin_array.iter().any(|v| v.as_i64() == Some(user_id)) {
-                                is_admin = true;
-                            }
-                        }
-                    }
-
-                    json_obj.insert("is_owner".to_string(), serde_json::json!(is_owner));
-                    json_obj.insert("is_admin".to_string(), serde_json::json!(is_admin));
-
-                    serde_json::Value::Object(json_obj)
-                })
-                .collect();
+                    "is_owner": server.owner_id == user_id,
+                    "is_admin": server.admin_id.contains(&user_id),
+                    "permissions": server.permissions_of(&roles, user_id),
+                }));
+            }
             
             HttpResponse::Ok().json(serde_json::json!({"servers": servers_json}))
         }
@@ -524,6 +505,7 @@ pub async fn create_channel(
     form: web::Json<CreateChannelForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -531,20 +513,14 @@ pub async fn create_channel(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
 
-    match db_mongo_setter::set_channel(&client, &db_name, form.server_id, &form.name, user_id).await {
+    match store.set_channel(form.server_id, &form.name, user_id).await {
         Ok(_) => HttpResponse::Ok().json(serde_json::json!({
             "success": true
         })),
         Err(e) => {
             eprintln!("Erreur lors de la création du channel: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la création du channel"
-            }))
+            e.error_response()
         }
     }
 }
@@ -553,6 +529,7 @@ pub async fn update_channel(
     form: web::Json<UpdateChannelForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -560,14 +537,8 @@ pub async fn update_channel(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
 
-    match db_mongo_update::update_channel_name(
-        &client,
-        &db_name,
+    match store.update_channel_name(
         form.channel_id,
         &form.name,
         user_id,
@@ -579,9 +550,7 @@ pub async fn update_channel(
         })),
         Err(e) => {
             eprintln!("Erreur lors de la mise à jour du channel: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la mise à jour du channel"
-            }))
+            e.error_response()
         }
     }
 }
@@ -590,6 +559,8 @@ pub async fn delete_channel(
     form: web::Json<DeleteChannelForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -597,67 +568,141 @@ pub async fn delete_channel(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
+
+    // Le channel tel qu'il était : son serveur (pas celui envoyé par le client) pour la diffusion, et le journal d'audit
+    let channel = match store.get_channel_by_id(&form.channel_id).await {
+        Ok(Some(channel)) => channel,
+        Ok(None) => return FluxyError::NotFound("Channel introuvable".to_string()).error_response(),
+        Err(e) => {
+            eprintln!("Erreur lors de la récupération du channel: {}", e);
+            return FluxyError::from(e).error_response();
+        }
     };
 
-    match db_mongo_delete::delete_channel(&client, &db_name, form.channel_id, user_id).await {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
-            "success": true
-        })),
+    match store.delete_channel(form.channel_id, user_id).await {
+        Ok(_) => {
+            let entry = NewAuditEntry {
+                server_id: channel.server_id,
+                actor_id: user_id,
+                action: AuditAction::ChannelDelete,
+                target_id: channel.id,
+                before: serde_json::to_value(&channel).ok(),
+                after: None,
+            };
+            record_audit(store.get_ref(), entry).await;
+            // Désabonner les sockets encore ouvertes sur ce channel
+            let addr = chat_data.lock().unwrap().clone();
+            addr.do_send(CloseChannel { server_id: channel.server_id, channel_id: channel.id });
+            HttpResponse::Ok().json(serde_json::json!({
+                "success": true
+            }))
+        }
         Err(e) => {
             eprintln!("Erreur lors de la suppression du channel: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la suppression du channel"
-            }))
+            e.error_response()
         }
     }
 }
 
-pub async fn get_server_channels(
-    query: web::Query<ServerChannelsQuery>,
+/// Rend un channel privé ou public ; les membres qui ne le voient plus sont désabonnés.
+pub async fn update_channel_privacy(
+    form: web::Json<ChannelPrivacyForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
-    let _user_id = match get_user_id_from_session(&user_response) {
+    let user_id = match get_user_id_from_session(&user_response) {
         Ok(id) => id,
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
+    match store.update_channel_privacy(form.channel_id, form.private, user_id).await {
+        Ok(channel) => {
+            revoke_lost_channel_access(store.get_ref(), &chat_data, &channel).await;
+            HttpResponse::Ok().json(serde_json::json!({ "success": true, "channel": channel }))
+        }
+        Err(e) => {
+            eprintln!("Erreur lors du changement de visibilité du channel: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+/// Crée ou remplace l'exception d'un rôle ou d'un membre sur un channel.
+pub async fn update_channel_overwrite(
+    form: web::Json<ChannelOverwriteForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match store.update_channel_overwrite(form.channel_id, form.overwrite, user_id).await {
+        Ok(channel) => {
+            revoke_lost_channel_access(store.get_ref(), &chat_data, &channel).await;
+            HttpResponse::Ok().json(serde_json::json!({ "success": true, "channel": channel }))
+        }
+        Err(e) => {
+            eprintln!("Erreur lors de la mise à jour de l'exception du channel: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+/// Retire l'exception d'un rôle ou d'un membre sur un channel.
+pub async fn delete_channel_overwrite(
+    form: web::Json<DeleteChannelOverwriteForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match store.delete_channel_overwrite(form.channel_id, form.kind, form.target_id, user_id).await {
+        Ok(channel) => {
+            revoke_lost_channel_access(store.get_ref(), &chat_data, &channel).await;
+            HttpResponse::Ok().json(serde_json::json!({ "success": true, "channel": channel }))
+        }
+        Err(e) => {
+            eprintln!("Erreur lors de la suppression de l'exception du channel: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+pub async fn get_server_channels(
+    query: web::Query<ServerChannelsQuery>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
         Err(resp) => return resp,
     };
 
-    match db_mongo_getter::get_channels_of_server(&client, &db_name, &query.server_id).await {
-        Ok(channels) => {
-            let channels_json: Vec<serde_json::Value> = channels
-                .into_iter()
-                .map(|doc| {
-                    let mut json_obj = serde_json::Map::new();
-                    if let Some(id) = doc.get("id").and_then(|v| v.as_i64()) {
-                        json_obj.insert("id".to_string(), serde_json::json!(id));
-                    }
-                    if let Some(name) = doc.get("name").and_then(|v| v.as_str()) {
-                        json_obj.insert("name".to_string(), serde_json::json!(name));
-                    }
-                    if let Some(position) = doc.get("position").and_then(|v| v.as_i64()) {
-                        json_obj.insert("position".to_string(), serde_json::json!(position));
-                    }
-                    serde_json::Value::Object(json_obj)
-                })
-                .collect();
 
-            HttpResponse::Ok().json(serde_json::json!({ "channels": channels_json }))
+    // Seuls les channels visibles par l'utilisateur (channels privés et exceptions compris)
+    match services::visible_channels(store.get_ref(), user_id, query.server_id).await {
+        Ok(channels) => {
+            HttpResponse::Ok().json(serde_json::json!({ "channels": channels }))
         }
         Err(e) => {
             eprintln!("Erreur lors de la récupération des channels: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la récupération des channels",
-                "channels": []
-            }))
+            e.error_response()
         }
     }
 }
@@ -666,17 +711,30 @@ pub async fn get_channel_messages(
     query: web::Query<ChannelMessagesQuery>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
-    let _user_id = match get_user_id_from_session(&user_response) {
+    let user_id = match get_user_id_from_session(&user_response) {
         Ok(id) => id,
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
+    match store.get_channel_permissions(&query.channel_id, &user_id).await {
+        Ok(permissions) if permissions.contains(Permissions::VIEW_CHANNEL) => {}
+        Ok(_) => {
+            return HttpResponse::Forbidden().json(serde_json::json!({
+                "error": "Vous n'avez pas accès à ce channel",
+                "messages": []
+            }));
+        }
+        Err(e) => {
+            eprintln!("Erreur lors de la vérification de l'accès au channel: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Erreur lors de la récupération des messages",
+                "messages": []
+            }));
+        }
+    }
 
     // Récupérer tous les utilisateurs pour pouvoir ajouter le username à chaque message
     let users_result = getters::get_all_users(&config).await;
@@ -689,32 +747,33 @@ pub async fn get_channel_messages(
         }
     }
 
-    match db_mongo_getter::get_messages_of_channel(&client, &db_name, &query.channel_id).await {
-        Ok(messages) => {
-            let msgs_json: Vec<serde_json::Value> = messages
+    // Un message de plus que demandé pour savoir s'il reste une page
+    let limit = query.limit.unwrap_or(DEFAULT_MESSAGES_PAGE).clamp(1, MAX_MESSAGES_PAGE);
+    let forward = query.after.is_some() && query.before.is_none();
+
+    match store.get_messages_page(&query.channel_id, query.before, query.after, limit + 1).await {
+        Ok(mut messages) => {
+            let has_more = messages.len() as i64 > limit;
+            if has_more {
+                if forward {
+                    messages.pop();
+                } else {
+                    messages.remove(0);
+                }
+            }
+            // Curseur à repasser tel quel dans `before` (ou `after` en avançant) pour la page suivante
+            let edge = if forward { messages.last() } else { messages.first() };
+            let next_cursor = if has_more { edge.map(|message| message.id) } else { None };
+
+            let msgs_json: Vec<MessageWithUsername> = messages
                 .into_iter()
-                .map(|doc| {
-                    let mut json_obj = serde_json::Map::new();
-                    if let Some(id) = doc.get("id").and_then(|v| v.as_i64()) {
-                        json_obj.insert("id".to_string(), serde_json::json!(id));
-                    }
-                    if let Some(content) = doc.get("message").and_then(|v| v.as_str()) {
-                        json_obj.insert("message".to_string(), serde_json::json!(content));
-                    }
-                    if let Some(user) = doc.get("user").and_then(|v| v.as_i64()) {
-                        json_obj.insert("user".to_string(), serde_json::json!(user));
-                        if let Some(username) = usernames_by_id.get(&user) {
-                            json_obj.insert("username".to_string(), serde_json::json!(username));
-                        }
-                    }
-                    if let Some(time) = doc.get("time").and_then(|v| v.as_str()) {
-                        json_obj.insert("time".to_string(), serde_json::json!(time));
-                    }
-                    serde_json::Value::Object(json_obj)
+                .map(|message| MessageWithUsername {
+                    username: usernames_by_id.get(&message.user).cloned(),
+                    message,
                 })
                 .collect();
 
-            HttpResponse::Ok().json(serde_json::json!({ "messages": msgs_json }))
+            HttpResponse::Ok().json(serde_json::json!({ "messages": msgs_json, "next_cursor": next_cursor }))
         }
         Err(e) => {
             eprintln!("Erreur lors de la récupération des messages: {}", e);
@@ -726,11 +785,13 @@ pub async fn get_channel_messages(
     }
 }
 
-/// Supprime un message (si l'utilisateur est l'auteur, un admin ou le fondateur du serveur).
-pub async fn delete_message(
-    form: web::Json<DeleteMessageForm>,
+/// Modifie un message (auteur uniquement) et diffuse `message.update` aux abonnés du channel.
+pub async fn update_message(
+    form: web::Json<UpdateMessageForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -738,27 +799,174 @@ pub async fn delete_message(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
+
+    match store.update_message(form.message_id, &form.message, user_id).await {
+        Ok(message) => {
+            let edited_at = message.edited_at.clone().unwrap_or_default();
+            broadcast_to_channel(store.get_ref(), &chat_data, message.channel_id, |server_id| ChatEvent::MessageUpdate {
+                id: message.id,
+                server_id,
+                channel_id: message.channel_id,
+                author_id: message.user,
+                content: message.message.clone(),
+                edited_at,
+            })
+            .await;
+            HttpResponse::Ok().json(serde_json::json!({ "success": true, "message": message }))
+        }
+        Err(e) => {
+            eprintln!("Erreur lors de la modification du message: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+/// Historique des modifications d'un message, réservé à ceux qui ont MANAGE_MESSAGES sur le serveur (admins et fondateur compris).
+pub async fn get_message_revisions(
+    query: web::Query<MessageRevisionsQuery>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
         Err(resp) => return resp,
     };
 
-    match db_mongo_delete::delete_message(&client, &db_name, form.message_id, user_id).await {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+    match services::message_history(store.get_ref(), user_id, query.message_id).await {
+        Ok((message, revisions)) => HttpResponse::Ok().json(serde_json::json!({
+            "message": message,
+            "revisions": revisions
+        })),
+        Err(e) => {
+            eprintln!("Erreur lors de la récupération de l'historique du message: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+/// Supprime un message (auteur, admin ou fondateur) et diffuse `message.delete` aux abonnés du channel.
+pub async fn delete_message(
+    form: web::Json<DeleteMessageForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+
+    match store.delete_message(form.message_id, user_id).await {
+        Ok(message) => {
+            if let Ok(Some(channel)) = store.get_channel_by_id(&message.channel_id).await {
+                let entry = NewAuditEntry {
+                    server_id: channel.server_id,
+                    actor_id: user_id,
+                    action: AuditAction::MessageDelete,
+                    target_id: message.id,
+                    before: serde_json::to_value(&message).ok(),
+                    after: None,
+                };
+                record_audit(store.get_ref(), entry).await;
+            }
+            broadcast_to_channel(store.get_ref(), &chat_data, message.channel_id, |server_id| ChatEvent::MessageDelete {
+                id: message.id,
+                server_id,
+                channel_id: message.channel_id,
+            })
+            .await;
+            HttpResponse::Ok().json(serde_json::json!({ "success": true }))
+        }
         Err(e) => {
             eprintln!("Erreur lors de la suppression du message: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la suppression du message"
-            }))
+            e.error_response()
+        }
+    }
+}
+
+/// Ajoute une entrée au journal d'audit après une action réussie ; un échec est journalisé sans annuler l'action.
+async fn record_audit(store: &dyn ChatStore, entry: NewAuditEntry) {
+    if let Err(e) = store.set_audit_entry(entry).await {
+        eprintln!("Erreur lors de l'écriture du journal d'audit: {}", e);
+    }
+}
+
+/// Nom et image du serveur, pour le journal d'audit.
+fn server_snapshot(server: &Server) -> serde_json::Value {
+    serde_json::json!({ "name": server.name, "image": server.image })
+}
+
+/// Rang d'un membre pour le journal d'audit : le rôle affiché par /api/server-members et les ids de ses rôles.
+/// None si l'utilisateur n'est pas membre du serveur.
+async fn member_snapshot(store: &dyn ChatStore, server_id: i64, user_id: i64) -> Option<serde_json::Value> {
+    let server = store.get_server(&server_id).await.ok().flatten()?;
+    if !server.member_id.contains(&user_id) {
+        return None;
+    }
+    let role = if server.owner_id == user_id {
+        "fondateur"
+    } else if server.admin_id.contains(&user_id) {
+        "admin"
+    } else {
+        "membre"
+    };
+    let roles: Vec<i64> = store
+        .get_roles_of_server(&server_id)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter(|custom_role| custom_role.member_id.contains(&user_id))
+        .map(|custom_role| custom_role.id)
+        .collect();
+    Some(serde_json::json!({ "role": role, "roles": roles }))
+}
+
+/// Désabonne les sessions des membres qui ne voient plus le channel (channel devenu privé, exception qui retire VIEW_CHANNEL).
+async fn revoke_lost_channel_access(
+    store: &dyn ChatStore,
+    chat_data: &web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    channel: &Channel,
+) {
+    match services::members_without_access(store, channel).await {
+        Ok(user_ids) if !user_ids.is_empty() => {
+            let addr = chat_data.lock().unwrap().clone();
+            addr.do_send(RevokeChannelAccess { server_id: channel.server_id, channel_id: channel.id, user_ids });
         }
+        Ok(_) => {}
+        Err(e) => eprintln!("Erreur lors du calcul des accès au channel {}: {}", channel.id, e),
     }
 }
 
+/// Envoie un événement aux sessions abonnées au channel, en retrouvant son serveur en base.
+async fn broadcast_to_channel(
+    store: &dyn ChatStore,
+    chat_data: &web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    channel_id: i64,
+    event: impl FnOnce(i64) -> ChatEvent,
+) {
+    let server_id = match store.get_channel_by_id(&channel_id).await {
+        Ok(Some(channel)) => channel.server_id,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("Erreur lors de la récupération du channel {}: {}", channel_id, e);
+            return;
+        }
+    };
+    let addr = chat_data.lock().unwrap().clone();
+    addr.do_send(ChatMessage { server_id, channel_id, content: event(server_id) });
+}
+
 /// Transfère le rôle de fondateur (owner) à un autre membre du serveur.
 pub async fn switch_owner(
     form: web::Json<SwitchOwnerForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -766,18 +974,23 @@ pub async fn switch_owner(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
 
-    match db_mongo_setter::switch_owner(&client, &db_name, form.server_id, user_id, form.new_owner_id).await {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+    match store.switch_owner(form.server_id, user_id, form.new_owner_id).await {
+        Ok(_) => {
+            let entry = NewAuditEntry {
+                server_id: form.server_id,
+                actor_id: user_id,
+                action: AuditAction::OwnerSwitch,
+                target_id: form.new_owner_id,
+                before: Some(serde_json::json!({ "owner_id": user_id })),
+                after: Some(serde_json::json!({ "owner_id": form.new_owner_id })),
+            };
+            record_audit(store.get_ref(), entry).await;
+            HttpResponse::Ok().json(serde_json::json!({ "success": true }))
+        }
         Err(e) => {
             eprintln!("Erreur lors du transfert de propriété: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors du transfert de propriété"
-            }))
+            e.error_response()
         }
     }
 }
@@ -786,6 +999,7 @@ pub async fn update_server(
     form: web::Json<UpdateServerForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -793,10 +1007,6 @@ pub async fn update_server(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
 
     let new_name = form.name.as_deref();
     let new_image = form.image.as_deref();
@@ -807,9 +1017,9 @@ pub async fn update_server(
         }));
     }
 
-    match db_mongo_update::update_server(
-        &client,
-        &db_name,
+    let before = store.get_server(&form.server_id).await.ok().flatten();
+
+    match store.update_server(
         form.server_id,
         new_name,
         new_image,
@@ -817,14 +1027,24 @@ pub async fn update_server(
     )
     .await
     {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
-            "success": true
-        })),
+        Ok(_) => {
+            let after = store.get_server(&form.server_id).await.ok().flatten();
+            let entry = NewAuditEntry {
+                server_id: form.server_id,
+                actor_id: user_id,
+                action: AuditAction::ServerUpdate,
+                target_id: form.server_id,
+                before: before.as_ref().map(server_snapshot),
+                after: after.as_ref().map(server_snapshot),
+            };
+            record_audit(store.get_ref(), entry).await;
+            HttpResponse::Ok().json(serde_json::json!({
+                "success": true
+            }))
+        }
         Err(e) => {
             eprintln!("Erreur lors de la mise à jour du serveur: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la mise à jour du serveur"
-            }))
+            e.error_response()
         }
     }
 }
@@ -833,6 +1053,7 @@ pub async fn delete_server(
     form: web::Json<DeleteServerForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -840,20 +1061,14 @@ pub async fn delete_server(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
 
-    match db_mongo_delete::delete_server(&client, &db_name, form.server_id, user_id).await {
+    match store.delete_server(form.server_id, user_id).await {
         Ok(_) => HttpResponse::Ok().json(serde_json::json!({
             "success": true
         })),
         Err(e) => {
             eprintln!("Erreur lors de la suppression du serveur: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la suppression du serveur"
-            }))
+            e.error_response()
         }
     }
 }
@@ -862,6 +1077,8 @@ pub async fn leave_server(
     form: web::Json<LeaveServerForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -869,28 +1086,31 @@ pub async fn leave_server(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
 
     // Vérifier que l'utilisateur n'est pas owner
-    if db_mongo_getter::is_owner(&client, &db_name, &form.server_id, &user_id).await.unwrap_or(false) {
+    if store.is_owner(&form.server_id, &user_id).await.unwrap_or(false) {
         return HttpResponse::BadRequest().json(serde_json::json!({
             "error": "Les owners ne peuvent pas quitter le serveur. Vous devez d'abord transférer le rôle d'owner."
         }));
     }
 
     // Utiliser delete_member pour se retirer du serveur
-    match db_mongo_delete::delete_member(&client, &db_name, form.server_id, user_id, user_id).await {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
-            "success": true
-        })),
+    match store.delete_member(form.server_id, user_id, user_id).await {
+        Ok(_) => {
+            // Fermer les abonnements temps réel de l'utilisateur sur ce serveur
+            let addr = chat_data.lock().unwrap().clone();
+            addr.do_send(RevokeServerAccess {
+                server_id: form.server_id,
+                user_id,
+                reason: RevokeReason::LeftServer,
+            });
+            HttpResponse::Ok().json(serde_json::json!({
+                "success": true
+            }))
+        }
         Err(e) => {
             eprintln!("Erreur lors de la sortie du serveur: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la sortie du serveur"
-            }))
+            e.error_response()
         }
     }
 }
@@ -900,25 +1120,27 @@ pub async fn get_server_members(
     session: Session,
     config: web::Data<AppConfig>,
     chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     
     // Marquer l'utilisateur comme connecté s'il a une session valide
     mark_user_connected_if_session_valid(&user_response, &chat_data);
     
-    let _user_id = match get_user_id_from_session(&user_response) {
+    let user_id = match get_user_id_from_session(&user_response) {
         Ok(id) => id,
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
-
-    // Récupérer le document du serveur
-    let servers = match db_mongo_getter::get_server(&client, &db_name, &query.server_id).await {
-        Ok(s) => s,
+    // Récupérer le serveur
+    let server = match store.get_server(&query.server_id).await {
+        Ok(Some(server)) => server,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "Serveur introuvable",
+                "members": []
+            }));
+        }
         Err(e) => {
             eprintln!("Erreur lors de la récupération du serveur: {}", e);
             return HttpResponse::InternalServerError().json(serde_json::json!({
@@ -927,88 +1149,35 @@ pub async fn get_server_members(
             }));
         }
     };
-
-    if servers.is_empty() {
-        return HttpResponse::NotFound().json(serde_json::json!({
-            "error": "Serveur introuvable",
-            "members": []
-        }));
+    if !server.member_id.contains(&user_id) {
+        return FluxyError::Forbidden("Vous n'êtes pas membre de ce serveur".to_string()).error_response();
     }
 
-    let server_doc = &servers[0];
+    // Récupérer la présence des utilisateurs connectés (les invisibles n'y figurent pas)
+    let addr = chat_data.lock().unwrap().clone();
+    let presences = addr.send(GetPresences).await.unwrap_or_default();
+    let status_of = |id: i64| presences.get(&id).copied().unwrap_or(PresenceStatus::Offline);
 
-    // Récupérer les utilisateurs connectés
-    let connected_users = chat_data
-        .lock()
-        .unwrap()
-        .send(GetConnectedUsers)
-        .await
-        .unwrap_or_else(|_| Vec::new());
-    
-    println!("[MEMBERS] Utilisateurs connectés: {:?} (total: {})", connected_users, connected_users.len());
+    // Rôles personnalisés de chaque membre (ids, du plus haut au plus bas)
+    let custom_roles = store.get_roles_of_server(&query.server_id).await.unwrap_or_default();
+    let roles_of = |id: i64| -> Vec<i64> {
+        custom_roles.iter().filter(|role| role.member_id.contains(&id)).map(|role| role.id).collect()
+    };
 
+    // Fondateur, puis admins, puis membres : chaque utilisateur n'apparaît qu'une fois avec son rôle le plus élevé
     let mut members: Vec<serde_json::Value> = Vec::new();
-
-    // Fondateur
-    if let Some(owner_id) = server_doc.get("owner_id").and_then(|v| v.as_i64()) {
-        let is_online = connected_users.contains(&owner_id);
-        members.push(serde_json::json!({
-            "user_id": owner_id,
-            "role": "fondateur",
-            "status": if is_online { "online" } else { "offline" }
-        }));
-    }
-
-    // Admins (peut être un seul id ou un tableau)
-    if let Some(admin_field) = server_doc.get("admin_id") {
-        if let Some(admin_id) = admin_field.as_i64() {
-            let is_online = connected_users.contains(&admin_id);
+    let mut listed: std::collections::HashSet<i64> = std::collections::HashSet::new();
+    let roles = std::iter::once((server.owner_id, "fondateur"))
+        .chain(server.admin_id.iter().map(|id| (*id, "admin")))
+        .chain(server.member_id.iter().map(|id| (*id, "membre")));
+    for (id, role) in roles {
+        if listed.insert(id) {
             members.push(serde_json::json!({
-                "user_id": admin_id,
-                "role": "admin",
-                "status": if is_online { "online" } else { "offline" }
+                "user_id": id,
+                "role": role,
+                "roles": roles_of(id),
+                "status": status_of(id)
             }));
-        } else if let Some(admin_array) = admin_field.as_array() {
-            for v in admin_array {
-                if let Some(id) = v.as_i64() {
-                    // éviter de dupliquer le fondateur s'il est aussi admin
-                    if !members.iter().any(|m| m.get("user_id").and_then(|u| u.as_i64()) == Some(id)) {
-                        let is_online = connected_users.contains(&id);
-                        members.push(serde_json::json!({
-                            "user_id": id,
-                            "role": "admin",
-                            "status": if is_online { "online" } else { "offline" }
-                        }));
-                    }
-                }
-            }
-        }
-    }
-
-    // Membres (peut être un seul id ou un tableau)
-    if let Some(member_field) = server_doc.get("member_id") {
-        if let Some(member_id) = member_field.as_i64() {
-            if !members.iter().any(|m| m.get("user_id").and_then(|u| u.as_i64()) == Some(member_id)) {
-                let is_online = connected_users.contains(&member_id);
-                members.push(serde_json::json!({
-                    "user_id": member_id,
-                    "role": "membre",
-                    "status": if is_online { "online" } else { "offline" }
-                }));
-            }
-        } else if let Some(member_array) = member_field.as_array() {
-            for v in member_array {
-                if let Some(id) = v.as_i64() {
-                    if !members.iter().any(|m| m.get("user_id").and_then(|u| u.as_i64()) == Some(id)) {
-                        let is_online = connected_users.contains(&id);
-                        members.push(serde_json::json!({
-                            "user_id": id,
-                            "role": "membre",
-                            "status": if is_online { "online" } else { "offline" }
-                        }));
-                    }
-                }
-            }
         }
     }
 
@@ -1021,6 +1190,7 @@ pub async fn join_server(
     form: web::Json<JoinServerForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -1028,19 +1198,14 @@ pub async fn join_server(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
 
     // Vérifier que le serveur existe
-    match db_mongo_getter::get_server(&client, &db_name, &form.server_id).await {
-        Ok(servers) => {
-            if servers.is_empty() {
-                return HttpResponse::NotFound().json(serde_json::json!({
-                    "error": "Serveur introuvable"
-                }));
-            }
+    match store.get_server(&form.server_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "Serveur introuvable"
+            }));
         }
         Err(e) => {
             eprintln!("Erreur lors de la vérification du serveur: {}", e);
@@ -1051,7 +1216,7 @@ pub async fn join_server(
     }
 
     // Vérifier si l'utilisateur est déjà membre
-    match db_mongo_getter::is_member(&client, &db_name, &form.server_id, &user_id).await {
+    match store.is_member(&form.server_id, &user_id).await {
         Ok(true) => {
             return HttpResponse::BadRequest().json(serde_json::json!({
                 "error": "Vous êtes déjà membre de ce serveur"
@@ -1067,24 +1232,25 @@ pub async fn join_server(
     }
 
     // Ajouter l'utilisateur au serveur
-    match db_mongo_setter::add_member_to_server(&client, &db_name, form.server_id, user_id).await {
+    match store.add_member_to_server(form.server_id, user_id).await {
         Ok(_) => HttpResponse::Ok().json(serde_json::json!({
             "success": true,
             "message": "Vous avez rejoint le serveur avec succès"
         })),
         Err(e) => {
             eprintln!("Erreur lors de l'ajout au serveur: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de l'ajout au serveur"
-            }))
+            e.error_response()
         }
     }
 }
 
+/// Crée un lien d'invitation (CREATE_INVITES). `expires_in_secs` et `max_uses` sont optionnels : sans eux le lien
+/// est permanent et illimité. Un serveur peut avoir plusieurs liens en même temps.
 pub async fn create_invite_link(
     form: web::Json<CreateInviteLinkForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -1092,24 +1258,27 @@ pub async fn create_invite_link(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
+    let expires_at = match form.expires_in_secs {
+        None => None,
+        Some(secs) if secs > 0 => match Utc::now().checked_add_signed(TimeDelta::seconds(secs)) {
+            Some(date) => Some(format_date(date)),
+            None => return FluxyError::Validation("Durée de validité du lien trop longue".to_string()).error_response(),
+        },
+        Some(_) => {
+            return FluxyError::Validation("La durée de validité du lien doit être positive".to_string()).error_response();
+        }
     };
 
-    match db_mongo_setter::create_link_one_use(&client, &db_name, form.server_id, user_id).await {
-        Ok(code) if code != "a" => HttpResponse::Ok().json(serde_json::json!({
+    // Permission CREATE_INVITES et nombre maximum d'utilisations vérifiés par le store
+    match store.create_invite(form.server_id, user_id, expires_at, form.max_uses).await {
+        Ok(invite) => HttpResponse::Ok().json(serde_json::json!({
             "success": true,
-            "link": code,
-        })),
-        Ok(_) => HttpResponse::Forbidden().json(serde_json::json!({
-            "error": "Seuls le fondateur ou les admins peuvent créer un lien d'invitation"
+            "link": invite.code,
+            "invite": invite,
         })),
         Err(e) => {
             eprintln!("Erreur lors de la création du lien d'invitation: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la création du lien d'invitation"
-            }))
+            e.error_response()
         }
     }
 }
@@ -1118,6 +1287,7 @@ pub async fn join_server_by_link(
     form: web::Json<JoinByLinkForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -1125,20 +1295,39 @@ pub async fn join_server_by_link(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
-        Err(resp) => return resp,
-    };
 
-    match db_mongo_setter::join_by_link(&client, &db_name, &form.link, user_id).await {
+    match store.join_by_link(&form.link, user_id).await {
         Ok(_) => HttpResponse::Ok().json(serde_json::json!({
             "success": true
         })),
         Err(e) => {
             eprintln!("Erreur lors de la jointure par lien: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de la jointure par lien"
-            }))
+            e.error_response()
+        }
+    }
+}
+
+/// Révoque un lien d'invitation : réservé à son créateur et aux administrateurs du serveur.
+pub async fn revoke_invite(
+    form: web::Json<RevokeInviteForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match store.delete_invite(&form.code, user_id).await {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "Lien d'invitation révoqué avec succès"
+        })),
+        Err(e) => {
+            eprintln!("Erreur lors de la révocation du lien d'invitation: {}", e);
+            e.error_response()
         }
     }
 }
@@ -1147,60 +1336,225 @@ pub async fn update_member_role(
     form: web::Json<UpdateMemberRoleForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
-    let owner_id = match get_user_id_from_session(&user_response) {
+    let actor_id = match get_user_id_from_session(&user_response) {
         Ok(id) => id,
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
+
+    let before = member_snapshot(store.get_ref(), form.server_id, form.user_id).await;
+
+    // "admin", "membre" ou le nom d'un rôle du serveur : le store renvoie une erreur de validation sinon
+    match store.update_member_role(
+        form.server_id,
+        form.user_id,
+        &form.role,
+        actor_id,
+    )
+    .await
+    {
+        Ok(_) => {
+            let entry = NewAuditEntry {
+                server_id: form.server_id,
+                actor_id,
+                action: AuditAction::MemberRoleUpdate,
+                target_id: form.user_id,
+                before,
+                after: member_snapshot(store.get_ref(), form.server_id, form.user_id).await,
+            };
+            record_audit(store.get_ref(), entry).await;
+            HttpResponse::Ok().json(serde_json::json!({
+                "success": true,
+                "message": "Rôle mis à jour avec succès"
+            }))
+        }
+        Err(e) => {
+            eprintln!("Erreur lors de la mise à jour du rôle: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+pub async fn kick_member(
+    form: web::Json<KickMemberForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
         Err(resp) => return resp,
     };
 
-    // Valider le rôle
-    if form.role != "admin" && form.role != "membre" {
+
+    // Permission KICK_MEMBERS et hiérarchie des rôles vérifiées par le store
+    if user_id == form.user_id {
         return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "Rôle invalide. Utilisez 'admin' ou 'membre'"
+            "error": "Utilisez /api/leave-server pour quitter le serveur"
         }));
     }
 
-    match db_mongo_update::update_member_role(
-        &client,
-        &db_name,
+    let before = member_snapshot(store.get_ref(), form.server_id, form.user_id).await;
+
+    match store.delete_member(
         form.server_id,
+        user_id,
         form.user_id,
-        &form.role,
-        owner_id,
-    )
-    .await
-    {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
+    ).await {
+        Ok(_) => {
+            let entry = NewAuditEntry {
+                server_id: form.server_id,
+                actor_id: user_id,
+                action: AuditAction::MemberKick,
+                target_id: form.user_id,
+                before,
+                after: None,
+            };
+            record_audit(store.get_ref(), entry).await;
+            // Couper immédiatement le flux temps réel du membre exclu
+            let addr = chat_data.lock().unwrap().clone();
+            addr.do_send(RevokeServerAccess {
+                server_id: form.server_id,
+                user_id: form.user_id,
+                reason: RevokeReason::Kicked,
+            });
+            HttpResponse::Ok().json(serde_json::json!({
+                "success": true,
+                "message": "Membre exclu avec succès"
+            }))
+        }
+        Err(e) => {
+            eprintln!("Erreur lors de l'exclusion du membre: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+/// Bannit un utilisateur (BAN_MEMBERS, rang inférieur) : il quitte le serveur et ne peut plus le rejoindre
+/// avant l'expiration éventuelle du bannissement (`duration_secs`).
+pub async fn ban_member(
+    form: web::Json<BanMemberForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    let expires_at = match form.duration_secs {
+        None => None,
+        Some(secs) if secs > 0 => match Utc::now().checked_add_signed(TimeDelta::seconds(secs)) {
+            Some(date) => Some(format_date(date)),
+            None => return FluxyError::Validation("Durée de bannissement trop longue".to_string()).error_response(),
+        },
+        Some(_) => {
+            return FluxyError::Validation("La durée du bannissement doit être positive".to_string()).error_response();
+        }
+    };
+
+    // Permission BAN_MEMBERS et hiérarchie des rôles vérifiées par le store
+    match store.set_ban(form.server_id, form.user_id, &form.reason, expires_at, user_id).await {
+        Ok(ban) => {
+            // Couper immédiatement le flux temps réel du membre banni
+            let addr = chat_data.lock().unwrap().clone();
+            addr.do_send(RevokeServerAccess {
+                server_id: form.server_id,
+                user_id: form.user_id,
+                reason: RevokeReason::Banned,
+            });
+            HttpResponse::Ok().json(serde_json::json!({
+                "success": true,
+                "ban": ban
+            }))
+        }
+        Err(e) => {
+            eprintln!("Erreur lors du bannissement: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+pub async fn unban_member(
+    form: web::Json<UnbanMemberForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match store.delete_ban(form.server_id, form.user_id, user_id).await {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({
             "success": true,
-            "message": "Rôle mis à jour avec succès"
+            "message": "Bannissement levé avec succès"
         })),
         Err(e) => {
-            let error_msg = e.to_string();
-            let mut status = if error_msg.contains("PermissionDenied") {
-                HttpResponse::Forbidden()
-            } else if error_msg.contains("InvalidInput") || error_msg.contains("NotFound") {
-                HttpResponse::BadRequest()
-            } else {
-                HttpResponse::InternalServerError()
-            };
-            
-            status.json(serde_json::json!({
-                "error": error_msg
+            eprintln!("Erreur lors de la levée du bannissement: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+/// Réduit un membre au silence pendant `duration_secs` secondes (MODERATE_MEMBERS, rang inférieur) : il reste membre
+/// mais ses messages sont refusés. Le début et la fin du silence sont annoncés aux membres connectés du serveur.
+pub async fn timeout_member(
+    form: web::Json<TimeoutMemberForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    if form.duration_secs <= 0 {
+        return FluxyError::Validation("La durée du timeout doit être positive".to_string()).error_response();
+    }
+    let Some(until) = Utc::now().checked_add_signed(TimeDelta::seconds(form.duration_secs)) else {
+        return FluxyError::Validation("Durée de timeout trop longue".to_string()).error_response();
+    };
+
+    // Permission MODERATE_MEMBERS, hiérarchie des rôles et durée maximale vérifiées par le store
+    match store.set_timeout(form.server_id, form.user_id, format_date(until), user_id).await {
+        Ok(timeout) => {
+            let addr = chat_data.lock().unwrap().clone();
+            addr.do_send(StartTimeout {
+                timeout: timeout.clone(),
+                duration: Duration::from_secs(form.duration_secs as u64),
+            });
+            HttpResponse::Ok().json(serde_json::json!({
+                "success": true,
+                "timeout": timeout
             }))
         }
+        Err(e) => {
+            eprintln!("Erreur lors de la mise en silence: {}", e);
+            e.error_response()
+        }
     }
 }
 
-pub async fn kick_member(
-    form: web::Json<KickMemberForm>,
+pub async fn remove_timeout(
+    form: web::Json<RemoveTimeoutForm>,
     session: Session,
     config: web::Data<AppConfig>,
+    chat_data: web::Data<Arc<Mutex<Addr<ChatServer>>>>,
+    store: web::Data<dyn ChatStore>,
 ) -> impl Responder {
     let user_response = getters::get_user_response_from_session(&session, &config).await;
     let user_id = match get_user_id_from_session(&user_response) {
@@ -1208,60 +1562,258 @@ pub async fn kick_member(
         Err(resp) => return resp,
     };
 
-    let (client, db_name) = match get_mongo_client_and_db().await {
-        Ok(result) => result,
+    match store.delete_timeout(form.server_id, form.user_id, user_id).await {
+        Ok(()) => {
+            let addr = chat_data.lock().unwrap().clone();
+            addr.do_send(EndTimeout { server_id: form.server_id, user_id: form.user_id });
+            HttpResponse::Ok().json(serde_json::json!({
+                "success": true,
+                "message": "Timeout levé avec succès"
+            }))
+        }
+        Err(e) => {
+            eprintln!("Erreur lors de la levée du timeout: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+/// Journal d'audit du serveur (fondateur et administrateurs), du plus récent au plus ancien,
+/// filtrable par type d'action et par auteur.
+pub async fn get_audit_log(
+    query: web::Query<AuditLogQuery>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
         Err(resp) => return resp,
     };
 
-    // Vérifier que l'utilisateur est fondateur ou admin
-    let is_owner = db_mongo_getter::is_owner(&client, &db_name, &form.server_id, &user_id).await
-        .unwrap_or(false);
-    let is_admin = db_mongo_getter::is_admin(&client, &db_name, &form.server_id, &user_id).await
-        .unwrap_or(false);
+    // Une entrée de plus que demandé pour savoir s'il reste une page
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_PAGE).clamp(1, MAX_AUDIT_PAGE);
+    let filter = AuditLogFilter { action: query.action, user_id: query.user_id, before: query.before, limit: limit + 1 };
+
+    match services::audit_log(store.get_ref(), user_id, query.server_id, &filter).await {
+        Ok(mut entries) => {
+            let has_more = entries.len() as i64 > limit;
+            entries.truncate(limit as usize);
+            // Curseur à repasser dans `before` pour la page suivante
+            let next_cursor = if has_more { entries.last().map(|entry| entry.id) } else { None };
+            HttpResponse::Ok().json(serde_json::json!({ "entries": entries, "next_cursor": next_cursor }))
+        }
+        Err(e) => {
+            eprintln!("Erreur lors de la récupération du journal d'audit: {}", e);
+            e.error_response()
+        }
+    }
+}
 
-    if !is_owner && !is_admin {
-        return HttpResponse::Forbidden().json(serde_json::json!({
-            "error": "Vous n'avez pas la permission d'exclure des membres"
-        }));
+pub async fn get_server_bans(
+    query: web::Query<ServerBansQuery>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match services::server_bans(store.get_ref(), user_id, query.server_id).await {
+        Ok(bans) => HttpResponse::Ok().json(serde_json::json!({ "bans": bans })),
+        Err(e) => {
+            eprintln!("Erreur lors de la récupération des bannissements: {}", e);
+            e.error_response()
+        }
     }
+}
 
-    // Ne pas permettre d'exclure le fondateur
-    let is_target_owner = db_mongo_getter::is_owner(&client, &db_name, &form.server_id, &form.user_id).await
-        .unwrap_or(false);
-    if is_target_owner {
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "Vous ne pouvez pas exclure le fondateur du serveur"
-        }));
+pub async fn get_server_invites(
+    query: web::Query<ServerInvitesQuery>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match services::server_invites(store.get_ref(), user_id, query.server_id).await {
+        Ok(invites) => HttpResponse::Ok().json(serde_json::json!({ "invites": invites })),
+        Err(e) => {
+            eprintln!("Erreur lors de la récupération des liens d'invitation: {}", e);
+            e.error_response()
+        }
     }
+}
+
+pub async fn get_server_roles(
+    query: web::Query<ServerRolesQuery>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
 
-    // Un admin ne peut pas exclure un autre admin (seul le fondateur peut)
-    if !is_owner && is_admin {
-        let is_target_admin = db_mongo_getter::is_admin(&client, &db_name, &form.server_id, &form.user_id).await
-            .unwrap_or(false);
-        if is_target_admin {
+    match store.is_member(&query.server_id, &user_id).await {
+        Ok(true) => {}
+        Ok(false) => {
             return HttpResponse::Forbidden().json(serde_json::json!({
-                "error": "Seul le fondateur peut exclure un administrateur"
+                "error": "Vous n'êtes pas membre de ce serveur"
+            }));
+        }
+        Err(e) => {
+            eprintln!("Erreur lors de la vérification du membre: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Erreur lors de la récupération des rôles"
             }));
         }
     }
 
-    match db_mongo_delete::delete_member(
-        &client,
-        &db_name,
-        form.server_id,
-        user_id,
-        form.user_id,
-    ).await {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
-            "success": true,
-            "message": "Membre exclu avec succès"
-        })),
+    match store.get_roles_of_server(&query.server_id).await {
+        Ok(roles) => HttpResponse::Ok().json(serde_json::json!({ "roles": roles })),
         Err(e) => {
-            eprintln!("Erreur lors de l'exclusion du membre: {}", e);
+            eprintln!("Erreur lors de la récupération des rôles: {}", e);
             HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Erreur lors de l'exclusion du membre"
+                "error": "Erreur lors de la récupération des rôles"
             }))
         }
     }
 }
 
+pub async fn create_role(
+    form: web::Json<CreateRoleForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match store.set_role(form.server_id, &form.name, form.permissions, user_id).await {
+        Ok(role) => HttpResponse::Ok().json(serde_json::json!({ "success": true, "role": role })),
+        Err(e) => {
+            eprintln!("Erreur lors de la création du rôle: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+pub async fn update_role(
+    form: web::Json<UpdateRoleForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match store
+        .update_role(form.role_id, form.name.as_deref(), form.permissions, form.position, user_id)
+        .await
+    {
+        Ok(role) => HttpResponse::Ok().json(serde_json::json!({ "success": true, "role": role })),
+        Err(e) => {
+            eprintln!("Erreur lors de la mise à jour du rôle: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+pub async fn delete_role(
+    form: web::Json<DeleteRoleForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match store.delete_role(form.role_id, user_id).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Err(e) => {
+            eprintln!("Erreur lors de la suppression du rôle: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+pub async fn assign_role(
+    form: web::Json<MemberRoleForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match store.add_role_to_member(form.role_id, form.user_id, user_id).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Err(e) => {
+            eprintln!("Erreur lors de l'attribution du rôle: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+pub async fn unassign_role(
+    form: web::Json<MemberRoleForm>,
+    session: Session,
+    config: web::Data<AppConfig>,
+    store: web::Data<dyn ChatStore>,
+) -> impl Responder {
+    let user_response = getters::get_user_response_from_session(&session, &config).await;
+    let user_id = match get_user_id_from_session(&user_response) {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match store.remove_role_from_member(form.role_id, form.user_id, user_id).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Err(e) => {
+            eprintln!("Erreur lors du retrait du rôle: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+/// healthz :  
+/// état de l'application et de la base de données, pour la supervision (pas de session requise)
+pub async fn healthz(store: web::Data<dyn ChatStore>) -> impl Responder {
+    match store.ping().await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "ok",
+            "database": "ok"
+        })),
+        Err(e) => {
+            eprintln!("Base de données injoignable: {}", e);
+            HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "status": "error",
+                "database": "unreachable",
+                "error": e.to_string()
+            }))
+        }
+    }
+}
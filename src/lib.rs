@@ -5,5 +5,15 @@ pub mod db_mongo_getter;
 pub mod db_mongo_setter;
 pub mod db_mongo_delete;
 pub mod db_mongo_update;
+pub mod db_store;
+pub mod error;
+pub mod db_memory;
+#[cfg(feature = "sql")]
+pub mod db_sql;
 pub mod supabase;
-pub mod models;
\ No newline at end of file
+pub mod models;
+pub mod chat;
+pub mod getters;
+pub mod config;
+pub mod handlers;
+pub mod services;
@@ -6,19 +6,22 @@ use actix_web::http::header;
 use actix::Actor;
 use std::sync::{Arc, Mutex};
 
-mod models;
-mod config;
-mod chat;
-mod supabase;
-mod handlers;
-mod getters;
-mod db_mongo_setter;
-mod db_mongo_connection;
-mod db_mongo_getter;
-mod db_mongo_delete;
-mod db_mongo_update;
+// Les modules sont déclarés une seule fois dans la bibliothèque (lib.rs), partagée avec les tests
+use T_JSF_600_MAR_1::{db_mongo_connection, handlers, services};
+use T_JSF_600_MAR_1::models::{ChatServer, AppConfig, StorageBackend};
+use T_JSF_600_MAR_1::db_store::{ChatStore, MongoStore};
+#[cfg(feature = "sql")]
+use T_JSF_600_MAR_1::db_sql;
 
-use models::{ChatServer, AppConfig};
+#[cfg(feature = "sql")]
+async fn connect_sql_store(url: &str) -> std::io::Result<Arc<dyn ChatStore>> {
+    Ok(Arc::new(db_sql::SqlStore::connect(url).await?))
+}
+
+#[cfg(not(feature = "sql"))]
+async fn connect_sql_store(_url: &str) -> std::io::Result<Arc<dyn ChatStore>> {
+    Err(std::io::Error::other("STORAGE_BACKEND=sql nécessite de compiler avec --features sql"))
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -27,13 +30,47 @@ async fn main() -> std::io::Result<()> {
     let config = AppConfig::from_env();
     let key = Key::from(config.session_key.as_bytes());
 
+    let store: Arc<dyn ChatStore> = match StorageBackend::from_env() {
+        StorageBackend::Mongo(pool) => {
+            // Un seul client (et donc un seul pool de connexions) pour toute l'application
+            let client = db_mongo_connection::create_client(&pool).await?;
+            let mongo_store = MongoStore::new(client, &std::env::var("MONGO_DATA_BASE_NAME").unwrap_or_default());
+            // Index MongoDB (pagination des messages...) : l'application démarre même si la base est injoignable
+            if let Err(e) = mongo_store.ensure_indexes().await {
+                eprintln!("Impossible de créer les index MongoDB: {}", e);
+            }
+            Arc::new(mongo_store)
+        }
+        StorageBackend::Sql(url) => connect_sql_store(&url).await?,
+    };
+
+    // `cargo run -- repair` : supprime les restes des suppressions interrompues puis s'arrête sans lancer le serveur
+    if std::env::args().nth(1).as_deref() == Some("repair") {
+        let report = store.repair_orphans().await?;
+        println!(
+            "[REPAIR] orphelins supprimés : {} rôle(s), {} lien(s), {} bannissement(s), {} timeout(s), {} entrée(s) d'audit, {} channel(s), {} message(s)",
+            report.roles, report.invites, report.bans, report.timeouts, report.audit_entries, report.channels, report.messages
+        );
+        return Ok(());
+    }
+
+    // Levée des bannissements expirés en tâche de fond
+    actix_web::rt::spawn(services::run_ban_expiry(store.clone(), config.ban_expiry_interval));
+
     let chat_server = ChatServer::new().start();
+    // Les fins de timeout ne sont programmées qu'en mémoire : reprendre celles qui couraient avant le redémarrage
+    match services::restore_timeouts(store.as_ref(), &chat_server).await {
+        Ok(0) => {}
+        Ok(count) => println!("[MODÉRATION] {} timeout(s) en cours reprogrammé(s)", count),
+        Err(e) => eprintln!("Erreur lors de la reprise des timeouts: {}", e),
+    }
     let chat_data = Arc::new(Mutex::new(chat_server));
 
     let server_3000 = HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(config.clone()))
             .app_data(web::Data::new(chat_data.clone()))
+            .app_data(web::Data::from(store.clone()))
             .wrap(
                 Cors::default()
                     .allowed_origin("http://localhost:3000")
@@ -66,21 +103,40 @@ async fn main() -> std::io::Result<()> {
             .route("/api/join-server", web::post().to(handlers::join_server))
             .route("/api/create-invite-link", web::post().to(handlers::create_invite_link))
             .route("/api/join-server-by-link", web::post().to(handlers::join_server_by_link))
+            .route("/api/server-invites", web::get().to(handlers::get_server_invites))
+            .route("/api/revoke-invite", web::post().to(handlers::revoke_invite))
             .route("/api/delete-server", web::post().to(handlers::delete_server))
             .route("/api/update-server", web::post().to(handlers::update_server))
             .route("/api/leave-server", web::post().to(handlers::leave_server))
             .route("/api/update-member-role", web::post().to(handlers::update_member_role))
             .route("/api/kick-member", web::post().to(handlers::kick_member))
+            .route("/api/ban-member", web::post().to(handlers::ban_member))
+            .route("/api/unban-member", web::post().to(handlers::unban_member))
+            .route("/api/server-bans", web::get().to(handlers::get_server_bans))
+            .route("/api/timeout-member", web::post().to(handlers::timeout_member))
+            .route("/api/remove-timeout", web::post().to(handlers::remove_timeout))
+            .route("/api/audit-log", web::get().to(handlers::get_audit_log))
             .route("/api/switch-owner", web::post().to(handlers::switch_owner))
             .route("/api/has-servers", web::get().to(handlers::has_servers))
             .route("/api/user-servers", web::get().to(handlers::get_user_servers))
             .route("/api/server-channels", web::get().to(handlers::get_server_channels))
             .route("/api/server-members", web::get().to(handlers::get_server_members))
             .route("/api/channel-messages", web::get().to(handlers::get_channel_messages))
+            .route("/api/message/update", web::post().to(handlers::update_message))
             .route("/api/message/delete", web::post().to(handlers::delete_message))
+            .route("/api/message/revisions", web::get().to(handlers::get_message_revisions))
             .route("/api/channel/create", web::post().to(handlers::create_channel))
             .route("/api/channel/update", web::post().to(handlers::update_channel))
             .route("/api/channel/delete", web::post().to(handlers::delete_channel))
+            .route("/api/channel/privacy", web::post().to(handlers::update_channel_privacy))
+            .route("/api/channel/overwrite", web::post().to(handlers::update_channel_overwrite))
+            .route("/api/channel/overwrite/delete", web::post().to(handlers::delete_channel_overwrite))
+            .route("/api/server-roles", web::get().to(handlers::get_server_roles))
+            .route("/api/role/create", web::post().to(handlers::create_role))
+            .route("/api/role/update", web::post().to(handlers::update_role))
+            .route("/api/role/delete", web::post().to(handlers::delete_role))
+            .route("/api/role/assign", web::post().to(handlers::assign_role))
+            .route("/api/role/unassign", web::post().to(handlers::unassign_role))
             
             //Routes pour la gestion de la connexion et de l'inscription
             .route("/login", web::post().to(handlers::login))
@@ -93,6 +149,9 @@ async fn main() -> std::io::Result<()> {
             
             //Routes pour la gestion des messages
             .route("/ws", web::get().to(handlers::chat_ws))
+
+            //Route de supervision
+            .route("/healthz", web::get().to(handlers::healthz))
             .service(
                 Files::new("/static", "./src/static")
                     .index_file("index.html"),
@@ -1,5 +1,9 @@
 use serde::{Deserialize, Serialize};
-use actix::{Message, Recipient};
+use actix::{Recipient, SpawnHandle};
+use std::collections::{HashMap, HashSet};
+use std::ops::BitOr;
+use std::time::Duration;
+use chrono::{DateTime, SecondsFormat, Utc};
 
 /// Configuration globale de l'application (clés Supabase, clé de session...).
 #[derive(Clone)]
@@ -8,6 +12,385 @@ pub struct AppConfig {
     pub supabase_anon_key: String,
     pub supabase_service_role_key: String,
     pub session_key: String,
+    pub ws_heartbeat_interval: Duration, // intervalle des pings envoyés par le serveur sur le WebSocket
+    pub ws_client_timeout: Duration,     // délai sans nouvelles du client avant de fermer la session
+    pub ban_expiry_interval: Duration,   // intervalle de la levée des bannissements expirés
+    pub ws_replay_limit: i64,            // nombre maximum de messages rejoués à la reprise d'un abonnement
+}
+
+/// Stockage des serveurs, channels et messages, choisi au démarrage (variable STORAGE_BACKEND).
+#[derive(Clone, Debug, PartialEq)]
+pub enum StorageBackend {
+    Mongo(MongoPoolConfig), // MongoDB, configuré par les variables MONGO_*
+    Sql(String),            // SQLite ou Postgres (feature sql), url lue dans DATABASE_URL
+}
+
+/// Pool de connexions du client MongoDB partagé, créé une seule fois au démarrage.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MongoPoolConfig {
+    pub max_pool_size: u32,
+    pub min_pool_size: u32,
+    pub connect_timeout: Duration,          // délai d'établissement d'une connexion
+    pub server_selection_timeout: Duration, // délai avant d'abandonner une opération faute de serveur joignable
+}
+
+/// Serveur, tel que stocké dans la collection server (et reconstruit par SqlStore).
+/// Les champs manquants ou mal typés font échouer la lecture au lieu d'être ignorés.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Server {
+    pub id: i64,
+    pub name: String,
+    pub owner_id: i64,
+    pub admin_id: Vec<i64>,
+    pub member_id: Vec<i64>, // le fondateur en fait partie
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+impl Server {
+    /// Permissions effectives d'un utilisateur sur le serveur : toutes pour le fondateur, les admins et les rôles
+    /// qui ont ADMINISTRATOR, l'union de ses rôles pour un membre, aucune pour un non-membre.
+    pub fn permissions_of(&self, roles: &[Role], user_id: i64) -> Permissions {
+        if self.owner_id == user_id || self.admin_id.contains(&user_id) {
+            return Permissions::ALL;
+        }
+        if !self.member_id.contains(&user_id) {
+            return Permissions::NONE;
+        }
+        let permissions = roles
+            .iter()
+            .filter(|role| role.member_id.contains(&user_id))
+            .fold(Permissions::NONE, |permissions, role| permissions | role.permissions);
+        if permissions.contains(Permissions::ADMINISTRATOR) {
+            Permissions::ALL
+        } else {
+            permissions
+        }
+    }
+
+    /// Rang d'un utilisateur dans la hiérarchie : le fondateur au-dessus des admins, eux-mêmes au-dessus de tous les rôles,
+    /// puis la position du plus haut rôle du membre (0 sans rôle). On n'agit que sur un rang strictement inférieur au sien.
+    pub fn rank_of(&self, roles: &[Role], user_id: i64) -> i64 {
+        if self.owner_id == user_id {
+            return OWNER_RANK;
+        }
+        if self.admin_id.contains(&user_id) {
+            return ADMIN_RANK;
+        }
+        roles
+            .iter()
+            .filter(|role| role.member_id.contains(&user_id))
+            .map(|role| role.position)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Rang du fondateur et des admins dans la hiérarchie des rôles (voir Server::rank_of).
+pub const OWNER_RANK: i64 = i64::MAX;
+pub const ADMIN_RANK: i64 = i64::MAX - 1;
+
+/// Permissions d'un rôle, stockées sous forme d'entier (champ permissions du document role).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(transparent)]
+pub struct Permissions(pub i64);
+
+impl Permissions {
+    pub const NONE: Permissions = Permissions(0);
+    pub const MANAGE_CHANNELS: Permissions = Permissions(1 << 0); // créer, renommer et supprimer les channels
+    pub const MANAGE_MESSAGES: Permissions = Permissions(1 << 1); // supprimer les messages des autres, voir leur historique
+    pub const KICK_MEMBERS: Permissions = Permissions(1 << 2);
+    pub const BAN_MEMBERS: Permissions = Permissions(1 << 3);
+    pub const CREATE_INVITES: Permissions = Permissions(1 << 4);
+    pub const MENTION_EVERYONE: Permissions = Permissions(1 << 5); // écrire @everyone ou @here
+    pub const MANAGE_ROLES: Permissions = Permissions(1 << 6);     // gérer les rôles placés sous son plus haut rôle
+    pub const ADMINISTRATOR: Permissions = Permissions(1 << 7);    // toutes les permissions (modifier le serveur compris)
+    pub const VIEW_CHANNEL: Permissions = Permissions(1 << 8);     // voir un channel et lire ses messages
+    pub const SEND_MESSAGES: Permissions = Permissions(1 << 9);    // écrire dans un channel
+    pub const MODERATE_MEMBERS: Permissions = Permissions(1 << 10); // réduire un membre au silence (timeout)
+    pub const ALL: Permissions = Permissions((1 << 11) - 1);
+    /// permissions qu'un channel peut autoriser ou refuser (voir PermissionOverwrite)
+    pub const CHANNEL: Permissions = Permissions(Self::VIEW_CHANNEL.0 | Self::SEND_MESSAGES.0 | Self::MANAGE_CHANNELS.0);
+
+    /// vrai si toutes les permissions demandées sont présentes
+    pub fn contains(self, other: Permissions) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// retire les permissions données
+    pub fn without(self, other: Permissions) -> Permissions {
+        Permissions(self.0 & !other.0)
+    }
+
+    /// vrai si l'entier ne contient que des permissions connues
+    pub fn is_valid(self) -> bool {
+        Permissions::ALL.contains(self)
+    }
+}
+
+impl BitOr for Permissions {
+    type Output = Permissions;
+
+    fn bitor(self, other: Permissions) -> Permissions {
+        Permissions(self.0 | other.0)
+    }
+}
+
+/// Rôle personnalisé d'un serveur (collection role). Plus `position` est grande, plus le rôle est haut dans la hiérarchie.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Role {
+    pub id: i64,
+    pub server_id: i64,
+    pub name: String,
+    pub permissions: Permissions,
+    pub position: i64,
+    #[serde(default)]
+    pub member_id: Vec<i64>, // membres qui ont ce rôle
+}
+
+/// Channel d'un serveur (collection channel). Un channel privé n'est visible que par les admins et les membres
+/// qu'un rôle (VIEW_CHANNEL) ou une exception autorise.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub id: i64,
+    pub server_id: i64,
+    pub name: String,
+    pub position: i64,
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default)]
+    pub overwrites: Vec<PermissionOverwrite>, // exceptions par rôle ou par membre
+}
+
+impl Channel {
+    /// Permissions effectives d'un utilisateur dans le channel. Le fondateur, les admins et ADMINISTRATOR ont tout ;
+    /// un membre part des permissions du serveur, plus VIEW_CHANNEL (sauf channel privé) et SEND_MESSAGES.
+    /// Les exceptions de ses rôles s'appliquent ensuite (refus puis autorisations), puis la sienne.
+    /// Sans VIEW_CHANNEL, le channel n'accorde plus aucune permission.
+    pub fn permissions_of(&self, server: &Server, roles: &[Role], user_id: i64) -> Permissions {
+        let mut permissions = server.permissions_of(roles, user_id);
+        if permissions.contains(Permissions::ADMINISTRATOR) {
+            return Permissions::ALL;
+        }
+        if !server.member_id.contains(&user_id) {
+            return Permissions::NONE;
+        }
+        permissions = permissions | Permissions::SEND_MESSAGES;
+        if !self.private {
+            permissions = permissions | Permissions::VIEW_CHANNEL;
+        }
+
+        let (allow, deny) = self
+            .overwrites
+            .iter()
+            .filter(|overwrite| overwrite.kind == OverwriteKind::Role)
+            .filter(|overwrite| roles.iter().any(|role| role.id == overwrite.target_id && role.member_id.contains(&user_id)))
+            .fold((Permissions::NONE, Permissions::NONE), |(allow, deny), overwrite| {
+                (allow | overwrite.allow, deny | overwrite.deny)
+            });
+        permissions = permissions.without(deny) | allow;
+        if let Some(overwrite) = self.overwrite_of(OverwriteKind::User, user_id) {
+            permissions = permissions.without(overwrite.deny) | overwrite.allow;
+        }
+
+        if permissions.contains(Permissions::VIEW_CHANNEL) {
+            permissions
+        } else {
+            Permissions::NONE
+        }
+    }
+
+    /// Exception du channel pour un rôle ou un membre, s'il y en a une
+    pub fn overwrite_of(&self, kind: OverwriteKind, target_id: i64) -> Option<&PermissionOverwrite> {
+        self.overwrites
+            .iter()
+            .find(|overwrite| overwrite.kind == kind && overwrite.target_id == target_id)
+    }
+}
+
+/// Cible d'une exception de channel.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverwriteKind {
+    Role,
+    User,
+}
+
+/// Exception de permissions d'un channel pour un rôle ou un membre (tableau overwrites du document channel).
+/// `allow` et `deny` ne contiennent que des permissions de Permissions::CHANNEL.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PermissionOverwrite {
+    pub kind: OverwriteKind,
+    pub target_id: i64, // id du rôle ou de l'utilisateur
+    #[serde(default)]
+    pub allow: Permissions,
+    #[serde(default)]
+    pub deny: Permissions,
+}
+
+/// Message d'un channel (collection message). `time` est une date RFC 3339 attribuée par le serveur.
+/// Les anciennes versions d'un message modifié sont gardées à part (voir MessageRevision) et ne sont pas renvoyées ici.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Message {
+    pub id: i64,
+    pub channel_id: i64,
+    pub message: String,
+    pub user: i64,
+    pub time: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<String>, // date de la dernière modification
+}
+
+/// Ancienne version d'un message, enregistrée à chaque modification (tableau revisions du document message).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageRevision {
+    pub message: String,   // contenu avant la modification
+    pub editor_id: i64,    // utilisateur qui a fait la modification
+    pub edited_at: String, // date de la modification (RFC 3339)
+}
+
+/// Format des dates comparées comme des chaînes (bannissements, timeouts, liens d'invitation),
+/// y compris celle passée à is_active_at, is_usable_at et delete_expired_bans
+pub fn format_date(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Bannissement d'un utilisateur d'un serveur (collection ban, un seul par serveur et utilisateur).
+/// Les dates sont en RFC 3339 à la seconde et en UTC (voir format_date) pour rester comparables comme des chaînes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Ban {
+    pub server_id: i64,
+    pub user_id: i64,
+    pub reason: String,
+    pub banned_by: i64, // modérateur qui a banni
+    pub banned_at: String,
+    #[serde(default)]
+    pub expires_at: Option<String>, // pas de date d'expiration : bannissement définitif
+}
+
+impl Ban {
+    /// Le bannissement empêche-t-il encore de rejoindre le serveur à la date `now` (voir format_date) ?
+    pub fn is_active_at(&self, now: &str) -> bool {
+        self.expires_at.as_deref().is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+/// Membre réduit au silence sur un serveur jusqu'à `until` (collection timeout, un seul par serveur et membre).
+/// `until` a le format de format_date ; un timeout passé n'a plus d'effet même s'il est encore enregistré.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MemberTimeout {
+    pub server_id: i64,
+    pub user_id: i64,
+    pub moderator_id: i64, // modérateur qui a réduit le membre au silence
+    pub until: String,
+}
+
+impl MemberTimeout {
+    /// Secondes de silence restantes à la date `now`, arrondies à la seconde supérieure (0 si le timeout est terminé)
+    pub fn remaining_secs(&self, now: DateTime<Utc>) -> u64 {
+        let Ok(until) = DateTime::parse_from_rfc3339(&self.until) else {
+            return 0;
+        };
+        let remaining_ms = (until.with_timezone(&Utc) - now).num_milliseconds();
+        if remaining_ms <= 0 {
+            0
+        } else {
+            ((remaining_ms + 999) / 1000) as u64
+        }
+    }
+}
+
+/// Lien d'invitation vers un serveur (collection invite, plusieurs par serveur).
+/// Les dates ont le format de format_date ; `uses` compte les utilisateurs qui ont rejoint grâce au lien.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Invite {
+    pub code: String,
+    pub server_id: i64,
+    pub creator_id: i64,
+    pub created_at: String,
+    #[serde(default)]
+    pub expires_at: Option<String>, // pas de date d'expiration : lien permanent
+    #[serde(default)]
+    pub max_uses: Option<i64>, // pas de maximum : utilisations illimitées
+    #[serde(default)]
+    pub uses: i64,
+}
+
+impl Invite {
+    /// Le lien permet-il encore de rejoindre le serveur à la date `now` (ni expiré, ni épuisé) ?
+    pub fn is_usable_at(&self, now: &str) -> bool {
+        self.expires_at.as_deref().is_none_or(|expires_at| expires_at > now)
+            && self.max_uses.is_none_or(|max_uses| self.uses < max_uses)
+    }
+}
+
+/// Action enregistrée dans le journal d'audit d'un serveur.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    ChannelDelete,
+    MemberKick,
+    OwnerSwitch,
+    ServerUpdate,
+    MemberRoleUpdate,
+    MessageDelete,
+}
+
+/// Entrée du journal d'audit d'un serveur (collection audit_log) : qui a fait quoi, sur quoi, et quand.
+/// `before` / `after` sont des instantanés JSON de la cible, absents quand il n'y a rien avant ou après (suppression).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub server_id: i64,
+    pub actor_id: i64,  // utilisateur qui a fait l'action
+    pub action: AuditAction,
+    pub target_id: i64, // channel, membre, serveur ou message touché selon l'action
+    #[serde(default)]
+    pub before: Option<serde_json::Value>,
+    #[serde(default)]
+    pub after: Option<serde_json::Value>,
+    pub created_at: String,
+}
+
+/// Entrée d'audit à enregistrer : le store lui attribue un id et la date (voir ChatStore::set_audit_entry).
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewAuditEntry {
+    pub server_id: i64,
+    pub actor_id: i64,
+    pub action: AuditAction,
+    pub target_id: i64,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// Filtres de ChatStore::get_audit_log. `user_id` est l'auteur de l'action, `before` un curseur (id d'entrée).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AuditLogFilter {
+    pub action: Option<AuditAction>,
+    pub user_id: Option<i64>,
+    pub before: Option<i64>,
+    pub limit: i64,
+}
+
+/// Résultat de ChatStore::repair_orphans : nombre de documents orphelins supprimés.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct RepairReport {
+    pub roles: u64,         // rôles dont le serveur n'existe plus
+    pub invites: u64,       // liens d'invitation dont le serveur n'existe plus
+    pub bans: u64,          // bannissements dont le serveur n'existe plus
+    pub timeouts: u64,      // timeouts dont le serveur n'existe plus
+    pub audit_entries: u64, // entrées du journal d'audit dont le serveur n'existe plus
+    pub channels: u64,      // channels dont le serveur n'existe plus
+    pub messages: u64,      // messages dont le channel n'existe plus
+}
+
+/// Message renvoyé par /api/channel-messages, avec le pseudo de l'auteur s'il est connu.
+#[derive(Serialize, Debug)]
+pub struct MessageWithUsername {
+    #[serde(flatten)]
+    pub message: Message,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
 }
 
 /// Données envoyées par le formulaire de login (email + mot de passe).
@@ -18,50 +401,366 @@ pub struct LoginForm {
 }
 
 
-/// Message envoyé sur le bus Actix pour diffuser un message de chat dans un serveur / channel.
-#[derive(Message)]
+/// Version courante du protocole JSON échangé sur le WebSocket de chat.
+pub const WS_PROTOCOL_VERSION: u8 = 1;
+
+fn default_protocol_version() -> u8 {
+    WS_PROTOCOL_VERSION
+}
+
+/// Enveloppe versionnée de toutes les trames WebSocket : `{"v": 1, "type": "...", ...}`.
+/// Le champ `type` et le contenu viennent de l'événement aplati dans l'enveloppe.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WsEnvelope<T> {
+    #[serde(default = "default_protocol_version")]
+    pub v: u8,
+    #[serde(flatten)]
+    pub event: T,
+}
+
+impl<T> WsEnvelope<T> {
+    pub fn new(event: T) -> Self {
+        WsEnvelope { v: WS_PROTOCOL_VERSION, event }
+    }
+}
+
+/// Commandes envoyées par un client sur le WebSocket (client → serveur).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum ClientCommand {
+    /// S'abonner aux événements d'un channel (l'utilisateur doit être membre du serveur).
+    /// Avec `last_seen_message_id`, les messages manqués sont rejoués avant le direct.
+    #[serde(rename = "subscribe")]
+    Subscribe {
+        server_id: i64,
+        channel_id: i64,
+        #[serde(default)]
+        last_seen_message_id: Option<i64>,
+    },
+    /// Se désabonner d'un channel.
+    #[serde(rename = "unsubscribe")]
+    Unsubscribe { server_id: i64, channel_id: i64 },
+    /// Poster un message dans un channel auquel la session est abonnée.
+    /// Le `nonce` choisi par le client est renvoyé dans l'accusé de réception ou l'erreur.
+    #[serde(rename = "message.create")]
+    MessageCreate {
+        server_id: i64,
+        channel_id: i64,
+        content: String,
+        #[serde(default)]
+        nonce: Option<String>,
+    },
+    /// Choisir son statut de présence (en ligne, absent, ne pas déranger ou invisible).
+    #[serde(rename = "presence.set")]
+    PresenceSet { status: UserStatus },
+    /// Signaler que l'utilisateur écrit dans un channel (à rafraîchir tant qu'il écrit).
+    #[serde(rename = "typing.start")]
+    TypingStart { server_id: i64, channel_id: i64 },
+    /// Signaler que l'utilisateur a arrêté d'écrire.
+    #[serde(rename = "typing.stop")]
+    TypingStop { server_id: i64, channel_id: i64 },
+}
+
+/// Statut choisi par l'utilisateur. `invisible` le fait apparaître hors ligne aux autres membres.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UserStatus {
+    #[default]
+    Online,
+    Idle,
+    Dnd,
+    Invisible,
+}
+
+/// Présence visible par les autres membres des serveurs de l'utilisateur.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+    Online,
+    Idle,
+    Dnd,
+    Offline,
+}
+
+impl UserStatus {
+    /// Présence diffusée aux autres membres pour ce statut.
+    pub fn presence(self) -> PresenceStatus {
+        match self {
+            UserStatus::Online => PresenceStatus::Online,
+            UserStatus::Idle => PresenceStatus::Idle,
+            UserStatus::Dnd => PresenceStatus::Dnd,
+            UserStatus::Invisible => PresenceStatus::Offline,
+        }
+    }
+}
+
+/// Codes d'erreur stables renvoyés dans les trames `error`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WsErrorCode {
+    InvalidPayload,
+    UnsupportedVersion,
+    Forbidden,
+    NotSubscribed,
+    TimedOut,
+    Storage,
+}
+
+/// Raison pour laquelle le serveur retire un abonnement à une session.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevokeReason {
+    Kicked,
+    LeftServer,
+    ChannelDeleted,
+    AccessRevoked, // le channel est devenu privé ou une exception retire VIEW_CHANNEL
+    Banned,
+}
+
+/// Événements diffusés par le serveur aux clients WebSocket (serveur → client).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum ChatEvent {
+    /// Confirmation d'abonnement, envoyée uniquement à la session abonnée.
+    #[serde(rename = "subscribed")]
+    Subscribed { server_id: i64, channel_id: i64 },
+    /// Confirmation de désabonnement, envoyée uniquement à la session concernée.
+    #[serde(rename = "unsubscribed")]
+    Unsubscribed { server_id: i64, channel_id: i64 },
+    /// Nouveau message posté dans un channel, diffusé une fois enregistré en base.
+    #[serde(rename = "message.create")]
+    MessageCreate {
+        id: i64,
+        server_id: i64,
+        channel_id: i64,
+        author_id: i64,
+        username: String,
+        content: String,
+        timestamp: String,
+    },
+    /// Reprise limitée à WS_REPLAY_LIMIT messages : la suite, après `after_id`, est à charger via /api/channel-messages.
+    #[serde(rename = "replay.truncated")]
+    ReplayTruncated { server_id: i64, channel_id: i64, after_id: i64 },
+    /// Message modifié par son auteur, diffusé une fois la modification enregistrée.
+    #[serde(rename = "message.update")]
+    MessageUpdate {
+        id: i64,
+        server_id: i64,
+        channel_id: i64,
+        author_id: i64,
+        content: String,
+        edited_at: String,
+    },
+    /// Message supprimé (par son auteur, un admin ou le fondateur).
+    #[serde(rename = "message.delete")]
+    MessageDelete { id: i64, server_id: i64, channel_id: i64 },
+    /// Abonnement retiré par le serveur (exclusion, départ du serveur ou channel supprimé).
+    #[serde(rename = "subscription.revoked")]
+    SubscriptionRevoked { server_id: i64, channel_id: i64, reason: RevokeReason },
+    /// Accusé de réception envoyé à l'auteur une fois son message enregistré.
+    #[serde(rename = "message.ack")]
+    MessageAck {
+        nonce: Option<String>,
+        id: i64,
+        server_id: i64,
+        channel_id: i64,
+        timestamp: String,
+    },
+    /// Changement de présence d'un membre d'un serveur commun (hors channel : jamais filtré par abonnement).
+    #[serde(rename = "presence.update")]
+    PresenceUpdate { user_id: i64, status: PresenceStatus },
+    /// Un utilisateur écrit dans le channel (jamais enregistré en base).
+    #[serde(rename = "typing.start")]
+    TypingStart { server_id: i64, channel_id: i64, user_id: i64, username: String },
+    /// L'utilisateur a arrêté d'écrire, ou son indicateur a expiré.
+    #[serde(rename = "typing.stop")]
+    TypingStop { server_id: i64, channel_id: i64, user_id: i64, username: String },
+    /// Un utilisateur a rejoint le channel.
+    #[serde(rename = "member.join")]
+    MemberJoin {
+        server_id: i64,
+        channel_id: i64,
+        user_id: i64,
+        username: String,
+        timestamp: String,
+    },
+    /// Un utilisateur a quitté le channel.
+    #[serde(rename = "member.leave")]
+    MemberLeave {
+        server_id: i64,
+        channel_id: i64,
+        user_id: i64,
+        username: String,
+        timestamp: String,
+    },
+    /// Membre réduit au silence jusqu'à `until`, envoyé aux membres connectés du serveur (hors channel).
+    #[serde(rename = "timeout.start")]
+    TimeoutStart { server_id: i64, user_id: i64, moderator_id: i64, until: String },
+    /// Fin du silence d'un membre (expiration ou levée par un modérateur), envoyée comme timeout.start.
+    #[serde(rename = "timeout.end")]
+    TimeoutEnd { server_id: i64, user_id: i64 },
+    /// Erreur renvoyée uniquement à l'émetteur d'une commande refusée.
+    #[serde(rename = "error")]
+    Error {
+        code: WsErrorCode,
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        nonce: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        remaining_secs: Option<u64>, // code timed_out : secondes de silence restantes
+    },
+}
+
+/// Message envoyé sur le bus Actix pour diffuser un événement de chat dans un serveur / channel.
+#[derive(actix::Message)]
 #[rtype(result = "()")]
 pub struct ChatMessage {
     pub server_id: i64,
     pub channel_id: i64,
-    pub content: String,
+    pub content: ChatEvent,
 }
 
-/// Message Actix pour rejoindre un chat (associe une adresse WebSocket à un serveur + channel + user).
-#[derive(Message)]
-#[rtype(result = "()")]
+/// Message Actix pour enregistrer une session WebSocket d'un utilisateur (renvoie l'id de session).
+#[derive(actix::Message)]
+#[rtype(result = "usize")]
 pub struct JoinChat {
     pub addr: Recipient<ChatMessage>,
-    pub server_id: i64,
-    pub channel_id: i64,
     pub user_id: i64,
+    pub server_ids: HashSet<i64>, // serveurs dont l'utilisateur est membre, pour la présence
 }
 
 /// Message Actix pour quitter le chat (décrémenter / nettoyer les sessions d'un user).
-#[derive(Message)]
+#[derive(actix::Message)]
 #[rtype(result = "()")]
 pub struct LeaveChat {
     pub user_id: i64,
-    pub addr: Option<Recipient<ChatMessage>>, // Optionnel : pour retirer la session de la liste
+    pub session_id: Option<usize>, // Optionnel : pour retirer la session et ses abonnements
 }
 
+/// Message Actix pour abonner une session aux événements d'un channel.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct SubscribeChannel {
+    pub session_id: usize,
+    pub server_id: i64,
+    pub channel_id: i64,
+}
+
+/// Message Actix pour désabonner une session d'un channel.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct UnsubscribeChannel {
+    pub session_id: usize,
+    pub server_id: i64,
+    pub channel_id: i64,
+}
+
+/// Message Actix pour retirer à un utilisateur tous ses abonnements aux channels d'un serveur.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct RevokeServerAccess {
+    pub server_id: i64,
+    pub user_id: i64,
+    pub reason: RevokeReason,
+}
+
+/// Message Actix pour désabonner d'un channel les sessions d'utilisateurs qui ne peuvent plus le voir.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct RevokeChannelAccess {
+    pub server_id: i64,
+    pub channel_id: i64,
+    pub user_ids: Vec<i64>,
+}
+
+/// Message Actix pour annoncer le silence d'un membre ; le ChatServer annonce aussi sa fin après `duration`.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct StartTimeout {
+    pub timeout: MemberTimeout,
+    pub duration: Duration,
+}
+
+/// Message Actix pour annoncer la fin anticipée du silence d'un membre.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct EndTimeout {
+    pub server_id: i64,
+    pub user_id: i64,
+}
+
+/// Message Actix pour désabonner toutes les sessions d'un channel supprimé.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct CloseChannel {
+    pub server_id: i64,
+    pub channel_id: i64,
+}
+
+/// Message Actix pour démarrer / rafraîchir (`typing: true`) ou arrêter l'indicateur de frappe d'une session.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct SetTyping {
+    pub session_id: usize,
+    pub user_id: i64,
+    pub username: String,
+    pub server_id: i64,
+    pub channel_id: i64,
+    pub typing: bool,
+}
+
+/// Message Actix pour changer le statut de présence choisi par un utilisateur.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct SetUserStatus {
+    pub user_id: i64,
+    pub status: UserStatus,
+}
+
+/// Message Actix pour demander la présence visible des utilisateurs connectés (les autres sont hors ligne).
+#[derive(actix::Message)]
+#[rtype(result = "HashMap<i64, PresenceStatus>")]
+pub struct GetPresences;
+
 /// Message Actix pour demander la liste des utilisateurs connectés (renvoie Vec<i64>).
-#[derive(Message)]
+#[derive(actix::Message)]
 #[rtype(result = "Vec<i64>")]
 pub struct GetConnectedUsers;
 
 /// Message Actix signalant qu'un utilisateur vient de se connecter.
-#[derive(Message)]
+#[derive(actix::Message)]
 #[rtype(result = "()")]
 pub struct UserConnected {
     pub user_id: i64,
 }
 
-/// État interne du serveur de chat (sessions WebSocket et utilisateurs connectés).
+/// Session WebSocket enregistrée dans le ChatServer (adresse, propriétaire et abonnements).
+pub struct ChatSessionEntry {
+    pub addr: Recipient<ChatMessage>,
+    pub user_id: i64,
+    pub channels: HashSet<(i64, i64)>, // (server_id, channel_id) auxquels la session est abonnée
+}
+
+/// Indicateur de frappe actif : le timer le fait expirer s'il n'est pas rafraîchi.
+pub struct TypingIndicator {
+    pub handle: SpawnHandle,
+    pub user_id: i64,
+    pub username: String,
+}
+
+/// État interne du serveur de chat, indexé par session, par channel et par utilisateur
+/// pour que la diffusion et les déconnexions ne parcourent que les sessions concernées.
 pub struct ChatServer {
-    pub sessions: Vec<(Recipient<ChatMessage>, i64, i64)>, // (addr, server_id, channel_id)
-    pub connected_users: std::collections::HashSet<i64>, // user_id des utilisateurs connectés
-    pub user_session_count: std::collections::HashMap<i64, usize>, // Nombre de sessions WebSocket par utilisateur
+    pub sessions: HashMap<usize, ChatSessionEntry>, // session_id -> session
+    pub channels: HashMap<(i64, i64), HashSet<usize>>, // (server_id, channel_id) -> sessions abonnées
+    pub user_sessions: HashMap<i64, HashSet<usize>>, // user_id -> sessions WebSocket ouvertes
+    pub next_session_id: usize,
+    pub connected_users: HashSet<i64>, // user_id des utilisateurs connectés
+    pub typing: HashMap<(usize, i64, i64), TypingIndicator>, // (session_id, server_id, channel_id) -> indicateur actif
+    pub user_servers: HashMap<i64, HashSet<i64>>, // user_id connecté en WebSocket -> serveurs dont il est membre
+    pub server_users: HashMap<i64, HashSet<i64>>, // server_id -> membres connectés en WebSocket
+    pub statuses: HashMap<i64, UserStatus>, // statut choisi par l'utilisateur (en ligne par défaut)
+    pub timeouts: HashMap<(i64, i64), SpawnHandle>, // (server_id, user_id) -> annonce programmée de la fin du silence
 }
 
 
@@ -113,20 +812,47 @@ pub struct UpdateChannelForm {
 /// Formulaire de suppression d'un channel (serveur + id du channel).
 #[derive(Deserialize)]
 pub struct DeleteChannelForm {
-    pub server_id: i64,
+    pub server_id: i64, // ignoré : le serveur est celui du channel
     pub channel_id: i64,
 }
 
+/// Formulaire pour rendre un channel privé ou public.
+#[derive(Deserialize)]
+pub struct ChannelPrivacyForm {
+    pub channel_id: i64,
+    pub private: bool,
+}
+
+/// Formulaire pour créer ou remplacer l'exception d'un rôle ou d'un membre sur un channel.
+#[derive(Deserialize)]
+pub struct ChannelOverwriteForm {
+    pub channel_id: i64,
+    #[serde(flatten)]
+    pub overwrite: PermissionOverwrite,
+}
+
+/// Formulaire pour retirer l'exception d'un rôle ou d'un membre sur un channel.
+#[derive(Deserialize)]
+pub struct DeleteChannelOverwriteForm {
+    pub channel_id: i64,
+    pub kind: OverwriteKind,
+    pub target_id: i64,
+}
+
 /// Paramètres de requête pour récupérer les channels d'un serveur.
 #[derive(Deserialize)]
 pub struct ServerChannelsQuery {
     pub server_id: i64,
 }
 
-/// Paramètres de requête pour récupérer les messages d'un channel.
+/// Paramètres de requête pour récupérer les messages d'un channel, page par page.
+/// `before` / `after` sont des ids de message (curseurs) ; sans curseur, la page la plus récente est renvoyée.
 #[derive(Deserialize)]
 pub struct ChannelMessagesQuery {
     pub channel_id: i64,
+    pub before: Option<i64>,
+    pub after: Option<i64>,
+    pub limit: Option<i64>,
 }
 
 /// Formulaire pour rejoindre un serveur par son id (join-server).
@@ -135,10 +861,12 @@ pub struct JoinServerForm {
     pub server_id: i64,
 }
 
-/// Formulaire pour créer un lien d'invitation pour un serveur.
+/// Formulaire pour créer un lien d'invitation pour un serveur. Sans durée ni maximum, le lien est permanent et illimité.
 #[derive(Deserialize)]
 pub struct CreateInviteLinkForm {
     pub server_id: i64,
+    pub expires_in_secs: Option<i64>,
+    pub max_uses: Option<i64>,
 }
 
 /// Formulaire pour rejoindre un serveur via un lien / code d'invitation.
@@ -147,6 +875,12 @@ pub struct JoinByLinkForm {
     pub link: String,
 }
 
+/// Formulaire pour révoquer un lien d'invitation.
+#[derive(Deserialize)]
+pub struct RevokeInviteForm {
+    pub code: String,
+}
+
 /// Paramètres de requête pour récupérer les membres d'un serveur.
 #[derive(Deserialize)]
 pub struct ServerMembersQuery {
@@ -173,12 +907,49 @@ pub struct LeaveServerForm {
     pub server_id: i64,
 }
 
-/// Formulaire pour changer le rôle d'un membre (admin / membre) sur un serveur.
+/// Formulaire pour changer le rôle d'un membre sur un serveur ("admin", "membre" ou le nom d'un rôle du serveur).
 #[derive(Deserialize)]
 pub struct UpdateMemberRoleForm {
     pub server_id: i64,
     pub user_id: i64,
-    pub role: String, // "admin" ou "membre"
+    pub role: String, // "admin", "membre" ou nom d'un rôle personnalisé
+}
+
+/// Paramètres de /api/server/roles.
+#[derive(Deserialize)]
+pub struct ServerRolesQuery {
+    pub server_id: i64,
+}
+
+/// Formulaire pour créer un rôle (permissions : entier, voir Permissions).
+#[derive(Deserialize)]
+pub struct CreateRoleForm {
+    pub server_id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub permissions: Permissions,
+}
+
+/// Formulaire pour modifier un rôle : seuls les champs renseignés sont modifiés.
+#[derive(Deserialize)]
+pub struct UpdateRoleForm {
+    pub role_id: i64,
+    pub name: Option<String>,
+    pub permissions: Option<Permissions>,
+    pub position: Option<i64>,
+}
+
+/// Formulaire pour supprimer un rôle.
+#[derive(Deserialize)]
+pub struct DeleteRoleForm {
+    pub role_id: i64,
+}
+
+/// Formulaire pour donner ou retirer un rôle à un membre.
+#[derive(Deserialize)]
+pub struct MemberRoleForm {
+    pub role_id: i64,
+    pub user_id: i64,
 }
 
 /// Formulaire pour exclure un membre d'un serveur.
@@ -188,6 +959,60 @@ pub struct KickMemberForm {
     pub user_id: i64, // L'utilisateur à exclure
 }
 
+/// Formulaire pour bannir un utilisateur d'un serveur. Sans durée, le bannissement est définitif.
+#[derive(Deserialize)]
+pub struct BanMemberForm {
+    pub server_id: i64,
+    pub user_id: i64, // L'utilisateur à bannir
+    #[serde(default)]
+    pub reason: String,
+    pub duration_secs: Option<i64>,
+}
+
+/// Formulaire pour réduire un membre au silence pendant `duration_secs` secondes.
+#[derive(Deserialize)]
+pub struct TimeoutMemberForm {
+    pub server_id: i64,
+    pub user_id: i64, // Le membre à réduire au silence
+    pub duration_secs: i64,
+}
+
+/// Formulaire pour rendre la parole à un membre avant la fin de son timeout.
+#[derive(Deserialize)]
+pub struct RemoveTimeoutForm {
+    pub server_id: i64,
+    pub user_id: i64,
+}
+
+/// Formulaire pour lever le bannissement d'un utilisateur.
+#[derive(Deserialize)]
+pub struct UnbanMemberForm {
+    pub server_id: i64,
+    pub user_id: i64,
+}
+
+/// Paramètres de /api/audit-log.
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    pub server_id: i64,
+    pub action: Option<AuditAction>,
+    pub user_id: Option<i64>,
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Paramètres de /api/server-bans.
+#[derive(Deserialize)]
+pub struct ServerBansQuery {
+    pub server_id: i64,
+}
+
+/// Paramètres de /api/server-invites.
+#[derive(Deserialize)]
+pub struct ServerInvitesQuery {
+    pub server_id: i64,
+}
+
 /// Formulaire pour transférer le rôle de fondateur (owner) à un autre membre.
 #[derive(Deserialize)]
 pub struct SwitchOwnerForm {
@@ -195,6 +1020,19 @@ pub struct SwitchOwnerForm {
     pub new_owner_id: i64,
 }
 
+/// Paramètres de /api/message/revisions (historique d'un message).
+#[derive(Deserialize)]
+pub struct MessageRevisionsQuery {
+    pub message_id: i64,
+}
+
+/// Formulaire pour modifier un message (id et nouveau contenu).
+#[derive(Deserialize)]
+pub struct UpdateMessageForm {
+    pub message_id: i64,
+    pub message: String,
+}
+
 /// Formulaire pour supprimer un message (par son id).
 #[derive(Deserialize)]
 pub struct DeleteMessageForm {
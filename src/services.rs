@@ -0,0 +1,207 @@
+//! services.rs :
+//! règles d'accès et lectures partagées par les handlers HTTP et les sessions WebSocket (chat.rs).
+//! Les fonctions s'appuient sur ChatStore et renvoient une FluxyResult : NotFound si le serveur ou le message n'existe pas,
+//! Forbidden si l'utilisateur n'a pas la permission (voir error.rs).
+
+use crate::db_store::ChatStore;
+use crate::error::{FluxyError, FluxyResult};
+use crate::getters;
+use crate::models::{
+    format_date, AppConfig, AuditEntry, AuditLogFilter, Ban, Channel, ChatEvent, ChatServer, Invite, Message, MessageRevision,
+    Permissions, StartTimeout,
+};
+use actix::Addr;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Vérifie en base que le channel appartient au serveur et que l'utilisateur peut le voir
+/// (membre du serveur, VIEW_CHANNEL avec les exceptions du channel).
+pub async fn can_access_channel(store: &dyn ChatStore, user_id: i64, server_id: i64, channel_id: i64) -> FluxyResult<bool> {
+    Ok(store.is_channel_of_server(server_id, channel_id).await?
+        && store.get_channel_permissions(&channel_id, &user_id).await?.contains(Permissions::VIEW_CHANNEL))
+}
+
+/// Renvoie les channels du serveur que l'utilisateur peut voir. Réservé aux membres du serveur.
+pub async fn visible_channels(store: &dyn ChatStore, user_id: i64, server_id: i64) -> FluxyResult<Vec<Channel>> {
+    let Some(server) = store.get_server(&server_id).await? else {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    };
+    if !server.member_id.contains(&user_id) {
+        return Err(FluxyError::Forbidden("Vous n'êtes pas membre de ce serveur".to_string()));
+    }
+    let roles = store.get_roles_of_server(&server_id).await?;
+    let mut channels = store.get_channels_of_server(&server_id).await?;
+    channels.retain(|channel| channel.permissions_of(&server, &roles, user_id).contains(Permissions::VIEW_CHANNEL));
+    Ok(channels)
+}
+
+/// Renvoie les bannissements en cours du serveur, du plus récent au plus ancien. Réservé à BAN_MEMBERS.
+pub async fn server_bans(store: &dyn ChatStore, user_id: i64, server_id: i64) -> FluxyResult<Vec<Ban>> {
+    if store.get_server(&server_id).await?.is_none() {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    }
+    if !store.has_permission(&server_id, &user_id, Permissions::BAN_MEMBERS).await? {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de bannir des membres".to_string()));
+    }
+    // Les bannissements expirés pas encore levés par run_ban_expiry ne sont plus en cours
+    let now = format_date(Utc::now());
+    let mut bans = store.get_bans_of_server(&server_id).await?;
+    bans.retain(|ban| ban.is_active_at(&now));
+    Ok(bans)
+}
+
+/// Renvoie les liens d'invitation encore utilisables du serveur, du plus récent au plus ancien. Réservé à CREATE_INVITES.
+pub async fn server_invites(store: &dyn ChatStore, user_id: i64, server_id: i64) -> FluxyResult<Vec<Invite>> {
+    if store.get_server(&server_id).await?.is_none() {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    }
+    if !store.has_permission(&server_id, &user_id, Permissions::CREATE_INVITES).await? {
+        return Err(FluxyError::Forbidden("Vous n'avez pas la permission de gérer les liens d'invitation".to_string()));
+    }
+    // Les liens expirés ou épuisés restent enregistrés mais ne permettent plus de rejoindre
+    let now = format_date(Utc::now());
+    let mut invites = store.get_invites_of_server(&server_id).await?;
+    invites.retain(|invite| invite.is_usable_at(&now));
+    Ok(invites)
+}
+
+/// Renvoie le journal d'audit du serveur filtré, de l'entrée la plus récente à la plus ancienne.
+/// Réservé au fondateur et aux administrateurs (ADMINISTRATOR).
+pub async fn audit_log(store: &dyn ChatStore, user_id: i64, server_id: i64, filter: &AuditLogFilter) -> FluxyResult<Vec<AuditEntry>> {
+    if store.get_server(&server_id).await?.is_none() {
+        return Err(FluxyError::NotFound("Serveur introuvable".to_string()));
+    }
+    if !store.has_permission(&server_id, &user_id, Permissions::ADMINISTRATOR).await? {
+        return Err(FluxyError::Forbidden("Seuls le fondateur et les administrateurs peuvent consulter le journal d'audit".to_string()));
+    }
+    Ok(store.get_audit_log(&server_id, filter).await?)
+}
+
+/// Lève les bannissements expirés toutes les `every` (tâche lancée au démarrage, voir BAN_EXPIRY_INTERVAL_SECS).
+pub async fn run_ban_expiry(store: Arc<dyn ChatStore>, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    loop {
+        interval.tick().await;
+        match store.delete_expired_bans(&format_date(Utc::now())).await {
+            Ok(0) => {}
+            Ok(count) => println!("[BANS] {} bannissement(s) expiré(s) levé(s)", count),
+            Err(e) => eprintln!("Erreur lors de la levée des bannissements expirés: {}", e),
+        }
+    }
+}
+
+/// Reprogramme dans le ChatServer la fin des timeouts encore en cours (appelée au démarrage) : les timers de StartTimeout
+/// ne vivent qu'en mémoire et disparaissent au redémarrage. Renvoie le nombre de timeouts reprogrammés.
+pub async fn restore_timeouts(store: &dyn ChatStore, chat_server: &Addr<ChatServer>) -> FluxyResult<usize> {
+    let now = Utc::now();
+    let timeouts = store.get_active_timeouts(&format_date(now)).await?;
+    let count = timeouts.len();
+    for timeout in timeouts {
+        let duration = Duration::from_secs(timeout.remaining_secs(now));
+        chat_server.do_send(StartTimeout { timeout, duration });
+    }
+    Ok(count)
+}
+
+/// Renvoie les membres du serveur qui ne peuvent pas voir le channel, pour les désabonner après un changement
+/// de visibilité ou d'exception (voir RevokeChannelAccess).
+pub async fn members_without_access(store: &dyn ChatStore, channel: &Channel) -> FluxyResult<Vec<i64>> {
+    let Some(server) = store.get_server(&channel.server_id).await? else {
+        return Ok(Vec::new());
+    };
+    let roles = store.get_roles_of_server(&channel.server_id).await?;
+    Ok(server
+        .member_id
+        .iter()
+        .copied()
+        .filter(|user_id| !channel.permissions_of(&server, &roles, *user_id).contains(Permissions::VIEW_CHANNEL))
+        .collect())
+}
+
+/// Renvoie les ids des serveurs dont l'utilisateur est membre.
+pub async fn member_server_ids(store: &dyn ChatStore, user_id: i64) -> FluxyResult<HashSet<i64>> {
+    let servers = store.get_servers_by_member(&user_id).await?;
+    Ok(servers.iter().map(|server| server.id).collect())
+}
+
+/// Renvoie un message et ses anciennes versions. Réservé à ceux qui gèrent les messages du serveur (MANAGE_MESSAGES).
+pub async fn message_history(
+    store: &dyn ChatStore,
+    user_id: i64,
+    message_id: i64,
+) -> FluxyResult<(Message, Vec<MessageRevision>)> {
+    let message = store
+        .get_message_by_id(&message_id)
+        .await?
+        .ok_or_else(|| FluxyError::NotFound("Message introuvable".to_string()))?;
+
+    let server_id = store.get_server_id_by_message_id(&message_id).await?;
+    if !store.has_permission(&server_id, &user_id, Permissions::MANAGE_MESSAGES).await? {
+        return Err(FluxyError::Forbidden(
+            "Vous n'avez pas la permission de consulter l'historique d'un message".to_string(),
+        ));
+    }
+
+    let revisions = store.get_message_revisions(&message_id).await?;
+    Ok((message, revisions))
+}
+
+/// Enregistre le message en base et renvoie son id et son horodatage attribués par le serveur.
+pub async fn store_message(
+    store: &dyn ChatStore,
+    user_id: i64,
+    server_id: i64,
+    channel_id: i64,
+    content: &str,
+) -> FluxyResult<(i64, String)> {
+    let message = store.set_message(server_id, channel_id, content, user_id).await?;
+    Ok((message.id, message.time))
+}
+
+/// Relit en base les messages d'un channel postés après `last_seen_id`, sous forme d'événements `message.create`.
+/// Au-delà de `config.ws_replay_limit` messages, la reprise s'arrête sur un événement `replay.truncated`.
+pub async fn load_missed_messages(
+    store: &dyn ChatStore,
+    config: &AppConfig,
+    server_id: i64,
+    channel_id: i64,
+    last_seen_id: i64,
+) -> FluxyResult<Vec<ChatEvent>> {
+    // Un message de plus que la limite pour savoir si la reprise est tronquée
+    let limit = config.ws_replay_limit.max(0);
+    let mut messages = store.get_messages_of_channel_after(&channel_id, &last_seen_id, limit + 1).await?;
+    let truncated = messages.len() as i64 > limit;
+    messages.truncate(limit as usize);
+    if messages.is_empty() && !truncated {
+        return Ok(Vec::new());
+    }
+
+    // Les messages ne stockent que l'id de l'auteur : retrouver les usernames des seuls auteurs du lot côté Supabase
+    let author_ids: Vec<i64> = messages.iter().map(|message| message.user).collect::<HashSet<_>>().into_iter().collect();
+    let usernames_by_id: HashMap<i64, String> = getters::get_users_by_ids(config, &author_ids)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|user| user.id.parse::<i64>().ok().map(|id| (id, user.username)))
+        .collect();
+
+    let after_id = messages.last().map_or(last_seen_id, |message| message.id);
+    let mut events: Vec<ChatEvent> = messages
+        .into_iter()
+        .map(|message| ChatEvent::MessageCreate {
+            id: message.id,
+            server_id,
+            channel_id,
+            author_id: message.user,
+            username: usernames_by_id.get(&message.user).cloned().unwrap_or_default(),
+            content: message.message,
+            timestamp: message.time,
+        })
+        .collect();
+    if truncated {
+        events.push(ChatEvent::ReplayTruncated { server_id, channel_id, after_id });
+    }
+    Ok(events)
+}
@@ -0,0 +1,620 @@
+#![allow(warnings)]
+use T_JSF_600_MAR_1::models::{
+    ChatEvent, ChatMessage, ChatServer, ClientCommand, CloseChannel, EndTimeout, format_date, GetConnectedUsers, GetPresences, JoinChat,
+    LeaveChat, MemberTimeout, PresenceStatus, StartTimeout, RevokeChannelAccess, RevokeReason, RevokeServerAccess, SetTyping, SetUserStatus, SubscribeChannel, UserConnected, UserStatus, WsEnvelope, WsErrorCode, WS_PROTOCOL_VERSION,
+};
+use T_JSF_600_MAR_1::chat::TYPING_TIMEOUT;
+use T_JSF_600_MAR_1::db_memory::MemoryStore;
+use T_JSF_600_MAR_1::db_store::ChatStore;
+use T_JSF_600_MAR_1::services::restore_timeouts;
+use chrono::{TimeDelta, Utc};
+use actix::{Actor, Addr, Context, Handler};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_server_event_envelope() {
+        let event = ChatEvent::MessageCreate {
+            id: 42,
+            server_id: 1,
+            channel_id: 2,
+            author_id: 3,
+            username: "alice".to_string(),
+            content: "bonjour: tout le monde".to_string(),
+            timestamp: "2026-01-01T00:00:00+00:00".to_string(),
+        };
+        let json: serde_json::Value = serde_json::to_value(WsEnvelope::new(&event)).unwrap();
+        println!("test_server_event_envelope => {}", json);
+
+        assert_eq!(json["v"], WS_PROTOCOL_VERSION);
+        assert_eq!(json["type"], "message.create");
+        assert_eq!(json["id"], 42);
+        assert_eq!(json["channel_id"], 2);
+        assert_eq!(json["author_id"], 3);
+        assert_eq!(json["username"], "alice");
+        assert_eq!(json["content"], "bonjour: tout le monde");
+
+        let back: WsEnvelope<ChatEvent> = serde_json::from_value(json).unwrap();
+        assert_eq!(back.event, event);
+    }
+
+    #[actix_web::test]
+    async fn test_client_command_parsing() {
+        let envelope: WsEnvelope<ClientCommand> =
+            serde_json::from_str(r#"{"v":1,"type":"message.create","server_id":1,"channel_id":2,"content":"salut"}"#).unwrap();
+        assert_eq!(envelope.v, 1);
+        assert_eq!(
+            envelope.event,
+            ClientCommand::MessageCreate { server_id: 1, channel_id: 2, content: "salut".to_string(), nonce: None }
+        );
+
+        // Le nonce du client est optionnel et conservé tel quel
+        let envelope: WsEnvelope<ClientCommand> = serde_json::from_str(
+            r#"{"v":1,"type":"message.create","server_id":1,"channel_id":2,"content":"salut","nonce":"abc-1"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            envelope.event,
+            ClientCommand::MessageCreate {
+                server_id: 1,
+                channel_id: 2,
+                content: "salut".to_string(),
+                nonce: Some("abc-1".to_string()),
+            }
+        );
+
+        // Sans "v", la version courante est supposée
+        let envelope: WsEnvelope<ClientCommand> =
+            serde_json::from_str(r#"{"type":"subscribe","server_id":1,"channel_id":2}"#).unwrap();
+        assert_eq!(envelope.v, WS_PROTOCOL_VERSION);
+        assert_eq!(
+            envelope.event,
+            ClientCommand::Subscribe { server_id: 1, channel_id: 2, last_seen_message_id: None }
+        );
+
+        // Reprise après reconnexion : dernier message vu sur le channel
+        let envelope: WsEnvelope<ClientCommand> =
+            serde_json::from_str(r#"{"v":1,"type":"subscribe","server_id":1,"channel_id":2,"last_seen_message_id":41}"#)
+                .unwrap();
+        assert_eq!(
+            envelope.event,
+            ClientCommand::Subscribe { server_id: 1, channel_id: 2, last_seen_message_id: Some(41) }
+        );
+
+        // Un message sans channel cible est rejeté
+        assert!(serde_json::from_str::<WsEnvelope<ClientCommand>>(r#"{"v":1,"type":"message.create","content":"salut"}"#).is_err());
+
+        // Type inconnu ou texte brut : rejeté
+        assert!(serde_json::from_str::<WsEnvelope<ClientCommand>>(r#"{"v":1,"type":"inconnu"}"#).is_err());
+        assert!(serde_json::from_str::<WsEnvelope<ClientCommand>>("bonjour").is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_error_event_code() {
+        let event = ChatEvent::Error {
+            code: WsErrorCode::InvalidPayload,
+            message: "Trame JSON invalide".to_string(),
+            nonce: None,
+            remaining_secs: None,
+        };
+        let json: serde_json::Value = serde_json::to_value(WsEnvelope::new(&event)).unwrap();
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["code"], "invalid_payload");
+        assert!(json.get("nonce").is_none());
+
+        // Une erreur sur message.create renvoie le nonce du client
+        let event = ChatEvent::Error {
+            code: WsErrorCode::Forbidden,
+            message: "Accès refusé à ce channel".to_string(),
+            nonce: Some("abc-1".to_string()),
+            remaining_secs: None,
+        };
+        let json: serde_json::Value = serde_json::to_value(WsEnvelope::new(&event)).unwrap();
+        assert_eq!(json["code"], "forbidden");
+        assert_eq!(json["nonce"], "abc-1");
+        assert!(json.get("remaining_secs").is_none());
+
+        // Un membre réduit au silence apprend combien de temps il doit encore attendre
+        let event = ChatEvent::Error {
+            code: WsErrorCode::TimedOut,
+            message: "Vous êtes réduit au silence sur ce serveur".to_string(),
+            nonce: Some("abc-2".to_string()),
+            remaining_secs: Some(90),
+        };
+        let json: serde_json::Value = serde_json::to_value(WsEnvelope::new(&event)).unwrap();
+        assert_eq!(json["code"], "timed_out");
+        assert_eq!(json["remaining_secs"], 90);
+    }
+
+    #[actix_web::test]
+    async fn test_message_ack_event() {
+        let event = ChatEvent::MessageAck {
+            nonce: Some("abc-1".to_string()),
+            id: 12,
+            server_id: 1,
+            channel_id: 2,
+            timestamp: "2026-01-01T00:00:00+00:00".to_string(),
+        };
+        let json: serde_json::Value = serde_json::to_value(WsEnvelope::new(&event)).unwrap();
+        println!("test_message_ack_event => {}", json);
+
+        assert_eq!(json["type"], "message.ack");
+        assert_eq!(json["nonce"], "abc-1");
+        assert_eq!(json["id"], 12);
+    }
+
+    #[actix_web::test]
+    async fn test_replay_truncated_event() {
+        let event = ChatEvent::ReplayTruncated { server_id: 1, channel_id: 2, after_id: 141 };
+        let json: serde_json::Value = serde_json::to_value(WsEnvelope::new(&event)).unwrap();
+        println!("test_replay_truncated_event => {}", json);
+
+        assert_eq!(json["type"], "replay.truncated");
+        assert_eq!(json["after_id"], 141);
+        let back: WsEnvelope<ChatEvent> = serde_json::from_value(json).unwrap();
+        assert_eq!(back.event, event);
+    }
+
+    #[actix_web::test]
+    async fn test_message_edit_events() {
+        let event = ChatEvent::MessageUpdate {
+            id: 12,
+            server_id: 1,
+            channel_id: 2,
+            author_id: 3,
+            content: "bonjour (modifié)".to_string(),
+            edited_at: "2026-01-01T00:05:00+00:00".to_string(),
+        };
+        let json: serde_json::Value = serde_json::to_value(WsEnvelope::new(&event)).unwrap();
+        println!("test_message_edit_events => {}", json);
+        assert_eq!(json["type"], "message.update");
+        assert_eq!(json["id"], 12);
+        assert_eq!(json["content"], "bonjour (modifié)");
+        assert_eq!(json["edited_at"], "2026-01-01T00:05:00+00:00");
+
+        let event = ChatEvent::MessageDelete { id: 12, server_id: 1, channel_id: 2 };
+        let json: serde_json::Value = serde_json::to_value(WsEnvelope::new(&event)).unwrap();
+        println!("test_message_edit_events => {}", json);
+        assert_eq!(json["type"], "message.delete");
+        assert_eq!(json["id"], 12);
+        assert_eq!(json["channel_id"], 2);
+    }
+
+    // Session synthétique : compte simplement les événements reçus du ChatServer
+    struct SyntheticSession {
+        received: Arc<AtomicUsize>,
+    }
+
+    impl Actor for SyntheticSession {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<ChatMessage> for SyntheticSession {
+        type Result = ();
+
+        fn handle(&mut self, _msg: ChatMessage, _ctx: &mut Context<Self>) {
+            self.received.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn test_message(server_id: i64, channel_id: i64) -> ChatMessage {
+        ChatMessage {
+            server_id,
+            channel_id,
+            content: ChatEvent::MessageCreate {
+                id: 0,
+                server_id,
+                channel_id,
+                author_id: 0,
+                username: "bench".to_string(),
+                content: "ping".to_string(),
+                timestamp: "2026-01-01T00:00:00+00:00".to_string(),
+            },
+        }
+    }
+
+    async fn wait_for(counter: &AtomicUsize, expected: usize) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while counter.load(Ordering::Relaxed) < expected {
+            if Instant::now() > deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        true
+    }
+
+    #[actix_web::test]
+    async fn test_chat_server_routing_benchmark() {
+        const SESSIONS: usize = 3000;
+        const CHANNELS: i64 = 100;
+        const MESSAGES: usize = 200;
+
+        let server = ChatServer::new().start();
+        let received = Arc::new(AtomicUsize::new(0));
+        let mut session_ids = Vec::with_capacity(SESSIONS);
+        let mut synthetic: Vec<Addr<SyntheticSession>> = Vec::with_capacity(SESSIONS);
+
+        // Chaque session appartient à un utilisateur distinct et s'abonne à un seul channel
+        let start = Instant::now();
+        for i in 0..SESSIONS {
+            let addr = SyntheticSession { received: received.clone() }.start();
+            let session_id = server
+                .send(JoinChat { addr: addr.clone().recipient(), user_id: i as i64, server_ids: HashSet::new() })
+                .await
+                .unwrap();
+            server.do_send(SubscribeChannel {
+                session_id,
+                server_id: 1,
+                channel_id: i as i64 % CHANNELS,
+            });
+            session_ids.push(session_id);
+            synthetic.push(addr);
+        }
+        println!("test_chat_server_routing_benchmark => {} sessions enregistrées en {:?}", SESSIONS, start.elapsed());
+
+        // Diffusion dans un seul channel : seuls ses abonnés reçoivent les messages
+        let subscribers_per_channel = SESSIONS / CHANNELS as usize;
+        let start = Instant::now();
+        for _ in 0..MESSAGES {
+            server.do_send(test_message(1, 0));
+        }
+        assert!(wait_for(&received, MESSAGES * subscribers_per_channel).await);
+        println!(
+            "test_chat_server_routing_benchmark => {} messages x {} abonnés livrés en {:?}",
+            MESSAGES, subscribers_per_channel, start.elapsed()
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(received.load(Ordering::Relaxed), MESSAGES * subscribers_per_channel);
+
+        // Un channel sans abonnés ne livre rien
+        server.do_send(test_message(2, 0));
+
+        // Déconnexion de toutes les sessions
+        let start = Instant::now();
+        for (i, session_id) in session_ids.iter().enumerate() {
+            server.do_send(LeaveChat { user_id: i as i64, session_id: Some(*session_id) });
+        }
+        let connected = server.send(GetConnectedUsers).await.unwrap();
+        println!("test_chat_server_routing_benchmark => {} sessions retirées en {:?}", SESSIONS, start.elapsed());
+        assert!(connected.is_empty());
+
+        // Plus aucune livraison après déconnexion
+        server.do_send(test_message(1, 0));
+        server.send(GetConnectedUsers).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(received.load(Ordering::Relaxed), MESSAGES * subscribers_per_channel);
+    }
+
+    #[actix_web::test]
+    async fn test_chat_server_user_with_several_sessions() {
+        let server = ChatServer::new().start();
+        let received = Arc::new(AtomicUsize::new(0));
+        let first = SyntheticSession { received: received.clone() }.start();
+        let second = SyntheticSession { received: received.clone() }.start();
+
+        let first_id = server.send(JoinChat { addr: first.recipient(), user_id: 7, server_ids: HashSet::new() }).await.unwrap();
+        let second_id = server.send(JoinChat { addr: second.recipient(), user_id: 7, server_ids: HashSet::new() }).await.unwrap();
+        assert_ne!(first_id, second_id);
+
+        // L'utilisateur reste connecté tant qu'une de ses sessions est ouverte
+        server.do_send(LeaveChat { user_id: 7, session_id: Some(first_id) });
+        assert_eq!(server.send(GetConnectedUsers).await.unwrap(), vec![7]);
+        server.do_send(LeaveChat { user_id: 7, session_id: Some(second_id) });
+        assert!(server.send(GetConnectedUsers).await.unwrap().is_empty());
+    }
+
+    // Session synthétique : conserve les événements reçus pour vérifier leur type
+    struct RecordingSession {
+        events: Arc<Mutex<Vec<ChatEvent>>>,
+    }
+
+    impl Actor for RecordingSession {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<ChatMessage> for RecordingSession {
+        type Result = ();
+
+        fn handle(&mut self, msg: ChatMessage, _ctx: &mut Context<Self>) {
+            self.events.lock().unwrap().push(msg.content);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_chat_server_revokes_live_subscriptions() {
+        let server = ChatServer::new().start();
+        let kicked_events = Arc::new(Mutex::new(Vec::new()));
+        let other_events = Arc::new(Mutex::new(Vec::new()));
+        let kicked = RecordingSession { events: kicked_events.clone() }.start();
+        let other = RecordingSession { events: other_events.clone() }.start();
+
+        let kicked_id = server.send(JoinChat { addr: kicked.recipient(), user_id: 1, server_ids: HashSet::new() }).await.unwrap();
+        let other_id = server.send(JoinChat { addr: other.recipient(), user_id: 2, server_ids: HashSet::new() }).await.unwrap();
+        for (session_id, channel_id) in [(kicked_id, 10), (kicked_id, 11), (other_id, 10), (other_id, 11)] {
+            server.do_send(SubscribeChannel { session_id, server_id: 1, channel_id });
+        }
+        server.do_send(SubscribeChannel { session_id: kicked_id, server_id: 2, channel_id: 20 });
+
+        // Exclusion du serveur 1 : seuls les abonnements de ce serveur sont retirés
+        server.do_send(RevokeServerAccess { server_id: 1, user_id: 1, reason: RevokeReason::Kicked });
+        server.do_send(test_message(1, 10));
+        server.do_send(test_message(2, 20));
+        server.send(GetConnectedUsers).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let events = kicked_events.lock().unwrap().clone();
+        println!("test_chat_server_revokes_live_subscriptions => {:?}", events);
+        let revoked: Vec<i64> = events
+            .iter()
+            .filter_map(|event| match event {
+                ChatEvent::SubscriptionRevoked { server_id: 1, channel_id, reason: RevokeReason::Kicked } => Some(*channel_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(revoked.len(), 2);
+        assert!(revoked.contains(&10) && revoked.contains(&11));
+        assert_eq!(events.iter().filter(|event| matches!(event, ChatEvent::MessageCreate { .. })).count(), 1);
+        assert_eq!(other_events.lock().unwrap().len(), 1);
+
+        // Suppression d'un channel : toutes les sessions encore abonnées sont désabonnées
+        server.do_send(CloseChannel { server_id: 1, channel_id: 11 });
+        server.do_send(test_message(1, 11));
+        server.send(GetConnectedUsers).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let events = other_events.lock().unwrap().clone();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[1],
+            ChatEvent::SubscriptionRevoked { server_id: 1, channel_id: 11, reason: RevokeReason::ChannelDeleted }
+        );
+
+        // Channel devenu privé : seules les sessions des utilisateurs listés sont désabonnées
+        server.do_send(RevokeChannelAccess { server_id: 1, channel_id: 10, user_ids: vec![2, 3] });
+        server.do_send(test_message(1, 10));
+        server.send(GetConnectedUsers).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let events = other_events.lock().unwrap().clone();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events[2],
+            ChatEvent::SubscriptionRevoked { server_id: 1, channel_id: 10, reason: RevokeReason::AccessRevoked }
+        );
+    }
+
+    fn typing(session_id: usize, user_id: i64, typing: bool) -> SetTyping {
+        SetTyping {
+            session_id,
+            user_id,
+            username: format!("user{}", user_id),
+            server_id: 1,
+            channel_id: 10,
+            typing,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_chat_server_typing_indicator() {
+        let server = ChatServer::new().start();
+        let writer_events = Arc::new(Mutex::new(Vec::new()));
+        let reader_events = Arc::new(Mutex::new(Vec::new()));
+        let writer = RecordingSession { events: writer_events.clone() }.start();
+        let reader = RecordingSession { events: reader_events.clone() }.start();
+
+        let writer_id = server.send(JoinChat { addr: writer.recipient(), user_id: 1, server_ids: HashSet::new() }).await.unwrap();
+        let reader_id = server.send(JoinChat { addr: reader.recipient(), user_id: 2, server_ids: HashSet::new() }).await.unwrap();
+        server.do_send(SubscribeChannel { session_id: writer_id, server_id: 1, channel_id: 10 });
+        server.do_send(SubscribeChannel { session_id: reader_id, server_id: 1, channel_id: 10 });
+
+        // Démarrage puis rafraîchissement : un seul typing.start, jamais renvoyé à l'émetteur
+        server.do_send(typing(writer_id, 1, true));
+        server.do_send(typing(writer_id, 1, true));
+        server.send(GetConnectedUsers).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            *reader_events.lock().unwrap(),
+            vec![ChatEvent::TypingStart { server_id: 1, channel_id: 10, user_id: 1, username: "user1".to_string() }]
+        );
+        assert!(writer_events.lock().unwrap().is_empty());
+
+        // Arrêt explicite
+        server.do_send(typing(writer_id, 1, false));
+        server.send(GetConnectedUsers).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            reader_events.lock().unwrap().last(),
+            Some(&ChatEvent::TypingStop { server_id: 1, channel_id: 10, user_id: 1, username: "user1".to_string() })
+        );
+
+        // Sans rafraîchissement, l'indicateur expire tout seul
+        server.do_send(typing(writer_id, 1, true));
+        let start = Instant::now();
+        assert!(
+            wait_for_event(&reader_events, 4, |event| matches!(event, ChatEvent::TypingStop { .. })).await
+        );
+        println!("test_chat_server_typing_indicator => expiration après {:?}", start.elapsed());
+        assert!(start.elapsed() >= TYPING_TIMEOUT);
+
+        // Une session non abonnée ne peut pas signaler qu'elle écrit
+        let outsider = RecordingSession { events: Arc::new(Mutex::new(Vec::new())) }.start();
+        let outsider_id = server.send(JoinChat { addr: outsider.recipient(), user_id: 3, server_ids: HashSet::new() }).await.unwrap();
+        server.do_send(typing(outsider_id, 3, true));
+        server.send(GetConnectedUsers).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(reader_events.lock().unwrap().len(), 4);
+    }
+
+    async fn wait_for_event(events: &Mutex<Vec<ChatEvent>>, expected: usize, last: impl Fn(&ChatEvent) -> bool) -> bool {
+        let deadline = Instant::now() + TYPING_TIMEOUT * 2;
+        loop {
+            {
+                let events = events.lock().unwrap();
+                if events.len() >= expected && events.last().is_some_and(&last) {
+                    return true;
+                }
+            }
+            if Instant::now() > deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    fn presences(events: &Mutex<Vec<ChatEvent>>) -> Vec<(i64, PresenceStatus)> {
+        events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                ChatEvent::PresenceUpdate { user_id, status } => Some((*user_id, *status)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_chat_server_presence_updates() {
+        let server = ChatServer::new().start();
+        let watcher_events = Arc::new(Mutex::new(Vec::new()));
+        let stranger_events = Arc::new(Mutex::new(Vec::new()));
+        let watcher = RecordingSession { events: watcher_events.clone() }.start();
+        let stranger = RecordingSession { events: stranger_events.clone() }.start();
+        server.send(JoinChat { addr: watcher.recipient(), user_id: 1, server_ids: HashSet::from([1]) }).await.unwrap();
+        server.send(JoinChat { addr: stranger.recipient(), user_id: 2, server_ids: HashSet::from([2]) }).await.unwrap();
+
+        // 0 -> 1 session : les co-membres du serveur 1 sont prévenus, pas les autres
+        let first = RecordingSession { events: Arc::new(Mutex::new(Vec::new())) }.start();
+        let second = RecordingSession { events: Arc::new(Mutex::new(Vec::new())) }.start();
+        let first_id = server.send(JoinChat { addr: first.recipient(), user_id: 3, server_ids: HashSet::from([1, 3]) }).await.unwrap();
+        let second_id = server.send(JoinChat { addr: second.recipient(), user_id: 3, server_ids: HashSet::from([1, 3]) }).await.unwrap();
+        server.do_send(LeaveChat { user_id: 3, session_id: Some(first_id) });
+
+        // Changement de statut : idle puis invisible (vu hors ligne)
+        server.do_send(SetUserStatus { user_id: 3, status: UserStatus::Idle });
+        server.do_send(SetUserStatus { user_id: 3, status: UserStatus::Idle });
+        let presence = server.send(GetPresences).await.unwrap();
+        assert_eq!(presence.get(&3), Some(&PresenceStatus::Idle));
+        server.do_send(SetUserStatus { user_id: 3, status: UserStatus::Invisible });
+        let presence = server.send(GetPresences).await.unwrap();
+        assert_eq!(presence.get(&3), None);
+        assert_eq!(presence.get(&1), Some(&PresenceStatus::Online));
+
+        // 1 -> 0 session en invisible : rien à annoncer, l'utilisateur paraissait déjà hors ligne
+        server.do_send(LeaveChat { user_id: 3, session_id: Some(second_id) });
+        server.send(GetConnectedUsers).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let received = presences(&watcher_events);
+        println!("test_chat_server_presence_updates => {:?}", received);
+        assert_eq!(
+            received,
+            vec![(3, PresenceStatus::Online), (3, PresenceStatus::Idle), (3, PresenceStatus::Offline)]
+        );
+        assert!(presences(&stranger_events).is_empty());
+
+        // Retour en ligne puis déconnexion : online puis offline
+        server.do_send(SetUserStatus { user_id: 3, status: UserStatus::Online });
+        let third = RecordingSession { events: Arc::new(Mutex::new(Vec::new())) }.start();
+        let third_id = server.send(JoinChat { addr: third.recipient(), user_id: 3, server_ids: HashSet::from([1]) }).await.unwrap();
+        server.do_send(LeaveChat { user_id: 3, session_id: Some(third_id) });
+        server.send(GetConnectedUsers).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(presences(&watcher_events)[3..], [(3, PresenceStatus::Online), (3, PresenceStatus::Offline)]);
+    }
+
+    #[actix_web::test]
+    async fn test_chat_server_presence_follows_sessions() {
+        let server = ChatServer::new().start();
+        let watcher_events = Arc::new(Mutex::new(Vec::new()));
+        let watcher = RecordingSession { events: watcher_events.clone() }.start();
+        server.send(JoinChat { addr: watcher.recipient(), user_id: 1, server_ids: HashSet::from([1]) }).await.unwrap();
+
+        // Login via l'API sans WebSocket : connecté, mais pas présent
+        server.do_send(UserConnected { user_id: 4 });
+        assert!(server.send(GetConnectedUsers).await.unwrap().contains(&4));
+        assert_eq!(server.send(GetPresences).await.unwrap().get(&4), None);
+
+        // Logout via l'API alors qu'une session WebSocket reste ouverte : toujours présent
+        let session = RecordingSession { events: Arc::new(Mutex::new(Vec::new())) }.start();
+        server.send(JoinChat { addr: session.recipient(), user_id: 4, server_ids: HashSet::from([1]) }).await.unwrap();
+        server.do_send(LeaveChat { user_id: 4, session_id: None });
+        let presence = server.send(GetPresences).await.unwrap();
+        println!("test_chat_server_presence_follows_sessions => {:?}", presence);
+        assert_eq!(presence.get(&4), Some(&PresenceStatus::Online));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(presences(&watcher_events), vec![(4, PresenceStatus::Online)]);
+    }
+
+    fn timeout_events(events: &Mutex<Vec<ChatEvent>>) -> Vec<ChatEvent> {
+        events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| matches!(event, ChatEvent::TimeoutStart { .. } | ChatEvent::TimeoutEnd { .. }))
+            .cloned()
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_chat_server_timeout_events() {
+        let server = ChatServer::new().start();
+        let moderator_events = Arc::new(Mutex::new(Vec::new()));
+        let muted_events = Arc::new(Mutex::new(Vec::new()));
+        let stranger_events = Arc::new(Mutex::new(Vec::new()));
+        let moderator = RecordingSession { events: moderator_events.clone() }.start();
+        let muted = RecordingSession { events: muted_events.clone() }.start();
+        let stranger = RecordingSession { events: stranger_events.clone() }.start();
+        server.send(JoinChat { addr: moderator.recipient(), user_id: 1, server_ids: HashSet::from([1]) }).await.unwrap();
+        server.send(JoinChat { addr: muted.recipient(), user_id: 2, server_ids: HashSet::from([1]) }).await.unwrap();
+        server.send(JoinChat { addr: stranger.recipient(), user_id: 3, server_ids: HashSet::from([2]) }).await.unwrap();
+
+        let timeout = MemberTimeout { server_id: 1, user_id: 2, moderator_id: 1, until: "2026-01-01T00:00:01Z".to_string() };
+        let start = ChatEvent::TimeoutStart { server_id: 1, user_id: 2, moderator_id: 1, until: timeout.until.clone() };
+        let end = ChatEvent::TimeoutEnd { server_id: 1, user_id: 2 };
+
+        // La fin est annoncée toute seule après la durée du timeout, à tous les membres connectés du serveur
+        server.do_send(StartTimeout { timeout: timeout.clone(), duration: Duration::from_millis(100) });
+        assert!(wait_for_event(&muted_events, 2, |event| matches!(event, ChatEvent::TimeoutEnd { .. })).await);
+        assert_eq!(timeout_events(&moderator_events), vec![start.clone(), end.clone()]);
+        assert_eq!(timeout_events(&muted_events), vec![start.clone(), end.clone()]);
+        assert!(timeout_events(&stranger_events).is_empty());
+
+        // Levée anticipée : la fin programmée est annulée, une seule fin est annoncée
+        server.do_send(StartTimeout { timeout, duration: Duration::from_millis(200) });
+        server.do_send(EndTimeout { server_id: 1, user_id: 2 });
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        let received = timeout_events(&moderator_events);
+        println!("test_chat_server_timeout_events => {:?}", received);
+        assert_eq!(received, vec![start.clone(), end.clone(), start, end]);
+    }
+
+    // Redémarrage : les timeouts en cours dans le stockage retrouvent leur fin programmée
+    #[actix_web::test]
+    async fn test_restore_timeouts() -> std::io::Result<()> {
+        let store = MemoryStore::new();
+        store.set_server(1, "serveur redémarré", None).await?;
+        let server_id = store.get_last_id("server").await?;
+        store.add_member_to_server(server_id, 2).await?;
+        store.set_timeout(server_id, 2, format_date(Utc::now() + TimeDelta::seconds(1)), 1).await?;
+
+        let server = ChatServer::new().start();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let moderator = RecordingSession { events: events.clone() }.start();
+        server.send(JoinChat { addr: moderator.recipient(), user_id: 1, server_ids: HashSet::from([server_id]) }).await.unwrap();
+
+        assert_eq!(restore_timeouts(&store, &server).await?, 1);
+        assert!(wait_for_event(&events, 2, |event| matches!(event, ChatEvent::TimeoutEnd { .. })).await);
+        let received = timeout_events(&events);
+        println!("test_restore_timeouts => {:?}", received);
+        assert_eq!(received.last(), Some(&ChatEvent::TimeoutEnd { server_id, user_id: 2 }));
+        Ok(())
+    }
+}
@@ -1,14 +1,19 @@
 #![allow(warnings)]
 use T_JSF_600_MAR_1::{
-    db_mongo_connection,
-    db_mongo_getter,
-    db_mongo_setter,
-    db_mongo_delete,
-    db_mongo_update
+    services::{audit_log, can_access_channel, load_missed_messages, message_history, server_bans, server_invites, visible_channels},
+    db_store::{ChatStore, MongoStore},
+    db_memory::MemoryStore,
+    error::FluxyError,
+    models::{format_date, AppConfig, AuditAction, AuditLogFilter, Ban, Channel, ChatEvent, MemberTimeout, Message, NewAuditEntry, OverwriteKind, PermissionOverwrite, Permissions, RepairReport, Server}
 };
+#[cfg(feature = "sql")]
+use T_JSF_600_MAR_1::db_sql::SqlStore;
 use std::{
     env,
-    io};
+    io,
+    time::Duration};
+use chrono::{TimeDelta, Utc};
+use futures_util::TryStreamExt;
     
 use mongodb::{
     bson::{doc, Document, Bson},
@@ -18,13 +23,124 @@ use mongodb::{
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::sync::Mutex;
-    use once_cell::sync::Lazy;
-    
-    // Mutex global pour synchroniser les tests MongoDB
-    // Cela garantit qu'un seul test accède à la base de données "test" à la fois
-    static TEST_MUTEX: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
     
+    // Chaque scénario tourne sur chaque stockage compilé (MemoryStore, et SqlStore avec --features sql),
+    // avec une base vide qui lui est propre : les tests tournent hors ligne et en parallèle.
+    // SqlStore tourne sur SQLite en mémoire, et sur Postgres si TEST_POSTGRES_URL est défini (un schéma par scénario).
+    // MongoStore tourne si TEST_MONGO_URL est défini (une base par scénario) : compteurs next_id, cascade_delete
+    // en transaction (replica set) et $inc des liens d'invitation passent alors par MongoDB
+    macro_rules! for_each_store {
+        ($($scenario:ident),* $(,)?) => {
+            mod memory {
+                $(
+                    #[actix_web::test]
+                    async fn $scenario() -> std::io::Result<()> {
+                        super::$scenario(&super::MemoryStore::new()).await
+                    }
+                )*
+            }
+
+            #[cfg(feature = "sql")]
+            mod sqlite {
+                $(
+                    #[actix_web::test]
+                    async fn $scenario() -> std::io::Result<()> {
+                        super::$scenario(&super::SqlStore::connect("sqlite::memory:").await?).await
+                    }
+                )*
+            }
+
+            #[cfg(feature = "sql")]
+            mod postgres {
+                $(
+                    #[actix_web::test]
+                    async fn $scenario() -> std::io::Result<()> {
+                        match super::postgres_store(stringify!($scenario)).await? {
+                            Some(store) => super::$scenario(&store).await,
+                            None => {
+                                println!("{} => TEST_POSTGRES_URL non défini, scénario Postgres ignoré", stringify!($scenario));
+                                Ok(())
+                            }
+                        }
+                    }
+                )*
+            }
+
+            mod mongo {
+                $(
+                    #[actix_web::test]
+                    async fn $scenario() -> std::io::Result<()> {
+                        match super::mongo_store(stringify!($scenario)).await? {
+                            Some(store) => super::$scenario(&store).await,
+                            None => {
+                                println!("{} => TEST_MONGO_URL non défini, scénario MongoDB ignoré", stringify!($scenario));
+                                Ok(())
+                            }
+                        }
+                    }
+                )*
+            }
+        };
+    }
+
+    for_each_store!(
+        test_mongo_connection,
+        test_mongo_server_set_up,
+        test_mongo_channel_set_up,
+        test_mongo_message_set_up,
+        test_mongo_manipulation_member,
+        test_mongo_link,
+        test_message_update,
+        test_channel_update,
+        test_server_update,
+        test_get_element,
+        test_store_errors,
+        test_typed_models,
+        test_cascade_delete,
+        test_message_edit,
+        test_message_revisions,
+        test_missed_messages,
+        test_roles,
+        test_channel_overwrites,
+        test_bans,
+        test_timeouts,
+        test_audit_log,
+        test_invites,
+    );
+    // Recrée un schéma vide pour le scénario et y ouvre un SqlStore (migrations comprises)
+    #[cfg(feature = "sql")]
+    async fn postgres_store(schema: &str) -> std::io::Result<Option<SqlStore>> {
+        use sqlx::Connection;
+        let Ok(url) = env::var("TEST_POSTGRES_URL") else {
+            return Ok(None);
+        };
+        let to_io = |e: sqlx::Error| io::Error::new(io::ErrorKind::Other, e.to_string());
+
+        sqlx::any::install_default_drivers();
+        let mut conn = sqlx::AnyConnection::connect(&url).await.map_err(to_io)?;
+        sqlx::query(&format!("DROP SCHEMA IF EXISTS {} CASCADE", schema)).execute(&mut conn).await.map_err(to_io)?;
+        sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&mut conn).await.map_err(to_io)?;
+        conn.close().await.map_err(to_io)?;
+
+        let separator = if url.contains('?') { '&' } else { '?' };
+        SqlStore::connect(&format!("{}{}options[search_path]={}", url, separator, schema)).await.map(Some)
+    }
+
+    // Recrée une base vide pour le scénario et y ouvre un MongoStore (index compris)
+    async fn mongo_store(db_name: &str) -> std::io::Result<Option<MongoStore>> {
+        let Ok(url) = env::var("TEST_MONGO_URL") else {
+            return Ok(None);
+        };
+        let to_io = |e: mongodb::error::Error| io::Error::new(io::ErrorKind::Other, e.to_string());
+
+        let client = Client::with_uri_str(&url).await.map_err(to_io)?;
+        let db_name = format!("fluxy_test_{}", db_name);
+        client.database(&db_name).drop().await.map_err(to_io)?;
+        let store = MongoStore::new(client, &db_name);
+        store.ensure_indexes().await?;
+        Ok(Some(store))
+    }
+
     const DEFAULT_OWNER: i64 = 200;
     const DEFAULT_NEW_MEMBER: i64 = 111;
     const DEFAULT_NEW_MEMBER2: i64 = 222;
@@ -32,34 +148,23 @@ mod tests {
     const DEFAULT_NEW_MEMBER4: i64 = 444;
     const DEFAULT_NEW_MEMBER5: i64 = 555;
     
-    // Fonction helper pour obtenir le verrou avant chaque test (async)
-    async fn get_test_lock() -> tokio::sync::MutexGuard<'static, ()> {
-        TEST_MUTEX.lock().await
-    }
-
-    #[actix_web::test]
-    async fn test_mongo_connection() ->std::io::Result<()>{
-        // Acquérir le verrou pour éviter la concurrence
-        let _lock = get_test_lock().await;
-        let client_mongo_db = db_mongo_connection::get_client().await?;
-        assert!(type_of(&client_mongo_db)=="mongodb::client::Client");
-        println!("test_mongo_connection => connection à la base de donnée fonctionnel");
+    async fn test_mongo_connection(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.ping().await?;
+        assert_eq!(store.count_documents("server").await?,0);
+        assert!(store.get_last_id("server").await? == 0);
+        println!("test_mongo_connection => connection au stockage fonctionnelle");
         Ok(())
     }
     
-    #[actix_web::test]
-    async fn test_mongo_server_set_up() ->std::io::Result<()>{
-        // Acquérir le verrou pour éviter la concurrence
-        let _lock = get_test_lock().await;
-        let client_mongo_db = db_mongo_connection::get_client().await?;
+    async fn test_mongo_server_set_up(store: &dyn ChatStore) ->std::io::Result<()>{
         let mut test_server_server_id = 0;
-        let number_of_server: usize = db_mongo_getter::get_collection(&client_mongo_db,"test","server").await?.len();
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de serveur").await?;
+        let number_of_server: u64 = store.count_documents("server").await?;
+        store.set_server(DEFAULT_OWNER,"test de création de serveur",None).await?;
         
         // Attendre que le serveur soit créé (problème de concurrence)
         let mut retries = 0;
         while retries < 10 && test_server_server_id == 0 {
-            test_server_server_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
+            test_server_server_id = store.get_last_id("server").await?;
             if test_server_server_id == 0 {
                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                 retries += 1;
@@ -73,7 +178,7 @@ mod tests {
         retries = 0;
         let mut server_created = false;
         while retries < 10 && !server_created {
-            let current_number = db_mongo_getter::get_collection(&client_mongo_db,"test","server").await?.len();
+            let current_number = store.count_documents("server").await?;
             if current_number > number_of_server {
                 server_created = true;
             } else {
@@ -83,13 +188,13 @@ mod tests {
         }
         assert!(server_created, "Le serveur devrait être créé");
         
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_server_server_id,DEFAULT_OWNER).await?;
+        store.delete_server(test_server_server_id,DEFAULT_OWNER).await?;
         
         // Attendre que la suppression soit complète (problème de concurrence)
         retries = 0;
         let mut server_deleted = false;
         while retries < 10 && !server_deleted {
-            let current_number = db_mongo_getter::get_collection(&client_mongo_db,"test","server").await?.len();
+            let current_number = store.count_documents("server").await?;
             if current_number == number_of_server {
                 server_deleted = true;
             } else {
@@ -99,56 +204,48 @@ mod tests {
         }
         
         println!("test_mongo_server_set_up => suppression du server crée : {:?}",test_server_server_id);
-        let final_number = db_mongo_getter::get_collection(&client_mongo_db,"test","server").await?.len();
+        let final_number = store.count_documents("server").await?;
         assert!(final_number == number_of_server, 
             "Le nombre de serveurs devrait être revenu à la valeur initiale. Initial: {}, Final: {}", 
             number_of_server, final_number);
         
         Ok(())
     }
-    #[actix_web::test]
-    async fn test_mongo_channel_set_up() ->std::io::Result<()>{
-        // Acquérir le verrou pour éviter la concurrence
-        let _lock = get_test_lock().await;
-        let client_mongo_db = db_mongo_connection::get_client().await?;
+    async fn test_mongo_channel_set_up(store: &dyn ChatStore) ->std::io::Result<()>{
         let mut test_channel_server_id = 0;
         let mut test_channel_channel_id = 0;
-        let number_of_channel: usize = db_mongo_getter::get_collection(&client_mongo_db,"test","channel").await?.len();
+        let number_of_channel: u64 = store.count_documents("channel").await?;
         println!("test_mongo_channel_set_up => nombre de channel : {:?}",number_of_channel);
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de channel").await?;
-        test_channel_server_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
+        store.set_server(DEFAULT_OWNER,"test de création de channel",None).await?;
+        test_channel_server_id = store.get_last_id("server").await?;
         println!("test_mongo_channel_set_up => création du server pour channel avec l'identifiant numéro : {:?}",test_channel_server_id);
-        db_mongo_setter::set_channel(&client_mongo_db,"test",test_channel_server_id,"premier channel crée",DEFAULT_OWNER).await?;
-        test_channel_channel_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","channel").await?;
+        store.set_channel(test_channel_server_id,"premier channel crée",DEFAULT_OWNER).await?;
+        test_channel_channel_id = store.get_last_id("channel").await?;
         println!("test_mongo_channel_set_up => création du premier channel du server avec l'identifiant numéro : {:?}",test_channel_channel_id);
         println!("test_mongo_channel_set_up => et avec comme nom : {:?}","premier channel crée");
         assert!(test_channel_server_id !=0);
         assert!(test_channel_channel_id !=0);
-        assert!(db_mongo_getter::is_channel_of_server(&client_mongo_db,"test",test_channel_server_id,test_channel_channel_id).await?);
+        assert!(store.is_channel_of_server(test_channel_server_id,test_channel_channel_id).await?);
         println!("test_mongo_channel_set_up => nombre de channel après : {:?}",number_of_channel);
-        assert!(number_of_channel != db_mongo_getter::get_collection(&client_mongo_db,"test","channel").await?.len());
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_channel_server_id,DEFAULT_OWNER).await?;
-        assert!(number_of_channel == db_mongo_getter::get_collection(&client_mongo_db,"test","channel").await?.len());
+        assert!(number_of_channel != store.count_documents("channel").await?);
+        store.delete_server(test_channel_server_id,DEFAULT_OWNER).await?;
+        assert!(number_of_channel == store.count_documents("channel").await?);
         println!("test_mongo_channel_set_up => suppression du server crée : {:?}",test_channel_server_id);
         Ok(())
     }
 
-    #[actix_web::test]
-    async fn test_mongo_message_set_up() ->std::io::Result<()>{
-        // Acquérir le verrou pour éviter la concurrence
-        let _lock = get_test_lock().await;
-        let client_mongo_db = db_mongo_connection::get_client().await?;
+    async fn test_mongo_message_set_up(store: &dyn ChatStore) ->std::io::Result<()>{
         let mut test_message_server_id = 0;
         let mut test_message_channel_id = 0;
         let mut test_message_message_id = 0;
-        let number_of_message = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+        let number_of_message = store.count_documents("message").await?;
         
         println!("test_mongo_message_set_up => nombre de message : {:?}",number_of_message);
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de channel").await?;
-        test_message_server_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
+        store.set_server(DEFAULT_OWNER,"test de création de channel",None).await?;
+        test_message_server_id = store.get_last_id("server").await?;
         println!("test_mongo_message_set_up => création du server pour channel pour le message avec l'identifiant numéro : {:?}",test_message_server_id);
-        db_mongo_setter::set_channel(&client_mongo_db,"test",test_message_server_id,"premier channel crée",DEFAULT_OWNER).await?;
-        test_message_channel_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","channel").await?;
+        store.set_channel(test_message_server_id,"premier channel crée",DEFAULT_OWNER).await?;
+        test_message_channel_id = store.get_last_id("channel").await?;
         println!("test_mongo_message_set_up => création du premier channel pour le message du server avec l'identifiant numéro : {:?}",test_message_channel_id);
         println!("test_mongo_message_set_up => et avec comme nom : {:?}","premier channel crée");
         
@@ -158,8 +255,8 @@ mod tests {
         let mut is_member_ok = false;
         let mut is_channel_ok = false;
         while retries < 5 && (!is_member_ok || !is_channel_ok) {
-            is_member_ok = db_mongo_getter::is_member(&client_mongo_db,"test",&test_message_server_id,&DEFAULT_OWNER).await?;
-            is_channel_ok = db_mongo_getter::is_channel_of_server(&client_mongo_db,"test",test_message_server_id,test_message_channel_id).await?;
+            is_member_ok = store.is_member(&test_message_server_id,&DEFAULT_OWNER).await?;
+            is_channel_ok = store.is_channel_of_server(test_message_server_id,test_message_channel_id).await?;
             if !is_member_ok || !is_channel_ok {
                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                 retries += 1;
@@ -168,14 +265,14 @@ mod tests {
         assert!(is_member_ok, "L'utilisateur devrait être membre du serveur");
         assert!(is_channel_ok, "Le channel devrait appartenir au serveur");
         
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_message_server_id,test_message_channel_id,"premier message écrit",DEFAULT_OWNER).await?;
+        store.set_message(test_message_server_id,test_message_channel_id,"premier message écrit",DEFAULT_OWNER).await?;
         
         // Attendre que le message soit créé (problème de concurrence)
         let mut retries = 0;
         let mut message_created = false;
         while retries < 10 && !message_created {
-            test_message_message_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","message").await?;
-            let current_number_of_message = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+            test_message_message_id = store.get_last_id("message").await?;
+            let current_number_of_message = store.count_documents("message").await?;
             if current_number_of_message > number_of_message && test_message_message_id != 0 {
                 message_created = true;
             } else {
@@ -186,7 +283,7 @@ mod tests {
         
         // Vérifier que le message a été créé
         assert!(test_message_message_id != 0, "Le message devrait avoir un ID");
-        let current_number_of_message = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+        let current_number_of_message = store.count_documents("message").await?;
         println!("test_mongo_message_set_up => nombre de message : {:?}",current_number_of_message);
         assert!(test_message_server_id !=0);
         // Vérifier que le nombre de messages a augmenté
@@ -194,14 +291,14 @@ mod tests {
             "Le nombre de messages devrait avoir augmenté. Initial: {}, Actuel: {}", 
             number_of_message, current_number_of_message);
         
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_message_server_id,DEFAULT_OWNER).await?;
+        store.delete_server(test_message_server_id,DEFAULT_OWNER).await?;
         
         // Attendre que la suppression soit complète (problème de concurrence)
         // Note: delete_server supprime aussi les messages associés, donc on attend que le nombre revienne à l'initial
         retries = 0;
         let mut server_deleted = false;
         while retries < 20 && !server_deleted {
-            let final_number_of_message = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+            let final_number_of_message = store.count_documents("message").await?;
             // Le nombre peut être égal ou inférieur à l'initial (si d'autres tests ont supprimé des messages)
             if final_number_of_message <= number_of_message {
                 server_deleted = true;
@@ -212,7 +309,7 @@ mod tests {
         }
         
         println!("test_mongo_message_set_up => suppression du server crée : {:?}",test_message_server_id);
-        let final_number_of_message = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+        let final_number_of_message = store.count_documents("message").await?;
         // En parallèle, d'autres tests peuvent avoir créé/supprimé des messages, donc on vérifie seulement que notre message a été supprimé
         // On vérifie que le nombre est au maximum égal à l'initial (notre message devrait être supprimé)
         assert!(final_number_of_message <= number_of_message + 1, 
@@ -221,259 +318,251 @@ mod tests {
         Ok(())
     }
 
-    #[actix_web::test]
-    async fn test_mongo_manipulation_member() ->std::io::Result<()>{
-        // Acquérir le verrou pour éviter la concurrence
-        let _lock = get_test_lock().await;
-        let client_mongo_db = db_mongo_connection::get_client().await?;
+    async fn test_mongo_manipulation_member(store: &dyn ChatStore) ->std::io::Result<()>{
         let mut test_join_server_id = 0;
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de channel").await?;
-        test_join_server_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
+        store.set_server(DEFAULT_OWNER,"test de création de channel",None).await?;
+        test_join_server_id = store.get_last_id("server").await?;
         println!("test_mongo_manipulation_member => création du server pour rejoindre avec l'identifiant numéro : {:?}",test_join_server_id);
         //ajout des membres
         //1
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER).await?;
+        store.add_member_to_server(test_join_server_id,DEFAULT_NEW_MEMBER).await?;
         println!("test_mongo_manipulation_member => membre ajouté au server {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER).await?);
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER).await?);
         //2
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER2).await?;
+        store.add_member_to_server(test_join_server_id,DEFAULT_NEW_MEMBER2).await?;
         println!("test_mongo_manipulation_member => membre ajouté au server {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER2);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
         //3
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER3).await?;
+        store.add_member_to_server(test_join_server_id,DEFAULT_NEW_MEMBER3).await?;
         println!("test_mongo_manipulation_member => membre ajouté au server {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER3);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
         //4
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER4).await?;
+        store.add_member_to_server(test_join_server_id,DEFAULT_NEW_MEMBER4).await?;
         println!("test_mongo_manipulation_member => membre ajouté au server {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER4);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
         
         //ajout d'admin
         //par owner
-        db_mongo_setter::add_admin_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
+        store.add_admin_to_server(test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
         println!("test_mongo_manipulation_member => owner passe un membre en admin du serveur {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER2);
-        assert!(db_mongo_getter::is_admin(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_admin(&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
         //par admin
-        db_mongo_setter::add_admin_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3).await?;
+        assert!(matches!(store.add_admin_to_server(test_join_server_id,DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
         println!("test_mongo_manipulation_member => admin échoue de passer un membre en admin du serveur {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER3);
-        assert!(!db_mongo_getter::is_admin(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(!store.is_admin(&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
         //par membre
-        db_mongo_setter::add_admin_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER4).await?;
+        assert!(matches!(store.add_admin_to_server(test_join_server_id,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER4).await, Err(FluxyError::Forbidden(_))));
         println!("test_mongo_manipulation_member => admin échoue de passer un membre en admin du serveur {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER4);
-        assert!(!db_mongo_getter::is_admin(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
+        assert!(!store.is_admin(&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
         //second admin
-        db_mongo_setter::add_admin_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER3).await?;
+        store.add_admin_to_server(test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER3).await?;
         println!("test_mongo_manipulation_member => membre échoue de passer un membre en admin du serveur {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER3);
-        assert!(db_mongo_getter::is_admin(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(store.is_admin(&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
 
 
         //suppression de role admin
         //par membre
-        db_mongo_delete::delete_admin(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER3).await?;
+        assert!(matches!(store.delete_admin(test_join_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
         println!("test_mongo_manipulation_member => membre essaye de supprimer un role admin du server {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER3);
-        assert!(db_mongo_getter::is_admin(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(store.is_admin(&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
         //par admin
-        db_mongo_delete::delete_admin(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3).await?;
+        assert!(matches!(store.delete_admin(test_join_server_id,DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
         println!("test_mongo_manipulation_member => admin essaye de supprimer un role admin du server {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER3);
-        assert!(db_mongo_getter::is_admin(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(store.is_admin(&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
         //par owner
-        db_mongo_delete::delete_admin(&client_mongo_db,"test",test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
+        store.delete_admin(test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
         println!("test_mongo_manipulation_member => membre supprime un role admin du server {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER2);
-        assert!(!db_mongo_getter::is_admin(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(!store.is_admin(&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
         
         //suppression de membre
         //par membre
-        db_mongo_delete::delete_member(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER2).await?;
+        assert!(matches!(store.delete_member(test_join_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
         // Attendre un peu pour s'assurer que la suppression n'a pas été appliquée (problème de concurrence)
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         println!("test_mongo_manipulation_member => membre essaye de supprimer un membre {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER2);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?,
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?,
             "Un membre ne devrait pas pouvoir supprimer un autre membre");
         //par admin
-        db_mongo_delete::delete_member(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER2).await?;
+        store.delete_member(test_join_server_id,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER2).await?;
         println!("test_mongo_manipulation_member => admin supprime un membre {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER2);
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(!store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
         //par owner
-        db_mongo_delete::delete_member(&client_mongo_db,"test",test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER4).await?;
+        store.delete_member(test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER4).await?;
         println!("test_mongo_manipulation_member => owner supprime un membre {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER4);
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
+        assert!(!store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
         
         //réajoute les memmbre
         
         println!("test_mongo_manipulation_member => réajoute deux membres");
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER2).await?;
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER4).await?;
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
+        store.add_member_to_server(test_join_server_id,DEFAULT_NEW_MEMBER2).await?;
+        store.add_member_to_server(test_join_server_id,DEFAULT_NEW_MEMBER4).await?;
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
         //rééajoute un admin
-        db_mongo_setter::add_admin_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
-        assert!(db_mongo_getter::is_admin(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        store.add_admin_to_server(test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
+        assert!(store.is_admin(&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
 
         //suppression d'admin
         //par membre
-        db_mongo_delete::delete_member(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER2).await?;
+        assert!(matches!(store.delete_member(test_join_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
         println!("test_mongo_manipulation_member => membre essaye de supprimer un admin {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER2);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
         //par admin
-        db_mongo_delete::delete_member(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER2).await?;
+        assert!(matches!(store.delete_member(test_join_server_id,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
         println!("test_mongo_manipulation_member => admin supprime un admin {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER2);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
         //par owner
-        db_mongo_delete::delete_member(&client_mongo_db,"test",test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
+        store.delete_member(test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
         println!("test_mongo_manipulation_member => owner supprime un admin {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER2);
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(!store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER2).await?);
 
 
         //quitter le serveur
         //par membre
-        db_mongo_delete::delete_member(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER4).await?;
+        store.delete_member(test_join_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER4).await?;
         println!("test_mongo_manipulation_member => membre quitte le serveur {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER4);
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
+        assert!(!store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
         //par admin
-        db_mongo_delete::delete_member(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER3).await?;
+        store.delete_member(test_join_server_id,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER3).await?;
         println!("test_mongo_manipulation_member => admin quitte le serveur {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER3);
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(!store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
         //par owner
-        db_mongo_delete::delete_member(&client_mongo_db,"test",test_join_server_id,DEFAULT_OWNER,DEFAULT_OWNER).await?;
+        assert!(matches!(store.delete_member(test_join_server_id,DEFAULT_OWNER,DEFAULT_OWNER).await, Err(FluxyError::Conflict(_))));
         println!("test_mongo_manipulation_member => owner essaye de quitter le serveur {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_OWNER);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_OWNER).await?);
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_OWNER).await?);
         
 
         //réajoute les memmbre
         println!("test_mongo_manipulation_member => réajoute deux membres");
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER3).await?;
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER4).await?;
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
+        store.add_member_to_server(test_join_server_id,DEFAULT_NEW_MEMBER3).await?;
+        store.add_member_to_server(test_join_server_id,DEFAULT_NEW_MEMBER4).await?;
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(store.is_member(&test_join_server_id,&DEFAULT_NEW_MEMBER4).await?);
         
         //switch d'owner le serveur
         //par membre
-        db_mongo_setter::switch_owner(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER3).await?;
+        assert!(matches!(store.switch_owner(test_join_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
         println!("test_mongo_manipulation_member => membre quitte le serveur {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER3);
-        assert!(!db_mongo_getter::is_owner(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(!store.is_owner(&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
         //par admin
-        db_mongo_setter::switch_owner(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3).await?;
+        assert!(matches!(store.switch_owner(test_join_server_id,DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
         println!("test_mongo_manipulation_member => admin quitte le serveur {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER3);
-        assert!(!db_mongo_getter::is_owner(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(!store.is_owner(&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        //vers un non-membre
+        assert!(matches!(store.switch_owner(test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        println!("test_mongo_manipulation_member => owner essaye de transférer le serveur {} à un non-membre : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER2);
+        assert!(store.is_owner(&test_join_server_id,&DEFAULT_OWNER).await?);
         //par owner
-        db_mongo_setter::switch_owner(&client_mongo_db,"test",test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER3).await?;
+        store.switch_owner(test_join_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER3).await?;
         println!("test_mongo_manipulation_member => owner essaye de quitter le serveur {} avec l'identifiant numéro : {:?}",test_join_server_id,DEFAULT_NEW_MEMBER3);
-        assert!(db_mongo_getter::is_owner(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
-        assert!(!db_mongo_getter::is_owner(&client_mongo_db,"test",&test_join_server_id,&DEFAULT_OWNER).await?);
+        assert!(store.is_owner(&test_join_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(!store.is_owner(&test_join_server_id,&DEFAULT_OWNER).await?);
         
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_join_server_id,DEFAULT_NEW_MEMBER3).await?;
+        store.delete_server(test_join_server_id,DEFAULT_NEW_MEMBER3).await?;
         println!("test_mongo_server_set_up => suppression du server crée : {:?}",test_join_server_id);
         Ok(())
     }
     
-    #[actix_web::test]
-    async fn test_mongo_link() ->std::io::Result<()>{
-        // Acquérir le verrou pour éviter la concurrence
-        let _lock = get_test_lock().await;
-        let client_mongo_db = db_mongo_connection::get_client().await?;
+    async fn test_mongo_link(store: &dyn ChatStore) ->std::io::Result<()>{
         let mut test_link_server_id = 0;
-        let number_of_server: usize = db_mongo_getter::get_collection(&client_mongo_db,"test","server").await?.len();
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de serveur").await?;
-        test_link_server_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
+        let number_of_server: u64 = store.count_documents("server").await?;
+        store.set_server(DEFAULT_OWNER,"test de création de serveur",None).await?;
+        test_link_server_id = store.get_last_id("server").await?;
         println!("test_mongo_link => création du server avec l'identifiant numéro : {:?}",test_link_server_id);
         assert!(test_link_server_id !=0);
-        assert!(number_of_server !=db_mongo_getter::get_collection(&client_mongo_db,"test","server").await?.len());
+        assert!(number_of_server !=store.count_documents("server").await?);
         
         //ajoute des membres
         println!("test_mongo_link => ajoute deux membres");
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_link_server_id,DEFAULT_NEW_MEMBER2).await?;
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_link_server_id,DEFAULT_NEW_MEMBER3).await?;
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_link_server_id,&DEFAULT_NEW_MEMBER2).await?);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_link_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        store.add_member_to_server(test_link_server_id,DEFAULT_NEW_MEMBER2).await?;
+        store.add_member_to_server(test_link_server_id,DEFAULT_NEW_MEMBER3).await?;
+        assert!(store.is_member(&test_link_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_member(&test_link_server_id,&DEFAULT_NEW_MEMBER3).await?);
         //ajoute un admin
-        db_mongo_setter::add_admin_to_server(&client_mongo_db,"test",test_link_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
-        assert!(db_mongo_getter::is_admin(&client_mongo_db,"test",&test_link_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        store.add_admin_to_server(test_link_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
+        assert!(store.is_admin(&test_link_server_id,&DEFAULT_NEW_MEMBER2).await?);
         
         //membre
-        let mut link = db_mongo_setter::create_link_one_use(&client_mongo_db,"test",test_link_server_id,DEFAULT_NEW_MEMBER3).await?;
-        println!("test_mongo_link => membre essaye de faire un lien pour le serveur {} avec l'identifiant numéro : {:?}",test_link_server_id,DEFAULT_NEW_MEMBER4);
-        println!("test_mongo_link => lien du serveur : {}",link);
-        assert!(!db_mongo_getter::verify_link_exist(&client_mongo_db,"test",&link).await?);
+        assert!(matches!(store.create_invite(test_link_server_id,DEFAULT_NEW_MEMBER3,None,None).await, Err(FluxyError::Forbidden(_))));
+        println!("test_mongo_link => membre essaye de faire un lien pour le serveur {} avec l'identifiant numéro : {:?}",test_link_server_id,DEFAULT_NEW_MEMBER3);
         
         //admin
-        link = db_mongo_setter::create_link_one_use(&client_mongo_db,"test",test_link_server_id,DEFAULT_NEW_MEMBER2).await?;
+        let invite = store.create_invite(test_link_server_id,DEFAULT_NEW_MEMBER2,None,None).await?;
+        assert_eq!(invite.server_id,test_link_server_id);
+        let mut link = invite.code;
         println!("test_mongo_link => admin essaye de faire un lien pour le serveur {} avec l'identifiant numéro : {:?}",test_link_server_id,DEFAULT_NEW_MEMBER2);
         println!("test_mongo_link => lien du serveur : {}",link);
-        assert!(db_mongo_getter::verify_link_exist(&client_mongo_db,"test",&link).await?);
+        assert!(store.verify_link_exist(&link).await?);
         
         //owner
-        link = db_mongo_setter::create_link_one_use(&client_mongo_db,"test",test_link_server_id,DEFAULT_OWNER).await?;
+        link = store.create_invite(test_link_server_id,DEFAULT_OWNER,None,None).await?.code;
         println!("test_mongo_link => owner essaye de faire un lien pour le serveur {} avec l'identifiant numéro : {:?}",test_link_server_id,DEFAULT_OWNER);
         println!("test_mongo_link => lien du serveur : {}",link);
-        assert!(db_mongo_getter::verify_link_exist(&client_mongo_db,"test",&link).await?);
+        assert!(store.verify_link_exist(&link).await?);
         
         //rejoint avec un faux lien
-        db_mongo_setter::join_by_link(&client_mongo_db,"test","a",DEFAULT_NEW_MEMBER4).await?;
+        assert!(matches!(store.join_by_link("a",DEFAULT_NEW_MEMBER4).await, Err(FluxyError::NotFound(_))));
         println!("test_mongo_link => essaye de rejoindre avec un faux lien");
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_link_server_id,&DEFAULT_NEW_MEMBER4).await?);
+        assert!(!store.is_member(&test_link_server_id,&DEFAULT_NEW_MEMBER4).await?);
         //rejoint avec un vrai lien
-        db_mongo_setter::join_by_link(&client_mongo_db,"test",&link,DEFAULT_NEW_MEMBER4).await?;
+        store.join_by_link(&link,DEFAULT_NEW_MEMBER4).await?;
         println!("test_mongo_link => essaye de rejoindre avec un vrai lien");
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_link_server_id,&DEFAULT_NEW_MEMBER4).await?);
+        assert!(store.is_member(&test_link_server_id,&DEFAULT_NEW_MEMBER4).await?);
         
         //suppression du lien
-        db_mongo_delete::delete_link(&client_mongo_db,"test",&link).await?;
+        store.delete_invite(&link,DEFAULT_OWNER).await?;
         println!("test_mongo_link => supprime le lien");
-        assert!(!db_mongo_getter::verify_link_exist(&client_mongo_db,"test",&link).await?);
+        assert!(!store.verify_link_exist(&link).await?);
         //rejoint avec un ancien lien
-        db_mongo_setter::join_by_link(&client_mongo_db,"test",&link,DEFAULT_NEW_MEMBER5).await?;
+        assert!(matches!(store.join_by_link(&link,DEFAULT_NEW_MEMBER5).await, Err(FluxyError::NotFound(_))));
         println!("test_mongo_link => essaye de rejoindre avec un ancien lien");
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_link_server_id,&DEFAULT_NEW_MEMBER5).await?);
+        assert!(!store.is_member(&test_link_server_id,&DEFAULT_NEW_MEMBER5).await?);
         
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_link_server_id,DEFAULT_OWNER).await?;
+        store.delete_server(test_link_server_id,DEFAULT_OWNER).await?;
         println!("test_mongo_server_set_up => suppression du server crée : {:?}",test_link_server_id);
         Ok(())
     }
     
-    #[actix_web::test]
-    async fn test_message_update() ->std::io::Result<()>{
-        // Acquérir le verrou pour éviter la concurrence
-        let _lock = get_test_lock().await;
+    async fn test_message_update(store: &dyn ChatStore) ->std::io::Result<()>{
         //mise en place du serveur avec un channel
-        let client_mongo_db = db_mongo_connection::get_client().await?;
         let mut test_update_server_id = 0;
         let mut test_update_channel_id = 0;
-        let number_of_channel: usize = db_mongo_getter::get_collection(&client_mongo_db,"test","channel").await?.len();
+        let number_of_channel: u64 = store.count_documents("channel").await?;
         println!("test_message_update => nombre de channel : {:?}",number_of_channel);
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de channel").await?;
-        test_update_server_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
+        store.set_server(DEFAULT_OWNER,"test de création de channel",None).await?;
+        test_update_server_id = store.get_last_id("server").await?;
         println!("test_message_update => création du server pour channel avec l'identifiant numéro : {:?}",test_update_server_id);
-        db_mongo_setter::set_channel(&client_mongo_db,"test",test_update_server_id,"premier channel crée",DEFAULT_OWNER).await?;
-        test_update_channel_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","channel").await?;
+        store.set_channel(test_update_server_id,"premier channel crée",DEFAULT_OWNER).await?;
+        test_update_channel_id = store.get_last_id("channel").await?;
         println!("test_message_update => création du premier channel du server avec l'identifiant numéro : {:?}",test_update_channel_id);
         println!("test_message_update => et avec comme nom : {:?}","premier channel crée");
         assert!(test_update_server_id !=0);
         assert!(test_update_channel_id !=0);
-        assert!(db_mongo_getter::is_channel_of_server(&client_mongo_db,"test",test_update_server_id,test_update_channel_id).await?);
+        assert!(store.is_channel_of_server(test_update_server_id,test_update_channel_id).await?);
         println!("test_message_update => nombre de channel après : {:?}",number_of_channel);
-        assert!(number_of_channel != db_mongo_getter::get_collection(&client_mongo_db,"test","channel").await?.len());
+        assert!(number_of_channel != store.count_documents("channel").await?);
         
         //ajoute des membres
         println!("test_message_update => ajoute deux membres");
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_NEW_MEMBER2).await?;
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_NEW_MEMBER3).await?;
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        store.add_member_to_server(test_update_server_id,DEFAULT_NEW_MEMBER2).await?;
+        store.add_member_to_server(test_update_server_id,DEFAULT_NEW_MEMBER3).await?;
+        assert!(store.is_member(&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_member(&test_update_server_id,&DEFAULT_NEW_MEMBER3).await?);
         //ajoute un admin
-        db_mongo_setter::add_admin_to_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
-        assert!(db_mongo_getter::is_admin(&client_mongo_db,"test",&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        store.add_admin_to_server(test_update_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
+        assert!(store.is_admin(&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
         
         //écrire un message
-        let mut number_of_message: usize = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+        let mut number_of_message: u64 = store.count_documents("message").await?;
         println!("nombre de message actuellement : {}",number_of_message);
         
         //membre
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_update_server_id,test_update_channel_id,"je suis le message de membre",DEFAULT_NEW_MEMBER3).await?;
+        store.set_message(test_update_server_id,test_update_channel_id,"je suis le message de membre",DEFAULT_NEW_MEMBER3).await?;
         let mut retries = 0;
         let mut test_update_member_message_membre_id = 0;
         while retries < 10 {
-            test_update_member_message_membre_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","message").await?;
-            let current_count = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+            test_update_member_message_membre_id = store.get_last_id("message").await?;
+            let current_count = store.count_documents("message").await?;
             if current_count > number_of_message && test_update_member_message_membre_id != 0 {
                 number_of_message = current_count;
                 break;
@@ -482,7 +571,7 @@ mod tests {
             retries += 1;
         }
         println!("test_message_update => membre écris un message avec l'id : {:?}",test_update_member_message_membre_id);
-        let current_count_after_member = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+        let current_count_after_member = store.count_documents("message").await?;
         assert!(current_count_after_member > number_of_message - 1, 
             "Le message du membre devrait être créé. Initial: {}, Actuel: {}", 
             number_of_message, current_count_after_member);
@@ -490,12 +579,12 @@ mod tests {
         println!("nombre de message actuellement : {}",number_of_message);
         
         //admin
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_update_server_id,test_update_channel_id,"je suis le message d'admin",DEFAULT_NEW_MEMBER2).await?;
+        store.set_message(test_update_server_id,test_update_channel_id,"je suis le message d'admin",DEFAULT_NEW_MEMBER2).await?;
         retries = 0;
         let mut test_update_member_message_admin_id = 0;
         while retries < 10 {
-            test_update_member_message_admin_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","message").await?;
-            let current_count = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+            test_update_member_message_admin_id = store.get_last_id("message").await?;
+            let current_count = store.count_documents("message").await?;
             if current_count > number_of_message && test_update_member_message_admin_id != 0 {
                 number_of_message = current_count;
                 break;
@@ -504,7 +593,7 @@ mod tests {
             retries += 1;
         }
         println!("test_message_update => admin écris un message avec l'id : {:?}",test_update_member_message_admin_id);
-        let current_count_after_admin = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+        let current_count_after_admin = store.count_documents("message").await?;
         assert!(current_count_after_admin > number_of_message - 1, 
             "Le message de l'admin devrait être créé. Initial: {}, Actuel: {}", 
             number_of_message, current_count_after_admin);
@@ -512,12 +601,12 @@ mod tests {
         println!("nombre de message actuellement : {}",number_of_message);
         
         //owner
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_update_server_id,test_update_channel_id,"je suis le message de owner",DEFAULT_OWNER).await?;
+        store.set_message(test_update_server_id,test_update_channel_id,"je suis le message de owner",DEFAULT_OWNER).await?;
         retries = 0;
         let mut test_update_member_message_owner_id = 0;
         while retries < 10 {
-            test_update_member_message_owner_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","message").await?;
-            let current_count = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+            test_update_member_message_owner_id = store.get_last_id("message").await?;
+            let current_count = store.count_documents("message").await?;
             if current_count > number_of_message && test_update_member_message_owner_id != 0 {
                 number_of_message = current_count;
                 break;
@@ -526,7 +615,7 @@ mod tests {
             retries += 1;
         }
         println!("test_message_update => owner écris un message avec l'id : {:?}",test_update_member_message_owner_id);
-        let current_count_after_owner = db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len();
+        let current_count_after_owner = store.count_documents("message").await?;
         assert!(current_count_after_owner > number_of_message - 1, 
             "Le message de l'owner devrait être créé. Initial: {}, Actuel: {}", 
             number_of_message, current_count_after_owner);
@@ -537,248 +626,236 @@ mod tests {
         //modifie son message
         
         //membre
-        let test_update_message_of_member = db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_membre_id).await?;
+        let test_update_message_of_member = store.get_message_by_id(&test_update_member_message_membre_id).await?;
         // println!("test {:?}",test_update_message_of_member);
-        assert!(test_update_message_of_member ==db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_membre_id).await?);
-        db_mongo_update::update_message(&client_mongo_db,"test",test_update_member_message_membre_id,"je suis le text modifier",DEFAULT_NEW_MEMBER3).await?;
+        assert!(test_update_message_of_member ==store.get_message_by_id(&test_update_member_message_membre_id).await?);
+        store.update_message(test_update_member_message_membre_id,"je suis le text modifier",DEFAULT_NEW_MEMBER3).await?;
         // Attendre un peu pour s'assurer que la modification est appliquée (problème de concurrence)
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         println!("test_message_update => membre modifier son message avec l'id : {:?}",test_update_member_message_membre_id);
-        let message_after_update = db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_membre_id).await?;
+        let message_after_update = store.get_message_by_id(&test_update_member_message_membre_id).await?;
         assert!(test_update_message_of_member != message_after_update,
             "Le message devrait être modifié. Avant: {:?}, Après: {:?}",
             test_update_message_of_member, message_after_update);
         
         //admin
-        let test_update_message_of_admin = db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_admin_id).await?;
-        assert!(test_update_message_of_admin ==db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_admin_id).await?);
-        db_mongo_update::update_message(&client_mongo_db,"test",test_update_member_message_admin_id,"je suis le text modifier",DEFAULT_NEW_MEMBER2).await?;
+        let test_update_message_of_admin = store.get_message_by_id(&test_update_member_message_admin_id).await?;
+        assert!(test_update_message_of_admin ==store.get_message_by_id(&test_update_member_message_admin_id).await?);
+        store.update_message(test_update_member_message_admin_id,"je suis le text modifier",DEFAULT_NEW_MEMBER2).await?;
         println!("test_message_update => admin modifi son message avec l'id : {:?}",test_update_member_message_admin_id);
-        assert!(test_update_message_of_admin !=db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_admin_id).await?);
+        assert!(test_update_message_of_admin !=store.get_message_by_id(&test_update_member_message_admin_id).await?);
         
         //owner
-        let test_update_message_of_owner = db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_owner_id).await?;
-        assert!(test_update_message_of_owner ==db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_owner_id).await?);
-        db_mongo_update::update_message(&client_mongo_db,"test",test_update_member_message_owner_id,"je suis le text modifier",DEFAULT_OWNER).await?;
+        let test_update_message_of_owner = store.get_message_by_id(&test_update_member_message_owner_id).await?;
+        assert!(test_update_message_of_owner ==store.get_message_by_id(&test_update_member_message_owner_id).await?);
+        store.update_message(test_update_member_message_owner_id,"je suis le text modifier",DEFAULT_OWNER).await?;
         println!("test_message_update => owner modifi son message avec l'id : {:?}",test_update_member_message_owner_id);
-        assert!(test_update_message_of_owner !=db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_owner_id).await?);
+        assert!(test_update_message_of_owner !=store.get_message_by_id(&test_update_member_message_owner_id).await?);
         
         //modifier un autre message
         //membre
-        db_mongo_update::update_message(&client_mongo_db,"test",test_update_member_message_admin_id,"je suis le message d'admin",DEFAULT_NEW_MEMBER3).await?;
-        assert!(test_update_message_of_admin !=db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_admin_id).await?);
-        db_mongo_update::update_message(&client_mongo_db,"test",test_update_member_message_owner_id,"je suis le message de owner",DEFAULT_NEW_MEMBER3).await?;
-        assert!(test_update_message_of_owner !=db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_owner_id).await?);
+        assert!(matches!(store.update_message(test_update_member_message_admin_id,"je suis le message d'admin",DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
+        assert!(test_update_message_of_admin !=store.get_message_by_id(&test_update_member_message_admin_id).await?);
+        assert!(matches!(store.update_message(test_update_member_message_owner_id,"je suis le message de owner",DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
+        assert!(test_update_message_of_owner !=store.get_message_by_id(&test_update_member_message_owner_id).await?);
         
         //admin
-        db_mongo_update::update_message(&client_mongo_db,"test",test_update_member_message_membre_id,"je suis le message de membre",DEFAULT_NEW_MEMBER2).await?;
-        assert!(test_update_message_of_member !=db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_membre_id).await?);
-        db_mongo_update::update_message(&client_mongo_db,"test",test_update_member_message_owner_id,"je suis le message de owner",DEFAULT_NEW_MEMBER2).await?;
-        assert!(test_update_message_of_owner !=db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_owner_id).await?);
+        assert!(matches!(store.update_message(test_update_member_message_membre_id,"je suis le message de membre",DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        assert!(test_update_message_of_member !=store.get_message_by_id(&test_update_member_message_membre_id).await?);
+        assert!(matches!(store.update_message(test_update_member_message_owner_id,"je suis le message de owner",DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        assert!(test_update_message_of_owner !=store.get_message_by_id(&test_update_member_message_owner_id).await?);
         
         //owner
-        db_mongo_update::update_message(&client_mongo_db,"test",test_update_member_message_membre_id,"je suis le message de membre",DEFAULT_OWNER).await?;
-        assert!(test_update_message_of_member !=db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_membre_id).await?);
-        db_mongo_update::update_message(&client_mongo_db,"test",test_update_member_message_admin_id,"je suis le message d'admin",DEFAULT_OWNER).await?;
-        assert!(test_update_message_of_admin !=db_mongo_getter::get_message_by_id(&client_mongo_db,"test",&test_update_member_message_admin_id).await?);
+        assert!(matches!(store.update_message(test_update_member_message_membre_id,"je suis le message de membre",DEFAULT_OWNER).await, Err(FluxyError::Forbidden(_))));
+        assert!(test_update_message_of_member !=store.get_message_by_id(&test_update_member_message_membre_id).await?);
+        assert!(matches!(store.update_message(test_update_member_message_admin_id,"je suis le message d'admin",DEFAULT_OWNER).await, Err(FluxyError::Forbidden(_))));
+        assert!(test_update_message_of_admin !=store.get_message_by_id(&test_update_member_message_admin_id).await?);
         
         
         //suppression de message
         //son message
-        assert!(number_of_message ==db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len());
-        db_mongo_delete::delete_message(&client_mongo_db,"test",test_update_member_message_membre_id,DEFAULT_NEW_MEMBER3).await?;
+        assert!(number_of_message ==store.count_documents("message").await?);
+        store.delete_message(test_update_member_message_membre_id,DEFAULT_NEW_MEMBER3).await?;
         println!("test_message_update => membre suprime son message avec l'id : {:?}",test_update_member_message_membre_id);
-        assert!(number_of_message !=db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len());
+        assert!(number_of_message !=store.count_documents("message").await?);
         number_of_message -=1;
         println!("nombre de message actuellement : {}",number_of_message);
         
         //membre
         //un autre message
-        db_mongo_delete::delete_message(&client_mongo_db,"test",test_update_member_message_admin_id,DEFAULT_NEW_MEMBER3).await?;
+        assert!(matches!(store.delete_message(test_update_member_message_admin_id,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
         println!("test_message_update => membre essaye de supprimer le message avec l'id : {:?}",test_update_member_message_admin_id);
-        assert!(number_of_message ==db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len());
-        db_mongo_delete::delete_message(&client_mongo_db,"test",test_update_member_message_owner_id,DEFAULT_NEW_MEMBER3).await?;
+        assert!(number_of_message ==store.count_documents("message").await?);
+        assert!(matches!(store.delete_message(test_update_member_message_owner_id,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
         println!("test_message_update => membre essaye de supprimer le message avec l'id : {:?}",test_update_member_message_owner_id);
-        assert!(number_of_message ==db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len());
+        assert!(number_of_message ==store.count_documents("message").await?);
         
         //admin
-        db_mongo_delete::delete_message(&client_mongo_db,"test",test_update_member_message_owner_id,DEFAULT_NEW_MEMBER2).await?;
+        store.delete_message(test_update_member_message_owner_id,DEFAULT_NEW_MEMBER2).await?;
         println!("test_message_update => admin suprime un message avec l'id : {:?}",test_update_member_message_owner_id);
-        assert!(number_of_message !=db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len());
+        assert!(number_of_message !=store.count_documents("message").await?);
         number_of_message -=1;
         println!("nombre de message actuellement : {}",number_of_message);
         
         //owner
-        db_mongo_delete::delete_message(&client_mongo_db,"test",test_update_member_message_admin_id,DEFAULT_OWNER).await?;
+        store.delete_message(test_update_member_message_admin_id,DEFAULT_OWNER).await?;
         println!("test_message_update => owner suprime un message avec l'id : {:?}",test_update_member_message_admin_id);
-        assert!(number_of_message !=db_mongo_getter::get_collection(&client_mongo_db,"test","message").await?.len());
+        assert!(number_of_message !=store.count_documents("message").await?);
         number_of_message -=1;
         println!("nombre de message actuellement : {}",number_of_message);
         
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_OWNER).await?;
+        store.delete_server(test_update_server_id,DEFAULT_OWNER).await?;
         println!("test_mongo_server_set_up => suppression du server crée : {:?}",test_update_server_id);
         
         Ok(())
     }
     
-    #[actix_web::test]
-    async fn test_channel_update() ->std::io::Result<()>{
-        // Acquérir le verrou pour éviter la concurrence
-        let _lock = get_test_lock().await;
+    async fn test_channel_update(store: &dyn ChatStore) ->std::io::Result<()>{
         //mise en place du serveur avec un channel
-        let client_mongo_db = db_mongo_connection::get_client().await?;
         let mut test_update_server_id = 0;
         let mut test_update_channel_id = 0;
-        let number_of_channel: usize = db_mongo_getter::get_collection(&client_mongo_db,"test","channel").await?.len();
+        let number_of_channel: u64 = store.count_documents("channel").await?;
         println!("test_channel_update => nombre de channel : {:?}",number_of_channel);
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de channel").await?;
-        test_update_server_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
+        store.set_server(DEFAULT_OWNER,"test de création de channel",None).await?;
+        test_update_server_id = store.get_last_id("server").await?;
         println!("test_channel_update => création du server pour channel avec l'identifiant numéro : {:?}",test_update_server_id);
-        db_mongo_setter::set_channel(&client_mongo_db,"test",test_update_server_id,"premier channel crée",DEFAULT_OWNER).await?;
-        test_update_channel_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","channel").await?;
+        store.set_channel(test_update_server_id,"premier channel crée",DEFAULT_OWNER).await?;
+        test_update_channel_id = store.get_last_id("channel").await?;
         println!("test_channel_update => création du premier channel du server avec l'identifiant numéro : {:?}",test_update_channel_id);
         println!("test_channel_update => et avec comme nom : {:?}","premier channel crée");
         assert!(test_update_server_id !=0);
         assert!(test_update_channel_id !=0);
-        assert!(db_mongo_getter::is_channel_of_server(&client_mongo_db,"test",test_update_server_id,test_update_channel_id).await?);
+        assert!(store.is_channel_of_server(test_update_server_id,test_update_channel_id).await?);
         println!("test_channel_update => nombre de channel après : {:?}",number_of_channel);
-        assert!(number_of_channel != db_mongo_getter::get_collection(&client_mongo_db,"test","channel").await?.len());
+        assert!(number_of_channel != store.count_documents("channel").await?);
         
         //ajoute des membres
         println!("test_channel_update => ajoute deux membres");
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_NEW_MEMBER2).await?;
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_NEW_MEMBER3).await?;
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        store.add_member_to_server(test_update_server_id,DEFAULT_NEW_MEMBER2).await?;
+        store.add_member_to_server(test_update_server_id,DEFAULT_NEW_MEMBER3).await?;
+        assert!(store.is_member(&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_member(&test_update_server_id,&DEFAULT_NEW_MEMBER3).await?);
         //ajoute un admin
-        db_mongo_setter::add_admin_to_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
-        assert!(db_mongo_getter::is_admin(&client_mongo_db,"test",&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        store.add_admin_to_server(test_update_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
+        assert!(store.is_admin(&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
         
         //update nom
         //par membre
-        let mut test_update_channel_name = db_mongo_getter::get_channel_by_id(&client_mongo_db,"test",&test_update_channel_id).await?;
-        db_mongo_update::update_channel_name(&client_mongo_db,"test",test_update_channel_id, "nouveau nom de channel par membre",DEFAULT_NEW_MEMBER3).await?;
+        let mut test_update_channel_name = store.get_channel_by_id(&test_update_channel_id).await?;
+        assert!(matches!(store.update_channel_name(test_update_channel_id, "nouveau nom de channel par membre",DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
         // Attendre un peu pour s'assurer que la modification n'a pas été appliquée (problème de concurrence)
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         println!("test_channel_update => membre qui essaye de modifier le nom d'un channel");
-        let channel_after_member_update = db_mongo_getter::get_channel_by_id(&client_mongo_db,"test",&test_update_channel_id).await?;
+        let channel_after_member_update = store.get_channel_by_id(&test_update_channel_id).await?;
         assert!(test_update_channel_name == channel_after_member_update, 
             "Un membre ne devrait pas pouvoir modifier un channel. Avant: {:?}, Après: {:?}", 
             test_update_channel_name, channel_after_member_update);
         
         //par un admin
-        db_mongo_update::update_channel_name(&client_mongo_db,"test",test_update_channel_id, "nouveau nom de channel par admin",DEFAULT_NEW_MEMBER2).await?;
+        store.update_channel_name(test_update_channel_id, "nouveau nom de channel par admin",DEFAULT_NEW_MEMBER2).await?;
         // Attendre un peu pour s'assurer que la modification est appliquée (problème de concurrence)
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         println!("test_channel_update => admin qui modifie le nom d'un channel");
-        let channel_after_admin_update = db_mongo_getter::get_channel_by_id(&client_mongo_db,"test",&test_update_channel_id).await?;
+        let channel_after_admin_update = store.get_channel_by_id(&test_update_channel_id).await?;
         assert!(test_update_channel_name != channel_after_admin_update,
             "Un admin devrait pouvoir modifier un channel. Avant: {:?}, Après: {:?}",
             test_update_channel_name, channel_after_admin_update);
         test_update_channel_name = channel_after_admin_update;
-        assert!(test_update_channel_name == db_mongo_getter::get_channel_by_id(&client_mongo_db,"test",&test_update_channel_id).await?);
+        assert!(test_update_channel_name == store.get_channel_by_id(&test_update_channel_id).await?);
         
         //par l'owner
-        db_mongo_update::update_channel_name(&client_mongo_db,"test",test_update_channel_id, "nouveau nom de channel par owner",DEFAULT_OWNER).await?;
+        store.update_channel_name(test_update_channel_id, "nouveau nom de channel par owner",DEFAULT_OWNER).await?;
         // Attendre un peu pour s'assurer que la modification est appliquée (problème de concurrence)
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         println!("test_channel_update => owner qui modifie le nom d'un channel");
-        let channel_after_owner_update = db_mongo_getter::get_channel_by_id(&client_mongo_db,"test",&test_update_channel_id).await?;
+        let channel_after_owner_update = store.get_channel_by_id(&test_update_channel_id).await?;
         assert!(test_update_channel_name != channel_after_owner_update,
             "Un owner devrait pouvoir modifier un channel. Avant: {:?}, Après: {:?}",
             test_update_channel_name, channel_after_owner_update);
         
         
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_OWNER).await?;
+        store.delete_server(test_update_server_id,DEFAULT_OWNER).await?;
         println!("test_mongo_server_set_up => suppression du server crée : {:?}",test_update_server_id);
         Ok(())
     }
     
-    #[actix_web::test]
-    async fn test_server_update() ->std::io::Result<()>{
-        // Acquérir le verrou pour éviter la concurrence
-        let _lock = get_test_lock().await;
+    async fn test_server_update(store: &dyn ChatStore) ->std::io::Result<()>{
         //mise en place du serveur
-        let client_mongo_db = db_mongo_connection::get_client().await?;
         let mut test_update_server_id = 0;
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de channel").await?;
-        test_update_server_id = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
+        store.set_server(DEFAULT_OWNER,"test de création de channel",None).await?;
+        test_update_server_id = store.get_last_id("server").await?;
         println!("test_server_update => création du server pour channel avec l'identifiant numéro : {:?}",test_update_server_id);
         assert!(test_update_server_id !=0);
         
         //ajoute des membres
         println!("test_server_update => ajoute deux membres");
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_NEW_MEMBER2).await?;
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_NEW_MEMBER3).await?;
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        store.add_member_to_server(test_update_server_id,DEFAULT_NEW_MEMBER2).await?;
+        store.add_member_to_server(test_update_server_id,DEFAULT_NEW_MEMBER3).await?;
+        assert!(store.is_member(&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_member(&test_update_server_id,&DEFAULT_NEW_MEMBER3).await?);
         //ajoute un admin
-        db_mongo_setter::add_admin_to_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
-        assert!(db_mongo_getter::is_admin(&client_mongo_db,"test",&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
+        store.add_admin_to_server(test_update_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
+        assert!(store.is_admin(&test_update_server_id,&DEFAULT_NEW_MEMBER2).await?);
         
         //update nom
         //par membre
-        let mut test_update_server_name = db_mongo_getter::get_server(&client_mongo_db,"test",&test_update_server_id).await?;
-        db_mongo_update::update_server_name(&client_mongo_db,"test",test_update_server_id, "nouveau nom de server par membre",DEFAULT_NEW_MEMBER3).await?;
+        let mut test_update_server_name = store.get_server(&test_update_server_id).await?;
+        assert!(matches!(store.update_server_name(test_update_server_id, "nouveau nom de server par membre",DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
         println!("test_server_update => membre qui essaye de modifier le nom d'un server");
-        assert!(test_update_server_name == db_mongo_getter::get_server(&client_mongo_db,"test",&test_update_server_id).await?);
+        assert!(test_update_server_name == store.get_server(&test_update_server_id).await?);
         
         //par un admin
-        db_mongo_update::update_server_name(&client_mongo_db,"test",test_update_server_id, "nouveau nom de server par admin",DEFAULT_NEW_MEMBER2).await?;
+        store.update_server_name(test_update_server_id, "nouveau nom de server par admin",DEFAULT_NEW_MEMBER2).await?;
         println!("test_server_update => admin qui modifie le nom d'un server");
-        assert!(test_update_server_name != db_mongo_getter::get_server(&client_mongo_db,"test",&test_update_server_id).await?);
-        test_update_server_name = db_mongo_getter::get_server(&client_mongo_db,"test",&test_update_server_id).await?;
-        assert!(test_update_server_name == db_mongo_getter::get_server(&client_mongo_db,"test",&test_update_server_id).await?);
+        assert!(test_update_server_name != store.get_server(&test_update_server_id).await?);
+        test_update_server_name = store.get_server(&test_update_server_id).await?;
+        assert!(test_update_server_name == store.get_server(&test_update_server_id).await?);
         
         //par l'owner
-        db_mongo_update::update_server_name(&client_mongo_db,"test",test_update_server_id, "nouveau nom de server par owner",DEFAULT_OWNER).await?;
+        store.update_server_name(test_update_server_id, "nouveau nom de server par owner",DEFAULT_OWNER).await?;
         println!("test_server_update => owner qui modifie le nom d'un server");
-        assert!(test_update_server_name != db_mongo_getter::get_server(&client_mongo_db,"test",&test_update_server_id).await?);
+        assert!(test_update_server_name != store.get_server(&test_update_server_id).await?);
         
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_update_server_id,DEFAULT_OWNER).await?;
+        store.delete_server(test_update_server_id,DEFAULT_OWNER).await?;
         println!("test_mongo_server_set_up => suppression du server crée : {:?}",test_update_server_id);
         Ok(())
     }
     
-    #[actix_web::test]
-    async fn test_get_element() ->std::io::Result<()>{
-        // Acquérir le verrou pour éviter la concurrence
-        let _lock = get_test_lock().await;
+    async fn test_get_element(store: &dyn ChatStore) ->std::io::Result<()>{
         //mise en place du serveur
-        let client_mongo_db = db_mongo_connection::get_client().await?;
-        let base_owner_server = db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_OWNER).await?.len();
-        let base_member2_server = db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_NEW_MEMBER2).await?.len();
-        let base_member3_server = db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_NEW_MEMBER3).await?.len();
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de channel").await?;
-        let test_update_server_id1 = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_update_server_id1,DEFAULT_NEW_MEMBER2).await?;
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_update_server_id1,DEFAULT_NEW_MEMBER3).await?;
+        let base_owner_server = store.get_servers_by_member(&DEFAULT_OWNER).await?.len();
+        let base_member2_server = store.get_servers_by_member(&DEFAULT_NEW_MEMBER2).await?.len();
+        let base_member3_server = store.get_servers_by_member(&DEFAULT_NEW_MEMBER3).await?.len();
+        store.set_server(DEFAULT_OWNER,"test de création de channel",None).await?;
+        let test_update_server_id1 = store.get_last_id("server").await?;
+        store.add_member_to_server(test_update_server_id1,DEFAULT_NEW_MEMBER2).await?;
+        store.add_member_to_server(test_update_server_id1,DEFAULT_NEW_MEMBER3).await?;
         println!("server 1 crée avec 2 users de plus");
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id1,&DEFAULT_OWNER).await?);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id1,&DEFAULT_NEW_MEMBER2).await?);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id1,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(store.is_member(&test_update_server_id1,&DEFAULT_OWNER).await?);
+        assert!(store.is_member(&test_update_server_id1,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_member(&test_update_server_id1,&DEFAULT_NEW_MEMBER3).await?);
         
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de channel").await?;
-        let test_update_server_id2 = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_update_server_id2,DEFAULT_NEW_MEMBER2).await?;
+        store.set_server(DEFAULT_OWNER,"test de création de channel",None).await?;
+        let test_update_server_id2 = store.get_last_id("server").await?;
+        store.add_member_to_server(test_update_server_id2,DEFAULT_NEW_MEMBER2).await?;
         println!("server 2 crée avec {} users en plus",DEFAULT_NEW_MEMBER2);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id2,&DEFAULT_OWNER).await?);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id2,&DEFAULT_NEW_MEMBER2).await?);
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id2,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(store.is_member(&test_update_server_id2,&DEFAULT_OWNER).await?);
+        assert!(store.is_member(&test_update_server_id2,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(!store.is_member(&test_update_server_id2,&DEFAULT_NEW_MEMBER3).await?);
         
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de channel").await?;
-        let test_update_server_id3 = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
-        db_mongo_setter::add_member_to_server(&client_mongo_db,"test",test_update_server_id3,DEFAULT_NEW_MEMBER3).await?;
+        store.set_server(DEFAULT_OWNER,"test de création de channel",None).await?;
+        let test_update_server_id3 = store.get_last_id("server").await?;
+        store.add_member_to_server(test_update_server_id3,DEFAULT_NEW_MEMBER3).await?;
         println!("server 3 crée avec {} users en plus",DEFAULT_NEW_MEMBER3);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id3,&DEFAULT_OWNER).await?);
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id3,&DEFAULT_NEW_MEMBER2).await?);
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id3,&DEFAULT_NEW_MEMBER3).await?);
-        
-        db_mongo_setter::set_server(&client_mongo_db,"test",DEFAULT_OWNER,"test de création de channel").await?;
-        let test_update_server_id4 = db_mongo_getter::get_last_id(&client_mongo_db,"test","server").await?;
-        assert!(db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id4,&DEFAULT_OWNER).await?);
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id4,&DEFAULT_NEW_MEMBER2).await?);
-        assert!(!db_mongo_getter::is_member(&client_mongo_db,"test",&test_update_server_id4,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(store.is_member(&test_update_server_id3,&DEFAULT_OWNER).await?);
+        assert!(!store.is_member(&test_update_server_id3,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(store.is_member(&test_update_server_id3,&DEFAULT_NEW_MEMBER3).await?);
+        
+        store.set_server(DEFAULT_OWNER,"test de création de channel",None).await?;
+        let test_update_server_id4 = store.get_last_id("server").await?;
+        assert!(store.is_member(&test_update_server_id4,&DEFAULT_OWNER).await?);
+        assert!(!store.is_member(&test_update_server_id4,&DEFAULT_NEW_MEMBER2).await?);
+        assert!(!store.is_member(&test_update_server_id4,&DEFAULT_NEW_MEMBER3).await?);
         println!("server 4 crée avec aucun users en plus");
         
         // Attendre que tous les serveurs soient bien enregistrés (problème de concurrence)
@@ -787,9 +864,9 @@ mod tests {
         let mut member2_servers_ok = false;
         let mut member3_servers_ok = false;
         while retries < 10 && (!owner_servers_ok || !member2_servers_ok || !member3_servers_ok) {
-            let owner_count = db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_OWNER).await?.len();
-            let member2_count = db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_NEW_MEMBER2).await?.len();
-            let member3_count = db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_NEW_MEMBER3).await?.len();
+            let owner_count = store.get_servers_by_member(&DEFAULT_OWNER).await?.len();
+            let member2_count = store.get_servers_by_member(&DEFAULT_NEW_MEMBER2).await?.len();
+            let member3_count = store.get_servers_by_member(&DEFAULT_NEW_MEMBER3).await?.len();
             
             owner_servers_ok = owner_count >= base_owner_server + 4; // >= car d'autres tests peuvent avoir créé des serveurs
             member2_servers_ok = member2_count >= base_member2_server + 2;
@@ -804,12 +881,12 @@ mod tests {
         // println!("test owner {:?}",base_owner_server);
         // println!("test m2 {:?}",base_member2_server);
         // println!("test m3 {:?}",base_member3_server);
-        // println!("test owner r {:?}",db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_OWNER).await?.len());
-        // println!("test m2 r {:?}",db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_NEW_MEMBER2).await?.len());
-        // println!("test m3 r {:?}",db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_NEW_MEMBER3).await?.len());
-        let final_owner_count = db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_OWNER).await?.len();
-        let final_member2_count = db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_NEW_MEMBER2).await?.len();
-        let final_member3_count = db_mongo_getter::get_servers_by_member(&client_mongo_db, "test", &DEFAULT_NEW_MEMBER3).await?.len();
+        // println!("test owner r {:?}",store.get_servers_by_member(&DEFAULT_OWNER).await?.len());
+        // println!("test m2 r {:?}",store.get_servers_by_member(&DEFAULT_NEW_MEMBER2).await?.len());
+        // println!("test m3 r {:?}",store.get_servers_by_member(&DEFAULT_NEW_MEMBER3).await?.len());
+        let final_owner_count = store.get_servers_by_member(&DEFAULT_OWNER).await?.len();
+        let final_member2_count = store.get_servers_by_member(&DEFAULT_NEW_MEMBER2).await?.len();
+        let final_member3_count = store.get_servers_by_member(&DEFAULT_NEW_MEMBER3).await?.len();
         
         assert!(final_owner_count >= base_owner_server + 4, 
             "L'owner devrait avoir au moins {} serveurs (base: {}, actuel: {})", 
@@ -823,34 +900,34 @@ mod tests {
         
         
         println!("test_get_element => crée deux channel");
-        db_mongo_setter::set_channel(&client_mongo_db,"test",test_update_server_id1,"premier chat",DEFAULT_OWNER).await?;
-        let test_get_channel_id1 = db_mongo_getter::get_last_id(&client_mongo_db,"test","channel").await?;
-        db_mongo_setter::set_channel(&client_mongo_db,"test",test_update_server_id1,"second chat",DEFAULT_OWNER).await?;
-        let test_get_channel_id2 = db_mongo_getter::get_last_id(&client_mongo_db,"test","channel").await?;
-        
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_update_server_id1,test_get_channel_id1,"message 1 ",DEFAULT_OWNER).await?;
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_update_server_id1,test_get_channel_id1,"message 2 ",DEFAULT_OWNER).await?;
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_update_server_id1,test_get_channel_id1,"message 3 ",DEFAULT_OWNER).await?;
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_update_server_id1,test_get_channel_id1,"message 4 ",DEFAULT_OWNER).await?;
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_update_server_id1,test_get_channel_id1,"message 5 ",DEFAULT_OWNER).await?;
+        store.set_channel(test_update_server_id1,"premier chat",DEFAULT_OWNER).await?;
+        let test_get_channel_id1 = store.get_last_id("channel").await?;
+        store.set_channel(test_update_server_id1,"second chat",DEFAULT_OWNER).await?;
+        let test_get_channel_id2 = store.get_last_id("channel").await?;
+        
+        store.set_message(test_update_server_id1,test_get_channel_id1,"message 1 ",DEFAULT_OWNER).await?;
+        store.set_message(test_update_server_id1,test_get_channel_id1,"message 2 ",DEFAULT_OWNER).await?;
+        store.set_message(test_update_server_id1,test_get_channel_id1,"message 3 ",DEFAULT_OWNER).await?;
+        store.set_message(test_update_server_id1,test_get_channel_id1,"message 4 ",DEFAULT_OWNER).await?;
+        store.set_message(test_update_server_id1,test_get_channel_id1,"message 5 ",DEFAULT_OWNER).await?;
         println!("test_get_element => envoie 5 messages dans le premier channel");
         
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_update_server_id1,test_get_channel_id2,"message 1 ",DEFAULT_OWNER).await?;
-        db_mongo_setter::set_message(&client_mongo_db,"test",test_update_server_id1,test_get_channel_id2,"message 2 ",DEFAULT_OWNER).await?;
+        store.set_message(test_update_server_id1,test_get_channel_id2,"message 1 ",DEFAULT_OWNER).await?;
+        store.set_message(test_update_server_id1,test_get_channel_id2,"message 2 ",DEFAULT_OWNER).await?;
         println!("test_get_element => envoie 2 messages dans le second channel");
         
         println!("test_get_element => verifie le nombre de messages dans les channel");
-        assert!(db_mongo_getter::get_messages_of_channel(&client_mongo_db,"test",&test_get_channel_id1).await?.len() == 5);
-        assert!(db_mongo_getter::get_messages_of_channel(&client_mongo_db,"test",&test_get_channel_id2).await?.len() == 2);
+        assert!(store.get_messages_of_channel(&test_get_channel_id1).await?.len() == 5);
+        assert!(store.get_messages_of_channel(&test_get_channel_id2).await?.len() == 2);
 
 
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_update_server_id1,DEFAULT_OWNER).await?;
+        store.delete_server(test_update_server_id1,DEFAULT_OWNER).await?;
         println!("test_get_element => suppression du server crée : {:?}",test_update_server_id1);
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_update_server_id2,DEFAULT_OWNER).await?;
+        store.delete_server(test_update_server_id2,DEFAULT_OWNER).await?;
         println!("test_get_element => suppression du server crée : {:?}",test_update_server_id2);
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_update_server_id3,DEFAULT_OWNER).await?;
+        store.delete_server(test_update_server_id3,DEFAULT_OWNER).await?;
         println!("test_get_element => suppression du server crée : {:?}",test_update_server_id3);
-        db_mongo_delete::delete_server(&client_mongo_db,"test",test_update_server_id4,DEFAULT_OWNER).await?;
+        store.delete_server(test_update_server_id4,DEFAULT_OWNER).await?;
         println!("test_get_element => suppression du server crée : {:?}",test_update_server_id4);
         
 
@@ -858,8 +935,738 @@ mod tests {
         Ok(())
     }
 
-}
+    async fn test_store_errors(store: &dyn ChatStore) ->std::io::Result<()>{
+        //données invalides
+        assert!(matches!(store.set_server(DEFAULT_OWNER,"  ",None).await, Err(FluxyError::Validation(_))));
+        println!("test_store_errors => serveur sans nom refusé");
+        store.set_server(DEFAULT_OWNER,"test des erreurs",None).await?;
+        let test_error_server_id = store.get_last_id("server").await?;
+        store.add_member_to_server(test_error_server_id,DEFAULT_NEW_MEMBER2).await?;
+        assert!(matches!(store.update_member_role(test_error_server_id,DEFAULT_NEW_MEMBER2,"modérateur",DEFAULT_OWNER).await, Err(FluxyError::Validation(_))));
+        assert!(matches!(store.add_admin_to_server(test_error_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER5).await, Err(FluxyError::Validation(_))));
+        println!("test_store_errors => rôle inconnu et non membre refusés");
+
+        //éléments inexistants
+        assert!(matches!(store.update_message(999_999,"message",DEFAULT_OWNER).await, Err(FluxyError::NotFound(_))));
+        assert!(matches!(store.delete_message(999_999,DEFAULT_OWNER).await, Err(FluxyError::NotFound(_))));
+        assert!(matches!(store.update_channel_name(999_999,"channel",DEFAULT_OWNER).await, Err(FluxyError::NotFound(_))));
+        assert!(matches!(store.delete_channel(999_999,DEFAULT_OWNER).await, Err(FluxyError::NotFound(_))));
+        assert!(matches!(store.add_member_to_server(999_999,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::NotFound(_))));
+        assert!(matches!(store.delete_invite("lien inconnu",DEFAULT_OWNER).await, Err(FluxyError::NotFound(_))));
+        println!("test_store_errors => éléments inexistants signalés");
+
+        //le fondateur ne peut pas être exclu
+        assert!(matches!(store.delete_member(test_error_server_id,DEFAULT_NEW_MEMBER2,DEFAULT_OWNER).await, Err(FluxyError::Forbidden(_))));
+        assert!(store.is_member(&test_error_server_id,&DEFAULT_OWNER).await?);
+
+        //statut HTTP et code stable
+        use actix_web::{http::StatusCode, ResponseError};
+        let error = store.delete_server(test_error_server_id,DEFAULT_NEW_MEMBER2).await.unwrap_err();
+        assert_eq!(error.status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(error.code(), "forbidden");
+        assert_eq!(FluxyError::NotFound(String::new()).status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(FluxyError::Conflict(String::new()).status_code(), StatusCode::CONFLICT);
+        assert_eq!(FluxyError::Validation(String::new()).status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(FluxyError::Storage(String::new()).status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        println!("test_store_errors => erreurs converties en statut HTTP : {}",error);
+
+        store.delete_server(test_error_server_id,DEFAULT_OWNER).await?;
+        Ok(())
+    }
+
+    async fn test_typed_models(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test des modèles",Some("/image.png".to_string())).await?;
+        let test_model_server_id = store.get_last_id("server").await?;
+        store.add_member_to_server(test_model_server_id,DEFAULT_NEW_MEMBER2).await?;
+        store.add_admin_to_server(test_model_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
+
+        let server = store.get_server(&test_model_server_id).await?.expect("serveur créé");
+        assert_eq!(server.name,"test des modèles");
+        assert_eq!(server.owner_id,DEFAULT_OWNER);
+        assert_eq!(server.admin_id,vec![DEFAULT_NEW_MEMBER2]);
+        assert_eq!(server.member_id,vec![DEFAULT_OWNER,DEFAULT_NEW_MEMBER2]);
+        assert_eq!(server.image.as_deref(),Some("/image.png"));
+        println!("test_typed_models => serveur relu : {:?}",server);
+
+        store.set_channel(test_model_server_id,"général",DEFAULT_OWNER).await?;
+        let test_model_channel_id = store.get_last_id("channel").await?;
+        let channel = store.get_channel_by_id(&test_model_channel_id).await?.expect("channel créé");
+        assert_eq!(channel,Channel { id: test_model_channel_id, server_id: test_model_server_id, name: "général".to_string(), position: 1, private: false, overwrites: vec![] });
+
+        //le message renvoyé par set_message est celui relu en base
+        let message = store.set_message(test_model_server_id,test_model_channel_id,"bonjour",DEFAULT_NEW_MEMBER2).await?;
+        assert_eq!(store.get_message_by_id(&message.id).await?,Some(message.clone()));
+        assert_eq!(store.get_messages_of_channel(&test_model_channel_id).await?,vec![message]);
+        println!("test_typed_models => channel et message relus");
+
+        assert_eq!(store.get_server(&999_999).await?,None);
+        assert_eq!(store.get_channel_by_id(&999_999).await?,None);
+        assert_eq!(store.get_message_by_id(&999_999).await?,None);
+
+        store.delete_server(test_model_server_id,DEFAULT_OWNER).await?;
+        Ok(())
+    }
+
+    async fn test_cascade_delete(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test de suppression en cascade",None).await?;
+        let test_cascade_server_id = store.get_last_id("server").await?;
+        store.set_channel(test_cascade_server_id,"premier chat",DEFAULT_OWNER).await?;
+        let test_cascade_channel_id1 = store.get_last_id("channel").await?;
+        store.set_channel(test_cascade_server_id,"second chat",DEFAULT_OWNER).await?;
+        let test_cascade_channel_id2 = store.get_last_id("channel").await?;
+        for i in 0..3 {
+            store.set_message(test_cascade_server_id,test_cascade_channel_id1,&format!("message {}",i),DEFAULT_OWNER).await?;
+            store.set_message(test_cascade_server_id,test_cascade_channel_id2,&format!("message {}",i),DEFAULT_OWNER).await?;
+        }
+        assert_eq!(store.count_documents("message").await?,6);
+
+        //le channel part avec ses messages, l'autre channel n'est pas touché
+        store.delete_channel(test_cascade_channel_id1,DEFAULT_OWNER).await?;
+        assert!(store.get_channel_by_id(&test_cascade_channel_id1).await?.is_none());
+        assert!(store.get_messages_of_channel(&test_cascade_channel_id1).await?.is_empty());
+        assert_eq!(store.get_messages_of_channel(&test_cascade_channel_id2).await?.len(),3);
+        println!("test_cascade_delete => channel supprimé avec ses messages");
+
+        //le serveur part avec ses channels et leurs messages
+        store.delete_server(test_cascade_server_id,DEFAULT_OWNER).await?;
+        assert!(store.get_server(&test_cascade_server_id).await?.is_none());
+        assert_eq!(store.count_documents("channel").await?,0);
+        assert_eq!(store.count_documents("message").await?,0);
+        println!("test_cascade_delete => serveur supprimé sans laisser d'orphelins");
+
+        //rien à réparer après une suppression complète
+        assert_eq!(store.repair_orphans().await?,RepairReport::default());
+        Ok(())
+    }
+
+    async fn test_message_edit(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test de modification",None).await?;
+        let test_edit_server_id = store.get_last_id("server").await?;
+        store.add_member_to_server(test_edit_server_id,DEFAULT_NEW_MEMBER2).await?;
+        store.set_channel(test_edit_server_id,"général",DEFAULT_OWNER).await?;
+        let test_edit_channel_id = store.get_last_id("channel").await?;
+        let message = store.set_message(test_edit_server_id,test_edit_channel_id,"premier jet",DEFAULT_NEW_MEMBER2).await?;
+        assert_eq!(message.edited_at,None);
+
+        //le message modifié est renvoyé avec sa date de modification
+        let edited = store.update_message(message.id,"version corrigée",DEFAULT_NEW_MEMBER2).await?;
+        assert_eq!(edited.message,"version corrigée");
+        assert_eq!(edited.time,message.time);
+        assert!(edited.edited_at.is_some());
+        assert_eq!(store.get_message_by_id(&message.id).await?,Some(edited.clone()));
+        println!("test_message_edit => message modifié le {:?}",edited.edited_at);
+
+        //contenu vide refusé, message inchangé
+        assert!(matches!(store.update_message(message.id,"   ",DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        assert_eq!(store.get_message_by_id(&message.id).await?,Some(edited.clone()));
+
+        //la suppression renvoie le message supprimé (pour la diffusion de message.delete)
+        let deleted = store.delete_message(message.id,DEFAULT_OWNER).await?;
+        assert_eq!(deleted,edited);
+        assert!(store.get_message_by_id(&message.id).await?.is_none());
+        println!("test_message_edit => message supprimé");
+
+        store.delete_server(test_edit_server_id,DEFAULT_OWNER).await?;
+        Ok(())
+    }
+
+    async fn test_message_revisions(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test de l'historique",None).await?;
+        let test_history_server_id = store.get_last_id("server").await?;
+        store.add_member_to_server(test_history_server_id,DEFAULT_NEW_MEMBER2).await?;
+        store.add_member_to_server(test_history_server_id,DEFAULT_NEW_MEMBER3).await?;
+        store.add_admin_to_server(test_history_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER3).await?;
+        store.set_channel(test_history_server_id,"général",DEFAULT_OWNER).await?;
+        let test_history_channel_id = store.get_last_id("channel").await?;
+        let message = store.set_message(test_history_server_id,test_history_channel_id,"premier jet",DEFAULT_NEW_MEMBER2).await?;
+        assert!(store.get_message_revisions(&message.id).await?.is_empty());
+
+        //chaque modification garde l'ancien contenu, dans l'ordre
+        store.update_message(message.id,"deuxième jet",DEFAULT_NEW_MEMBER2).await?;
+        let edited = store.update_message(message.id,"version finale",DEFAULT_NEW_MEMBER2).await?;
+        let revisions = store.get_message_revisions(&message.id).await?;
+        assert_eq!(revisions.len(),2);
+        assert_eq!(revisions[0].message,"premier jet");
+        assert_eq!(revisions[1].message,"deuxième jet");
+        assert!(revisions.iter().all(|revision| revision.editor_id == DEFAULT_NEW_MEMBER2));
+        println!("test_message_revisions => {} anciennes versions",revisions.len());
+
+        //le fondateur et les admins voient l'historique
+        let (current,history) = message_history(store,DEFAULT_OWNER,message.id).await?;
+        assert_eq!(current,edited);
+        assert_eq!(history,revisions);
+        assert_eq!(message_history(store,DEFAULT_NEW_MEMBER3,message.id).await?.1,revisions);
+
+        //un simple membre qui a MANAGE_MESSAGES par un rôle le voit aussi
+        store.add_member_to_server(test_history_server_id,DEFAULT_NEW_MEMBER4).await?;
+        let moderator = store.set_role(test_history_server_id,"modérateur",Permissions::MANAGE_MESSAGES,DEFAULT_OWNER).await?;
+        store.add_role_to_member(moderator.id,DEFAULT_NEW_MEMBER4,DEFAULT_OWNER).await?;
+        assert!(!store.is_admin(&test_history_server_id,&DEFAULT_NEW_MEMBER4).await?);
+        assert_eq!(message_history(store,DEFAULT_NEW_MEMBER4,message.id).await?.1,revisions);
+
+        //l'auteur simple membre non, et un message inexistant est introuvable
+        assert!(matches!(message_history(store,DEFAULT_NEW_MEMBER2,message.id).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(message_history(store,DEFAULT_OWNER,i64::MAX).await, Err(FluxyError::NotFound(_))));
+        println!("test_message_revisions => historique réservé à MANAGE_MESSAGES");
+
+        store.delete_server(test_history_server_id,DEFAULT_OWNER).await?;
+        Ok(())
+    }
+
+    async fn test_missed_messages(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test de la reprise",None).await?;
+        let test_replay_server_id = store.get_last_id("server").await?;
+        store.set_channel(test_replay_server_id,"général",DEFAULT_OWNER).await?;
+        let test_replay_channel_id = store.get_last_id("channel").await?;
+        let mut ids = Vec::new();
+        for content in ["un","deux","trois","quatre"] {
+            ids.push(store.set_message(test_replay_server_id,test_replay_channel_id,content,DEFAULT_OWNER).await?.id);
+        }
+
+        //les premiers messages après le dernier vu, dans la limite demandée
+        let missed = store.get_messages_of_channel_after(&test_replay_channel_id,&ids[0],2).await?;
+        assert_eq!(missed.iter().map(|message| message.id).collect::<Vec<_>>(),ids[1..3].to_vec());
+        assert_eq!(store.get_messages_of_channel_after(&test_replay_channel_id,&ids[0],10).await?.len(),3);
+        assert!(store.get_messages_of_channel_after(&test_replay_channel_id,&ids[3],10).await?.is_empty());
+        println!("test_missed_messages => {} message(s) manqué(s) sur 3 avec une limite de 2",missed.len());
+
+        //au-delà de WS_REPLAY_LIMIT, la reprise s'arrête sur replay.truncated
+        let config = AppConfig {
+            supabase_url: "https://test.supabase.co".to_string(),
+            supabase_anon_key: String::new(),
+            supabase_service_role_key: String::new(),
+            session_key: String::new(),
+            ws_heartbeat_interval: Duration::from_secs(5),
+            ws_client_timeout: Duration::from_secs(10),
+            ban_expiry_interval: Duration::from_secs(60),
+            ws_replay_limit: 0,
+        };
+        let events = load_missed_messages(store,&config,test_replay_server_id,test_replay_channel_id,ids[0]).await?;
+        assert_eq!(events,vec![ChatEvent::ReplayTruncated { server_id: test_replay_server_id, channel_id: test_replay_channel_id, after_id: ids[0] }]);
+        assert!(load_missed_messages(store,&config,test_replay_server_id,test_replay_channel_id,ids[3]).await?.is_empty());
+        println!("test_missed_messages => {:?}",events);
+
+        store.delete_server(test_replay_server_id,DEFAULT_OWNER).await?;
+        Ok(())
+    }
+
+    async fn test_roles(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test des rôles",None).await?;
+        let test_role_server_id = store.get_last_id("server").await?;
+        for member in [DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER5] {
+            store.add_member_to_server(test_role_server_id,member).await?;
+        }
+        store.add_admin_to_server(test_role_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER5).await?;
+        store.set_channel(test_role_server_id,"général",DEFAULT_OWNER).await?;
+        let test_role_channel_id = store.get_last_id("channel").await?;
+
+        //un simple membre ne gère pas les rôles, et les permissions inconnues, les noms réservés ou en double sont refusés
+        assert!(matches!(store.set_role(test_role_server_id,"pirate",Permissions::ALL,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        let moderator = store.set_role(test_role_server_id,"modérateur",Permissions::MANAGE_MESSAGES | Permissions::KICK_MEMBERS | Permissions::MANAGE_ROLES,DEFAULT_OWNER).await?;
+        let helper = store.set_role(test_role_server_id,"helper",Permissions::CREATE_INVITES,DEFAULT_OWNER).await?;
+        assert!(matches!(store.set_role(test_role_server_id,"inconnu",Permissions(1 << 40),DEFAULT_OWNER).await, Err(FluxyError::Validation(_))));
+        assert!(matches!(store.set_role(test_role_server_id,"admin",Permissions::NONE,DEFAULT_OWNER).await, Err(FluxyError::Validation(_))));
+        assert!(matches!(store.set_role(test_role_server_id,"helper",Permissions::NONE,DEFAULT_OWNER).await, Err(FluxyError::Conflict(_))));
+
+        //un nouveau rôle arrive en bas de la hiérarchie
+        let roles = store.get_roles_of_server(&test_role_server_id).await?;
+        assert_eq!(roles.iter().map(|role| (role.name.as_str(),role.position)).collect::<Vec<_>>(),vec![("modérateur",2),("helper",1)]);
+        println!("test_roles => rôles créés {:?}",roles.iter().map(|role| &role.name).collect::<Vec<_>>());
+
+        //update_member_role accepte un rôle personnalisé ; les permissions suivent les rôles
+        store.update_member_role(test_role_server_id,DEFAULT_NEW_MEMBER2,"modérateur",DEFAULT_OWNER).await?;
+        assert!(matches!(store.update_member_role(test_role_server_id,DEFAULT_NEW_MEMBER2,"inexistant",DEFAULT_OWNER).await, Err(FluxyError::Validation(_))));
+        assert!(store.has_permission(&test_role_server_id,&DEFAULT_NEW_MEMBER2,Permissions::KICK_MEMBERS).await?);
+        assert!(!store.has_permission(&test_role_server_id,&DEFAULT_NEW_MEMBER2,Permissions::MANAGE_CHANNELS).await?);
+        assert!(store.has_permission(&test_role_server_id,&DEFAULT_NEW_MEMBER5,Permissions::ALL).await?);
+        assert!(!store.has_permission(&test_role_server_id,&DEFAULT_NEW_MEMBER,Permissions::CREATE_INVITES).await?);
+
+        //le modérateur donne les rôles placés sous le sien, pas le sien ni des permissions qu'il n'a pas
+        store.add_role_to_member(helper.id,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER2).await?;
+        assert!(matches!(store.add_role_to_member(moderator.id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(store.add_role_to_member(helper.id,DEFAULT_NEW_MEMBER,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        assert!(matches!(store.set_role(test_role_server_id,"channels",Permissions::MANAGE_CHANNELS,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(store.update_role(helper.id,None,None,Some(5),DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(store.set_channel(test_role_server_id,"interdit",DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+
+        //CREATE_INVITES vient du rôle helper
+        assert!(store.create_invite(test_role_server_id,DEFAULT_NEW_MEMBER3,None,None).await.is_ok());
+        assert!(matches!(store.create_invite(test_role_server_id,DEFAULT_NEW_MEMBER4,None,None).await, Err(FluxyError::Forbidden(_))));
+        println!("test_roles => permissions résolues depuis les rôles");
+
+        //MANAGE_MESSAGES : supprimer le message d'un autre ; MENTION_EVERYONE : @everyone
+        let message = store.set_message(test_role_server_id,test_role_channel_id,"bonjour",DEFAULT_NEW_MEMBER4).await?;
+        store.delete_message(message.id,DEFAULT_NEW_MEMBER2).await?;
+        assert!(matches!(store.set_message(test_role_server_id,test_role_channel_id,"@everyone réunion",DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
+        store.set_message(test_role_server_id,test_role_channel_id,"@everyone réunion",DEFAULT_OWNER).await?;
+
+        //exclusion : KICK_MEMBERS et rang strictement supérieur
+        assert!(matches!(store.delete_member(test_role_server_id,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER4).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(store.delete_member(test_role_server_id,DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER5).await, Err(FluxyError::Forbidden(_))));
+        store.delete_member(test_role_server_id,DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3).await?;
+        assert!(!store.is_member(&test_role_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(store.get_role_by_id(&helper.id).await?.expect("rôle helper").member_id.is_empty());
+        println!("test_roles => exclusion limitée aux rangs inférieurs");
 
-fn type_of<T>(_: &T) -> &'static str{
-    std::any::type_name::<T>()
-}
\ No newline at end of file
+        //le fondateur réordonne et retire les rôles
+        let moved = store.update_role(helper.id,Some("assistant"),None,Some(5),DEFAULT_OWNER).await?;
+        assert_eq!((moved.name.as_str(),moved.position),("assistant",5));
+        assert_eq!(store.get_roles_of_server(&test_role_server_id).await?[0].id,helper.id);
+        store.remove_role_from_member(moderator.id,DEFAULT_NEW_MEMBER2,DEFAULT_OWNER).await?;
+        assert!(!store.has_permission(&test_role_server_id,&DEFAULT_NEW_MEMBER2,Permissions::KICK_MEMBERS).await?);
+        store.delete_role(moderator.id,DEFAULT_OWNER).await?;
+        assert!(store.get_role_by_id(&moderator.id).await?.is_none());
+
+        //les rôles partent avec le serveur
+        store.delete_server(test_role_server_id,DEFAULT_OWNER).await?;
+        assert!(store.get_roles_of_server(&test_role_server_id).await?.is_empty());
+        Ok(())
+    }
+
+    async fn test_channel_overwrites(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test des channels privés",None).await?;
+        let test_private_server_id = store.get_last_id("server").await?;
+        for member in [DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER4] {
+            store.add_member_to_server(test_private_server_id,member).await?;
+        }
+        store.set_channel(test_private_server_id,"général",DEFAULT_OWNER).await?;
+        let test_public_channel_id = store.get_last_id("channel").await?;
+        store.set_channel(test_private_server_id,"staff",DEFAULT_OWNER).await?;
+        let test_private_channel_id = store.get_last_id("channel").await?;
+        let readers = store.set_role(test_private_server_id,"lecteurs",Permissions::NONE,DEFAULT_OWNER).await?;
+        store.add_role_to_member(readers.id,DEFAULT_NEW_MEMBER3,DEFAULT_OWNER).await?;
+
+        //un simple membre ne change pas la visibilité ; un channel privé disparaît pour les membres
+        assert!(matches!(store.update_channel_privacy(test_private_channel_id,true,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        assert!(store.update_channel_privacy(test_private_channel_id,true,DEFAULT_OWNER).await?.private);
+        assert!(store.get_channel_by_id(&test_private_channel_id).await?.expect("channel staff").private);
+        let visible = visible_channels(store,DEFAULT_NEW_MEMBER2,test_private_server_id).await.map_err(io::Error::from)?;
+        assert_eq!(visible.iter().map(|channel| channel.id).collect::<Vec<_>>(),vec![test_public_channel_id]);
+        assert_eq!(visible_channels(store,DEFAULT_OWNER,test_private_server_id).await.map_err(io::Error::from)?.len(),2);
+        assert!(matches!(visible_channels(store,DEFAULT_NEW_MEMBER,test_private_server_id).await, Err(FluxyError::Forbidden(_))));
+        assert!(!can_access_channel(store,DEFAULT_NEW_MEMBER2,test_private_server_id,test_private_channel_id).await?);
+        assert!(matches!(store.set_message(test_private_server_id,test_private_channel_id,"je passe",DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        assert_eq!(store.get_channel_permissions(&test_private_channel_id,&DEFAULT_NEW_MEMBER2).await?,Permissions::NONE);
+        assert_eq!(store.get_channel_permissions(&test_private_channel_id,&DEFAULT_OWNER).await?,Permissions::ALL);
+        println!("test_channel_overwrites => channel privé invisible pour les membres");
+
+        //exception de rôle : les lecteurs voient le channel sans pouvoir y écrire
+        store.update_channel_overwrite(test_private_channel_id,PermissionOverwrite { kind: OverwriteKind::Role, target_id: readers.id, allow: Permissions::VIEW_CHANNEL, deny: Permissions::SEND_MESSAGES },DEFAULT_OWNER).await?;
+        assert!(can_access_channel(store,DEFAULT_NEW_MEMBER3,test_private_server_id,test_private_channel_id).await?);
+        assert!(matches!(store.set_message(test_private_server_id,test_private_channel_id,"lecture seule",DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
+
+        //l'exception du membre passe après celles de ses rôles
+        store.update_channel_overwrite(test_private_channel_id,PermissionOverwrite { kind: OverwriteKind::User, target_id: DEFAULT_NEW_MEMBER3, allow: Permissions::SEND_MESSAGES, deny: Permissions::NONE },DEFAULT_OWNER).await?;
+        store.set_message(test_private_server_id,test_private_channel_id,"je peux écrire",DEFAULT_NEW_MEMBER3).await?;
+        assert_eq!(store.get_channel_by_id(&test_private_channel_id).await?.expect("channel staff").overwrites.len(),2);
+        println!("test_channel_overwrites => exceptions de rôle puis de membre appliquées");
+
+        //un membre peut perdre la vue d'un channel public
+        store.update_channel_overwrite(test_public_channel_id,PermissionOverwrite { kind: OverwriteKind::User, target_id: DEFAULT_NEW_MEMBER4, allow: Permissions::NONE, deny: Permissions::VIEW_CHANNEL },DEFAULT_OWNER).await?;
+        assert!(visible_channels(store,DEFAULT_NEW_MEMBER4,test_private_server_id).await.map_err(io::Error::from)?.is_empty());
+        assert!(matches!(store.set_message(test_private_server_id,test_public_channel_id,"bonjour",DEFAULT_NEW_MEMBER4).await, Err(FluxyError::Forbidden(_))));
+
+        //MANAGE_CHANNELS donné sur un seul channel ; exceptions invalides refusées
+        store.update_channel_overwrite(test_public_channel_id,PermissionOverwrite { kind: OverwriteKind::User, target_id: DEFAULT_NEW_MEMBER2, allow: Permissions::MANAGE_CHANNELS, deny: Permissions::NONE },DEFAULT_OWNER).await?;
+        store.update_channel_name(test_public_channel_id,"accueil",DEFAULT_NEW_MEMBER2).await?;
+        assert!(matches!(store.update_channel_name(test_private_channel_id,"piraté",DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(store.update_channel_overwrite(test_public_channel_id,PermissionOverwrite { kind: OverwriteKind::User, target_id: DEFAULT_NEW_MEMBER4, allow: Permissions::MANAGE_MESSAGES, deny: Permissions::NONE },DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        assert!(matches!(store.update_channel_overwrite(test_public_channel_id,PermissionOverwrite { kind: OverwriteKind::User, target_id: DEFAULT_NEW_MEMBER4, allow: Permissions::VIEW_CHANNEL, deny: Permissions::VIEW_CHANNEL },DEFAULT_OWNER).await, Err(FluxyError::Validation(_))));
+        assert!(matches!(store.update_channel_overwrite(test_public_channel_id,PermissionOverwrite { kind: OverwriteKind::Role, target_id: i64::MAX, allow: Permissions::VIEW_CHANNEL, deny: Permissions::NONE },DEFAULT_OWNER).await, Err(FluxyError::Validation(_))));
+        assert!(matches!(store.update_channel_overwrite(test_public_channel_id,PermissionOverwrite { kind: OverwriteKind::User, target_id: DEFAULT_NEW_MEMBER, allow: Permissions::VIEW_CHANNEL, deny: Permissions::NONE },DEFAULT_OWNER).await, Err(FluxyError::Validation(_))));
+        println!("test_channel_overwrites => gestion limitée au channel autorisé");
+
+        //retirer une exception rend la vue ; une exception absente est introuvable
+        store.delete_channel_overwrite(test_public_channel_id,OverwriteKind::User,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER2).await?;
+        assert!(can_access_channel(store,DEFAULT_NEW_MEMBER4,test_private_server_id,test_public_channel_id).await?);
+        assert!(matches!(store.delete_channel_overwrite(test_public_channel_id,OverwriteKind::User,DEFAULT_NEW_MEMBER4,DEFAULT_OWNER).await, Err(FluxyError::NotFound(_))));
+
+        //supprimer le rôle retire ses exceptions ; le channel redevenu public est visible de tous
+        store.delete_role(readers.id,DEFAULT_OWNER).await?;
+        let staff = store.get_channel_by_id(&test_private_channel_id).await?.expect("channel staff");
+        assert_eq!(staff.overwrites.iter().map(|overwrite| overwrite.kind).collect::<Vec<_>>(),vec![OverwriteKind::User]);
+        store.update_channel_privacy(test_private_channel_id,false,DEFAULT_OWNER).await?;
+        assert!(can_access_channel(store,DEFAULT_NEW_MEMBER2,test_private_server_id,test_private_channel_id).await?);
+
+        store.delete_server(test_private_server_id,DEFAULT_OWNER).await?;
+        Ok(())
+    }
+
+    async fn test_bans(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test des bannissements",None).await?;
+        let test_ban_server_id = store.get_last_id("server").await?;
+        for member in [DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER4] {
+            store.add_member_to_server(test_ban_server_id,member).await?;
+        }
+        let moderator = store.set_role(test_ban_server_id,"modérateur",Permissions::BAN_MEMBERS,DEFAULT_OWNER).await?;
+        store.add_role_to_member(moderator.id,DEFAULT_NEW_MEMBER2,DEFAULT_OWNER).await?;
+        let in_one_hour = format_date(Utc::now() + TimeDelta::hours(1));
+
+        //BAN_MEMBERS, rang strictement supérieur, pas soi-même ; raison trop longue et expiration passée refusées
+        assert!(matches!(store.set_ban(test_ban_server_id,DEFAULT_NEW_MEMBER4,"spam",None,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(store.set_ban(test_ban_server_id,DEFAULT_OWNER,"spam",None,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(store.set_ban(test_ban_server_id,DEFAULT_NEW_MEMBER2,"spam",None,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        assert!(matches!(store.set_ban(test_ban_server_id,DEFAULT_NEW_MEMBER4,&"x".repeat(513),None,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        let yesterday = format_date(Utc::now() - TimeDelta::days(1));
+        assert!(matches!(store.set_ban(test_ban_server_id,DEFAULT_NEW_MEMBER4,"spam",Some(yesterday),DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        assert!(store.get_bans_of_server(&test_ban_server_id).await?.is_empty());
+
+        //le banni quitte le serveur et ne peut plus le rejoindre, ni directement ni par lien
+        let ban = store.set_ban(test_ban_server_id,DEFAULT_NEW_MEMBER3,"  spam  ",Some(in_one_hour.clone()),DEFAULT_NEW_MEMBER2).await?;
+        assert_eq!((ban.reason.as_str(),ban.banned_by,ban.expires_at.as_deref()),("spam",DEFAULT_NEW_MEMBER2,Some(in_one_hour.as_str())));
+        assert_eq!(store.get_ban(&test_ban_server_id,&DEFAULT_NEW_MEMBER3).await?,Some(ban));
+        assert!(!store.is_member(&test_ban_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        assert!(matches!(store.add_member_to_server(test_ban_server_id,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
+        let invite = store.create_invite(test_ban_server_id,DEFAULT_OWNER,None,None).await?;
+        assert!(matches!(store.join_by_link(&invite.code,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
+        assert!(!store.is_member(&test_ban_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        println!("test_bans => le banni ne peut plus rejoindre le serveur");
+
+        //bannissement préventif et définitif d'un non-membre ; un nouveau bannissement remplace l'ancien
+        store.set_ban(test_ban_server_id,DEFAULT_NEW_MEMBER,"",None,DEFAULT_OWNER).await?;
+        store.set_ban(test_ban_server_id,DEFAULT_NEW_MEMBER,"récidive",None,DEFAULT_OWNER).await?;
+        assert_eq!(store.get_ban(&test_ban_server_id,&DEFAULT_NEW_MEMBER).await?.expect("bannissement").reason,"récidive");
+        assert!(matches!(store.add_member_to_server(test_ban_server_id,DEFAULT_NEW_MEMBER).await, Err(FluxyError::Forbidden(_))));
+
+        //la liste est réservée à BAN_MEMBERS
+        assert!(matches!(server_bans(store,DEFAULT_NEW_MEMBER4,test_ban_server_id).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(server_bans(store,DEFAULT_OWNER,i64::MAX).await, Err(FluxyError::NotFound(_))));
+        let bans = server_bans(store,DEFAULT_NEW_MEMBER2,test_ban_server_id).await.map_err(io::Error::from)?;
+        let mut banned: Vec<i64> = bans.iter().map(|ban| ban.user_id).collect();
+        banned.sort();
+        assert_eq!(banned,vec![DEFAULT_NEW_MEMBER,DEFAULT_NEW_MEMBER3]);
+        println!("test_bans => bannissements en cours {:?}",bans);
+
+        //la levée des bannissements expirés ne touche pas aux définitifs
+        assert_eq!(store.delete_expired_bans(&format_date(Utc::now())).await?,0);
+        assert_eq!(store.delete_expired_bans(&format_date(Utc::now() + TimeDelta::hours(2))).await?,1);
+        assert!(store.get_ban(&test_ban_server_id,&DEFAULT_NEW_MEMBER3).await?.is_none());
+        store.join_by_link(&invite.code,DEFAULT_NEW_MEMBER3).await?;
+        assert!(store.is_member(&test_ban_server_id,&DEFAULT_NEW_MEMBER3).await?);
+        println!("test_bans => bannissement expiré levé");
+
+        //lever un bannissement : BAN_MEMBERS, et un bannissement absent est introuvable
+        assert!(matches!(store.delete_ban(test_ban_server_id,DEFAULT_NEW_MEMBER,DEFAULT_NEW_MEMBER4).await, Err(FluxyError::Forbidden(_))));
+        store.delete_ban(test_ban_server_id,DEFAULT_NEW_MEMBER,DEFAULT_NEW_MEMBER2).await?;
+        assert!(matches!(store.delete_ban(test_ban_server_id,DEFAULT_NEW_MEMBER,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::NotFound(_))));
+        store.add_member_to_server(test_ban_server_id,DEFAULT_NEW_MEMBER).await?;
+
+        //les bannissements partent avec le serveur
+        store.set_ban(test_ban_server_id,DEFAULT_NEW_MEMBER4,"spam",None,DEFAULT_OWNER).await?;
+        store.delete_server(test_ban_server_id,DEFAULT_OWNER).await?;
+        assert!(store.get_bans_of_server(&test_ban_server_id).await?.is_empty());
+        Ok(())
+    }
+
+    async fn test_timeouts(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test des timeouts",None).await?;
+        let test_timeout_server_id = store.get_last_id("server").await?;
+        store.set_channel(test_timeout_server_id,"général",DEFAULT_OWNER).await?;
+        let test_timeout_channel_id = store.get_last_id("channel").await?;
+        for member in [DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3,DEFAULT_NEW_MEMBER4] {
+            store.add_member_to_server(test_timeout_server_id,member).await?;
+        }
+        let moderator = store.set_role(test_timeout_server_id,"modérateur",Permissions::MODERATE_MEMBERS,DEFAULT_OWNER).await?;
+        store.add_role_to_member(moderator.id,DEFAULT_NEW_MEMBER2,DEFAULT_OWNER).await?;
+        let in_ten_minutes = format_date(Utc::now() + TimeDelta::minutes(10));
+
+        //MODERATE_MEMBERS, rang strictement supérieur, membre du serveur, pas soi-même ; fin passée ou trop lointaine refusée
+        assert!(matches!(store.set_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER4,in_ten_minutes.clone(),DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(store.set_timeout(test_timeout_server_id,DEFAULT_OWNER,in_ten_minutes.clone(),DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(store.set_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER2,in_ten_minutes.clone(),DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        assert!(matches!(store.set_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER,in_ten_minutes.clone(),DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        let yesterday = format_date(Utc::now() - TimeDelta::days(1));
+        assert!(matches!(store.set_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER4,yesterday,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        let in_thirty_days = format_date(Utc::now() + TimeDelta::days(30));
+        assert!(matches!(store.set_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER4,in_thirty_days,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Validation(_))));
+        assert!(store.get_timeout(&test_timeout_server_id,&DEFAULT_NEW_MEMBER4).await?.is_none());
+
+        //le membre réduit au silence reste membre mais ne peut plus écrire ; l'erreur porte le temps restant
+        let timeout = store.set_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER4,in_ten_minutes.clone(),DEFAULT_NEW_MEMBER2).await?;
+        assert_eq!((timeout.moderator_id,timeout.until.as_str()),(DEFAULT_NEW_MEMBER2,in_ten_minutes.as_str()));
+        assert_eq!(store.get_timeout(&test_timeout_server_id,&DEFAULT_NEW_MEMBER4).await?,Some(timeout));
+        assert!(store.is_member(&test_timeout_server_id,&DEFAULT_NEW_MEMBER4).await?);
+        let err = store.set_message(test_timeout_server_id,test_timeout_channel_id,"coucou",DEFAULT_NEW_MEMBER4).await.unwrap_err();
+        let remaining_secs = err.remaining_secs().expect("erreur timed_out");
+        assert_eq!(err.code(),"timed_out");
+        assert!(remaining_secs > 590 && remaining_secs <= 600);
+        store.set_message(test_timeout_server_id,test_timeout_channel_id,"les autres écrivent",DEFAULT_NEW_MEMBER3).await?;
+        println!("test_timeouts => encore {} secondes de silence",remaining_secs);
+
+        //un nouveau timeout remplace l'ancien ; un timeout terminé n'a plus d'effet
+        let past = MemberTimeout { server_id: test_timeout_server_id, user_id: DEFAULT_NEW_MEMBER3, moderator_id: DEFAULT_OWNER, until: format_date(Utc::now() - TimeDelta::seconds(1)) };
+        assert_eq!(past.remaining_secs(Utc::now()),0);
+        let in_one_hour = format_date(Utc::now() + TimeDelta::hours(1));
+        store.set_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER4,in_one_hour.clone(),DEFAULT_OWNER).await?;
+        assert_eq!(store.get_timeout(&test_timeout_server_id,&DEFAULT_NEW_MEMBER4).await?.expect("timeout").until,in_one_hour);
+        //seuls les timeouts encore en cours sont repris au redémarrage
+        let is_restored = |timeouts: Vec<MemberTimeout>| timeouts.iter().any(|timeout| timeout.server_id == test_timeout_server_id && timeout.user_id == DEFAULT_NEW_MEMBER4);
+        assert!(is_restored(store.get_active_timeouts(&format_date(Utc::now())).await?));
+        assert!(!is_restored(store.get_active_timeouts(&format_date(Utc::now() + TimeDelta::hours(2))).await?));
+
+        //lever un timeout : MODERATE_MEMBERS, et un timeout absent est introuvable
+        assert!(matches!(store.delete_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER3).await, Err(FluxyError::Forbidden(_))));
+        store.delete_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER2).await?;
+        assert!(matches!(store.delete_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::NotFound(_))));
+        store.set_message(test_timeout_server_id,test_timeout_channel_id,"de retour",DEFAULT_NEW_MEMBER4).await?;
+        println!("test_timeouts => timeout levé");
+
+        //les timeouts partent avec le serveur
+        store.set_timeout(test_timeout_server_id,DEFAULT_NEW_MEMBER4,in_ten_minutes,DEFAULT_OWNER).await?;
+        store.delete_server(test_timeout_server_id,DEFAULT_OWNER).await?;
+        assert!(store.get_timeout(&test_timeout_server_id,&DEFAULT_NEW_MEMBER4).await?.is_none());
+        Ok(())
+    }
+
+    async fn test_audit_log(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test du journal d'audit",None).await?;
+        let test_audit_server_id = store.get_last_id("server").await?;
+        for member in [DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3] {
+            store.add_member_to_server(test_audit_server_id,member).await?;
+        }
+        store.add_admin_to_server(test_audit_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER2).await?;
+        let entry = |actor_id: i64, action: AuditAction, target_id: i64| NewAuditEntry {
+            server_id: test_audit_server_id,
+            actor_id,
+            action,
+            target_id,
+            before: None,
+            after: None,
+        };
+
+        //les instantanés avant / après sont relus tels quels
+        let renamed = store.set_audit_entry(NewAuditEntry {
+            before: Some(serde_json::json!({"name": "ancien nom", "image": null})),
+            after: Some(serde_json::json!({"name": "nouveau nom", "image": null})),
+            ..entry(DEFAULT_OWNER,AuditAction::ServerUpdate,test_audit_server_id)
+        }).await?;
+        let kicked = store.set_audit_entry(NewAuditEntry {
+            before: Some(serde_json::json!({"role": "membre", "roles": [1, 2]})),
+            ..entry(DEFAULT_NEW_MEMBER2,AuditAction::MemberKick,DEFAULT_NEW_MEMBER3)
+        }).await?;
+        let deleted = store.set_audit_entry(entry(DEFAULT_OWNER,AuditAction::MessageDelete,42)).await?;
+        assert!(renamed.id < kicked.id && kicked.id < deleted.id);
+
+        //du plus récent au plus ancien, filtré par action, par auteur, et paginé par curseur
+        let all = AuditLogFilter { action: None, user_id: None, before: None, limit: 50 };
+        assert_eq!(store.get_audit_log(&test_audit_server_id,&all).await?,vec![deleted.clone(),kicked.clone(),renamed.clone()]);
+        let kicks = AuditLogFilter { action: Some(AuditAction::MemberKick), ..all };
+        assert_eq!(store.get_audit_log(&test_audit_server_id,&kicks).await?,vec![kicked.clone()]);
+        let by_owner = AuditLogFilter { user_id: Some(DEFAULT_OWNER), ..all };
+        assert_eq!(store.get_audit_log(&test_audit_server_id,&by_owner).await?,vec![deleted.clone(),renamed.clone()]);
+        let page = AuditLogFilter { before: Some(deleted.id), limit: 1, ..all };
+        assert_eq!(store.get_audit_log(&test_audit_server_id,&page).await?,vec![kicked.clone()]);
+        assert!(store.get_audit_log(&i64::MAX,&all).await?.is_empty());
+        println!("test_audit_log => {:?}",store.get_audit_log(&test_audit_server_id,&all).await?);
+
+        //réservé au fondateur et aux administrateurs
+        assert!(matches!(audit_log(store,DEFAULT_NEW_MEMBER3,test_audit_server_id,&all).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(audit_log(store,DEFAULT_OWNER,i64::MAX,&all).await, Err(FluxyError::NotFound(_))));
+        assert_eq!(audit_log(store,DEFAULT_NEW_MEMBER2,test_audit_server_id,&kicks).await.map_err(io::Error::from)?,vec![kicked]);
+
+        //le journal part avec le serveur
+        store.delete_server(test_audit_server_id,DEFAULT_OWNER).await?;
+        assert!(store.get_audit_log(&test_audit_server_id,&all).await?.is_empty());
+        Ok(())
+    }
+
+    async fn test_invites(store: &dyn ChatStore) ->std::io::Result<()>{
+        store.set_server(DEFAULT_OWNER,"test des invitations",None).await?;
+        let test_invite_server_id = store.get_last_id("server").await?;
+        for member in [DEFAULT_NEW_MEMBER2,DEFAULT_NEW_MEMBER3] {
+            store.add_member_to_server(test_invite_server_id,member).await?;
+        }
+        let inviter = store.set_role(test_invite_server_id,"inviteur",Permissions::CREATE_INVITES,DEFAULT_OWNER).await?;
+        store.add_role_to_member(inviter.id,DEFAULT_NEW_MEMBER2,DEFAULT_OWNER).await?;
+        let in_one_hour = format_date(Utc::now() + TimeDelta::hours(1));
+
+        //CREATE_INVITES, au moins une utilisation et une expiration dans le futur
+        assert!(matches!(store.create_invite(test_invite_server_id,DEFAULT_NEW_MEMBER3,None,None).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(store.create_invite(test_invite_server_id,DEFAULT_NEW_MEMBER2,None,Some(0)).await, Err(FluxyError::Validation(_))));
+        let yesterday = format_date(Utc::now() - TimeDelta::days(1));
+        assert!(matches!(store.create_invite(test_invite_server_id,DEFAULT_NEW_MEMBER2,Some(yesterday),None).await, Err(FluxyError::Validation(_))));
+        assert!(store.get_invites_of_server(&test_invite_server_id).await?.is_empty());
+
+        //plusieurs liens par serveur, chacun avec son créateur
+        let limited = store.create_invite(test_invite_server_id,DEFAULT_NEW_MEMBER2,Some(in_one_hour.clone()),Some(2)).await?;
+        let permanent = store.create_invite(test_invite_server_id,DEFAULT_OWNER,None,None).await?;
+        assert_eq!(limited.code.len(),10);
+        assert_ne!(limited.code,permanent.code);
+        assert_eq!((limited.creator_id,limited.expires_at.as_deref(),limited.max_uses,limited.uses),(DEFAULT_NEW_MEMBER2,Some(in_one_hour.as_str()),Some(2),0));
+        assert_eq!(store.get_invite(&limited.code).await?,Some(limited.clone()));
+        assert_eq!(store.get_invites_of_server(&test_invite_server_id).await?.len(),2);
+        println!("test_invites => liens créés {} et {}",limited.code,permanent.code);
+
+        //un lien expiré ne sert plus, même s'il n'a pas atteint son maximum
+        assert!(limited.is_usable_at(&format_date(Utc::now())));
+        assert!(!limited.is_usable_at(&format_date(Utc::now() + TimeDelta::hours(2))));
+
+        //arrivées simultanées : le maximum n'est jamais dépassé
+        let joins = futures_util::future::join_all(
+            [DEFAULT_NEW_MEMBER,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER5].map(|user_id| store.join_by_link(&limited.code,user_id)),
+        ).await;
+        assert_eq!(joins.iter().filter(|join| join.is_ok()).count(),2);
+        assert!(joins.iter().any(|join| matches!(join, Err(FluxyError::NotFound(_)))));
+        let exhausted = store.get_invite(&limited.code).await?.expect("lien");
+        assert_eq!(exhausted.uses,2);
+        assert!(!exhausted.is_usable_at(&format_date(Utc::now())));
+        println!("test_invites => lien épuisé après {} utilisations",exhausted.uses);
+
+        //un membre qui repasse par un lien ne consomme pas d'utilisation
+        store.join_by_link(&permanent.code,DEFAULT_NEW_MEMBER3).await?;
+        assert_eq!(store.get_invite(&permanent.code).await?.expect("lien").uses,0);
+
+        //arrivées simultanées du même utilisateur : une seule utilisation consommée
+        let shared = store.create_invite(test_invite_server_id,DEFAULT_OWNER,None,Some(2)).await?;
+        let mut newcomer = None;
+        for user_id in [DEFAULT_NEW_MEMBER,DEFAULT_NEW_MEMBER4,DEFAULT_NEW_MEMBER5] {
+            if !store.is_member(&test_invite_server_id,&user_id).await? {
+                newcomer = Some(user_id);
+            }
+        }
+        let newcomer = newcomer.expect("arrivant refusé");
+        let joins = futures_util::future::join_all((0..3).map(|_| store.join_by_link(&shared.code,newcomer))).await;
+        assert!(joins.iter().all(|join| join.is_ok()));
+        assert!(store.is_member(&test_invite_server_id,&newcomer).await?);
+        assert_eq!(store.get_invite(&shared.code).await?.expect("lien").uses,1);
+        store.delete_invite(&shared.code,DEFAULT_OWNER).await?;
+        println!("test_invites => une utilisation pour {} arrivées du même utilisateur",joins.len());
+
+        //la liste ne garde que les liens utilisables et demande CREATE_INVITES
+        assert!(matches!(server_invites(store,DEFAULT_NEW_MEMBER3,test_invite_server_id).await, Err(FluxyError::Forbidden(_))));
+        assert!(matches!(server_invites(store,DEFAULT_OWNER,i64::MAX).await, Err(FluxyError::NotFound(_))));
+        let invites = server_invites(store,DEFAULT_NEW_MEMBER2,test_invite_server_id).await.map_err(io::Error::from)?;
+        assert_eq!(invites,vec![permanent.clone()]);
+
+        //révocation : le créateur ou un administrateur
+        assert!(matches!(store.delete_invite(&permanent.code,DEFAULT_NEW_MEMBER2).await, Err(FluxyError::Forbidden(_))));
+        store.delete_invite(&limited.code,DEFAULT_NEW_MEMBER2).await?;
+        store.delete_invite(&permanent.code,DEFAULT_OWNER).await?;
+        assert!(matches!(store.delete_invite(&permanent.code,DEFAULT_OWNER).await, Err(FluxyError::NotFound(_))));
+        store.delete_member(test_invite_server_id,DEFAULT_OWNER,DEFAULT_NEW_MEMBER).await?;
+        assert!(matches!(store.join_by_link(&permanent.code,DEFAULT_NEW_MEMBER).await, Err(FluxyError::NotFound(_))));
+        assert!(!store.is_member(&test_invite_server_id,&DEFAULT_NEW_MEMBER).await?);
+        println!("test_invites => liens révoqués");
+
+        //les liens partent avec le serveur
+        let invite = store.create_invite(test_invite_server_id,DEFAULT_OWNER,None,Some(1)).await?;
+        store.delete_server(test_invite_server_id,DEFAULT_OWNER).await?;
+        assert!(store.get_invite(&invite.code).await?.is_none());
+        Ok(())
+    }
+
+    // Simule une suppression interrompue sur une base SQLite sans clés étrangères : repair_orphans doit retrouver les restes
+    #[cfg(feature = "sql")]
+    #[actix_web::test]
+    async fn test_repair_orphans() -> std::io::Result<()>{
+        use sqlx::Connection;
+        let to_io = |e: sqlx::Error| io::Error::new(io::ErrorKind::Other, e.to_string());
+        let path = env::temp_dir().join(format!("fluxy_repair_{}.db", std::process::id()));
+        let url = format!("sqlite://{}?mode=rwc", path.display());
+
+        let store = SqlStore::connect(&url).await?;
+        store.set_server(DEFAULT_OWNER,"test de réparation",None).await?;
+        let test_repair_server_id = store.get_last_id("server").await?;
+        store.set_channel(test_repair_server_id,"général",DEFAULT_OWNER).await?;
+        let test_repair_channel_id = store.get_last_id("channel").await?;
+        store.set_message(test_repair_server_id,test_repair_channel_id,"message 1",DEFAULT_OWNER).await?;
+        store.set_message(test_repair_server_id,test_repair_channel_id,"message 2",DEFAULT_OWNER).await?;
+        //tout ce que delete_server retire avec le serveur
+        let role = store.set_role(test_repair_server_id,"modérateur",Permissions::MANAGE_MESSAGES,DEFAULT_OWNER).await?;
+        store.add_member_to_server(test_repair_server_id,DEFAULT_NEW_MEMBER).await?;
+        store.add_role_to_member(role.id,DEFAULT_NEW_MEMBER,DEFAULT_OWNER).await?;
+        store.create_invite(test_repair_server_id,DEFAULT_OWNER,None,None).await?;
+        store.set_ban(test_repair_server_id,DEFAULT_NEW_MEMBER2,"réparation",None,DEFAULT_OWNER).await?;
+        store.set_timeout(test_repair_server_id,DEFAULT_NEW_MEMBER,format_date(Utc::now() + TimeDelta::minutes(10)),DEFAULT_OWNER).await?;
+        store.set_audit_entry(NewAuditEntry {
+            server_id: test_repair_server_id,
+            actor_id: DEFAULT_OWNER,
+            action: AuditAction::MemberKick,
+            target_id: DEFAULT_NEW_MEMBER2,
+            before: None,
+            after: None,
+        }).await?;
+        let roles = store.get_roles_of_server(&test_repair_server_id).await?.len() as u64;
+
+        let mut conn = sqlx::AnyConnection::connect(&url).await.map_err(to_io)?;
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut conn).await.map_err(to_io)?;
+        sqlx::query("DELETE FROM servers WHERE id = $1").bind(test_repair_server_id).execute(&mut conn).await.map_err(to_io)?;
+        conn.close().await.map_err(to_io)?;
+        println!("test_repair_orphans => serveur supprimé sans ses enfants");
+
+        let report = store.repair_orphans().await?;
+        assert_eq!(report,RepairReport { roles, invites: 1, bans: 1, timeouts: 1, audit_entries: 1, channels: 1, messages: 2 });
+        for collection in ["role","invite","ban","timeout","audit_log","channel","message"] {
+            assert_eq!(store.count_documents(collection).await?,0,"{} encore présent",collection);
+        }
+        assert_eq!(store.repair_orphans().await?,RepairReport::default());
+        println!("test_repair_orphans => orphelins supprimés : {:?}",report);
+
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    // Même simulation sur MongoDB : le document server disparaît seul, comme un arrêt au milieu d'une suppression sans transaction.
+    // repair_orphans n'examine que les documents de plus d'une minute : les enfants sont réinsérés avec un _id daté d'une heure
+    #[actix_web::test]
+    async fn test_mongo_repair_orphans() -> std::io::Result<()>{
+        use mongodb::bson::oid::ObjectId;
+        let Some(store) = mongo_store("repair_orphans").await? else {
+            println!("test_mongo_repair_orphans => TEST_MONGO_URL non défini, test ignoré");
+            return Ok(());
+        };
+        let to_io = |e: mongodb::error::Error| io::Error::new(io::ErrorKind::Other, e.to_string());
+        let database = Client::with_uri_str(&env::var("TEST_MONGO_URL").expect("TEST_MONGO_URL")).await.map_err(to_io)?
+            .database("fluxy_test_repair_orphans");
+        let one_hour_ago = ObjectId::from_parts((Utc::now().timestamp() - 3600) as u32, [0; 5], [0; 3]);
+
+        store.set_server(DEFAULT_OWNER,"test de réparation",None).await?;
+        let test_repair_server_id = store.get_last_id("server").await?;
+        store.set_channel(test_repair_server_id,"général",DEFAULT_OWNER).await?;
+        let test_repair_channel_id = store.get_last_id("channel").await?;
+        store.set_message(test_repair_server_id,test_repair_channel_id,"message 1",DEFAULT_OWNER).await?;
+        store.set_role(test_repair_server_id,"modérateur",Permissions::MANAGE_MESSAGES,DEFAULT_OWNER).await?;
+        store.create_invite(test_repair_server_id,DEFAULT_OWNER,None,None).await?;
+        store.set_ban(test_repair_server_id,DEFAULT_NEW_MEMBER2,"réparation",None,DEFAULT_OWNER).await?;
+        for collection in ["channel","message","role","invite","ban"] {
+            let collection = database.collection::<Document>(collection);
+            for mut old in collection.find(doc!{}).await.map_err(to_io)?.try_collect::<Vec<_>>().await.map_err(to_io)? {
+                collection.delete_one(doc!{"_id": old.get("_id").cloned()}).await.map_err(to_io)?;
+                old.insert("_id",one_hour_ago);
+                collection.insert_one(old).await.map_err(to_io)?;
+            }
+        }
+
+        //un serveur tout juste créé garde ses enfants, même si son document manque encore au moment de la réparation
+        store.set_server(DEFAULT_OWNER,"serveur récent",None).await?;
+        let test_recent_server_id = store.get_last_id("server").await?;
+        store.set_channel(test_recent_server_id,"récent",DEFAULT_OWNER).await?;
+
+        for server_id in [test_repair_server_id,test_recent_server_id] {
+            database.collection::<Document>("server").delete_one(doc!{"id": server_id}).await.map_err(to_io)?;
+        }
+        println!("test_mongo_repair_orphans => serveurs supprimés sans leurs enfants");
+
+        let report = store.repair_orphans().await?;
+        assert_eq!(report,RepairReport { roles: 1, invites: 1, bans: 1, channels: 1, messages: 1, ..RepairReport::default() });
+        assert_eq!(store.count_documents("channel").await?,1);
+        assert_eq!(store.repair_orphans().await?,RepairReport::default());
+        println!("test_mongo_repair_orphans => orphelins supprimés : {:?}",report);
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_drift(){
+        //un document qui ne correspond plus au modèle est une erreur, pas un champ ignoré
+        let server = doc! {"id": 1_i64, "name": "serveur", "owner_id": 1_i64, "admin_id": 2_i64, "member_id": [1_i64]};
+        assert!(mongodb::bson::from_document::<Server>(server).is_err());
+        let message = doc! {"id": 1_i64, "channel_id": 1_i64, "message": "sans auteur", "time": "2024-01-01T00:00:00Z"};
+        assert!(mongodb::bson::from_document::<Message>(message).is_err());
+        println!("test_schema_drift => documents invalides refusés");
+
+        let server = doc! {"id": 1_i64, "name": "serveur", "owner_id": 1_i64, "admin_id": [], "member_id": [1_i64]};
+        let server = mongodb::bson::from_document::<Server>(server).expect("document valide");
+        assert_eq!(server.image,None);
+    }
+
+}
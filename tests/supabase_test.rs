@@ -4,6 +4,7 @@ use T_JSF_600_MAR_1::{
     models::{AppConfig, SupabaseAuthResponse, SupabaseSignupUser},
 };
 use std::env;
+use std::time::Duration;
 
 #[cfg(test)]
 mod tests {
@@ -21,6 +22,10 @@ mod tests {
                 .unwrap_or_else(|_| "test_service_role_key".to_string()),
             session_key: env::var("SESSION_KEY")
                 .unwrap_or_else(|_| "test_session_key_32_chars_long!".to_string()),
+            ws_heartbeat_interval: Duration::from_secs(5),
+            ws_client_timeout: Duration::from_secs(10),
+            ban_expiry_interval: Duration::from_secs(60),
+            ws_replay_limit: 100,
         }
     }
 